@@ -0,0 +1,57 @@
+use crate::{blocks::Blocks, Device, Devno, Result};
+
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct PartitionAlignment {
+    pub device: Device,
+    pub number: Option<usize>,
+    pub start: u64,
+    pub mib_aligned: bool,
+    pub physical_aligned: bool,
+    pub optimal_aligned: Option<bool>,
+}
+
+impl PartitionAlignment {
+    #[inline]
+    pub fn is_aligned(&self) -> bool {
+        self.mib_aligned && self.physical_aligned && self.optimal_aligned.unwrap_or(true)
+    }
+}
+
+#[inline]
+fn aligned(start: u64, granularity: u64, offset: u64) -> bool {
+    granularity == 0 || start % granularity == offset % granularity
+}
+
+pub(crate) fn report(
+    blocks: &Blocks,
+    disk: &Devno,
+    device: impl Fn(Devno) -> Device,
+) -> Result<Vec<PartitionAlignment>> {
+    let sysfs = blocks.sysfs();
+    let physical = sysfs.physical_block_size(disk)?;
+    let optimal = sysfs.optimal_io_size(disk)?;
+    // a partition's own alignment_offset is already relative to its start,
+    // the disk's one is what the partition start has to line up with
+    let offset = sysfs.alignment_offset(disk)?;
+
+    let mut res = Vec::new();
+    for part in blocks.partitions(disk)? {
+        let part = part?;
+        let start = match sysfs.partition_start(&part)? {
+            Some(start) => start,
+            None => continue,
+        };
+
+        res.push(PartitionAlignment {
+            number: blocks.partition_number(&part)?,
+            start,
+            mib_aligned: aligned(start, MIB, 0),
+            physical_aligned: aligned(start, physical, offset),
+            optimal_aligned: optimal.map(|optimal| aligned(start, optimal, offset)),
+            device: device(part),
+        });
+    }
+    Ok(res)
+}
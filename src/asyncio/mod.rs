@@ -0,0 +1,123 @@
+use std::{
+    io,
+    os::unix::prelude::{AsRawFd, RawFd},
+    path::Path,
+};
+
+use tokio::io::{unix::AsyncFd, Interest};
+use tokio_stream::Iter;
+
+use crate::{
+    events::{WatchEvent, Watcher},
+    Blocks, Device, Devno, Result,
+};
+
+pub type DeviceStream = Iter<std::vec::IntoIter<Result<Device>>>;
+
+async fn blocking<R: Send + 'static, F: FnOnce() -> Result<R> + Send + 'static>(f: F) -> Result<R> {
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(err) => Err(io::Error::other(err).into()),
+    }
+}
+
+#[inline]
+fn stream<I: Iterator<Item = Result<Device>>>(it: I) -> DeviceStream {
+    tokio_stream::iter(it.collect::<Vec<_>>())
+}
+
+#[derive(Clone)]
+pub struct AsyncBlocks(Blocks);
+
+impl AsyncBlocks {
+    pub async fn new() -> Result<Self> {
+        blocking(Blocks::new).await.map(Self)
+    }
+
+    #[inline]
+    pub fn blocking(&self) -> &Blocks {
+        &self.0
+    }
+
+    pub async fn from_path<P: AsRef<Path>>(&self, p: P) -> Result<Device> {
+        let blocks = self.0.clone();
+        let p = p.as_ref().to_path_buf();
+        blocking(move || blocks.from_path(p)).await
+    }
+
+    pub async fn from_devno(&self, d: Devno) -> Result<Device> {
+        let blocks = self.0.clone();
+        blocking(move || blocks.from_devno(d)).await
+    }
+
+    pub async fn disks(&self) -> Result<DeviceStream> {
+        let blocks = self.0.clone();
+        blocking(move || Ok(stream(blocks.disks()?))).await
+    }
+
+    pub async fn blocks(&self) -> Result<DeviceStream> {
+        let blocks = self.0.clone();
+        blocking(move || Ok(stream(blocks.blocks()?))).await
+    }
+
+    pub async fn partitions(&self, device: &Device) -> Result<DeviceStream> {
+        let device = device.clone();
+        blocking(move || Ok(stream(device.partitions()?))).await
+    }
+
+    pub async fn slaves(&self, device: &Device) -> Result<DeviceStream> {
+        let device = device.clone();
+        blocking(move || Ok(stream(device.slaves()?))).await
+    }
+
+    pub async fn watch(&self) -> Result<AsyncWatcher> {
+        let blocks = self.0.clone();
+        AsyncWatcher::new(blocking(move || blocks.watch()).await?)
+    }
+}
+
+impl From<Blocks> for AsyncBlocks {
+    #[inline]
+    fn from(blocks: Blocks) -> Self {
+        Self(blocks)
+    }
+}
+
+pub struct AsyncWatcher {
+    watcher: Watcher,
+    uevents: AsyncFd<RawFd>,
+    mounts: AsyncFd<RawFd>,
+}
+
+impl AsyncWatcher {
+    fn new(watcher: Watcher) -> Result<Self> {
+        watcher.uevents().set_nonblocking(true)?;
+        Ok(Self {
+            uevents: AsyncFd::with_interest(watcher.uevents().as_raw_fd(), Interest::READABLE)?,
+            mounts: AsyncFd::with_interest(watcher.mounts().as_raw_fd(), Interest::PRIORITY)?,
+            watcher,
+        })
+    }
+
+    pub async fn next(&mut self) -> Result<WatchEvent> {
+        loop {
+            if let Some(event) = self.watcher.pop_pending() {
+                return Ok(event);
+            }
+
+            tokio::select! {
+                guard = self.uevents.readable() => {
+                    let mut guard = guard?;
+                    match self.watcher.recv_uevent() {
+                        Err(err) if err.kind() == io::ErrorKind::WouldBlock => guard.clear_ready(),
+                        res => res?,
+                    }
+                }
+                guard = self.mounts.ready(Interest::PRIORITY) => {
+                    guard?.clear_ready();
+                    self.watcher.handle_mounts()?;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+
+use crate::{parttable::fill_random, DeviceHandle, Result};
+
+const MIB: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchOptions {
+    sequential_bytes: u64,
+    sequential_block: usize,
+    random_ops: u64,
+    random_block: usize,
+    max_duration: Duration,
+}
+
+impl Default for BenchOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            sequential_bytes: 128 * MIB,
+            sequential_block: MIB as usize,
+            random_ops: 2000,
+            random_block: 4096,
+            max_duration: Duration::from_secs(3),
+        }
+    }
+}
+
+impl BenchOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn sequential_bytes(mut self, bytes: u64) -> Self {
+        self.sequential_bytes = bytes;
+        self
+    }
+
+    #[inline]
+    pub fn sequential_block(mut self, size: usize) -> Self {
+        self.sequential_block = size;
+        self
+    }
+
+    #[inline]
+    pub fn random_ops(mut self, ops: u64) -> Self {
+        self.random_ops = ops;
+        self
+    }
+
+    #[inline]
+    pub fn random_block(mut self, size: usize) -> Self {
+        self.random_block = size;
+        self
+    }
+
+    // upper bound for each of the two phases
+    #[inline]
+    pub fn max_duration(mut self, duration: Duration) -> Self {
+        self.max_duration = duration;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BenchReport {
+    pub sequential_bytes: u64,
+    pub sequential_elapsed: Duration,
+    pub random_ops: u64,
+    pub random_block: usize,
+    pub random_elapsed: Duration,
+}
+
+impl BenchReport {
+    pub fn sequential_throughput(&self) -> f64 {
+        let secs = self.sequential_elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.sequential_bytes as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    pub fn random_iops(&self) -> f64 {
+        let secs = self.random_elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.random_ops as f64 / secs
+        } else {
+            0.0
+        }
+    }
+
+    pub fn random_latency(&self) -> Option<Duration> {
+        if self.random_ops == 0 {
+            None
+        } else {
+            Some(self.random_elapsed / self.random_ops as u32)
+        }
+    }
+}
+
+// xorshift is plenty to scatter offsets, only the seed comes from the kernel
+struct Rng(u64);
+
+impl Rng {
+    fn new() -> Result<Self> {
+        let mut seed = [0u8; 8];
+        fill_random(&mut seed)?;
+        Ok(Self(u64::from_ne_bytes(seed) | 1))
+    }
+
+    #[inline]
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
+
+pub(crate) fn run(handle: &DeviceHandle, opts: BenchOptions) -> Result<BenchReport> {
+    let bs = handle.block_size() as usize;
+    let size = handle.size();
+
+    let seq_block = opts.sequential_block.max(bs).next_multiple_of(bs);
+    let seq_total = opts.sequential_bytes.min(size);
+    let mut buf = handle.alloc(seq_block)?;
+    let mut sequential_bytes = 0;
+    let start = Instant::now();
+    while sequential_bytes < seq_total && start.elapsed() < opts.max_duration {
+        let len = ((seq_total - sequential_bytes) as usize).min(seq_block);
+        let len = len.next_multiple_of(bs).min(buf.len());
+        let n = handle.read_at(&mut buf[..len], sequential_bytes)?;
+        if n == 0 {
+            break;
+        }
+        sequential_bytes += n as u64;
+    }
+    let sequential_elapsed = start.elapsed();
+
+    let random_block = opts.random_block.max(bs).next_multiple_of(bs);
+    let slots = size / random_block as u64;
+    let mut rng = Rng::new()?;
+    let mut buf = handle.alloc(random_block)?;
+    let mut random_ops = 0;
+    let start = Instant::now();
+    while slots > 0 && random_ops < opts.random_ops && start.elapsed() < opts.max_duration {
+        let offset = (rng.next() % slots) * random_block as u64;
+        handle.read_at(&mut buf, offset)?;
+        random_ops += 1;
+    }
+    let random_elapsed = start.elapsed();
+
+    Ok(BenchReport {
+        sequential_bytes,
+        sequential_elapsed,
+        random_ops,
+        random_block,
+        random_elapsed,
+    })
+}
@@ -0,0 +1,116 @@
+use std::process::ExitCode;
+
+use linux_blocks::{events::WatchEvent, Blocks, Device, Devno, Result};
+
+fn usage() -> ExitCode {
+    eprintln!("usage: linux-blocks <list|tree|info <device>|watch>");
+    ExitCode::from(2)
+}
+
+fn mountpoints(device: &Device) -> Result<String> {
+    Ok(device
+        .mounts()?
+        .into_iter()
+        .map(|m| m.mount_point.display().to_string())
+        .collect::<Vec<_>>()
+        .join(","))
+}
+
+fn list(blocks: &Blocks) -> Result<()> {
+    println!(
+        "{:<16} {:>8} {:>16} {:<5} MOUNTPOINTS",
+        "NAME", "MAJ:MIN", "SIZE", "TYPE"
+    );
+    let mut devices = blocks.blocks()?.collect::<Result<Vec<_>>>()?;
+    devices.sort();
+    for device in devices {
+        let info = device.info()?;
+        println!(
+            "{:<16} {:>8} {:>16} {:<5} {}",
+            info.name,
+            info.devno.to_string(),
+            info.size,
+            info.device_type,
+            mountpoints(&device)?
+        );
+    }
+    Ok(())
+}
+
+fn tree_node(device: &Device, depth: usize) -> Result<()> {
+    let info = device.info()?;
+    println!(
+        "{:indent$}{} ({}, {}, {} bytes) {}",
+        "",
+        info.name,
+        info.devno,
+        info.device_type,
+        info.size,
+        mountpoints(device)?,
+        indent = depth * 2
+    );
+    for child in device.partitions()? {
+        tree_node(&child?, depth + 1)?;
+    }
+    Ok(())
+}
+
+fn tree(blocks: &Blocks) -> Result<()> {
+    for disk in blocks.disks()? {
+        tree_node(&disk?, 0)?;
+    }
+    Ok(())
+}
+
+fn info(blocks: &Blocks, dev: &str) -> Result<()> {
+    let device = match dev.parse::<Devno>() {
+        Ok(devno) => blocks.from_devno(devno)?,
+        Err(_) => blocks.from_path(dev)?,
+    };
+    println!("{:#?}", device.info()?);
+    Ok(())
+}
+
+fn watch(blocks: &Blocks) -> Result<()> {
+    for event in blocks.watch()? {
+        match event? {
+            WatchEvent::DeviceAdded(device) => println!("add {}", *device),
+            WatchEvent::DeviceRemoved(device) => println!("remove {}", *device),
+            WatchEvent::DeviceChanged(device) => println!("change {}", *device),
+            WatchEvent::Mounted { info, .. } => {
+                println!("mount {} {}", info.dev, info.mount_point.display())
+            }
+            WatchEvent::Unmounted { info, .. } => {
+                println!("umount {} {}", info.dev, info.mount_point.display())
+            }
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let blocks = match Blocks::new() {
+        Ok(blocks) => blocks,
+        Err(err) => {
+            eprintln!("linux-blocks: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let res = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["list"] => list(&blocks),
+        ["tree"] => tree(&blocks),
+        ["info", dev] => info(&blocks, dev),
+        ["watch"] => watch(&blocks),
+        _ => return usage(),
+    };
+
+    match res {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("linux-blocks: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
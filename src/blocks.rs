@@ -1,40 +1,112 @@
 use std::{
     borrow::Borrow,
-    fs::OpenOptions,
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
     io,
-    os::unix::prelude::{AsRawFd, FileTypeExt, MetadataExt},
+    ops::Deref,
+    os::unix::prelude::{AsRawFd, FileTypeExt, MetadataExt, OpenOptionsExt},
     path::{Path, PathBuf},
+    sync::{Arc, Mutex},
 };
 
 use crate::{
+    capability::Capabilities,
+    cgroup::{Cgroup, IoMax, IoStat},
+    clone::{self, CloneOptions, CloneProgress},
     devfs::DevFs,
-    iter,
-    procfs::ProcFs,
+    eject::{self, EjectError},
+    fingerprint::{self, Fingerprint, FingerprintOptions},
+    fsstate,
+    handle::DeviceHandle,
+    health, ioctl, iter, media, naming,
+    parttable::{GptTable, PartitionTable, PartitionTableType, TableLayout},
+    power,
+    procfs::{MountInfo, ProcFs},
+    rescan::{self, RescanOptions, RescanOutcome},
+    resize, scsi, swap,
     sysfs::{
+        self,
         iter::{BlocksIterator, DisksIterator},
-        SysFs,
+        queue::{QueueTuning, WriteCache},
+        MmcInfo, PciAddress, PmemInfo, SysFs, Uevent, UeventAction, UsbInfo, VirtioInfo,
     },
-    Devno,
+    udevdb::{UdevDb, UdevRecord},
+    unmount, usage, DeviceInfo, DeviceKind, Devno, DiskEvent, Error, FsState, FsUsage, Health,
+    PowerState, Result, SizeChange, SwapHeader, UnmountOptions,
 };
 
+pub(crate) enum BlocksRef<'a> {
+    Borrowed(&'a Blocks),
+    Shared(Arc<Blocks>),
+}
+
+impl Deref for BlocksRef<'_> {
+    type Target = Blocks;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Self::Borrowed(blocks) => blocks,
+            Self::Shared(blocks) => blocks,
+        }
+    }
+}
+
 pub(crate) struct Blocks {
     procfs: ProcFs,
     sysfs: SysFs,
-    devfs: DevFs,
+    devfs: Option<DevFs>,
+    udevdb: UdevDb,
+    captured: bool,
+    // last diskseq seen by check_media_changed for non-optical disks
+    media_seqs: Mutex<BTreeMap<Devno, u64>>,
 }
 
-const BLKRRPART: u64 = 4703;
-
 impl Blocks {
-    pub fn new() -> io::Result<Self> {
+    pub fn new() -> Result<Self> {
         let procfs = ProcFs::new()?;
         let sysfs = SysFs::new(&procfs)?;
-        let devfs = DevFs::new(&procfs)?;
-        Ok(Self {
+        let devfs = match DevFs::new(&procfs) {
+            Ok(devfs) => Some(devfs),
+            Err(Error::DevfsNotFound) => None,
+            Err(err) => return Err(err),
+        };
+        Ok(Self::from_parts(procfs, sysfs, devfs, UdevDb::new()))
+    }
+
+    #[inline]
+    pub fn from_parts(procfs: ProcFs, sysfs: SysFs, devfs: Option<DevFs>, udevdb: UdevDb) -> Self {
+        Self {
             procfs,
             sysfs,
             devfs,
-        })
+            udevdb,
+            captured: false,
+            media_seqs: Mutex::default(),
+        }
+    }
+
+    #[inline]
+    pub fn captured(mut self) -> Self {
+        self.captured = true;
+        self
+    }
+
+    #[inline]
+    pub fn is_captured(&self) -> bool {
+        self.captured
+    }
+
+    // the nodes of a captured tree belong to another machine
+    fn live_node(&self, devno: &Devno) -> Result<PathBuf> {
+        if self.captured {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "devices of a captured system cannot be opened",
+            )
+            .into());
+        }
+        self.devfs()?.resolve(devno)
     }
 
     #[inline]
@@ -48,27 +120,73 @@ impl Blocks {
     }
 
     #[inline]
-    pub fn devfs(&self) -> &DevFs {
-        &self.devfs
+    pub fn devfs(&self) -> Result<&DevFs> {
+        self.devfs.as_ref().ok_or(Error::NoDevfs)
+    }
+
+    #[inline]
+    pub fn has_devfs(&self) -> bool {
+        self.devfs.is_some()
+    }
+
+    #[inline]
+    pub fn udevdb(&self) -> &UdevDb {
+        &self.udevdb
+    }
+
+    #[inline]
+    pub fn invalidate(&self, devno: &Devno) {
+        self.sysfs().invalidate(devno);
+        if let Some(devfs) = self.devfs.as_ref() {
+            devfs.invalidate(devno);
+        }
+    }
+
+    pub fn refresh(&self) -> Result<()> {
+        self.sysfs().clear_cache();
+        if let Some(devfs) = self.devfs.as_ref() {
+            devfs.clear_cache();
+        }
+        self.procfs().devices().refresh()
+    }
+
+    #[inline]
+    pub fn udev_properties(&self, devno: &Devno) -> Result<Option<UdevRecord>> {
+        self.udevdb().get(devno)
+    }
+
+    #[inline]
+    pub fn info(&self, devno: &Devno) -> Result<DeviceInfo> {
+        DeviceInfo::read(self, devno)
+    }
+
+    #[inline]
+    pub fn uevent(&self, devno: &Devno) -> Result<Uevent> {
+        self.sysfs().uevent(devno)
+    }
+
+    #[inline]
+    pub fn trigger_uevent(&self, devno: &Devno, action: UeventAction) -> Result<()> {
+        self.sysfs().trigger_uevent(devno, action)
     }
 
     #[inline]
-    pub fn is_type(&self, devno: &Devno, ty: impl AsRef<str>) -> io::Result<bool> {
+    pub fn is_type(&self, devno: &Devno, ty: impl AsRef<str>) -> Result<bool> {
         self.procfs().is_type(devno, ty)
     }
 
     #[inline]
-    pub fn is_device_mapper(&self, devno: &Devno) -> io::Result<bool> {
+    pub fn is_device_mapper(&self, devno: &Devno) -> Result<bool> {
         self.is_type(devno, "device-mapper")
     }
 
     #[inline]
-    pub fn dm_uuid(&self, devno: &Devno) -> io::Result<Option<String>> {
+    pub fn dm_uuid(&self, devno: &Devno) -> Result<Option<String>> {
         self.sysfs().dm_uuid(devno)
     }
 
     #[inline]
-    pub fn dm_type(&self, devno: &Devno) -> io::Result<Option<String>> {
+    pub fn dm_type(&self, devno: &Devno) -> Result<Option<String>> {
         self.sysfs().dm_type(devno)
     }
 
@@ -76,19 +194,18 @@ impl Blocks {
         &self,
         devno: &Devno,
         types: I,
-    ) -> io::Result<bool> {
+    ) -> Result<bool> {
         if self.is_device_mapper(devno)? {
             match self.dm_type(devno)? {
                 Some(t) => {
                     for t1 in types {
-                        println!("{} == {} -> {:?}", t, t1.as_ref(), t == t1.as_ref());
                         if t == t1.as_ref() {
                             return Ok(true);
                         }
                     }
                     Ok(false)
                 }
-                None => return Err(io::ErrorKind::InvalidData.into()),
+                None => Err(io::ErrorKind::InvalidData.into()),
             }
         } else {
             Ok(false)
@@ -96,12 +213,12 @@ impl Blocks {
     }
 
     #[inline]
-    pub fn is_dm_type<S: AsRef<str>>(&self, devno: &Devno, t: S) -> io::Result<bool> {
+    pub fn is_dm_type<S: AsRef<str>>(&self, devno: &Devno, t: S) -> Result<bool> {
         self.is_dm_types(devno, &[t])
     }
 
     #[inline]
-    pub fn is_luks(&self, devno: &Devno) -> io::Result<bool> {
+    pub fn is_luks(&self, devno: &Devno) -> Result<bool> {
         match self.dm_type(devno)? {
             Some(x) => Ok(x.starts_with("CRYPT-LUKS")),
             None => Ok(false),
@@ -109,32 +226,94 @@ impl Blocks {
     }
 
     #[inline]
-    pub fn is_luks2(&self, devno: &Devno) -> io::Result<bool> {
+    pub fn is_luks2(&self, devno: &Devno) -> Result<bool> {
         self.is_dm_type(devno, "CRYPT-LUKS2")
     }
 
     #[inline]
-    pub fn partition_number(&self, devno: &Devno) -> io::Result<Option<usize>> {
+    pub fn size(&self, devno: &Devno) -> Result<u64> {
+        self.sysfs().size(devno)
+    }
+
+    #[inline]
+    pub fn is_hidden(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().is_hidden(devno)
+    }
+
+    #[inline]
+    pub fn usb_info(&self, devno: &Devno) -> Result<Option<UsbInfo>> {
+        self.sysfs().usb_info(devno)
+    }
+
+    #[inline]
+    pub fn pci_address(&self, devno: &Devno) -> Result<Option<PciAddress>> {
+        self.sysfs().pci_address(devno)
+    }
+
+    #[inline]
+    pub fn numa_node(&self, devno: &Devno) -> Result<Option<u32>> {
+        self.sysfs().numa_node(devno)
+    }
+
+    #[inline]
+    pub fn mmc_info(&self, devno: &Devno) -> Result<Option<MmcInfo>> {
+        self.sysfs().mmc_info(devno)
+    }
+
+    #[inline]
+    pub fn virtio_info(&self, devno: &Devno) -> Result<Option<VirtioInfo>> {
+        self.sysfs().virtio_info(devno)
+    }
+
+    #[inline]
+    pub fn pmem_info(&self, devno: &Devno) -> Result<Option<PmemInfo>> {
+        self.sysfs().pmem_info(devno)
+    }
+
+    #[inline]
+    pub fn is_pmem(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().is_pmem(devno)
+    }
+
+    #[inline]
+    pub fn partition_number(&self, devno: &Devno) -> Result<Option<usize>> {
         self.sysfs().partition_number(devno)
     }
 
     #[inline]
-    pub fn is_partition(&self, devno: &Devno) -> io::Result<bool> {
+    pub fn is_partition(&self, devno: &Devno) -> Result<bool> {
         self.sysfs().is_partition(devno)
     }
 
     #[inline]
-    pub fn is_disk(&self, devno: &Devno) -> io::Result<bool> {
+    pub fn is_disk(&self, devno: &Devno) -> Result<bool> {
         self.is_partition(devno).map(|x| !x)
     }
 
-    pub fn partitions<'a>(&'a self, devno: &Devno) -> io::Result<iter::PartitionsIterator<'a>> {
+    #[inline]
+    pub fn partitions<'a>(&'a self, devno: &Devno) -> Result<iter::PartitionsIterator<'a>> {
+        self.partitions_in(BlocksRef::Borrowed(self), devno)
+    }
+
+    #[inline]
+    pub fn owned_partitions(
+        self: &Arc<Self>,
+        devno: &Devno,
+    ) -> Result<iter::PartitionsIterator<'static>> {
+        self.partitions_in(BlocksRef::Shared(Arc::clone(self)), devno)
+    }
+
+    fn partitions_in<'a>(
+        &self,
+        holder: BlocksRef<'a>,
+        devno: &Devno,
+    ) -> Result<iter::PartitionsIterator<'a>> {
         if self.is_disk(devno)? {
             let path = self.sysfs().resolve(devno)?;
 
             if self.is_type(devno, "device-mapper")? {
                 if self.is_luks(devno)? {
-                    iter::PartitionsIterator::masters(&self, *devno)
+                    iter::PartitionsIterator::masters(holder, *devno)
                 } else {
                     iter::PartitionsIterator::empty()
                 }
@@ -146,7 +325,7 @@ impl Blocks {
         }
     }
 
-    pub fn slaves(&self, devno: &Devno) -> io::Result<iter::SlavesIterator> {
+    pub fn slaves(&self, devno: &Devno) -> Result<iter::SlavesIterator> {
         let path = self.sysfs().resolve(devno)?;
 
         if self.is_luks(devno)? {
@@ -156,7 +335,7 @@ impl Blocks {
         }
     }
 
-    pub fn parent(&self, devno: &Devno) -> io::Result<Option<Devno>> {
+    pub fn parent(&self, devno: &Devno) -> Result<Option<Devno>> {
         if self.is_disk(devno)? {
             if self.is_luks(devno)? {
                 match iter::RawSlavesIterator::new(self.sysfs().resolve(devno)?.join("slaves"))?
@@ -191,24 +370,44 @@ impl Blocks {
         }
     }
 
-    pub fn from_path<P: AsRef<Path>>(&self, p: P) -> io::Result<Devno> {
-        let md = p.as_ref().metadata()?;
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_path<P: AsRef<Path>>(&self, p: P) -> Result<Devno> {
+        if let Some(devfs) = self.devfs.as_ref().filter(|devfs| devfs.is_listing()) {
+            return devfs
+                .lookup(p.as_ref())
+                .ok_or_else(|| Error::path(p.as_ref(), io::ErrorKind::NotFound.into()));
+        }
+
+        let md = p
+            .as_ref()
+            .metadata()
+            .map_err(|err| Error::path(p.as_ref(), err))?;
         if md.file_type().is_block_device() {
             Ok(md.rdev().into())
         } else {
-            Err(io::ErrorKind::InvalidInput.into())
+            Err(Error::NotBlockDevice {
+                path: p.as_ref().to_path_buf(),
+            })
         }
     }
 
     #[inline]
-    pub fn from_devno<B: Borrow<Devno>>(&self, d: B) -> io::Result<Devno> {
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_devno<B: Borrow<Devno>>(&self, d: B) -> Result<Devno> {
         let devno = d.borrow();
-        let _ = self.devfs().resolve(devno)?;
+        match self.devfs.as_ref() {
+            Some(devfs) => {
+                let _ = devfs.resolve(devno)?;
+            }
+            None => {
+                let _ = self.sysfs().resolve(devno)?;
+            }
+        }
         Ok(*devno)
     }
 
     #[inline]
-    pub fn resolve<B: Borrow<Devno>>(&self, d: B) -> io::Result<PathBuf> {
+    pub fn resolve<B: Borrow<Devno>>(&self, d: B) -> Result<PathBuf> {
         let devno = d.borrow();
         if self.is_device_mapper(devno)? {
             let name = match self.sysfs().dm_name(devno)? {
@@ -217,42 +416,569 @@ impl Blocks {
                     return Err(io::Error::new(
                         io::ErrorKind::InvalidData,
                         "Device mapper has no dm name",
-                    ))
+                    )
+                    .into())
                 }
             };
-            let dmpath = self.devfs().path().join("mapper").join(name);
+            let devfs = self.devfs()?;
+            let dmpath = devfs.path().join("mapper").join(name);
 
-            if dmpath.exists() {
+            if devfs.lookup(&dmpath).is_some() {
                 return Ok(dmpath);
             }
         }
 
-        self.devfs().resolve(devno)
+        self.devfs()?.resolve(devno)
+    }
+
+    // "vg/lv" is turned into the dm name LVM would have used
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_dm_name(&self, name: &str) -> Result<Devno> {
+        let name = match name.split_once('/') {
+            Some((vg, lv)) => naming::dm_name(vg, lv),
+            None => name.to_string(),
+        };
+        for devno in self.blocks()? {
+            let devno = devno?;
+            match self.sysfs().dm_name(&devno) {
+                Ok(Some(n)) if n == name => return Ok(devno),
+                Ok(_) | Err(Error::NotFound { .. }) => (),
+                Err(err) => return Err(err),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("no device-mapper device named {:?}", name),
+        )
+        .into())
+    }
+
+    // the kernel's own node, /dev/dm-0 rather than its /dev/mapper alias
+    #[inline]
+    pub fn devfs_path(&self, devno: &Devno) -> Result<PathBuf> {
+        self.devfs()?.resolve(devno)
+    }
+
+    #[inline]
+    pub fn sysfs_path(&self, devno: &Devno) -> Result<PathBuf> {
+        self.sysfs().resolve(devno)
     }
 
     #[inline]
-    pub fn disks(&self) -> io::Result<DisksIterator> {
+    pub fn disks(&self) -> Result<DisksIterator> {
         self.sysfs().disks()
     }
 
     #[inline]
-    pub fn blocks(&self) -> io::Result<BlocksIterator> {
+    pub fn blocks(&self) -> Result<BlocksIterator> {
         self.sysfs().blocks()
     }
 
-    pub fn reread_partition_table(&self, devno: &Devno) -> io::Result<()> {
-        let p = self.devfs().resolve(devno)?;
-        let f = OpenOptions::new()
+    pub(crate) fn open(&self, devno: &Devno, write: bool) -> Result<File> {
+        let p = self.live_node(devno)?;
+        OpenOptions::new()
             .read(true)
-            .write(false)
+            .write(write)
             .create_new(false)
             .truncate(false)
             .create(false)
             .append(false)
-            .open(p)?;
-        let ret = unsafe { libc::ioctl(f.as_raw_fd() as _, BLKRRPART) };
+            .open(&p)
+            .map_err(|err| Error::path(p, err))
+    }
+
+    pub fn clone_to_file<P: AsRef<Path>, F: FnMut(&CloneProgress)>(
+        &self,
+        devno: &Devno,
+        p: P,
+        opts: CloneOptions,
+        progress: F,
+    ) -> Result<CloneProgress> {
+        let src = self.open(devno, false)?;
+        let p = p.as_ref();
+        let dst = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(p)
+            .map_err(|err| Error::path(p, err))?;
+        clone::copy(&src, &dst, opts, progress)
+    }
+
+    pub fn clone_to_device<F: FnMut(&CloneProgress)>(
+        &self,
+        devno: &Devno,
+        target: &Devno,
+        opts: CloneOptions,
+        progress: F,
+    ) -> Result<CloneProgress> {
+        if self.whole_disk(devno)? == self.whole_disk(target)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "source and target are on the same disk",
+            )
+            .into());
+        }
+        if self.in_use(target)? {
+            return Err(
+                io::Error::new(io::ErrorKind::ResourceBusy, "target device is in use").into(),
+            );
+        }
+        let src = self.open(devno, false)?;
+        // O_EXCL on a block device fails with EBUSY while anything, a
+        // partition of it included, holds the device
+        let p = self.live_node(target)?;
+        let dst = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_EXCL)
+            .open(&p)
+            .map_err(|err| Error::path(p, err))?;
+        clone::copy(&src, &dst, opts, progress)
+    }
+
+    pub fn fingerprint(&self, devno: &Devno, opts: FingerprintOptions) -> Result<Fingerprint> {
+        let sector_size = self.sysfs().logical_block_size(&self.whole_disk(devno)?)?;
+        fingerprint::fingerprint(&self.open(devno, false)?, sector_size, opts)
+    }
+
+    pub fn open_direct(&self, devno: &Devno, write: bool) -> Result<DeviceHandle> {
+        let p = self.live_node(devno)?;
+        let f = OpenOptions::new()
+            .read(true)
+            .write(write)
+            .custom_flags(libc::O_DIRECT)
+            .open(&p)
+            .map_err(|err| Error::path(p, err))?;
+        // partitions have no queue of their own
+        let block_size = self.sysfs().logical_block_size(&self.whole_disk(devno)?)?;
+        DeviceHandle::new(f, block_size)
+    }
+
+    pub fn whole_disk(&self, devno: &Devno) -> Result<Devno> {
+        if self.is_partition(devno)? {
+            match self.parent(devno)? {
+                Some(parent) => Ok(parent),
+                None => Err(io::ErrorKind::NotFound.into()),
+            }
+        } else {
+            Ok(*devno)
+        }
+    }
+
+    #[inline]
+    pub fn kind(&self, devno: &Devno) -> Result<DeviceKind> {
+        Ok(DeviceKind::from_name(&self.sysfs().name(devno)?))
+    }
+
+    // io.stat only accounts whole disks, partitions have no entry of their own
+    pub fn cgroup_usage(&self, devno: &Devno, cgroup: &Cgroup) -> Result<Option<IoStat>> {
+        if self.is_partition(devno)? {
+            return Ok(None);
+        }
+        Ok(cgroup.io_stat()?.remove(devno))
+    }
+
+    // io.max only accepts whole disks, a limit on a partition would throttle its siblings too
+    pub fn set_io_limit(&self, devno: &Devno, cgroup: &Cgroup, limit: &IoMax) -> Result<()> {
+        if self.is_partition(devno)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "I/O limits can only be set on whole disks",
+            )
+            .into());
+        }
+        cgroup.set_io_max(devno, limit)
+    }
+
+    pub fn capabilities(&self, devno: &Devno) -> Result<Capabilities> {
+        let raw = self.sysfs().capability(&self.whole_disk(devno)?)?;
+        Ok(Capabilities::from_raw(
+            raw.unwrap_or(0),
+            self.procfs().kernel_version()?,
+        ))
+    }
+
+    #[inline]
+    pub fn is_virtual(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().is_virtual(devno)
+    }
+
+    #[inline]
+    pub fn is_read_only(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().is_read_only(devno)
+    }
+
+    #[inline]
+    pub fn is_removable(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().is_removable(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn supports_dax(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().supports_dax(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn is_rotational(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().is_rotational(&self.whole_disk(devno)?)
+    }
+
+    pub fn in_use(&self, devno: &Devno) -> Result<bool> {
+        if !self.mounts(devno)?.is_empty() || self.sysfs().has_holders(devno)? {
+            return Ok(true);
+        }
+        if self.is_disk(devno)? {
+            for part in iter::PartitionsIterator::partitions(self.sysfs().resolve(devno)?)? {
+                let part = part?;
+                if !self.mounts(&part)?.is_empty() || self.sysfs().has_holders(&part)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    #[inline]
+    fn is_nvme(&self, devno: &Devno) -> Result<bool> {
+        Ok(self.sysfs().name(devno)?.starts_with("nvme"))
+    }
+
+    pub fn power_state(&self, devno: &Devno) -> Result<PowerState> {
+        let disk = self.whole_disk(devno)?;
+        let f = self.open(&disk, false)?;
+        if self.is_nvme(&disk)? {
+            power::nvme_power_state(&f)
+        } else {
+            power::ata_power_state(&f)
+        }
+    }
+
+    pub fn health(&self, devno: &Devno) -> Result<Health> {
+        let disk = self.whole_disk(devno)?;
+        match self.kind(&disk)? {
+            DeviceKind::Nvme => health::nvme_health(&self.open(&disk, false)?),
+            DeviceKind::Scsi => {
+                let vendor =
+                    sysfs::read_attr(self.sysfs().resolve(&disk)?.join("device").join("vendor"))?;
+                let f = self.open(&disk, false)?;
+                // libata presents SATA disks as SCSI ones, SMART is still ATA
+                if vendor.as_deref() == Some("ATA") {
+                    health::ata_health(&f)
+                } else {
+                    health::scsi_health(&f)
+                }
+            }
+            kind => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("no health reporting for {:?} devices", kind),
+            )
+            .into()),
+        }
+    }
+
+    pub fn standby_now(&self, devno: &Devno) -> Result<()> {
+        let disk = self.whole_disk(devno)?;
+        if self.is_nvme(&disk)? {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "NVMe devices manage their power states autonomously",
+            )
+            .into());
+        }
+        let f = self.open(&disk, false)?;
+        power::ata_standby_now(&f)
+    }
+
+    #[inline]
+    pub fn swap_header(&self, devno: &Devno) -> Result<Option<SwapHeader>> {
+        SwapHeader::read(&self.open(devno, false)?)
+    }
+
+    #[inline]
+    pub fn swapon(&self, devno: &Devno, priority: Option<i32>) -> Result<()> {
+        swap::swapon(&self.live_node(devno)?, priority)
+    }
+
+    #[inline]
+    pub fn swapoff(&self, devno: &Devno) -> Result<()> {
+        swap::swapoff(&self.live_node(devno)?)
+    }
+
+    #[inline]
+    pub fn is_swap_active(&self, devno: &Devno) -> Result<bool> {
+        swap::is_active(self.procfs().path(), devno)
+    }
+
+    #[inline]
+    pub fn unmount_all(&self, devno: &Devno, opts: UnmountOptions) -> Result<Vec<MountInfo>> {
+        unmount::unmount_all(self, devno, opts)
+    }
+
+    #[inline]
+    pub fn fs_usage(&self, devno: &Devno) -> Result<Option<FsUsage>> {
+        usage::fs_usage(&self.mounts(devno)?)
+    }
+
+    // btrfs subvolumes report an anonymous dev, fall back to the mount source
+    pub fn mount_devno(&self, mount: &MountInfo) -> Result<Devno> {
+        match self.from_devno(mount.dev) {
+            Ok(devno) => Ok(devno),
+            Err(Error::NotFound { .. }) => match mount.source.as_deref() {
+                Some(source) if source.starts_with('/') => self.from_path(source),
+                _ => Err(Error::NotFound { devno: mount.dev }),
+            },
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn mounts(&self, devno: &Devno) -> Result<Vec<MountInfo>> {
+        let mut res = Vec::new();
+        for info in self.procfs().mounts().iter()? {
+            let info = info?;
+            if info.dev == *devno {
+                res.push(info);
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn eject(&self, devno: &Devno, power_off: bool) -> Result<(), EjectError> {
+        let disk = self.whole_disk(devno).map_err(EjectError::Resolve)?;
+        let name = self.sysfs().name(&disk).map_err(EjectError::Resolve)?;
+        let path = self.live_node(&disk).map_err(EjectError::Resolve)?;
+
+        let mut mounts = self.mounts(&disk).map_err(EjectError::Resolve)?;
+        for part in self.partitions(&disk).map_err(EjectError::Resolve)? {
+            let part = part.map_err(EjectError::Resolve)?;
+            mounts.extend(self.mounts(&part).map_err(EjectError::Resolve)?);
+        }
+        mounts.sort_by_key(|m| std::cmp::Reverse(m.mount_point.components().count()));
+
+        let f = OpenOptions::new()
+            .read(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&path)
+            .map_err(|err| EjectError::Resolve(Error::path(path, err)))?;
+
+        eject::sync(&f).map_err(EjectError::Sync)?;
+        for mount in mounts {
+            eject::umount(&mount.mount_point, 0).map_err(|error| EjectError::Unmount {
+                mount_point: mount.mount_point,
+                error,
+            })?;
+        }
+        eject::flush_buffers(&f).map_err(EjectError::Flush)?;
+
+        if name.starts_with("sr") {
+            eject::cdrom_eject(&f).map_err(EjectError::Eject)?;
+        } else {
+            scsi::eject(&f).map_err(EjectError::Eject)?;
+        }
+        drop(f);
+
+        if power_off {
+            match self
+                .sysfs()
+                .usb_device_path(&disk)
+                .map_err(EjectError::PowerOff)?
+            {
+                Some(usb) => {
+                    sysfs::write_attr(usb.join("remove"), "1").map_err(EjectError::PowerOff)?
+                }
+                None => {
+                    return Err(EjectError::PowerOff(
+                        io::Error::new(
+                            io::ErrorKind::Unsupported,
+                            "device is not attached to a USB port",
+                        )
+                        .into(),
+                    ))
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn events(&self, devno: &Devno) -> Result<Vec<DiskEvent>> {
+        self.sysfs().events(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn write_cache(&self, devno: &Devno) -> Result<Option<WriteCache>> {
+        self.sysfs().write_cache(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn set_write_cache(&self, devno: &Devno, mode: WriteCache) -> Result<()> {
+        self.sysfs().set_write_cache(&self.whole_disk(devno)?, mode)
+    }
+
+    #[inline]
+    pub fn fua(&self, devno: &Devno) -> Result<bool> {
+        self.sysfs().fua(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn queue_tuning(&self, devno: &Devno) -> Result<QueueTuning> {
+        self.sysfs().queue_tuning(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn set_queue_tuning(&self, devno: &Devno, tuning: &QueueTuning) -> Result<()> {
+        self.sysfs()
+            .set_queue_tuning(&self.whole_disk(devno)?, tuning)
+    }
+
+    #[inline]
+    pub fn events_poll_msecs(&self, devno: &Devno) -> Result<Option<u64>> {
+        self.sysfs().events_poll_msecs(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn set_events_poll_msecs(&self, devno: &Devno, ms: Option<u64>) -> Result<()> {
+        self.sysfs()
+            .set_events_poll_msecs(&self.whole_disk(devno)?, ms)
+    }
+
+    #[inline]
+    pub fn diskseq(&self, devno: &Devno) -> Result<Option<u64>> {
+        self.sysfs().diskseq(&self.whole_disk(devno)?)
+    }
+
+    pub fn check_media_changed(&self, devno: &Devno) -> Result<bool> {
+        let disk = self.whole_disk(devno)?;
+        if self.sysfs().name(&disk)?.starts_with("sr") {
+            let f = OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(self.live_node(&disk)?)?;
+            return media::cdrom_media_changed(&f);
+        }
+
+        // the kernel bumps diskseq on every media change it reports through
+        // the events mechanism, so compare against the last one we saw
+        let unsupported = |msg| Err(io::Error::new(io::ErrorKind::Unsupported, msg).into());
+        if !self
+            .sysfs()
+            .events(&disk)?
+            .contains(&DiskEvent::MediaChange)
+        {
+            return unsupported("device does not report media change events");
+        }
+        let seq = match self.sysfs().diskseq(&disk)? {
+            Some(seq) => seq,
+            None => return unsupported("kernel does not expose diskseq"),
+        };
+        let mut seqs = self.media_seqs.lock().unwrap();
+        Ok(seqs.insert(disk, seq).is_some_and(|old| old != seq))
+    }
+
+    #[inline]
+    pub fn delete(&self, devno: &Devno) -> Result<()> {
+        self.sysfs().delete(&self.whole_disk(devno)?)
+    }
+
+    #[inline]
+    pub fn rescan(&self, devno: &Devno) -> Result<()> {
+        self.sysfs().rescan(&self.whole_disk(devno)?)
+    }
+
+    pub fn refresh_size(&self, devno: &Devno) -> Result<SizeChange> {
+        let old = self.size(devno)?;
+        let current = resize::blk_getsize64(&self.open(devno, false)?)?;
+
+        if old != current {
+            let disk = self.whole_disk(devno)?;
+            match self.sysfs().rescan(&disk) {
+                Ok(()) => (),
+                Err(err) if err.kind() == io::ErrorKind::Unsupported => {
+                    self.reread_partition_table(&disk)?
+                }
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(SizeChange::new(old, self.size(devno)?))
+    }
+
+    pub fn partition_table(&self, devno: &Devno) -> Result<Option<PartitionTable>> {
+        let disk = self.whole_disk(devno)?;
+        let f = self.open(&disk, false)?;
+        PartitionTable::read(&f, self.sysfs().logical_block_size(&disk)?)
+    }
+
+    pub fn partition_table_layout(&self, devno: &Devno) -> Result<Option<TableLayout>> {
+        let disk = self.whole_disk(devno)?;
+        let f = self.open(&disk, false)?;
+        TableLayout::probe(&f, self.sysfs().logical_block_size(&disk)?)
+    }
+
+    pub fn partition_table_type(&self, devno: &Devno) -> Result<Option<PartitionTableType>> {
+        let disk = self.whole_disk(devno)?;
+        // raw reads usually need privileges, udev has mostly probed already
+        if let Some(record) = self.udev_properties(&disk)? {
+            if let Some(ty) = record
+                .get("ID_PART_TABLE_TYPE")
+                .and_then(PartitionTableType::from_udev)
+            {
+                return Ok(Some(ty));
+            }
+        }
+        Ok(self
+            .partition_table_layout(&disk)?
+            .map(PartitionTableType::from))
+    }
+
+    #[inline]
+    pub fn fs_state(&self, devno: &Devno) -> Result<Option<FsState>> {
+        fsstate::probe(&self.open(devno, false)?)
+    }
+
+    pub fn gpt_table(&self, devno: &Devno) -> Result<Option<GptTable>> {
+        let disk = self.whole_disk(devno)?;
+        let f = self.open(&disk, false)?;
+        GptTable::read(&f, self.sysfs().logical_block_size(&disk)?)
+    }
+
+    pub fn repair_gpt_backup(&self, devno: &Devno) -> Result<()> {
+        let disk = self.whole_disk(devno)?;
+        let f = self.open(&disk, true)?;
+        match GptTable::read(&f, self.sysfs().logical_block_size(&disk)?)? {
+            Some(table) => table.repair_backup(&f),
+            None => Err(io::Error::new(io::ErrorKind::InvalidInput, "no GPT found").into()),
+        }
+    }
+
+    pub fn write_partition_table(
+        &self,
+        devno: &Devno,
+        table: &PartitionTable,
+    ) -> Result<RescanOutcome> {
+        let disk = self.whole_disk(devno)?;
+        if table.sector_size != self.sysfs().logical_block_size(&disk)? {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "partition table sector size does not match the disk",
+            )
+            .into());
+        }
+        table.write(&self.open(&disk, true)?)?;
+        self.rescan_partitions(&disk, RescanOptions::default())
+    }
+
+    #[inline]
+    pub fn rescan_partitions(&self, devno: &Devno, opts: RescanOptions) -> Result<RescanOutcome> {
+        rescan::rescan(self, &self.whole_disk(devno)?, opts)
+    }
+
+    pub fn reread_partition_table(&self, devno: &Devno) -> Result<()> {
+        let f = self.open(devno, false)?;
+        ioctl::allowed("BLKRRPART")?;
+        let ret = unsafe { libc::ioctl(f.as_raw_fd(), ioctl::BLKRRPART as _) };
         if ret < 0 {
-            Err(io::Error::last_os_error())
+            Err(Error::ioctl("BLKRRPART", io::Error::last_os_error()))
         } else {
             Ok(())
         }
@@ -0,0 +1,198 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io,
+    os::unix::fs::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    blocks,
+    iter::RawSlavesIterator,
+    mountpoint::mount_device,
+    parttable::{read_at, PartitionType},
+    sysfs::read_attr,
+    Blocks, Device, Devno, Error, Result,
+};
+
+const ESP_MOUNT_POINTS: [&str; 3] = ["/boot/efi", "/efi", "/boot"];
+
+const FAT_SIGNATURE_OFFSET: usize = 510;
+// BS_FilSysType, where FAT12/16 and FAT32 boot sectors keep it
+const FAT_TYPE_OFFSETS: [usize; 2] = [54, 82];
+
+fn udev_part_type(device: &Device) -> Result<Option<PartitionType>> {
+    let record = match device.udev_properties()? {
+        Some(record) => record,
+        None => return Ok(None),
+    };
+    Ok(match record.get("ID_PART_ENTRY_TYPE") {
+        Some(ty) => match ty.strip_prefix("0x") {
+            Some(hex) => u8::from_str_radix(hex, 16).ok().map(PartitionType::Mbr),
+            None => ty.parse().ok().map(PartitionType::Gpt),
+        },
+        None => None,
+    })
+}
+
+fn has_fat_signature(f: &File) -> Result<bool> {
+    let sector = match read_at(f, 0, 512) {
+        Ok(sector) => sector,
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    Ok(matches!(sector[0], 0xeb | 0xe9)
+        && sector[FAT_SIGNATURE_OFFSET..FAT_SIGNATURE_OFFSET + 2] == [0x55, 0xaa]
+        && FAT_TYPE_OFFSETS
+            .iter()
+            .any(|off| sector[*off..*off + 3] == *b"FAT"))
+}
+
+// the partition type says ESP and a FAT filesystem is actually there
+pub(crate) fn is_esp(blocks: &blocks::Blocks, device: &Device) -> Result<bool> {
+    let number = match device.partition_number()? {
+        Some(number) => number,
+        None => return Ok(false),
+    };
+    let part_type = match udev_part_type(device)? {
+        Some(ty) => ty,
+        None => match device.partition_table()? {
+            Some(table) => match table.get(number as u32) {
+                Some(entry) => entry.part_type,
+                None => return Ok(false),
+            },
+            None => return Ok(false),
+        },
+    };
+    if !part_type.is_esp() {
+        return Ok(false);
+    }
+
+    // raw reads usually need privileges, trust what udev probed
+    if let Some(fs_type) = device
+        .udev_properties()?
+        .and_then(|r| r.fs_type().map(str::to_string))
+    {
+        return Ok(fs_type == "vfat");
+    }
+    has_fat_signature(&blocks.open(&device.to_devno(), false)?)
+}
+
+// the device holding the filesystem a loop device's backing file lives on
+fn loop_backing(blocks: &blocks::Blocks, dir: &Path) -> Result<Option<Devno>> {
+    let file = match read_attr(dir.join("loop").join("backing_file"))? {
+        Some(file) if !file.ends_with(" (deleted)") => PathBuf::from(file),
+        _ => return Ok(None),
+    };
+    let dev: Devno = match file.metadata() {
+        // loops stacked on block devices point at the node itself
+        Ok(md) if md.file_type().is_block_device() => return Ok(Some(md.rdev().into())),
+        Ok(md) => md.dev().into(),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::path(file, err)),
+    };
+
+    match blocks.sysfs().resolve(&dev) {
+        Ok(_) => return Ok(Some(dev)),
+        Err(Error::NotFound { .. }) => (),
+        Err(err) => return Err(err),
+    }
+    // btrfs and friends hand out anonymous devs, the mount source knows better
+    let mount = blocks.procfs().mountinfo_from_path(&file)?;
+    match mount.source.as_deref() {
+        Some(source) if source.starts_with('/') => match blocks.from_path(source) {
+            Ok(devno) => Ok(Some(devno)),
+            Err(Error::NotBlockDevice { .. }) => Ok(None),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        },
+        _ => Ok(None),
+    }
+}
+
+// whole disks at the bottom of the dm/md/loop stack holding `devno`
+pub(crate) fn backing_disks(blocks: &blocks::Blocks, devno: &Devno) -> Result<Vec<Devno>> {
+    let mut seen = BTreeSet::new();
+    let mut res = BTreeSet::new();
+    let mut stack = vec![*devno];
+    while let Some(devno) = stack.pop() {
+        // partitions have no slaves, their disk might
+        let disk = blocks.whole_disk(&devno)?;
+        if !seen.insert(disk) {
+            continue;
+        }
+
+        let dir = blocks.sysfs().resolve(&disk)?;
+        let slaves = dir.join("slaves");
+        let before = stack.len();
+        if slaves.is_dir() {
+            for slave in RawSlavesIterator::new(slaves)? {
+                stack.push(slave?);
+            }
+        }
+        if dir.join("loop").is_dir() {
+            // files on virtual filesystems are not backed by any disk
+            stack.extend(loop_backing(blocks, &dir)?);
+        } else if stack.len() == before {
+            res.insert(disk);
+        }
+    }
+    Ok(res.into_iter().collect())
+}
+
+pub(crate) fn boot_disks(blocks: &Blocks) -> Result<Vec<Device>> {
+    let root = blocks.from_mountpoint("/")?.device;
+    backing_disks(&blocks.0, &root.to_devno())?
+        .into_iter()
+        .map(|devno| blocks.from_devno(devno))
+        .collect()
+}
+
+pub(crate) fn esp(blocks: &Blocks) -> Result<Option<Device>> {
+    for p in ESP_MOUNT_POINTS {
+        let mount = match blocks.mountinfo_from_path(p) {
+            Ok(mount) => mount,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err),
+        };
+        // a plain directory on the root filesystem resolves to the root mount
+        if mount.mount_point != Path::new(p) || mount.file_system != "vfat" {
+            continue;
+        }
+        match mount_device(blocks, &mount) {
+            Ok(device) => return Ok(Some(device)),
+            Err(Error::NotFound { .. }) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    // not mounted, look for the partition type on the disks holding /
+    for disk in boot_disks(blocks)? {
+        let parts = disk.partitions()?.collect::<Result<Vec<_>>>()?;
+        let mut unknown = false;
+        for part in &parts {
+            match udev_part_type(part)? {
+                Some(ty) if ty.is_esp() => return Ok(Some(part.clone())),
+                Some(_) => (),
+                None => unknown = true,
+            }
+        }
+        // raw reads usually need privileges, only fall back when udev had no answer
+        if !unknown {
+            continue;
+        }
+
+        let table = match disk.partition_table()? {
+            Some(table) => table,
+            None => continue,
+        };
+        for entry in &table.entries {
+            if entry.part_type.is_esp() {
+                if let Some(part) = disk.partition_by_number(entry.number as usize)? {
+                    return Ok(Some(part));
+                }
+            }
+        }
+    }
+    Ok(None)
+}
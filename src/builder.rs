@@ -0,0 +1,84 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    blocks, devfs::DevFs, procfs::ProcFs, sysfs::SysFs, udevdb::UdevDb, Blocks, Error, Result,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct BlocksBuilder {
+    procfs: Option<PathBuf>,
+    sysfs: Option<PathBuf>,
+    devfs: Option<PathBuf>,
+    no_devfs: bool,
+    udevdb: Option<PathBuf>,
+}
+
+impl BlocksBuilder {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn procfs<P: AsRef<Path>>(mut self, p: P) -> Self {
+        self.procfs = Some(p.as_ref().to_path_buf());
+        self
+    }
+
+    #[inline]
+    pub fn sysfs<P: AsRef<Path>>(mut self, p: P) -> Self {
+        self.sysfs = Some(p.as_ref().to_path_buf());
+        self
+    }
+
+    #[inline]
+    pub fn devfs<P: AsRef<Path>>(mut self, p: P) -> Self {
+        self.devfs = Some(p.as_ref().to_path_buf());
+        self.no_devfs = false;
+        self
+    }
+
+    #[inline]
+    pub fn without_devfs(mut self) -> Self {
+        self.devfs = None;
+        self.no_devfs = true;
+        self
+    }
+
+    #[inline]
+    pub fn udevdb<P: AsRef<Path>>(mut self, p: P) -> Self {
+        self.udevdb = Some(p.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn build(self) -> Result<Blocks> {
+        let procfs = match self.procfs {
+            Some(p) => ProcFs::with_root(p)?,
+            None => ProcFs::new()?,
+        };
+        let sysfs = match self.sysfs {
+            Some(p) => SysFs::with_path(p),
+            None => SysFs::new(&procfs)?,
+        };
+        let devfs = match self.devfs {
+            Some(p) => Some(DevFs::with_path(p)),
+            None if self.no_devfs => None,
+            None => match DevFs::new(&procfs) {
+                Ok(devfs) => Some(devfs),
+                Err(Error::DevfsNotFound) => None,
+                Err(err) => return Err(err),
+            },
+        };
+        let udevdb = match self.udevdb {
+            Some(p) => UdevDb::with_path(p),
+            None => UdevDb::new(),
+        };
+
+        Ok(Blocks::from_inner(Arc::new(blocks::Blocks::from_parts(
+            procfs, sysfs, devfs, udevdb,
+        ))))
+    }
+}
@@ -0,0 +1,43 @@
+// kernels before 5.17 used a different, larger set of GENHD_FL_* bits
+const REMAPPED_SINCE: (u32, u32) = (5, 17);
+
+crate::flags::flags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct Capabilities(u32) {
+        const REMOVABLE = 0x0001 => "removable";
+        const MEDIA_CHANGE_NOTIFY = 0x0004 => "media_change_notify";
+        const CD = 0x0008 => "cd";
+        const UP = 0x0010 => "up";
+        const SUPPRESS_PARTITION_INFO = 0x0020 => "suppress_partition_info";
+        const EXT_DEVT = 0x0040 => "ext_devt";
+        const NATIVE_CAPACITY = 0x0080 => "native_capacity";
+        const BLOCK_EVENTS_ON_EXCL_WRITE = 0x0100 => "block_events_on_excl_write";
+        const NO_PART_SCAN = 0x0200 => "no_part_scan";
+        const HIDDEN = 0x0400 => "hidden";
+    }
+}
+
+impl Capabilities {
+    // translates the raw sysfs value of the given kernel into the bits above,
+    // an unknown kernel is assumed to be a recent one
+    pub fn from_raw(raw: u32, kernel: Option<(u32, u32)>) -> Self {
+        if kernel.is_some_and(|v| v < REMAPPED_SINCE) {
+            return Self(raw);
+        }
+
+        let mut bits = raw & Self::REMOVABLE.0;
+        if raw & 0x0002 != 0 {
+            bits |= Self::HIDDEN.0;
+        }
+        if raw & 0x0004 != 0 {
+            bits |= Self::NO_PART_SCAN.0;
+        }
+        Self(bits)
+    }
+
+    #[inline]
+    pub const fn scans_partitions(&self) -> bool {
+        self.0 & (Self::NO_PART_SCAN.0 | Self::HIDDEN.0) == 0
+    }
+}
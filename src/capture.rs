@@ -0,0 +1,255 @@
+use std::{
+    collections::BTreeSet,
+    fmt::Write as _,
+    io,
+    os::unix::prelude::*,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    blocks::Blocks,
+    devfs::DevFs,
+    parse,
+    procfs::{Escaped, ProcFs},
+    sysfs::SysFs,
+    udevdb::UdevDb,
+    Error, Result,
+};
+
+const PROC_FILES: &[&str] = &[
+    "partitions",
+    "devices",
+    "diskstats",
+    "mdstat",
+    "swaps",
+    "self/mountinfo",
+    "sys/kernel/osrelease",
+];
+
+// per-device sysfs subdirectories nothing here reads
+const SYS_SKIP: &[&str] = &["power", "trace", "mq"];
+
+// sysfs gives text attributes a size of one page, larger files are binary
+// ones like PCI BARs and ROMs
+const SYS_ATTR_MAX: u64 = 4096;
+
+// where a /dev listing may be found, ours first then what sosreport leaves
+const DEV_LISTINGS: &[&str] = &[
+    "dev.list",
+    "sos_commands/block/ls_-lanR_.dev",
+    "sos_commands/devices/ls_-lanR_.dev",
+    "sos_commands/filesys/ls_-lanR_.dev",
+];
+
+fn dev_listing(dir: &Path) -> Option<PathBuf> {
+    DEV_LISTINGS
+        .iter()
+        .map(|name| dir.join(name))
+        .find(|p| p.is_file())
+}
+
+fn devfs(dir: &Path) -> Result<Option<DevFs>> {
+    if let Some(p) = dev_listing(dir) {
+        let content = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+        let listing = parse::devlist::from_str(&content).map_err(|err| Error::ParseError {
+            file: p,
+            line: Some(err.line),
+        })?;
+        return Ok(Some(DevFs::with_listing("/dev", listing)));
+    }
+
+    let p = dir.join("dev");
+    Ok(p.is_dir().then(|| DevFs::with_path(p)))
+}
+
+// dir/proc, dir/sys and dir/run/udev/data laid out as on the live system, /dev
+// either copied to dir/dev or described by a listing
+pub(crate) fn load(dir: &Path) -> Result<Blocks> {
+    let procfs = dir.join("proc");
+    if !procfs.is_dir() {
+        return Err(Error::ProcfsNotFound);
+    }
+    let sysfs = dir.join("sys");
+    if !sysfs.is_dir() {
+        return Err(Error::SysfsNotFound);
+    }
+
+    Ok(Blocks::from_parts(
+        ProcFs::with_root(procfs)?,
+        SysFs::with_path(sysfs),
+        devfs(dir)?,
+        UdevDb::with_path(dir.join("run").join("udev").join("data")),
+    )
+    .captured())
+}
+
+fn create_dir(p: &Path) -> Result<()> {
+    std::fs::create_dir_all(p).map_err(|err| Error::path(p, err))
+}
+
+fn write(p: &Path, content: &[u8]) -> Result<()> {
+    if let Some(parent) = p.parent() {
+        create_dir(parent)?;
+    }
+    std::fs::write(p, content).map_err(|err| Error::path(p, err))
+}
+
+fn symlink(target: &Path, p: &Path) -> Result<()> {
+    if let Some(parent) = p.parent() {
+        create_dir(parent)?;
+    }
+    match std::os::unix::fs::symlink(target, p) {
+        Err(err) if err.kind() != io::ErrorKind::AlreadyExists => Err(Error::path(p, err)),
+        _ => Ok(()),
+    }
+}
+
+fn copy_file(src: &Path, dst: &Path) -> Result<()> {
+    match std::fs::read(src) {
+        Ok(content) => write(dst, &content),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(Error::path(src, err)),
+    }
+}
+
+// write-only and failing attributes are left out, partitions are directories
+// of their own and copied with their device
+fn copy_sys_dir(src: &Path, dst: &Path) -> Result<()> {
+    create_dir(dst)?;
+    let entries = std::fs::read_dir(src).map_err(|err| Error::path(src, err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| Error::path(src, err))?;
+        let ty = entry
+            .file_type()
+            .map_err(|err| Error::path(entry.path(), err))?;
+        let (from, to) = (entry.path(), dst.join(entry.file_name()));
+        if ty.is_symlink() {
+            let target = std::fs::read_link(&from).map_err(|err| Error::path(&from, err))?;
+            symlink(&target, &to)?;
+        } else if ty.is_dir() {
+            let skip = SYS_SKIP.iter().any(|name| entry.file_name() == *name);
+            if !skip && !from.join("dev").exists() {
+                copy_sys_dir(&from, &to)?;
+            }
+        } else if let Ok(content) = std::fs::read(&from) {
+            write(&to, &content)?;
+        }
+    }
+    Ok(())
+}
+
+// the attributes and links of a parent device, its children are left out
+fn copy_sys_attrs(src: &Path, dst: &Path) -> Result<()> {
+    create_dir(dst)?;
+    let entries = std::fs::read_dir(src).map_err(|err| Error::path(src, err))?;
+    for entry in entries {
+        let entry = entry.map_err(|err| Error::path(src, err))?;
+        let (from, to) = (entry.path(), dst.join(entry.file_name()));
+        let md = std::fs::symlink_metadata(&from).map_err(|err| Error::path(&from, err))?;
+        if md.file_type().is_symlink() {
+            let target = std::fs::read_link(&from).map_err(|err| Error::path(&from, err))?;
+            symlink(&target, &to)?;
+        } else if md.is_file() && md.len() <= SYS_ATTR_MAX {
+            if let Ok(content) = std::fs::read(&from) {
+                write(&to, &content)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// model, serial, wwid and the transport (usb, virtio, mmc...) live on the
+// devices above the block directory, reached through its `device` link
+fn copy_sys_parents(
+    sysfs: &Path,
+    path: &Path,
+    dir: &Path,
+    done: &mut BTreeSet<PathBuf>,
+) -> Result<()> {
+    let devices = sysfs.join("devices");
+    let target = path.join("device").canonicalize().ok();
+    let parents = path
+        .ancestors()
+        .skip(1)
+        .chain(target.iter().flat_map(|t| t.ancestors()));
+    for parent in parents {
+        if !parent.starts_with(&devices) || parent == devices || !done.insert(parent.to_path_buf())
+        {
+            continue;
+        }
+        if let Ok(rel) = parent.strip_prefix(sysfs) {
+            copy_sys_attrs(parent, &dir.join("sys").join(rel))?;
+        }
+    }
+    Ok(())
+}
+
+fn dev_list(blocks: &Blocks) -> Result<String> {
+    let devfs = blocks.devfs()?;
+    let mut nodes = devfs.iter()?.collect::<Result<Vec<_>>>()?;
+    nodes.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut res = String::new();
+    for node in nodes.iter() {
+        let _ = writeln!(
+            res,
+            "{} {}",
+            node.devno,
+            Escaped(node.path.as_os_str().as_bytes())
+        );
+    }
+    for link in devfs.links()? {
+        if let Some(node) = nodes.iter().find(|node| node.devno == link.devno) {
+            let _ = writeln!(
+                res,
+                "{} {} -> {}",
+                link.devno,
+                Escaped(link.path.as_os_str().as_bytes()),
+                Escaped(node.path.as_os_str().as_bytes())
+            );
+        }
+    }
+    Ok(res)
+}
+
+// the layout load() reads back, packing it is left to tar
+pub(crate) fn save(blocks: &Blocks, dir: &Path) -> Result<()> {
+    let procfs = blocks.procfs().path();
+    for name in PROC_FILES {
+        copy_file(&procfs.join(name), &dir.join("proc").join(name))?;
+    }
+
+    let sysfs = blocks.sysfs().path();
+    let udevdb = blocks.udevdb().path();
+    let mut parents = BTreeSet::new();
+    for devno in blocks.blocks()? {
+        let devno = devno?;
+        let path = blocks.sysfs().resolve(&devno)?;
+        let rel = match path.strip_prefix(sysfs) {
+            Ok(rel) => rel,
+            Err(_) => continue,
+        };
+        copy_sys_dir(&path, &dir.join("sys").join(rel))?;
+        copy_sys_parents(sysfs, &path, dir, &mut parents)?;
+        symlink(
+            &Path::new("../..").join(rel),
+            &dir.join("sys/dev/block").join(devno.to_string()),
+        )?;
+        if !blocks.is_partition(&devno)? {
+            if let Some(name) = rel.file_name() {
+                symlink(
+                    &Path::new("..").join(rel),
+                    &dir.join("sys/block").join(name),
+                )?;
+            }
+        }
+
+        let name = format!("b{}", devno);
+        copy_file(&udevdb.join(&name), &dir.join("run/udev/data").join(name))?;
+    }
+
+    if blocks.has_devfs() {
+        write(&dir.join("dev.list"), dev_list(blocks)?.as_bytes())?;
+    }
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{procfs::ProcFs, Blocks, Device, Devno, Error, Result};
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+const CGROUP_UNIFIED: &str = "/sys/fs/cgroup/unified";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoStat {
+    pub rbytes: u64,
+    pub wbytes: u64,
+    pub rios: u64,
+    pub wios: u64,
+    pub dbytes: u64,
+    pub dios: u64,
+}
+
+impl IoStat {
+    #[inline]
+    pub fn bytes(&self) -> u64 {
+        self.rbytes + self.wbytes + self.dbytes
+    }
+
+    #[inline]
+    pub fn ios(&self) -> u64 {
+        self.rios + self.wios + self.dios
+    }
+}
+
+// a missing limit is written as "max" by the kernel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct IoMax {
+    pub rbps: Option<u64>,
+    pub wbps: Option<u64>,
+    pub riops: Option<u64>,
+    pub wiops: Option<u64>,
+}
+
+impl IoMax {
+    #[inline]
+    pub fn is_unlimited(&self) -> bool {
+        self.rbps.is_none() && self.wbps.is_none() && self.riops.is_none() && self.wiops.is_none()
+    }
+}
+
+impl fmt::Display for IoMax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let limits = [
+            ("rbps", self.rbps),
+            ("wbps", self.wbps),
+            ("riops", self.riops),
+            ("wiops", self.wiops),
+        ];
+        for (i, (key, value)) in limits.into_iter().enumerate() {
+            if i != 0 {
+                f.write_str(" ")?;
+            }
+            match value {
+                Some(value) => write!(f, "{}={}", key, value)?,
+                None => write!(f, "{}=max", key)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceUsage {
+    pub device: Device,
+    pub stat: IoStat,
+    pub limit: Option<IoMax>,
+}
+
+fn parse_keyed<T: Default>(
+    path: &Path,
+    mut set: impl FnMut(&mut T, &str, &str) -> Option<()>,
+) -> Result<HashMap<Devno, T>> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(err) => return Err(Error::path(path, err)),
+    };
+
+    let mut res = HashMap::new();
+    for (i, line) in content.lines().enumerate() {
+        let err = || Error::ParseError {
+            file: path.to_path_buf(),
+            line: Some(i + 1),
+        };
+        let mut it = line.split_whitespace();
+        let devno: Devno = match it.next() {
+            Some(devno) => devno.parse().map_err(|_| err())?,
+            None => continue,
+        };
+
+        let mut value = T::default();
+        for pair in it {
+            let (key, v) = pair.split_once('=').ok_or_else(err)?;
+            set(&mut value, key, v).ok_or_else(err)?;
+        }
+        res.insert(devno, value);
+    }
+    Ok(res)
+}
+
+fn parse_limit(value: &str) -> Option<Option<u64>> {
+    match value {
+        "max" => Some(None),
+        value => value.parse().ok().map(Some),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    #[inline]
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Self {
+        Self {
+            path: p.as_ref().to_path_buf(),
+        }
+    }
+
+    // where the cgroup v2 hierarchy is mounted in procfs' mount namespace
+    pub fn root(procfs: &ProcFs) -> Result<PathBuf> {
+        procfs
+            .mounts()
+            .find_mount_point(
+                |m| m.file_system == "cgroup2",
+                &[CGROUP_ROOT, CGROUP_UNIFIED],
+            )?
+            .ok_or_else(|| Error::path(CGROUP_ROOT, io::ErrorKind::NotFound.into()))
+    }
+
+    // names are relative to the cgroup v2 hierarchy, as in /proc/<pid>/cgroup
+    pub fn from_name_in<P: AsRef<Path>, S: AsRef<str>>(root: P, name: S) -> Self {
+        match name.as_ref().trim_start_matches('/') {
+            "" => Self::with_path(root),
+            name => Self::with_path(root.as_ref().join(name)),
+        }
+    }
+
+    // assumes the hierarchy at its usual place, see Blocks::cgroup
+    pub fn from_name<S: AsRef<str>>(name: S) -> Self {
+        let root = Path::new(CGROUP_ROOT);
+        let root = if root.join("cgroup.controllers").exists() {
+            root
+        } else {
+            Path::new(CGROUP_UNIFIED)
+        };
+        Self::from_name_in(root, name)
+    }
+
+    pub fn current_in(procfs: &ProcFs) -> Result<Self> {
+        let p = procfs.path().join("self/cgroup");
+        let content = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+        let name = content
+            .lines()
+            .find_map(|line| line.strip_prefix("0::"))
+            .ok_or_else(|| Error::parse(&p))?;
+        Ok(Self::from_name_in(Self::root(procfs)?, name))
+    }
+
+    #[inline]
+    pub fn current() -> Result<Self> {
+        Self::current_in(&ProcFs::new()?)
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn io_stat(&self) -> Result<HashMap<Devno, IoStat>> {
+        parse_keyed(
+            &self.path.join("io.stat"),
+            |stat: &mut IoStat, key, value| {
+                let field = match key {
+                    "rbytes" => &mut stat.rbytes,
+                    "wbytes" => &mut stat.wbytes,
+                    "rios" => &mut stat.rios,
+                    "wios" => &mut stat.wios,
+                    "dbytes" => &mut stat.dbytes,
+                    "dios" => &mut stat.dios,
+                    // controller specific extras like cost.* or io latency
+                    _ => return Some(()),
+                };
+                *field = value.parse().ok()?;
+                Some(())
+            },
+        )
+    }
+
+    pub fn io_max(&self) -> Result<HashMap<Devno, IoMax>> {
+        parse_keyed(&self.path.join("io.max"), |max: &mut IoMax, key, value| {
+            let field = match key {
+                "rbps" => &mut max.rbps,
+                "wbps" => &mut max.wbps,
+                "riops" => &mut max.riops,
+                "wiops" => &mut max.wiops,
+                _ => return Some(()),
+            };
+            *field = parse_limit(value)?;
+            Some(())
+        })
+    }
+
+    pub fn set_io_max(&self, devno: &Devno, limit: &IoMax) -> Result<()> {
+        let limits = [limit.rbps, limit.wbps, limit.riops, limit.wiops];
+        if limits.contains(&Some(0)) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "I/O limits must be greater than zero",
+            )
+            .into());
+        }
+
+        let p = self.path.join("io.max");
+        std::fs::write(&p, format!("{} {}\n", devno, limit)).map_err(|err| Error::path(&p, err))?;
+
+        // fully unlimited devices are dropped from io.max
+        let current = self.io_max()?.remove(devno).unwrap_or_default();
+        if current != *limit {
+            return Err(Error::path(
+                p,
+                io::Error::other(format!("io.max reads back as \"{}\"", current)),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn usage(&self, blocks: &Blocks) -> Result<Vec<DeviceUsage>> {
+        let mut limits = self.io_max()?;
+        let mut res = Vec::new();
+        for (devno, stat) in self.io_stat()? {
+            // stats can outlive a removed device
+            let device = match blocks.from_devno(devno) {
+                Ok(device) => device,
+                Err(Error::NotFound { .. }) => continue,
+                Err(err) => return Err(err),
+            };
+            res.push(DeviceUsage {
+                device,
+                stat,
+                limit: limits.remove(&devno),
+            });
+        }
+        res.sort_by_key(|usage| usage.device.to_devno());
+        Ok(res)
+    }
+}
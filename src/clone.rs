@@ -0,0 +1,300 @@
+use std::{
+    fs::File,
+    io::{self, Seek, SeekFrom},
+    os::unix::prelude::{AsRawFd, FileExt, FileTypeExt},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    ioctl::{self, BLKZEROOUT},
+    resize, Error, Result,
+};
+
+const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CloneOptions {
+    chunk_size: usize,
+    sparse: bool,
+    max_rate: Option<u64>,
+}
+
+impl Default for CloneOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            sparse: true,
+            max_rate: None,
+        }
+    }
+}
+
+impl CloneOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size.max(4096).next_multiple_of(4096);
+        self
+    }
+
+    #[inline]
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.sparse = sparse;
+        self
+    }
+
+    #[inline]
+    pub fn max_rate(mut self, bytes_per_sec: Option<u64>) -> Self {
+        self.max_rate = bytes_per_sec.filter(|r| *r > 0);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CloneProgress {
+    pub total: u64,
+    pub copied: u64,
+    pub skipped: u64,
+    pub elapsed: Duration,
+}
+
+impl CloneProgress {
+    #[inline]
+    pub fn done(&self) -> u64 {
+        self.copied + self.skipped
+    }
+
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.done() >= self.total
+    }
+
+    pub fn rate(&self) -> Option<f64> {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            Some(self.done() as f64 / secs)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    CopyFileRange,
+    Sendfile,
+    ReadWrite,
+}
+
+fn size_of(f: &File) -> Result<u64> {
+    let md = f.metadata()?;
+    if md.file_type().is_block_device() {
+        resize::blk_getsize64(f)
+    } else {
+        Ok(md.len())
+    }
+}
+
+#[inline]
+fn is_block(f: &File) -> Result<bool> {
+    Ok(f.metadata()?.file_type().is_block_device())
+}
+
+// errors meaning "not supported for these files", so the next method is tried
+#[inline]
+fn unsupported(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::EINVAL | libc::EXDEV | libc::ENOSYS | libc::EOPNOTSUPP | libc::EBADF)
+    )
+}
+
+// unlike BLKDISCARD the range is guaranteed to read back as zeroes, the
+// kernel unmaps it only where the device promises that
+fn zero_out(f: &File, offset: u64, len: u64) -> io::Result<()> {
+    if !ioctl::ENABLED {
+        // reported like a kernel without support, zeroes get written
+        return Err(io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+    }
+    let range = [offset, len];
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), BLKZEROOUT as _, &range) };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+fn copy_file_range(src: &File, dst: &File, offset: u64, len: usize) -> io::Result<usize> {
+    let mut off_in = offset as libc::loff_t;
+    let mut off_out = offset as libc::loff_t;
+    let ret = unsafe {
+        libc::copy_file_range(
+            src.as_raw_fd(),
+            &mut off_in,
+            dst.as_raw_fd(),
+            &mut off_out,
+            len,
+            0,
+        )
+    };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+fn sendfile(src: &File, mut dst: &File, offset: u64, len: usize) -> io::Result<usize> {
+    dst.seek(SeekFrom::Start(offset))?;
+    let mut off = offset as libc::off_t;
+    let ret = unsafe { libc::sendfile(dst.as_raw_fd(), src.as_raw_fd(), &mut off, len) };
+    if ret < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+fn read_full(f: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut done = 0;
+    while done < buf.len() {
+        match f.read_at(&mut buf[done..], offset + done as u64) {
+            Ok(0) => break,
+            Ok(n) => done += n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(done)
+}
+
+struct Copier<'a> {
+    src: &'a File,
+    dst: &'a File,
+    dst_block: bool,
+    opts: CloneOptions,
+    method: Method,
+    buf: Vec<u8>,
+    can_zero_out: bool,
+}
+
+impl Copier<'_> {
+    // copies at most `len` bytes at `offset`, returns (copied, skipped)
+    fn chunk(&mut self, offset: u64, len: usize) -> Result<(u64, u64)> {
+        loop {
+            let res = match self.method {
+                Method::CopyFileRange => copy_file_range(self.src, self.dst, offset, len),
+                Method::Sendfile => sendfile(self.src, self.dst, offset, len),
+                Method::ReadWrite => return self.read_write(offset, len),
+            };
+            match res {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => return Ok((n as u64, 0)),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) if unsupported(&err) => {
+                    self.method = match self.method {
+                        Method::CopyFileRange => Method::Sendfile,
+                        _ => Method::ReadWrite,
+                    };
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn read_write(&mut self, offset: u64, len: usize) -> Result<(u64, u64)> {
+        let buf = &mut self.buf[..len];
+        let n = read_full(self.src, buf, offset)?;
+        if n == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        let buf = &buf[..n];
+
+        if self.opts.sparse && buf.iter().all(|b| *b == 0) {
+            if !self.dst_block {
+                // regular targets were truncated beforehand, this stays a hole
+                return Ok((0, n as u64));
+            }
+            if self.can_zero_out {
+                match zero_out(self.dst, offset, n as u64) {
+                    Ok(()) => return Ok((0, n as u64)),
+                    Err(err) if unsupported(&err) => self.can_zero_out = false,
+                    Err(err) => return Err(Error::ioctl("BLKZEROOUT", err)),
+                }
+            }
+        }
+
+        self.dst.write_all_at(buf, offset)?;
+        Ok((n as u64, 0))
+    }
+}
+
+pub fn copy<F: FnMut(&CloneProgress)>(
+    src: &File,
+    dst: &File,
+    opts: CloneOptions,
+    mut progress: F,
+) -> Result<CloneProgress> {
+    let total = size_of(src)?;
+    let dst_block = is_block(dst)?;
+    if dst_block {
+        if size_of(dst)? < total {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "target device is smaller than the source",
+            )
+            .into());
+        }
+    } else {
+        dst.set_len(0)?;
+        dst.set_len(total)?;
+    }
+
+    let mut copier = Copier {
+        src,
+        dst,
+        dst_block,
+        opts,
+        // zero detection needs the data in userspace
+        method: if opts.sparse {
+            Method::ReadWrite
+        } else {
+            Method::CopyFileRange
+        },
+        buf: vec![0u8; opts.chunk_size],
+        can_zero_out: true,
+    };
+
+    let start = Instant::now();
+    let mut state = CloneProgress {
+        total,
+        ..Default::default()
+    };
+    while state.done() < total {
+        let len = (total - state.done()).min(opts.chunk_size as u64) as usize;
+        let (copied, skipped) = copier.chunk(state.done(), len)?;
+        state.copied += copied;
+        state.skipped += skipped;
+
+        if let Some(rate) = opts.max_rate {
+            let budget = Duration::from_secs_f64(state.copied as f64 / rate as f64);
+            if let Some(wait) = budget.checked_sub(start.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+
+        state.elapsed = start.elapsed();
+        progress(&state);
+    }
+
+    dst.sync_all()?;
+    state.elapsed = start.elapsed();
+    Ok(state)
+}
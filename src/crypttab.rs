@@ -0,0 +1,164 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    os::unix::prelude::OsStrExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    fstab::FstabSource,
+    procfs::{unescape_path, Escaped, MountOptions},
+    Blocks, Device, Error, Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrypttabEntry {
+    pub name: String,
+    pub source: FstabSource,
+    pub key_file: Option<PathBuf>,
+    pub options: MountOptions,
+}
+
+#[derive(Debug)]
+pub struct ParseCrypttabError;
+
+impl FromStr for CrypttabEntry {
+    type Err = ParseCrypttabError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.split_whitespace();
+
+        Ok(Self {
+            name: it.next().ok_or(ParseCrypttabError)?.to_string(),
+            source: FstabSource::parse(it.next().ok_or(ParseCrypttabError)?),
+            key_file: match it.next() {
+                None | Some("none") | Some("-") => None,
+                Some(key_file) => Some(unescape_path(key_file)),
+            },
+            options: MountOptions::parse(it.next().unwrap_or("")),
+        })
+    }
+}
+
+impl std::fmt::Display for CrypttabEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {} ", self.name, self.source)?;
+        match self.key_file {
+            Some(ref key_file) => write!(f, "{}", Escaped(key_file.as_os_str().as_bytes()))?,
+            None => f.write_str("none")?,
+        }
+        if !self.options.is_empty() {
+            write!(f, " {}", self.options)?;
+        }
+        Ok(())
+    }
+}
+
+impl CrypttabEntry {
+    #[inline]
+    pub fn resolve(&self, blocks: &Blocks) -> Result<Option<Device>> {
+        self.source.resolve(blocks)
+    }
+
+    pub fn active(&self, blocks: &Blocks) -> Result<Option<Device>> {
+        if let Some(devfs) = blocks.devfs() {
+            let p = devfs.path().join("mapper").join(&self.name);
+            if devfs.lookup(&p).is_some() {
+                return blocks.from_path(p).map(Some);
+            }
+        }
+
+        for device in blocks.blocks()? {
+            let device = device?;
+            if !device.is_device_mapper()? {
+                continue;
+            }
+            if blocks.sysfs().dm_name(&device.to_devno())?.as_deref() == Some(&self.name) {
+                return Ok(Some(device));
+            }
+        }
+        Ok(None)
+    }
+}
+
+pub struct CrypttabIterator {
+    path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    line: usize,
+}
+
+impl CrypttabIterator {
+    pub fn new<P: AsRef<Path>>(file: P) -> Result<Self> {
+        let path = file.as_ref().to_path_buf();
+        let f = File::open(&path).map_err(|err| Error::path(&path, err))?;
+        Ok(Self {
+            path,
+            lines: BufReader::new(f).lines(),
+            line: 0,
+        })
+    }
+}
+
+impl Iterator for CrypttabIterator {
+    type Item = Result<CrypttabEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line += 1;
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(Error::path(&self.path, err))),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            return Some(line.parse().map_err(|_| Error::ParseError {
+                file: self.path.clone(),
+                line: Some(self.line),
+            }));
+        }
+    }
+}
+
+pub struct Crypttab {
+    path: PathBuf,
+}
+
+impl Crypttab {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_path("/etc/crypttab")
+    }
+
+    #[inline]
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Self {
+        Self {
+            path: p.as_ref().to_path_buf(),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Result<CrypttabIterator> {
+        CrypttabIterator::new(&self.path)
+    }
+
+    #[inline]
+    pub fn all(&self) -> Result<Vec<CrypttabEntry>> {
+        self.iter()?.collect()
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Default for Crypttab {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,110 @@
+use std::{
+    collections::BTreeMap,
+    ffi::{CString, OsStr},
+    io,
+    os::unix::prelude::*,
+    path::{Path, PathBuf},
+};
+
+use crate::{Error, Result};
+
+const WATCH_MASK: u32 = libc::IN_CREATE | libc::IN_DELETE | libc::IN_MOVED_FROM | libc::IN_MOVED_TO;
+const EVENT_HEADER_SIZE: usize = std::mem::size_of::<libc::inotify_event>();
+
+pub(crate) enum NodeEvent {
+    Created(PathBuf),
+    Removed(PathBuf),
+    // the kernel dropped events, whatever came before is unreliable
+    Overflow,
+}
+
+pub(crate) struct Inotify {
+    fd: OwnedFd,
+    watches: BTreeMap<libc::c_int, PathBuf>,
+}
+
+impl Inotify {
+    pub fn new() -> Result<Self> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_NONBLOCK | libc::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            watches: BTreeMap::new(),
+        })
+    }
+
+    pub fn add_watch<P: AsRef<Path>>(&mut self, p: P) -> Result<()> {
+        let p = p.as_ref();
+        let path = CString::new(p.as_os_str().as_bytes())
+            .map_err(|_| Error::from(io::ErrorKind::InvalidInput))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), WATCH_MASK) };
+        if wd < 0 {
+            return Err(Error::path(p, io::Error::last_os_error()));
+        }
+        self.watches.insert(wd, p.to_path_buf());
+        Ok(())
+    }
+
+    pub fn read_events(&mut self) -> Result<Vec<NodeEvent>> {
+        let mut res = Vec::new();
+        let mut buf = [0u8; 4096];
+
+        loop {
+            let len = unsafe {
+                libc::read(
+                    self.fd.as_raw_fd(),
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                )
+            };
+            if len < 0 {
+                let err = io::Error::last_os_error();
+                match err.kind() {
+                    io::ErrorKind::WouldBlock => return Ok(res),
+                    io::ErrorKind::Interrupted => continue,
+                    _ => return Err(err.into()),
+                }
+            }
+
+            let len = len as usize;
+            let mut offset = 0;
+            while offset + EVENT_HEADER_SIZE <= len {
+                let event = unsafe {
+                    std::ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event)
+                };
+                let name_start = offset + EVENT_HEADER_SIZE;
+                let name_end = (name_start + event.len as usize).min(len);
+                offset = name_end;
+
+                if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                    res.clear();
+                    res.push(NodeEvent::Overflow);
+                    continue;
+                }
+
+                let name = &buf[name_start..name_end];
+                let name = &name[..name.iter().position(|c| *c == 0).unwrap_or(name.len())];
+                let dir = match self.watches.get(&event.wd) {
+                    Some(dir) if !name.is_empty() => dir,
+                    _ => continue,
+                };
+                let path = dir.join(OsStr::from_bytes(name));
+
+                if event.mask & (libc::IN_CREATE | libc::IN_MOVED_TO) != 0 {
+                    if event.mask & libc::IN_ISDIR != 0 {
+                        if name == b"mapper" {
+                            self.add_watch(&path)?;
+                        }
+                    } else {
+                        res.push(NodeEvent::Created(path));
+                    }
+                } else if event.mask & (libc::IN_DELETE | libc::IN_MOVED_FROM) != 0 {
+                    res.push(NodeEvent::Removed(path));
+                }
+            }
+        }
+    }
+}
@@ -1,32 +1,70 @@
+mod inotify;
+
 use std::{
-    cell::{Ref, RefCell},
-    collections::BTreeMap,
-    fs::{DirEntry, ReadDir},
-    io,
+    fs::ReadDir,
     os::unix::prelude::*,
     path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use indexmap::IndexMap;
+
+use self::inotify::{Inotify, NodeEvent};
+use crate::{
+    magic, naming, parse::devlist::DevListing, procfs::ProcFs, trace, Devno, Error, Result,
 };
 
-use crate::{procfs::ProcFs, Devno};
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DevNode {
+    pub path: PathBuf,
+    pub devno: Devno,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MapperEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub devno: Devno,
+}
 
 pub struct BlocksIterator {
-    dir: ReadDir,
+    dir: Option<ReadDir>,
+    listed: std::vec::IntoIter<DevNode>,
     inner: Option<Box<BlocksIterator>>,
 }
 
 impl BlocksIterator {
-    fn new<P: AsRef<Path>>(p: P) -> io::Result<Self> {
-        Ok(Self {
-            dir: std::fs::read_dir(p)?,
+    fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
+        Ok(Self::from_dir(std::fs::read_dir(p)?))
+    }
+
+    #[inline]
+    fn from_dir(dir: ReadDir) -> Self {
+        Self {
+            dir: Some(dir),
+            listed: Vec::new().into_iter(),
+            inner: None,
+        }
+    }
+
+    #[inline]
+    fn listed(nodes: Vec<DevNode>) -> Self {
+        Self {
+            dir: None,
+            listed: nodes.into_iter(),
             inner: None,
-        })
+        }
     }
 }
 
 impl Iterator for BlocksIterator {
-    type Item = io::Result<DirEntry>;
+    type Item = Result<DevNode>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        if let Some(node) = self.listed.next() {
+            return Some(Ok(node));
+        }
+
         loop {
             if let Some(ref mut inner) = self.inner {
                 if let Some(res) = inner.next() {
@@ -36,12 +74,17 @@ impl Iterator for BlocksIterator {
                 }
             }
 
-            let res = match self.dir.next()? {
+            let res = match self.dir.as_mut()?.next()? {
                 Ok(res) => res,
-                Err(err) => return Some(Err(err)),
+                Err(err) => return Some(Err(err.into())),
+            };
+
+            let ty = match res.file_type() {
+                Ok(ty) => ty,
+                Err(err) => return Some(Err(Error::path(res.path(), err))),
             };
 
-            if res.path().is_symlink() {
+            if ty.is_symlink() {
                 continue;
             }
 
@@ -53,26 +96,64 @@ impl Iterator for BlocksIterator {
                 continue;
             }
 
-            if res.path().is_dir() {
+            if ty.is_dir() {
                 let inner = match std::fs::read_dir(res.path()) {
                     Ok(i) => i,
-                    Err(err) => return Some(Err(err)),
+                    Err(err) => return Some(Err(err.into())),
                 };
 
-                self.inner = Some(Box::new(Self {
-                    dir: inner,
-                    inner: None,
-                }));
+                self.inner = Some(Box::new(Self::from_dir(inner)));
                 continue;
             }
 
-            let md = match res.path().metadata() {
-                Ok(md) => md,
-                Err(err) => return Some(Err(err)),
-            };
+            if !ty.is_block_device() {
+                continue;
+            }
 
-            if md.file_type().is_block_device() {
-                return Some(Ok(res));
+            let path = res.path();
+            match path.metadata() {
+                Ok(md) => {
+                    return Some(Ok(DevNode {
+                        path,
+                        devno: md.rdev().into(),
+                    }))
+                }
+                // removed while iterating
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Some(Err(Error::path(path, err))),
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct PathCache {
+    entries: IndexMap<Devno, PathBuf>,
+    limit: Option<usize>,
+}
+
+impl PathCache {
+    fn get(&mut self, devno: &Devno) -> Option<PathBuf> {
+        let path = self.entries.shift_remove(devno)?;
+        self.entries.insert(*devno, path.clone());
+        Some(path)
+    }
+
+    fn insert(&mut self, devno: Devno, path: PathBuf) {
+        self.entries.shift_remove(&devno);
+        self.entries.insert(devno, path);
+        self.evict();
+    }
+
+    #[inline]
+    fn remove(&mut self, devno: &Devno) {
+        self.entries.shift_remove(devno);
+    }
+
+    fn evict(&mut self) {
+        if let Some(limit) = self.limit {
+            while self.entries.len() > limit {
+                self.entries.shift_remove_index(0);
             }
         }
     }
@@ -80,35 +161,136 @@ impl Iterator for BlocksIterator {
 
 pub struct DevFs {
     path: PathBuf,
-    cache: RefCell<BTreeMap<Devno, PathBuf>>,
+    cache: Mutex<PathCache>,
+    inotify: Mutex<Option<Inotify>>,
+    listing: Option<DevListing>,
 }
 
 impl DevFs {
-    pub fn new(procfs: &ProcFs) -> io::Result<Self> {
-        let path = match procfs.mounts().find(|m| {
-            m.file_system == "devtmpfs"
-                && matches!(m.source.as_ref().map(|x| x.as_str()), Some("dev"))
-        })? {
-            Some(pp) => pp.mount_point,
-            None => return Err(io::Error::new(io::ErrorKind::NotFound, "devfs not found")),
+    pub fn new(procfs: &ProcFs) -> Result<Self> {
+        let path = match procfs
+            .mounts()
+            .find_mount_point(|m| m.file_system == "devtmpfs", &["/dev"])?
+        {
+            Some(p) => p,
+            None if magic::is_fs("/dev", &[magic::TMPFS_MAGIC, magic::RAMFS_MAGIC]) => {
+                PathBuf::from("/dev")
+            }
+            None => return Err(Error::DevfsNotFound),
         };
 
-        Ok(Self {
-            path,
-            cache: RefCell::new(BTreeMap::new()),
-        })
+        Ok(Self::with_path(path))
+    }
+
+    #[inline]
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Self {
+        Self {
+            path: p.as_ref().to_path_buf(),
+            cache: Mutex::new(PathCache::default()),
+            inotify: Mutex::new(None),
+            listing: None,
+        }
+    }
+
+    // nothing under `p` is touched, every answer comes from the listing
+    pub fn with_listing<P: AsRef<Path>>(p: P, listing: DevListing) -> Self {
+        Self {
+            listing: Some(listing),
+            ..Self::with_path(p)
+        }
+    }
+
+    #[inline]
+    pub fn is_listing(&self) -> bool {
+        self.listing.is_some()
+    }
+
+    // the node or symlink at `p`, from the listing or the filesystem
+    pub fn lookup(&self, p: &Path) -> Option<Devno> {
+        match self.listing {
+            Some(ref listing) => listing.lookup(p),
+            None => match p.metadata() {
+                Ok(md) if md.file_type().is_block_device() => Some(md.rdev().into()),
+                _ => None,
+            },
+        }
+    }
+
+    pub fn watch(&self) -> Result<()> {
+        if self.is_listing() {
+            return Ok(());
+        }
+
+        let mut inotify = self.inotify.lock().unwrap();
+        if inotify.is_some() {
+            return Ok(());
+        }
+
+        let mut watch = Inotify::new()?;
+        watch.add_watch(&self.path)?;
+        let mapper = self.path.join("mapper");
+        if mapper.is_dir() {
+            watch.add_watch(mapper)?;
+        }
+        *inotify = Some(watch);
+        Ok(())
+    }
+
+    #[inline]
+    pub fn unwatch(&self) {
+        *self.inotify.lock().unwrap() = None;
     }
 
     #[inline]
-    fn cache_get<'a>(&'a self, devno: &Devno) -> Option<Ref<'a, Path>> {
-        let c = self.cache.borrow();
-        c.get(devno)
-            .map(|v| v.as_path())
-            .map(|v| v as *const _)
-            .map(|v| Ref::map(c, |_| unsafe { &*v }))
+    pub fn is_watching(&self) -> bool {
+        self.inotify.lock().unwrap().is_some()
     }
 
-    fn find_in_cache<'a>(&'a self, devno: &Devno) -> io::Result<Option<Ref<Path>>> {
+    fn sync_watch(&self) -> Result<()> {
+        let events = match self.inotify.lock().unwrap().as_mut() {
+            Some(inotify) => inotify.read_events()?,
+            None => return Ok(()),
+        };
+
+        for event in events {
+            match event {
+                NodeEvent::Created(p) => {
+                    if let Ok(md) = p.symlink_metadata() {
+                        if md.file_type().is_block_device() {
+                            self.cache.lock().unwrap().insert(md.rdev().into(), p);
+                        }
+                    }
+                }
+                NodeEvent::Removed(p) => {
+                    self.cache
+                        .lock()
+                        .unwrap()
+                        .entries
+                        .retain(|_, path| *path != p);
+                }
+                NodeEvent::Overflow => {
+                    trace::debug!("devfs watch overflowed, rescanning");
+                    // /dev/mapper may have shown up among the lost events
+                    let mapper = self.path.join("mapper");
+                    if mapper.is_dir() {
+                        if let Some(inotify) = self.inotify.lock().unwrap().as_mut() {
+                            inotify.add_watch(mapper)?;
+                        }
+                    }
+                    self.clear_cache();
+                    self.prewarm()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn cache_get(&self, devno: &Devno) -> Option<PathBuf> {
+        self.cache.lock().unwrap().get(devno)
+    }
+
+    fn find_in_cache(&self, devno: &Devno) -> Result<Option<PathBuf>> {
         if let Some(p) = self.cache_get(devno) {
             if p.exists() {
                 let md = p.metadata()?;
@@ -118,30 +300,35 @@ impl DevFs {
             }
         }
 
-        self.cache.borrow_mut().remove(devno);
+        self.cache.lock().unwrap().remove(devno);
         Ok(None)
     }
 
-    fn by_dev<'a>(&'a self, devno: &Devno) -> io::Result<Option<Ref<'a, Path>>> {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(self), fields(%devno)))]
+    fn by_dev(&self, devno: &Devno) -> Result<Option<PathBuf>> {
+        if let Some(ref listing) = self.listing {
+            return Ok(listing
+                .nodes
+                .iter()
+                .find(|node| node.devno == *devno)
+                .map(|node| node.path.clone()));
+        }
+
+        self.sync_watch()?;
         if let Some(x) = self.find_in_cache(devno)? {
             return Ok(Some(x));
         }
 
         {
-            for p in self.iter()? {
-                let p = p?.path();
-
-                if p.exists() {
-                    let md = p.metadata()?;
-                    let d = Devno::from(md.rdev());
-                    {
-                        self.cache.borrow_mut().insert(d, p);
-                    }
-                    let p = self.cache_get(&d).unwrap();
+            for node in self.iter()? {
+                let node = node?;
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .insert(node.devno, node.path.clone());
 
-                    if d == *devno {
-                        return Ok(Some(p));
-                    }
+                if node.devno == *devno {
+                    return Ok(Some(node.path));
                 }
             }
         }
@@ -149,28 +336,175 @@ impl DevFs {
         Ok(None)
     }
 
-    pub fn resolve(&self, devno: &Devno) -> io::Result<PathBuf> {
-        let path = self.path.join("blocks").join(devno.to_string());
+    pub fn resolve(&self, devno: &Devno) -> Result<PathBuf> {
+        let path = self.path.join("block").join(devno.to_string());
 
-        if path.exists() {
-            let path = path.canonicalize()?;
-            let md = path.metadata()?;
+        if !self.is_listing() && path.exists() {
+            let path = path.canonicalize().map_err(|err| Error::path(&path, err))?;
+            let md = path.metadata().map_err(|err| Error::path(&path, err))?;
             if md.file_type().is_block_device() {
                 Ok(path)
             } else {
-                Err(io::ErrorKind::InvalidInput.into())
+                Err(Error::NotBlockDevice { path })
             }
         } else {
             match self.by_dev(devno)? {
-                Some(p) => Ok(p.to_path_buf()),
-                None => Err(io::ErrorKind::NotFound.into()),
+                Some(p) => Ok(p),
+                None => Err(Error::NotFound { devno: *devno }),
             }
         }
     }
 
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn prewarm(&self) -> Result<usize> {
+        let mut found = Vec::new();
+        for node in self.iter()? {
+            let node = node?;
+            found.push((node.devno, node.path));
+        }
+
+        trace::debug!(nodes = found.len(), "devfs scanned");
+        let mut cache = self.cache.lock().unwrap();
+        for (devno, p) in found {
+            cache.insert(devno, p);
+        }
+        Ok(cache.entries.len())
+    }
+
+    #[inline]
+    pub fn invalidate(&self, devno: &Devno) {
+        self.cache.lock().unwrap().remove(devno);
+    }
+
+    #[inline]
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().entries.clear();
+    }
+
+    #[inline]
+    pub fn cache_len(&self) -> usize {
+        self.cache.lock().unwrap().entries.len()
+    }
+
     #[inline]
-    pub fn iter(&self) -> io::Result<BlocksIterator> {
-        BlocksIterator::new(&self.path)
+    pub fn cache_limit(&self) -> Option<usize> {
+        self.cache.lock().unwrap().limit
+    }
+
+    pub fn set_cache_limit(&self, limit: Option<usize>) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.limit = limit;
+        cache.evict();
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Result<BlocksIterator> {
+        match self.listing {
+            Some(ref listing) => Ok(BlocksIterator::listed(listing.nodes.clone())),
+            None => BlocksIterator::new(&self.path),
+        }
+    }
+
+    // every node standing for `devno`, aliases made with mknod included
+    pub fn nodes_for(&self, devno: &Devno) -> Result<Vec<DevNode>> {
+        self.iter()?
+            .filter(|node| !matches!(node, Ok(node) if node.devno != *devno))
+            .collect()
+    }
+
+    // symlinks to block nodes, /dev/mapper and the /dev/disk/by-* trees
+    pub fn links(&self) -> Result<Vec<DevNode>> {
+        if let Some(ref listing) = self.listing {
+            return Ok(listing.links.clone());
+        }
+
+        let mut dirs = vec![self.path.join("mapper")];
+        let disk = self.path.join("disk");
+        match std::fs::read_dir(&disk) {
+            Ok(entries) => {
+                for entry in entries {
+                    let entry = entry.map_err(|err| Error::path(&disk, err))?;
+                    if entry.file_type().is_ok_and(|ty| ty.is_dir()) {
+                        dirs.push(entry.path());
+                    }
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => (),
+            Err(err) => return Err(Error::path(disk, err)),
+        }
+
+        let mut res = Vec::new();
+        for dir in dirs {
+            let entries = match std::fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Error::path(dir, err)),
+            };
+            for entry in entries {
+                let entry = entry.map_err(|err| Error::path(&dir, err))?;
+                if !entry.file_type().is_ok_and(|ty| ty.is_symlink()) {
+                    continue;
+                }
+                let path = entry.path();
+                if let Some(devno) = self.lookup(&path) {
+                    res.push(DevNode { path, devno });
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    pub fn mapper_entries(&self) -> Result<Vec<MapperEntry>> {
+        let dir = self.path.join("mapper");
+        if let Some(ref listing) = self.listing {
+            let mut res = listing
+                .nodes
+                .iter()
+                .chain(listing.links.iter())
+                .filter(|node| node.path.parent() == Some(&dir))
+                .filter_map(|node| {
+                    Some(MapperEntry {
+                        name: naming::unmangle_dm_name(&node.path.file_name()?.to_string_lossy()),
+                        path: node.path.clone(),
+                        devno: node.devno,
+                    })
+                })
+                .collect::<Vec<_>>();
+            res.sort_by(|a, b| a.name.cmp(&b.name));
+            return Ok(res);
+        }
+
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(Error::path(dir, err)),
+        };
+
+        let mut res = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|err| Error::path(&dir, err))?.path();
+            // udev links them to ../dm-N, older setups have real nodes
+            let md = match path.metadata() {
+                Ok(md) if md.file_type().is_block_device() => md,
+                Ok(_) => continue,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(Error::path(path, err)),
+            };
+            let name = match path.file_name() {
+                Some(name) => naming::unmangle_dm_name(&name.to_string_lossy()),
+                None => continue,
+            };
+            res.push(MapperEntry {
+                name,
+                path,
+                devno: md.rdev().into(),
+            });
+        }
+        res.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(res)
     }
 
     #[inline]
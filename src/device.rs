@@ -1,8 +1,37 @@
-use std::{borrow::Borrow, io, ops::Deref, path::PathBuf, rc::Rc, str::FromStr};
+use std::{
+    borrow::Borrow,
+    fs::Metadata,
+    ops::Deref,
+    os::{
+        fd::OwnedFd,
+        unix::fs::{FileTypeExt, MetadataExt},
+    },
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, OnceLock},
+};
 
 use libc::dev_t;
 
-use crate::iter::{DevnoMapper, PartitionsIterator, SlavesIterator};
+use crate::{
+    alignment::{self, PartitionAlignment},
+    bench::{self, BenchOptions, BenchReport},
+    boot,
+    capability::Capabilities,
+    cgroup::{Cgroup, IoMax, IoStat},
+    clone::{CloneOptions, CloneProgress},
+    fingerprint::{Fingerprint, FingerprintOptions},
+    iter::{DevnoMapper, PartitionsIterator, SlavesIterator},
+    parttable::{GptTable, PartitionTable, PartitionTableType, TableLayout},
+    procfs::{MountInfo, Opener},
+    sysfs::{
+        queue::{QueueTuning, WriteCache},
+        MmcInfo, PciAddress, PmemInfo, Uevent, UeventAction, UsbInfo, VirtioInfo,
+    },
+    udevdb::UdevRecord,
+    DeviceHandle, DeviceInfo, DeviceKind, DiskEvent, EjectError, FsState, FsUsage, Health,
+    PowerState, RescanOptions, RescanOutcome, Result, SizeChange, SwapHeader, UnmountOptions,
+};
 
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Devno(dev_t);
@@ -19,19 +48,81 @@ impl std::fmt::Display for Devno {
     }
 }
 
+impl std::fmt::LowerHex for Devno {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl std::fmt::UpperHex for Devno {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+// the 64-bit dev_t layout shared by glibc and musl, spelled out so the 12
+// bit major and 20 bit minor split does not depend on the libc macros:
+// MMMM_Mmmm_mmmM_MMmm with M the major and m the minor bits
+const fn makedev(major: u32, minor: u32) -> dev_t {
+    let (major, minor) = (major as dev_t, minor as dev_t);
+    ((major & 0xffff_f000) << 32)
+        | ((major & 0x0000_0fff) << 8)
+        | ((minor & 0xffff_ff00) << 12)
+        | (minor & 0x0000_00ff)
+}
+
+const fn major(dev: dev_t) -> u32 {
+    (((dev >> 32) & 0xffff_f000) | ((dev >> 8) & 0x0000_0fff)) as u32
+}
+
+const fn minor(dev: dev_t) -> u32 {
+    (((dev >> 12) & 0xffff_ff00) | (dev & 0x0000_00ff)) as u32
+}
+
 impl Devno {
-    pub fn major(&self) -> u32 {
-        unsafe { libc::major(self.0) }
+    #[inline]
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self(makedev(major, minor))
     }
 
-    pub fn minor(&self) -> u32 {
-        unsafe { libc::minor(self.0) }
+    #[inline]
+    pub const fn major(&self) -> u32 {
+        major(self.0)
+    }
+
+    #[inline]
+    pub const fn minor(&self) -> u32 {
+        minor(self.0)
+    }
+
+    // the kernel's own encoding as found in /proc/<pid>/stat and friends,
+    // values below 0x10000 are the old 8:8 kdev_t and decode the same way
+    pub const fn from_kdev_t(kdev: u32) -> Self {
+        let major = (kdev & 0x000f_ff00) >> 8;
+        let minor = (kdev & 0x0000_00ff) | ((kdev >> 12) & 0x000f_ff00);
+        Self::new(major, minor)
+    }
+
+    // raw dev_t in hex, with or without the 0x prefix
+    pub fn from_hex(s: &str) -> Result<Self, ParseDevnoError> {
+        let hex = s
+            .strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0X"))
+            .unwrap_or(s);
+        dev_t::from_str_radix(hex, 16)
+            .map(Self)
+            .map_err(|_| ParseDevnoError::new(s, 0))
+    }
+
+    #[inline]
+    pub fn to_hex(&self) -> String {
+        format!("{:x}", self)
     }
 }
 
 impl From<(u32, u32)> for Devno {
     fn from(raw: (u32, u32)) -> Self {
-        Self(unsafe { libc::makedev(raw.0, raw.1) })
+        Self::new(raw.0, raw.1)
     }
 }
 
@@ -42,10 +133,49 @@ impl From<dev_t> for Devno {
     }
 }
 
-impl Into<dev_t> for Devno {
+// device nodes yield the device they stand for, anything else the device
+// holding it
+impl From<&Metadata> for Devno {
+    fn from(md: &Metadata) -> Self {
+        let ty = md.file_type();
+        if ty.is_block_device() || ty.is_char_device() {
+            Self(md.rdev())
+        } else {
+            Self(md.dev())
+        }
+    }
+}
+
+impl TryFrom<&Path> for Devno {
+    type Error = crate::Error;
+
+    fn try_from(path: &Path) -> Result<Self, Self::Error> {
+        path.metadata()
+            .map(|md| Self::from(&md))
+            .map_err(|err| crate::Error::path(path, err))
+    }
+}
+
+impl From<Devno> for dev_t {
     #[inline]
-    fn into(self) -> dev_t {
-        self.0
+    fn from(devno: Devno) -> Self {
+        devno.0
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Devno {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Devno {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| serde::de::Error::custom(format_args!("invalid device number {:?}", s)))
     }
 }
 
@@ -72,115 +202,225 @@ impl Borrow<dev_t> for Devno {
     }
 }
 
-#[derive(Debug)]
-pub struct ParseDevnoError;
+// field is 0 for the major (or a plain dev_t) and 1 for the minor
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid device number {input:?} at field {field}")]
+pub struct ParseDevnoError {
+    pub input: String,
+    pub field: usize,
+}
+
+impl ParseDevnoError {
+    #[inline]
+    fn new(input: &str, field: usize) -> Self {
+        Self {
+            input: input.to_string(),
+            field,
+        }
+    }
+}
 
 impl FromStr for Devno {
     type Err = ParseDevnoError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if s.contains(':') {
-            let mut it = s.splitn(2, ':');
-            let major: u32 = it.next().unwrap().parse().map_err(|_| ParseDevnoError)?;
-            let minor: u32 = it
-                .next()
-                .map_or_else(|| Err(ParseDevnoError), |x| Ok(x))?
-                .parse()
-                .map_err(|_| ParseDevnoError)?;
-
-            Ok(Self(unsafe { libc::makedev(major, minor) }))
-        } else {
-            s.parse::<libc::dev_t>()
-                .map_err(|_| ParseDevnoError)
-                .map(|x| Self(x))
+        match s.split_once(':') {
+            Some((major, minor)) => {
+                let major: u32 = major.parse().map_err(|_| ParseDevnoError::new(s, 0))?;
+                let minor: u32 = minor.parse().map_err(|_| ParseDevnoError::new(s, 1))?;
+                Ok(Self::new(major, minor))
+            }
+            None if s.starts_with("0x") || s.starts_with("0X") => Self::from_hex(s),
+            None => s
+                .parse::<libc::dev_t>()
+                .map_err(|_| ParseDevnoError::new(s, 0))
+                .map(Self),
         }
     }
 }
 
 #[derive(Clone)]
 pub struct Device {
-    blocks: Rc<crate::blocks::Blocks>,
+    blocks: Arc<crate::blocks::Blocks>,
     devno: Devno,
+    // first name seen through name(), so Debug can show it without IO
+    name: Arc<OnceLock<String>>,
 }
 
 impl Device {
     #[inline]
-    pub(crate) fn new(blocks: Rc<crate::blocks::Blocks>, devno: Devno) -> Self {
-        Self { blocks, devno }
+    pub(crate) fn new(blocks: Arc<crate::blocks::Blocks>, devno: Devno) -> Self {
+        Self {
+            blocks,
+            devno,
+            name: Arc::new(OnceLock::new()),
+        }
+    }
+
+    pub fn name(&self) -> Result<String> {
+        let name = self.blocks.sysfs().name(&self.devno)?;
+        self.name.get_or_init(|| name.clone());
+        Ok(name)
     }
 
     #[inline]
-    pub fn is_partition(&self) -> io::Result<bool> {
-        self.blocks.sysfs().is_partition(self)
+    pub fn is_partition(&self) -> Result<bool> {
+        self.blocks.sysfs().is_partition(&self.devno)
     }
 
     #[inline]
-    pub fn is_disk(&self) -> io::Result<bool> {
+    pub fn is_disk(&self) -> Result<bool> {
         self.is_partition().map(|x| !x)
     }
 
     #[inline]
-    pub fn is_type(&self, ty: impl AsRef<str>) -> io::Result<bool> {
+    pub fn is_type(&self, ty: impl AsRef<str>) -> Result<bool> {
         self.blocks.is_type(self, ty)
     }
 
     #[inline]
-    pub fn is_device_mapper(&self) -> io::Result<bool> {
+    pub fn is_device_mapper(&self) -> Result<bool> {
         self.blocks.is_device_mapper(&self.devno)
     }
 
     #[inline]
-    pub fn dm_uuid(&self) -> io::Result<Option<String>> {
+    pub fn dm_uuid(&self) -> Result<Option<String>> {
         self.blocks.dm_uuid(&self.devno)
     }
 
     #[inline]
-    pub fn dm_type(&self) -> io::Result<Option<String>> {
+    pub fn dm_type(&self) -> Result<Option<String>> {
         self.blocks.dm_type(&self.devno)
     }
 
     #[inline]
-    pub fn is_dm_types<S: AsRef<str>, I: IntoIterator<Item = S>>(
-        &self,
-        types: I,
-    ) -> io::Result<bool> {
+    pub fn is_dm_types<S: AsRef<str>, I: IntoIterator<Item = S>>(&self, types: I) -> Result<bool> {
         self.blocks.is_dm_types(&self.devno, types)
     }
 
     #[inline]
-    pub fn is_dm_type<S: AsRef<str>>(&self, t: S) -> io::Result<bool> {
+    pub fn is_dm_type<S: AsRef<str>>(&self, t: S) -> Result<bool> {
         self.blocks.is_dm_type(&self.devno, t)
     }
 
     #[inline]
-    pub fn is_luks(&self) -> io::Result<bool> {
+    pub fn is_luks(&self) -> Result<bool> {
         self.blocks.is_luks(&self.devno)
     }
 
     #[inline]
-    pub fn is_luks2(&self) -> io::Result<bool> {
+    pub fn is_luks2(&self) -> Result<bool> {
         self.blocks.is_luks2(&self.devno)
     }
 
     #[inline]
-    pub fn partition_number(&self) -> io::Result<Option<usize>> {
+    pub fn size(&self) -> Result<u64> {
+        self.blocks.size(&self.devno)
+    }
+
+    #[inline]
+    pub fn is_hidden(&self) -> Result<bool> {
+        self.blocks.is_hidden(&self.devno)
+    }
+
+    #[inline]
+    pub fn info(&self) -> Result<DeviceInfo> {
+        self.blocks.info(&self.devno)
+    }
+
+    // "sda1 8:1 512G ext4 part of sda, mounted on /"
+    pub fn describe(&self) -> Result<String> {
+        let info = self.info()?;
+        let mut res = format!("{} {} {}", info.name, info.devno, human_size(info.size));
+        if let Some(fs_type) = info.fs.as_ref().and_then(|fs| fs.fs_type.as_deref()) {
+            res.push(' ');
+            res.push_str(fs_type);
+        }
+        if let Some(parent) = info.parent {
+            res.push_str(" part of ");
+            res.push_str(&self.blocks.sysfs().name(&parent)?);
+        }
+        if !info.mountpoints.is_empty() {
+            let mountpoints = info
+                .mountpoints
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>();
+            res.push_str(", mounted on ");
+            res.push_str(&mountpoints.join(", "));
+        }
+        Ok(res)
+    }
+
+    #[inline]
+    pub fn uevent(&self) -> Result<Uevent> {
+        self.blocks.uevent(&self.devno)
+    }
+
+    #[inline]
+    pub fn trigger_uevent(&self, action: UeventAction) -> Result<()> {
+        self.blocks.trigger_uevent(&self.devno, action)
+    }
+
+    #[inline]
+    pub fn udev_properties(&self) -> Result<Option<UdevRecord>> {
+        self.blocks.udev_properties(&self.devno)
+    }
+
+    #[inline]
+    pub fn usb_info(&self) -> Result<Option<UsbInfo>> {
+        self.blocks.usb_info(&self.devno)
+    }
+
+    #[inline]
+    pub fn pci_address(&self) -> Result<Option<PciAddress>> {
+        self.blocks.pci_address(&self.devno)
+    }
+
+    #[inline]
+    pub fn numa_node(&self) -> Result<Option<u32>> {
+        self.blocks.numa_node(&self.devno)
+    }
+
+    #[inline]
+    pub fn mmc_info(&self) -> Result<Option<MmcInfo>> {
+        self.blocks.mmc_info(&self.devno)
+    }
+
+    #[inline]
+    pub fn virtio_info(&self) -> Result<Option<VirtioInfo>> {
+        self.blocks.virtio_info(&self.devno)
+    }
+
+    #[inline]
+    pub fn pmem_info(&self) -> Result<Option<PmemInfo>> {
+        self.blocks.pmem_info(&self.devno)
+    }
+
+    #[inline]
+    pub fn is_pmem(&self) -> Result<bool> {
+        self.blocks.is_pmem(&self.devno)
+    }
+
+    #[inline]
+    pub fn partition_number(&self) -> Result<Option<usize>> {
         self.blocks.partition_number(&self.devno)
     }
 
     #[inline]
-    pub fn slaves(&self) -> io::Result<DevnoMapper<SlavesIterator>> {
+    pub fn slaves(&self) -> Result<DevnoMapper<'static, SlavesIterator>> {
         let it = self.blocks.slaves(&self.devno)?;
         Ok(DevnoMapper::from_raw(&self.blocks, it))
     }
 
     #[inline]
-    pub fn partitions(&self) -> io::Result<DevnoMapper<PartitionsIterator>> {
-        let it = self.blocks.partitions(&self.devno)?;
+    pub fn partitions(&self) -> Result<DevnoMapper<'static, PartitionsIterator<'static>>> {
+        let it = self.blocks.owned_partitions(&self.devno)?;
         Ok(DevnoMapper::from_raw(&self.blocks, it))
     }
 
     #[inline]
-    pub fn parent(&self) -> io::Result<Option<Self>> {
+    pub fn parent(&self) -> Result<Option<Self>> {
         self.blocks
             .parent(&self.devno)
             .map(|x| x.map(|devno| Self::new(self.blocks.clone(), devno)))
@@ -192,25 +432,372 @@ impl Device {
     }
 
     #[inline]
-    pub fn path(&self) -> io::Result<PathBuf> {
-        self.blocks.resolve(&self.devno)
+    pub fn path(&self) -> Result<PathBuf> {
+        self.blocks.resolve(self.devno)
+    }
+
+    #[inline]
+    pub fn devfs_path(&self) -> Result<PathBuf> {
+        self.blocks.devfs_path(&self.devno)
     }
 
     #[inline]
-    pub fn reread_partition_table(&self) -> io::Result<()> {
+    pub fn sysfs_path(&self) -> Result<PathBuf> {
+        self.blocks.sysfs_path(&self.devno)
+    }
+
+    #[inline]
+    pub fn reread_partition_table(&self) -> Result<()> {
         self.blocks.reread_partition_table(&self.devno)
     }
+
+    #[inline]
+    pub fn clone_to_file<P: AsRef<Path>, F: FnMut(&CloneProgress)>(
+        &self,
+        p: P,
+        opts: CloneOptions,
+        progress: F,
+    ) -> Result<CloneProgress> {
+        self.blocks.clone_to_file(&self.devno, p, opts, progress)
+    }
+
+    #[inline]
+    pub fn clone_to<F: FnMut(&CloneProgress)>(
+        &self,
+        target: &Device,
+        opts: CloneOptions,
+        progress: F,
+    ) -> Result<CloneProgress> {
+        self.blocks
+            .clone_to_device(&self.devno, &target.devno, opts, progress)
+    }
+
+    #[inline]
+    pub fn quick_bench(&self, opts: BenchOptions) -> Result<BenchReport> {
+        bench::run(&self.open_direct(false)?, opts)
+    }
+
+    #[inline]
+    pub fn fingerprint(&self) -> Result<Fingerprint> {
+        self.fingerprint_with(FingerprintOptions::default())
+    }
+
+    #[inline]
+    pub fn fingerprint_with(&self, opts: FingerprintOptions) -> Result<Fingerprint> {
+        self.blocks.fingerprint(&self.devno, opts)
+    }
+
+    #[inline]
+    pub fn open_direct(&self, write: bool) -> Result<DeviceHandle> {
+        self.blocks.open_direct(&self.devno, write)
+    }
+
+    #[inline]
+    pub fn partition_table(&self) -> Result<Option<PartitionTable>> {
+        self.blocks.partition_table(&self.devno)
+    }
+
+    #[inline]
+    pub fn partition_table_layout(&self) -> Result<Option<TableLayout>> {
+        self.blocks.partition_table_layout(&self.devno)
+    }
+
+    #[inline]
+    pub fn partition_table_type(&self) -> Result<Option<PartitionTableType>> {
+        self.blocks.partition_table_type(&self.devno)
+    }
+
+    #[inline]
+    pub fn fs_state(&self) -> Result<Option<FsState>> {
+        self.blocks.fs_state(&self.devno)
+    }
+
+    #[inline]
+    pub fn gpt_table(&self) -> Result<Option<GptTable>> {
+        self.blocks.gpt_table(&self.devno)
+    }
+
+    #[inline]
+    pub fn repair_gpt_backup(&self) -> Result<()> {
+        self.blocks.repair_gpt_backup(&self.devno)
+    }
+
+    #[inline]
+    pub fn write_partition_table(&self, table: &PartitionTable) -> Result<RescanOutcome> {
+        self.blocks.write_partition_table(&self.devno, table)
+    }
+
+    pub fn alignment_report(&self) -> Result<Vec<PartitionAlignment>> {
+        let disk = self.blocks.whole_disk(&self.devno)?;
+        alignment::report(&self.blocks, &disk, |devno| {
+            Self::new(Arc::clone(&self.blocks), devno)
+        })
+    }
+
+    pub fn physical_parents(&self) -> Result<Vec<Self>> {
+        Ok(boot::backing_disks(&self.blocks, &self.devno)?
+            .into_iter()
+            .map(|devno| Self::new(Arc::clone(&self.blocks), devno))
+            .collect())
+    }
+
+    #[inline]
+    pub fn is_esp(&self) -> Result<bool> {
+        boot::is_esp(&self.blocks, self)
+    }
+
+    pub fn partition_by_number(&self, number: usize) -> Result<Option<Self>> {
+        for part in self.partitions()? {
+            let part = part?;
+            if part.partition_number()? == Some(number) {
+                return Ok(Some(part));
+            }
+        }
+        Ok(None)
+    }
+
+    #[inline]
+    pub fn rescan_partitions(&self, opts: RescanOptions) -> Result<RescanOutcome> {
+        self.blocks.rescan_partitions(&self.devno, opts)
+    }
+
+    #[inline]
+    pub fn mounts(&self) -> Result<Vec<MountInfo>> {
+        self.blocks.mounts(&self.devno)
+    }
+
+    #[inline]
+    pub fn swap_header(&self) -> Result<Option<SwapHeader>> {
+        self.blocks.swap_header(&self.devno)
+    }
+
+    #[inline]
+    pub fn swapon(&self, priority: Option<i32>) -> Result<()> {
+        self.blocks.swapon(&self.devno, priority)
+    }
+
+    #[inline]
+    pub fn swapoff(&self) -> Result<()> {
+        self.blocks.swapoff(&self.devno)
+    }
+
+    #[inline]
+    pub fn is_swap_active(&self) -> Result<bool> {
+        self.blocks.is_swap_active(&self.devno)
+    }
+
+    #[inline]
+    pub fn unmount_all(&self, opts: UnmountOptions) -> Result<Vec<MountInfo>> {
+        self.blocks.unmount_all(&self.devno, opts)
+    }
+
+    #[inline]
+    pub fn openers(&self) -> Result<Vec<Opener>> {
+        self.blocks.procfs().openers(&self.devno)
+    }
+
+    #[inline]
+    pub fn fs_usage(&self) -> Result<Option<FsUsage>> {
+        self.blocks.fs_usage(&self.devno)
+    }
+
+    #[inline]
+    pub fn eject(&self, power_off: bool) -> Result<(), EjectError> {
+        self.blocks.eject(&self.devno, power_off)
+    }
+
+    #[inline]
+    pub fn events(&self) -> Result<Vec<DiskEvent>> {
+        self.blocks.events(&self.devno)
+    }
+
+    #[inline]
+    pub fn write_cache(&self) -> Result<Option<WriteCache>> {
+        self.blocks.write_cache(&self.devno)
+    }
+
+    #[inline]
+    pub fn set_write_cache(&self, mode: WriteCache) -> Result<()> {
+        self.blocks.set_write_cache(&self.devno, mode)
+    }
+
+    #[inline]
+    pub fn fua(&self) -> Result<bool> {
+        self.blocks.fua(&self.devno)
+    }
+
+    #[inline]
+    pub fn queue_tuning(&self) -> Result<QueueTuning> {
+        self.blocks.queue_tuning(&self.devno)
+    }
+
+    #[inline]
+    pub fn set_queue_tuning(&self, tuning: &QueueTuning) -> Result<()> {
+        self.blocks.set_queue_tuning(&self.devno, tuning)
+    }
+
+    #[inline]
+    pub fn events_poll_msecs(&self) -> Result<Option<u64>> {
+        self.blocks.events_poll_msecs(&self.devno)
+    }
+
+    #[inline]
+    pub fn set_events_poll_msecs(&self, ms: Option<u64>) -> Result<()> {
+        self.blocks.set_events_poll_msecs(&self.devno, ms)
+    }
+
+    #[inline]
+    pub fn diskseq(&self) -> Result<Option<u64>> {
+        self.blocks.diskseq(&self.devno)
+    }
+
+    #[inline]
+    pub fn check_media_changed(&self) -> Result<bool> {
+        self.blocks.check_media_changed(&self.devno)
+    }
+
+    #[inline]
+    pub fn delete(&self) -> Result<()> {
+        self.blocks.delete(&self.devno)
+    }
+
+    #[inline]
+    pub fn rescan(&self) -> Result<()> {
+        self.blocks.rescan(&self.devno)
+    }
+
+    #[inline]
+    pub fn refresh_size(&self) -> Result<SizeChange> {
+        self.blocks.refresh_size(&self.devno)
+    }
+
+    #[inline]
+    pub fn whole_disk(&self) -> Result<Self> {
+        self.blocks
+            .whole_disk(&self.devno)
+            .map(|devno| Self::new(self.blocks.clone(), devno))
+    }
+
+    #[inline]
+    pub fn kind(&self) -> Result<DeviceKind> {
+        self.blocks.kind(&self.devno)
+    }
+
+    #[inline]
+    pub fn cgroup_usage(&self, cgroup: &Cgroup) -> Result<Option<IoStat>> {
+        self.blocks.cgroup_usage(&self.devno, cgroup)
+    }
+
+    pub fn set_io_limit(
+        &self,
+        cgroup: &Cgroup,
+        rbps: Option<u64>,
+        wbps: Option<u64>,
+        riops: Option<u64>,
+        wiops: Option<u64>,
+    ) -> Result<()> {
+        let limit = IoMax {
+            rbps,
+            wbps,
+            riops,
+            wiops,
+        };
+        self.blocks.set_io_limit(&self.devno, cgroup, &limit)
+    }
+
+    #[inline]
+    pub fn capabilities(&self) -> Result<Capabilities> {
+        self.blocks.capabilities(&self.devno)
+    }
+
+    #[inline]
+    pub fn is_virtual(&self) -> Result<bool> {
+        self.blocks.is_virtual(&self.devno)
+    }
+
+    // O_PATH descriptor of the device's sysfs directory, for use with openat
+    pub fn sysfs_dirfd(&self) -> Result<OwnedFd> {
+        Ok(self.blocks.sysfs().dirfd(&self.devno)?.try_clone()?)
+    }
+
+    #[inline]
+    pub fn is_read_only(&self) -> Result<bool> {
+        self.blocks.is_read_only(&self.devno)
+    }
+
+    #[inline]
+    pub fn is_removable(&self) -> Result<bool> {
+        self.blocks.is_removable(&self.devno)
+    }
+
+    #[inline]
+    pub fn supports_dax(&self) -> Result<bool> {
+        self.blocks.supports_dax(&self.devno)
+    }
+
+    #[inline]
+    pub fn is_rotational(&self) -> Result<bool> {
+        self.blocks.is_rotational(&self.devno)
+    }
+
+    #[inline]
+    pub fn in_use(&self) -> Result<bool> {
+        self.blocks.in_use(&self.devno)
+    }
+
+    #[inline]
+    pub fn power_state(&self) -> Result<PowerState> {
+        self.blocks.power_state(&self.devno)
+    }
+
+    #[inline]
+    pub fn health(&self) -> Result<Health> {
+        self.blocks.health(&self.devno)
+    }
+
+    #[inline]
+    pub fn standby_now(&self) -> Result<()> {
+        self.blocks.standby_now(&self.devno)
+    }
+}
+
+fn human_size(size: u64) -> String {
+    const UNITS: [&str; 7] = ["B", "K", "M", "G", "T", "P", "E"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    let rounded = (value * 10.0).round() / 10.0;
+    if rounded.fract() == 0.0 {
+        format!("{}{}", rounded as u64, UNITS[unit])
+    } else {
+        format!("{:.1}{}", rounded, UNITS[unit])
+    }
 }
 
 impl std::fmt::Debug for Device {
-    #[inline]
+    // only what is already known, formatting must not touch sysfs
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.path() {
-            Ok(path) => write!(f, "Device({:?})", path.display()),
-            Err(err) => {
-                println!("{:?}", err);
-                write!(f, "Device({:?})", self.devno)
-            }
+        let mut s = f.debug_struct("Device");
+        s.field("devno", &self.devno);
+        match self.name.get() {
+            Some(name) => s.field("name", name),
+            None => match self.blocks.sysfs().cached_name(&self.devno) {
+                Some(name) => s.field("name", &name),
+                None => &mut s,
+            },
+        };
+        s.finish()
+    }
+}
+
+impl std::fmt::Display for Device {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.name() {
+            Ok(name) => f.pad(&name),
+            Err(_) => f.pad(&self.devno.to_string()),
         }
     }
 }
@@ -227,7 +814,7 @@ impl Eq for Device {}
 impl PartialOrd for Device {
     #[inline]
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        self.devno.partial_cmp(&other.devno)
+        Some(self.cmp(other))
     }
 }
 
@@ -245,17 +832,17 @@ impl std::hash::Hash for Device {
     }
 }
 
-impl Into<Devno> for Device {
+impl From<Device> for Devno {
     #[inline]
-    fn into(self) -> Devno {
-        self.devno
+    fn from(device: Device) -> Self {
+        device.devno
     }
 }
 
-impl Into<libc::dev_t> for Device {
+impl From<Device> for libc::dev_t {
     #[inline]
-    fn into(self) -> libc::dev_t {
-        self.devno.into()
+    fn from(device: Device) -> Self {
+        device.devno.into()
     }
 }
 
@@ -295,3 +882,52 @@ impl Borrow<libc::dev_t> for Device {
         self.devno.borrow()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KNOWN: [(u32, u32); 7] = [
+        (8, 1),
+        (259, 0),
+        (4096, 3),
+        (0xf_ffff, 0),
+        (8, 256),
+        (253, 0xf_ffff),
+        (u32::MAX, u32::MAX),
+    ];
+
+    #[test]
+    fn matches_libc() {
+        for (major, minor) in KNOWN {
+            let devno = Devno::new(major, minor);
+            assert_eq!(devno.0, libc::makedev(major, minor), "{}:{}", major, minor);
+            assert_eq!((devno.major(), devno.minor()), (major, minor));
+            assert_eq!(libc::major(devno.0), major);
+            assert_eq!(libc::minor(devno.0), minor);
+        }
+    }
+
+    #[test]
+    fn kdev_t() {
+        assert_eq!(Devno::from_kdev_t(0x0801), Devno::new(8, 1));
+        assert_eq!(Devno::from_kdev_t(0xfd00), Devno::new(253, 0));
+        // 8:300, the minor's upper bits sit above the 12 bit major
+        assert_eq!(Devno::from_kdev_t(0x0010_082c), Devno::new(8, 300));
+        assert_eq!(Devno::from_kdev_t(0xffff_ffff), Devno::new(0xfff, 0xf_ffff));
+    }
+
+    #[test]
+    fn text_forms() {
+        for (major, minor) in KNOWN {
+            let devno = Devno::new(major, minor);
+            assert_eq!(devno.to_string().parse::<Devno>().unwrap(), devno);
+            assert_eq!(Devno::from_hex(&devno.to_hex()).unwrap(), devno);
+        }
+        assert_eq!("0x801".parse::<Devno>().unwrap(), Devno::new(8, 1));
+        assert!("8:".parse::<Devno>().is_err());
+        // a bare number is a raw dev_t
+        assert_eq!("2049".parse::<Devno>().unwrap(), Devno::new(8, 1));
+        assert!("x:1".parse::<Devno>().is_err());
+    }
+}
@@ -0,0 +1,100 @@
+use std::{
+    ffi::CString,
+    fs::File,
+    io,
+    os::unix::prelude::{AsRawFd, OsStrExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ioctl::{self, BLKFLSBUF, CDROM_EJECT},
+    Error, Result,
+};
+
+#[derive(Debug)]
+pub enum EjectError {
+    Resolve(Error),
+    Sync(Error),
+    Unmount { mount_point: PathBuf, error: Error },
+    Flush(Error),
+    Eject(Error),
+    PowerOff(Error),
+}
+
+impl std::fmt::Display for EjectError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Resolve(err) => write!(f, "cannot resolve device: {}", err),
+            Self::Sync(err) => write!(f, "sync failed: {}", err),
+            Self::Unmount { mount_point, error } => {
+                write!(f, "cannot unmount {}: {}", mount_point.display(), error)
+            }
+            Self::Flush(err) => write!(f, "cannot flush buffers: {}", err),
+            Self::Eject(err) => write!(f, "eject failed: {}", err),
+            Self::PowerOff(err) => write!(f, "cannot power off port: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for EjectError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Resolve(err)
+            | Self::Sync(err)
+            | Self::Unmount { error: err, .. }
+            | Self::Flush(err)
+            | Self::Eject(err)
+            | Self::PowerOff(err) => Some(err),
+        }
+    }
+}
+
+impl From<EjectError> for io::Error {
+    fn from(err: EjectError) -> Self {
+        let kind = match err {
+            EjectError::Resolve(ref e)
+            | EjectError::Sync(ref e)
+            | EjectError::Unmount { error: ref e, .. }
+            | EjectError::Flush(ref e)
+            | EjectError::Eject(ref e)
+            | EjectError::PowerOff(ref e) => e.kind(),
+        };
+        io::Error::new(kind, err)
+    }
+}
+
+pub(crate) fn sync(f: &File) -> Result<()> {
+    unsafe { libc::sync() };
+    Ok(f.sync_all()?)
+}
+
+pub(crate) fn umount<P: AsRef<Path>>(p: P, flags: libc::c_int) -> Result<()> {
+    let path = CString::new(p.as_ref().as_os_str().as_bytes())
+        .map_err(|_| Error::from(io::ErrorKind::InvalidInput))?;
+    let ret = unsafe { libc::umount2(path.as_ptr(), flags) };
+    if ret < 0 {
+        Err(Error::path(p.as_ref(), io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn flush_buffers(f: &File) -> Result<()> {
+    ioctl::allowed("BLKFLSBUF")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), BLKFLSBUF as _) };
+    if ret < 0 {
+        Err(Error::ioctl("BLKFLSBUF", io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn cdrom_eject(f: &File) -> Result<()> {
+    ioctl::allowed("CDROM_EJECT")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), CDROM_EJECT as _) };
+    if ret < 0 {
+        Err(Error::ioctl("CDROM_EJECT", io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
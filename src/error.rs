@@ -0,0 +1,104 @@
+use std::{io, path::PathBuf};
+
+use crate::{procfs::ParseMountInfoError, trace, Devno, ParseDevnoError};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("{} is not a block device", path.display())]
+    NotBlockDevice { path: PathBuf },
+    #[error("devtmpfs mount not found")]
+    DevfsNotFound,
+    #[error("no devfs available, device nodes cannot be resolved")]
+    NoDevfs,
+    #[error("sysfs mount not found")]
+    SysfsNotFound,
+    #[error("procfs mount not found")]
+    ProcfsNotFound,
+    #[error("cannot parse {}{}", file.display(), line.map(|l| format!(" at line {}", l)).unwrap_or_default())]
+    ParseError { file: PathBuf, line: Option<usize> },
+    #[error("ioctl {op} failed: {}", io::Error::from_raw_os_error(*errno))]
+    IoctlFailed { op: &'static str, errno: i32 },
+    #[error("ioctl {op} is disabled in this build")]
+    Unsupported { op: &'static str },
+    #[error("partitions {partitions:?} of {disk} are busy")]
+    PartitionsBusy { disk: Devno, partitions: Vec<u32> },
+    #[error("device {devno} not found")]
+    NotFound { devno: Devno },
+    #[error("{}: {source}", path.display())]
+    Path { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    ParseDevno(#[from] ParseDevnoError),
+    #[error(transparent)]
+    ParseMountInfo(#[from] ParseMountInfoError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    #[inline]
+    pub(crate) fn parse<P: Into<PathBuf>>(file: P) -> Self {
+        Self::ParseError {
+            file: file.into(),
+            line: None,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn path<P: Into<PathBuf>>(path: P, source: io::Error) -> Self {
+        Self::Path {
+            path: path.into(),
+            source,
+        }
+    }
+
+    pub(crate) fn ioctl(op: &'static str, err: io::Error) -> Self {
+        trace::debug!(op, error = %err, "ioctl failed");
+        match err.raw_os_error() {
+            Some(errno) => Self::IoctlFailed { op, errno },
+            None => Self::Io(err),
+        }
+    }
+
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Self::NotBlockDevice { .. } => io::ErrorKind::InvalidInput,
+            Self::NoDevfs | Self::Unsupported { .. } => io::ErrorKind::Unsupported,
+            Self::DevfsNotFound
+            | Self::SysfsNotFound
+            | Self::ProcfsNotFound
+            | Self::NotFound { .. } => io::ErrorKind::NotFound,
+            Self::ParseError { .. } | Self::ParseDevno(_) | Self::ParseMountInfo(_) => {
+                io::ErrorKind::InvalidData
+            }
+            Self::PartitionsBusy { .. } => io::ErrorKind::ResourceBusy,
+            Self::IoctlFailed { errno, .. } => io::Error::from_raw_os_error(*errno).kind(),
+            Self::Path { source, .. } | Self::Io(source) => source.kind(),
+        }
+    }
+
+    pub fn raw_os_error(&self) -> Option<i32> {
+        match self {
+            Self::IoctlFailed { errno, .. } => Some(*errno),
+            Self::Path { source, .. } | Self::Io(source) => source.raw_os_error(),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::ErrorKind> for Error {
+    #[inline]
+    fn from(kind: io::ErrorKind) -> Self {
+        Self::Io(kind.into())
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    collections::VecDeque,
+    io,
+    os::unix::prelude::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use crate::{
+    procfs::{MountChange, MountInfo, MountWatcher},
+    sysfs::{Uevent, UeventAction},
+    Blocks, Device, Devno, Result,
+};
+
+const UEVENT_BUFFER_SIZE: usize = 8192;
+const KERNEL_GROUP: u32 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockEvent {
+    pub action: UeventAction,
+    pub devno: Devno,
+    pub devname: Option<String>,
+    pub devpath: String,
+    pub uevent: Uevent,
+}
+
+impl BlockEvent {
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let mut it = buf
+            .split(|&c| c == 0)
+            .filter(|x| !x.is_empty())
+            .map(String::from_utf8_lossy);
+        let header = it.next()?;
+        let (_, devpath) = header.split_once('@')?;
+
+        let mut uevent = Uevent::default();
+        for field in it {
+            if let Some((key, value)) = field.split_once('=') {
+                uevent.properties.insert(key.to_string(), value.to_string());
+            }
+        }
+
+        if uevent.get("SUBSYSTEM") != Some("block") {
+            return None;
+        }
+
+        Some(Self {
+            action: uevent.get("ACTION")?.parse().ok()?,
+            devno: uevent.devno()?,
+            devname: uevent.devname().map(|x| x.to_string()),
+            devpath: devpath.to_string(),
+            uevent,
+        })
+    }
+}
+
+pub struct UeventMonitor {
+    fd: OwnedFd,
+    buf: Box<[u8; UEVENT_BUFFER_SIZE]>,
+}
+
+impl UeventMonitor {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as _;
+        addr.nl_groups = KERNEL_GROUP;
+        let ret = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                std::mem::size_of::<libc::sockaddr_nl>() as _,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd,
+            buf: Box::new([0; UEVENT_BUFFER_SIZE]),
+        })
+    }
+
+    pub fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        let fd = self.fd.as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let flags = if nonblocking {
+            flags | libc::O_NONBLOCK
+        } else {
+            flags & !libc::O_NONBLOCK
+        };
+        if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn recv(&mut self) -> io::Result<Option<BlockEvent>> {
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                self.buf.as_mut_ptr() as *mut _,
+                self.buf.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(BlockEvent::parse(&self.buf[..len as usize]))
+    }
+}
+
+impl AsRawFd for UeventMonitor {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl Iterator for UeventMonitor {
+    type Item = io::Result<BlockEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.recv() {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => (),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum WatchEvent {
+    DeviceAdded(Device),
+    DeviceRemoved(Device),
+    DeviceChanged(Device),
+    Mounted {
+        device: Option<Device>,
+        info: MountInfo,
+    },
+    Unmounted {
+        device: Option<Device>,
+        info: MountInfo,
+    },
+}
+
+pub struct Watcher {
+    blocks: Blocks,
+    uevents: UeventMonitor,
+    mounts: MountWatcher,
+    pending: VecDeque<WatchEvent>,
+}
+
+impl Watcher {
+    pub fn new(blocks: &Blocks) -> Result<Self> {
+        Ok(Self {
+            uevents: UeventMonitor::new()?,
+            mounts: blocks.procfs().mounts().watch()?,
+            blocks: blocks.clone(),
+            pending: VecDeque::new(),
+        })
+    }
+
+    #[inline]
+    pub fn uevents(&self) -> &UeventMonitor {
+        &self.uevents
+    }
+
+    #[inline]
+    pub fn mounts(&self) -> &MountWatcher {
+        &self.mounts
+    }
+
+    #[inline]
+    pub(crate) fn pop_pending(&mut self) -> Option<WatchEvent> {
+        self.pending.pop_front()
+    }
+
+    pub(crate) fn recv_uevent(&mut self) -> Result<()> {
+        if let Some(event) = self.uevents.recv()? {
+            self.handle_uevent(event)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn device(&self, devno: Devno) -> Device {
+        Device::new(self.blocks.0.clone(), devno)
+    }
+
+    fn mount_device(&self, info: &MountInfo) -> Option<Device> {
+        match self.blocks.procfs().devices().get_by_id(info.dev.major()) {
+            Ok(Some(_)) => Some(self.device(info.dev)),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn handle_uevent(&mut self, event: BlockEvent) -> Result<()> {
+        let device = self.device(event.devno);
+        let event = match event.action {
+            UeventAction::Add => {
+                self.blocks.invalidate(event.devno);
+                self.blocks.procfs().devices().refresh()?;
+                WatchEvent::DeviceAdded(device)
+            }
+            UeventAction::Remove => {
+                self.blocks.invalidate(event.devno);
+                WatchEvent::DeviceRemoved(device)
+            }
+            UeventAction::Change => WatchEvent::DeviceChanged(device),
+            _ => return Ok(()),
+        };
+        self.pending.push_back(event);
+        Ok(())
+    }
+
+    pub(crate) fn handle_mounts(&mut self) -> Result<()> {
+        for change in self.mounts.changes()? {
+            let event = match change {
+                MountChange::Mounted(info) => WatchEvent::Mounted {
+                    device: self.mount_device(&info),
+                    info,
+                },
+                MountChange::Unmounted(info) => WatchEvent::Unmounted {
+                    device: self.mount_device(&info),
+                    info,
+                },
+            };
+            self.pending.push_back(event);
+        }
+        Ok(())
+    }
+
+    fn poll(&mut self) -> Result<()> {
+        let mut fds = [
+            libc::pollfd {
+                fd: self.uevents.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: self.mounts.as_raw_fd(),
+                events: libc::POLLPRI,
+                revents: 0,
+            },
+        ];
+
+        let ret = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as _, -1) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            return if err.kind() == io::ErrorKind::Interrupted {
+                Ok(())
+            } else {
+                Err(err.into())
+            };
+        }
+
+        if fds[0].revents & libc::POLLIN != 0 {
+            self.recv_uevent()?;
+        }
+        if fds[1].revents & (libc::POLLPRI | libc::POLLERR) != 0 {
+            self.handle_mounts()?;
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Watcher {
+    type Item = Result<WatchEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pop_pending() {
+                return Some(Ok(event));
+            }
+            if let Err(err) = self.poll() {
+                return Some(Err(err));
+            }
+        }
+    }
+}
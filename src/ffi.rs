@@ -0,0 +1,332 @@
+// Flat C API. Functions return 0 or a negative errno and leave a message for
+// lb_last_error() on failure. Pointers passed in must be valid for the call,
+// anything handed out is released with the matching lb_*_free function.
+//
+// The shared library is not built by default, produce it with
+// `cargo rustc --release --features ffi --crate-type cdylib` and the header
+// with `cbindgen --config cbindgen.toml --output linux_blocks.h`.
+#![allow(clippy::missing_safety_doc)]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CString},
+    io, ptr,
+};
+
+use crate::{
+    iter::RawSlavesIterator, Blocks, Device, DeviceInfo, DeviceType, Devno, Error, Result,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn errno(err: &Error) -> c_int {
+    err.raw_os_error().unwrap_or(match err.kind() {
+        io::ErrorKind::NotFound => libc::ENOENT,
+        io::ErrorKind::PermissionDenied => libc::EACCES,
+        io::ErrorKind::InvalidInput => libc::EINVAL,
+        io::ErrorKind::InvalidData => libc::EILSEQ,
+        io::ErrorKind::Unsupported => libc::EOPNOTSUPP,
+        io::ErrorKind::ResourceBusy => libc::EBUSY,
+        _ => libc::EIO,
+    })
+}
+
+fn set_error(err: &Error) {
+    let msg = CString::new(err.to_string().replace('\0', "")).ok();
+    LAST_ERROR.with(|last| *last.borrow_mut() = msg);
+}
+
+fn ret(res: Result<()>) -> c_int {
+    match res {
+        Ok(()) => 0,
+        Err(err) => {
+            set_error(&err);
+            -errno(&err)
+        }
+    }
+}
+
+fn invalid() -> Error {
+    io::Error::from(io::ErrorKind::InvalidInput).into()
+}
+
+fn c_string(s: Option<&str>) -> *mut c_char {
+    match s.and_then(|s| CString::new(s).ok()) {
+        Some(s) => s.into_raw(),
+        None => ptr::null_mut(),
+    }
+}
+
+unsafe fn free_string(s: &mut *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(*s));
+        *s = ptr::null_mut();
+    }
+}
+
+pub struct LbBlocks(Blocks);
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LbDevno {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl From<Devno> for LbDevno {
+    #[inline]
+    fn from(devno: Devno) -> Self {
+        Self {
+            major: devno.major(),
+            minor: devno.minor(),
+        }
+    }
+}
+
+impl From<LbDevno> for Devno {
+    #[inline]
+    fn from(devno: LbDevno) -> Self {
+        Devno::new(devno.major, devno.minor)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct LbDevnoList {
+    pub devnos: *mut LbDevno,
+    pub len: usize,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LbDeviceType {
+    Disk = 0,
+    Partition = 1,
+    Rom = 2,
+    Loop = 3,
+    Md = 4,
+    Dm = 5,
+}
+
+impl From<DeviceType> for LbDeviceType {
+    fn from(ty: DeviceType) -> Self {
+        match ty {
+            DeviceType::Disk => Self::Disk,
+            DeviceType::Partition => Self::Partition,
+            DeviceType::Rom => Self::Rom,
+            DeviceType::Loop => Self::Loop,
+            DeviceType::Md => Self::Md,
+            DeviceType::Dm => Self::Dm,
+        }
+    }
+}
+
+// strings are NULL when unknown, partition_number is 0 for whole disks
+#[repr(C)]
+#[derive(Debug)]
+pub struct LbDeviceInfo {
+    pub devno: LbDevno,
+    pub name: *mut c_char,
+    pub path: *mut c_char,
+    pub device_type: LbDeviceType,
+    pub size: u64,
+    pub read_only: bool,
+    pub removable: bool,
+    pub model: *mut c_char,
+    pub partition_number: u32,
+    pub fs_type: *mut c_char,
+    pub fs_uuid: *mut c_char,
+    pub fs_label: *mut c_char,
+    pub has_parent: bool,
+    pub parent: LbDevno,
+}
+
+impl From<DeviceInfo> for LbDeviceInfo {
+    fn from(info: DeviceInfo) -> Self {
+        let fs = info.fs.unwrap_or_default();
+        Self {
+            devno: info.devno.into(),
+            name: c_string(Some(&info.name)),
+            path: c_string(info.path.as_ref().and_then(|p| p.to_str())),
+            device_type: info.device_type.into(),
+            size: info.size,
+            read_only: info.read_only,
+            removable: info.removable,
+            model: c_string(info.model.as_deref()),
+            partition_number: info.partition_number.unwrap_or(0) as u32,
+            fs_type: c_string(fs.fs_type.as_deref()),
+            fs_uuid: c_string(fs.uuid.as_deref()),
+            fs_label: c_string(fs.label.as_deref()),
+            has_parent: info.parent.is_some(),
+            parent: info.parent.unwrap_or_else(|| Devno::new(0, 0)).into(),
+        }
+    }
+}
+
+unsafe fn write_list<I: IntoIterator<Item = Result<Devno>>>(
+    out: *mut LbDevnoList,
+    it: I,
+) -> Result<()> {
+    let out = out.as_mut().ok_or_else(invalid)?;
+    let devnos = it
+        .into_iter()
+        .map(|devno| devno.map(LbDevno::from))
+        .collect::<Result<Vec<_>>>()?
+        .into_boxed_slice();
+    out.len = devnos.len();
+    out.devnos = Box::into_raw(devnos) as *mut LbDevno;
+    Ok(())
+}
+
+unsafe fn device(blocks: *const LbBlocks, devno: LbDevno) -> Result<Device> {
+    let blocks = blocks.as_ref().ok_or_else(invalid)?;
+    blocks.0.from_devno(Devno::from(devno))
+}
+
+#[no_mangle]
+pub extern "C" fn lb_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match last.borrow().as_ref() {
+        Some(msg) => msg.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn lb_blocks_new() -> *mut LbBlocks {
+    match Blocks::new() {
+        Ok(blocks) => Box::into_raw(Box::new(LbBlocks(blocks))),
+        Err(err) => {
+            set_error(&err);
+            ptr::null_mut()
+        }
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_blocks_free(blocks: *mut LbBlocks) {
+    if !blocks.is_null() {
+        drop(Box::from_raw(blocks));
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_blocks_disks(blocks: *const LbBlocks, out: *mut LbDevnoList) -> c_int {
+    ret((|| {
+        let blocks = blocks.as_ref().ok_or_else(invalid)?;
+        write_list(out, blocks.0.disks()?.map(|d| d.map(|d| d.to_devno())))
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_blocks_all(blocks: *const LbBlocks, out: *mut LbDevnoList) -> c_int {
+    ret((|| {
+        let blocks = blocks.as_ref().ok_or_else(invalid)?;
+        write_list(out, blocks.0.blocks()?.map(|d| d.map(|d| d.to_devno())))
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_devno_list_free(list: *mut LbDevnoList) {
+    if let Some(list) = list.as_mut() {
+        if !list.devnos.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+                list.devnos,
+                list.len,
+            )));
+        }
+        list.devnos = ptr::null_mut();
+        list.len = 0;
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_device_info(
+    blocks: *const LbBlocks,
+    devno: LbDevno,
+    out: *mut LbDeviceInfo,
+) -> c_int {
+    ret((|| {
+        if out.is_null() {
+            return Err(invalid());
+        }
+        let info = device(blocks, devno)?.info()?;
+        out.write(LbDeviceInfo::from(info));
+        Ok(())
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_device_info_free(info: *mut LbDeviceInfo) {
+    if let Some(info) = info.as_mut() {
+        free_string(&mut info.name);
+        free_string(&mut info.path);
+        free_string(&mut info.model);
+        free_string(&mut info.fs_type);
+        free_string(&mut info.fs_uuid);
+        free_string(&mut info.fs_label);
+    }
+}
+
+// `found` is false for whole disks at the bottom of the stack
+#[no_mangle]
+pub unsafe extern "C" fn lb_device_parent(
+    blocks: *const LbBlocks,
+    devno: LbDevno,
+    out: *mut LbDevno,
+    found: *mut bool,
+) -> c_int {
+    ret((|| {
+        let (out, found) = match (out.as_mut(), found.as_mut()) {
+            (Some(out), Some(found)) => (out, found),
+            _ => return Err(invalid()),
+        };
+        let parent = device(blocks, devno)?.parent()?;
+        *found = parent.is_some();
+        if let Some(parent) = parent {
+            *out = parent.to_devno().into();
+        }
+        Ok(())
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_device_partitions(
+    blocks: *const LbBlocks,
+    devno: LbDevno,
+    out: *mut LbDevnoList,
+) -> c_int {
+    ret((|| {
+        let device = device(blocks, devno)?;
+        write_list(out, device.partitions()?.map(|d| d.map(|d| d.to_devno())))
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_device_slaves(
+    blocks: *const LbBlocks,
+    devno: LbDevno,
+    out: *mut LbDevnoList,
+) -> c_int {
+    ret((|| {
+        let device = device(blocks, devno)?;
+        write_list(out, device.slaves()?.map(|d| d.map(|d| d.to_devno())))
+    })())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lb_device_holders(
+    blocks: *const LbBlocks,
+    devno: LbDevno,
+    out: *mut LbDevnoList,
+) -> c_int {
+    ret((|| {
+        let holders = device(blocks, devno)?.sysfs_path()?.join("holders");
+        if holders.is_dir() {
+            write_list(out, RawSlavesIterator::new(holders)?)
+        } else {
+            write_list(out, std::iter::empty())
+        }
+    })())
+}
@@ -0,0 +1,149 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd, path::Path};
+
+use crate::{ioctl, mountpoint::mount_device, Blocks, Device, Error, Result};
+
+const FS_IOC_FIEMAP: u32 = ioctl::iowr::<fiemap>(b'f' as _, 11);
+const FIEMAP_FLAG_SYNC: u32 = 0x0001;
+const FIEMAP_BATCH: usize = 64;
+
+// fe_physical is an address in the filesystem's own logical space there,
+// not an offset on the mounted device
+const NON_DEVICE_ADDRESSING: &[&str] = &["btrfs", "bcachefs"];
+
+#[repr(C)]
+#[derive(Default)]
+struct fiemap {
+    fm_start: u64,
+    fm_length: u64,
+    fm_flags: u32,
+    fm_mapped_extents: u32,
+    fm_extent_count: u32,
+    fm_reserved: u32,
+}
+
+#[repr(C)]
+#[derive(Default, Clone, Copy)]
+struct fiemap_extent {
+    fe_logical: u64,
+    fe_physical: u64,
+    fe_length: u64,
+    fe_reserved64: [u64; 2],
+    fe_flags: u32,
+    fe_reserved: [u32; 3],
+}
+
+#[repr(C)]
+struct FiemapRequest {
+    header: fiemap,
+    extents: [fiemap_extent; FIEMAP_BATCH],
+}
+
+crate::flags::flags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct ExtentFlags(u32) {
+        const LAST = 0x0001 => "last";
+        const UNKNOWN = 0x0002 => "unknown";
+        const DELALLOC = 0x0004 => "delalloc";
+        const ENCODED = 0x0008 => "encoded";
+        const DATA_ENCRYPTED = 0x0080 => "encrypted";
+        const NOT_ALIGNED = 0x0100 => "not_aligned";
+        const DATA_INLINE = 0x0200 => "inline";
+        const DATA_TAIL = 0x0400 => "tail";
+        const UNWRITTEN = 0x0800 => "unwritten";
+        const MERGED = 0x1000 => "merged";
+        const SHARED = 0x2000 => "shared";
+    }
+}
+
+impl ExtentFlags {
+    // these extents have no stable location a raw copy could rely on
+    #[inline]
+    pub const fn is_copyable(&self) -> bool {
+        self.0
+            & (Self::UNKNOWN.0
+                | Self::DELALLOC.0
+                | Self::ENCODED.0
+                | Self::DATA_ENCRYPTED.0
+                | Self::NOT_ALIGNED.0
+                | Self::DATA_INLINE.0)
+            == 0
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Extent {
+    pub device: Device,
+    pub logical_offset: u64,
+    pub physical_offset: u64,
+    pub length: u64,
+    pub flags: ExtentFlags,
+}
+
+impl Extent {
+    #[inline]
+    pub fn is_last(&self) -> bool {
+        self.flags.contains(ExtentFlags::LAST)
+    }
+}
+
+fn raw_extents(f: &File) -> io::Result<Vec<fiemap_extent>> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    loop {
+        let mut req = FiemapRequest {
+            header: fiemap {
+                fm_start: start,
+                fm_length: u64::MAX - start,
+                fm_flags: FIEMAP_FLAG_SYNC,
+                fm_extent_count: FIEMAP_BATCH as _,
+                ..Default::default()
+            },
+            extents: [fiemap_extent::default(); FIEMAP_BATCH],
+        };
+        let ret = unsafe { libc::ioctl(f.as_raw_fd(), FS_IOC_FIEMAP as _, &mut req) };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mapped = &req.extents[..req.header.fm_mapped_extents as usize];
+        res.extend_from_slice(mapped);
+        match mapped.last() {
+            Some(last) if last.fe_flags & ExtentFlags::LAST.0 == 0 => {
+                start = last.fe_logical + last.fe_length;
+            }
+            _ => return Ok(res),
+        }
+    }
+}
+
+pub(crate) fn extents<P: AsRef<Path>>(blocks: &Blocks, p: P) -> Result<Vec<Extent>> {
+    ioctl::allowed("FS_IOC_FIEMAP")?;
+    let p = p.as_ref();
+    let f = File::open(p).map_err(|err| Error::path(p, err))?;
+    let mount = blocks.mountinfo_from_fd(&f)?;
+    if NON_DEVICE_ADDRESSING.contains(&mount.file_system.as_str()) {
+        return Err(Error::ioctl(
+            "FS_IOC_FIEMAP",
+            io::Error::from_raw_os_error(libc::EOPNOTSUPP),
+        ));
+    }
+    let device = mount_device(blocks, &mount)?;
+    let raw = raw_extents(&f).map_err(|err| Error::ioctl("FS_IOC_FIEMAP", err))?;
+
+    Ok(raw
+        .into_iter()
+        .map(|e| Extent {
+            device: device.clone(),
+            logical_offset: e.fe_logical,
+            physical_offset: e.fe_physical,
+            length: e.fe_length,
+            flags: ExtentFlags(e.fe_flags),
+        })
+        .collect())
+}
+
+#[inline]
+pub fn file_extents<P: AsRef<Path>>(p: P) -> Result<Vec<Extent>> {
+    extents(&Blocks::new()?, p)
+}
@@ -0,0 +1,201 @@
+use std::{fmt, fs::File, io, os::unix::prelude::FileExt, str::FromStr};
+
+use crate::{
+    parttable::{self, PartitionTable, PartitionType, TableKind},
+    Result,
+};
+
+const DEFAULT_SAMPLE: u64 = 1024 * 1024;
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FingerprintOptions {
+    head: u64,
+    tail: u64,
+    size: bool,
+    partition_ids: bool,
+}
+
+impl Default for FingerprintOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            head: DEFAULT_SAMPLE,
+            tail: DEFAULT_SAMPLE,
+            size: true,
+            partition_ids: true,
+        }
+    }
+}
+
+impl FingerprintOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn head(mut self, bytes: u64) -> Self {
+        self.head = bytes;
+        self
+    }
+
+    #[inline]
+    pub fn tail(mut self, bytes: u64) -> Self {
+        self.tail = bytes;
+        self
+    }
+
+    #[inline]
+    pub fn size(mut self, enabled: bool) -> Self {
+        self.size = enabled;
+        self
+    }
+
+    #[inline]
+    pub fn partition_ids(mut self, enabled: bool) -> Self {
+        self.partition_ids = enabled;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(u64);
+
+impl Fingerprint {
+    #[inline]
+    pub fn as_u64(&self) -> u64 {
+        self.0
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseFingerprintError;
+
+impl fmt::Display for ParseFingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid fingerprint")
+    }
+}
+
+impl std::error::Error for ParseFingerprintError {}
+
+impl FromStr for Fingerprint {
+    type Err = ParseFingerprintError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.len() != 16 {
+            return Err(ParseFingerprintError);
+        }
+        u64::from_str_radix(s, 16)
+            .map(Self)
+            .map_err(|_| ParseFingerprintError)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Fingerprint {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Fingerprint {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+// FNV-1a, stable across runs and builds unlike std's hashers
+struct Hasher(u64);
+
+impl Hasher {
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    #[inline]
+    fn write_u64(&mut self, n: u64) {
+        self.write(&n.to_le_bytes())
+    }
+}
+
+fn hash_range(h: &mut Hasher, f: &File, offset: u64, len: u64) -> Result<()> {
+    let mut buf = vec![0u8; len.min(DEFAULT_SAMPLE) as usize];
+    let mut done = 0;
+    while done < len {
+        let n = (len - done).min(buf.len() as u64) as usize;
+        f.read_exact_at(&mut buf[..n], offset + done)?;
+        h.write(&buf[..n]);
+        done += n as u64;
+    }
+    Ok(())
+}
+
+fn hash_table(h: &mut Hasher, table: &PartitionTable) {
+    match table.kind {
+        TableKind::Mbr { disk_id } => h.write_u64(disk_id as u64),
+        TableKind::Gpt { disk_guid } => h.write(disk_guid.as_bytes()),
+    }
+    for e in table.entries.iter() {
+        h.write_u64(e.number as u64);
+        match e.uuid {
+            Some(uuid) => h.write(uuid.as_bytes()),
+            // MBR entries have no identity of their own besides their geometry
+            None => {
+                h.write_u64(e.start);
+                h.write_u64(e.sectors);
+                if let PartitionType::Mbr(ty) = e.part_type {
+                    h.write(&[ty]);
+                }
+            }
+        }
+    }
+}
+
+pub(crate) fn fingerprint(
+    f: &File,
+    sector_size: u64,
+    opts: FingerprintOptions,
+) -> Result<Fingerprint> {
+    let size = parttable::disk_sectors(f, 1)?;
+    let mut h = Hasher(FNV_OFFSET);
+
+    if opts.size {
+        h.write_u64(size);
+    }
+    let head = opts.head.min(size);
+    hash_range(&mut h, f, 0, head)?;
+    // do not hash the overlap twice on small devices
+    let tail = opts.tail.min(size - head);
+    hash_range(&mut h, f, size - tail, tail)?;
+
+    if opts.partition_ids {
+        match PartitionTable::read(f, sector_size) {
+            Ok(Some(table)) => hash_table(&mut h, &table),
+            Ok(None) => (),
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => (),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(Fingerprint(h.0))
+}
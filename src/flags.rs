@@ -0,0 +1,80 @@
+// a bit set newtype with one constant per flag, from_bits/bits/contains and a
+// Display listing the names of the set flags, comma separated
+macro_rules! flags {
+    (
+        $(#[$meta:meta])*
+        pub struct $name:ident($ty:ty) {
+            $(const $flag:ident = $value:expr => $display:literal;)*
+        }
+    ) => {
+        $(#[$meta])*
+        pub struct $name($ty);
+
+        impl $name {
+            $(pub const $flag: Self = Self($value);)*
+
+            #[inline]
+            pub const fn from_bits(bits: $ty) -> Self {
+                Self(bits)
+            }
+
+            #[inline]
+            pub const fn bits(&self) -> $ty {
+                self.0
+            }
+
+            #[inline]
+            pub const fn contains(&self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                let mut first = true;
+                for (flag, name) in [$((Self::$flag, $display)),*] {
+                    if self.contains(flag) {
+                        if !first {
+                            f.write_str(",")?;
+                        }
+                        f.write_str(name)?;
+                        first = false;
+                    }
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+pub(crate) use flags;
+
+#[cfg(test)]
+mod tests {
+    flags! {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+        pub struct Test(u8) {
+            const A = 1 << 0 => "a";
+            const B = 1 << 1 => "b";
+            const C = 1 << 7 => "c";
+        }
+    }
+
+    #[test]
+    fn bits() {
+        let t = Test::from_bits(0b1000_0001);
+        assert_eq!(t.bits(), 0x81);
+        assert!(t.contains(Test::A) && t.contains(Test::C));
+        assert!(!t.contains(Test::B));
+        assert!(t.contains(Test::default()));
+        assert!(!t.contains(Test::from_bits(0b11)));
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Test::from_bits(0b1000_0011).to_string(), "a,b,c");
+        assert_eq!(Test::from_bits(0b1000_0010).to_string(), "b,c");
+        // bits without a name are left out
+        assert_eq!(Test::from_bits(0b0000_0100).to_string(), "");
+    }
+}
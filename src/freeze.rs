@@ -0,0 +1,108 @@
+use std::{
+    fs::File,
+    io,
+    os::unix::prelude::{AsRawFd, MetadataExt},
+    path::Path,
+};
+
+use crate::{
+    ioctl::{self, FIFREEZE, FITHAW},
+    procfs::MountInfo,
+    Devno, Error, Result,
+};
+
+#[derive(Debug, Clone)]
+pub struct MountedFilesystem {
+    info: MountInfo,
+}
+
+impl MountedFilesystem {
+    #[inline]
+    pub fn new(info: MountInfo) -> Self {
+        Self { info }
+    }
+
+    #[inline]
+    pub fn mount_info(&self) -> &MountInfo {
+        &self.info
+    }
+
+    #[inline]
+    pub fn mount_point(&self) -> &Path {
+        &self.info.mount_point
+    }
+
+    #[inline]
+    pub fn into_inner(self) -> MountInfo {
+        self.info
+    }
+
+    fn open(&self) -> Result<File> {
+        let p = &self.info.mount_point;
+        let f = File::open(p).map_err(|err| Error::path(p, err))?;
+        // something else may have been mounted on top in the meantime
+        let dev: Devno = f
+            .metadata()
+            .map_err(|err| Error::path(p, err))?
+            .dev()
+            .into();
+        if dev != self.info.dev {
+            return Err(Error::path(
+                p,
+                io::Error::new(io::ErrorKind::NotFound, "mount point is shadowed"),
+            ));
+        }
+        Ok(f)
+    }
+
+    pub fn freeze(&self) -> Result<FreezeGuard> {
+        let f = self.open()?;
+        ioctl(&f, FIFREEZE, "FIFREEZE")?;
+        Ok(FreezeGuard { f: Some(f) })
+    }
+
+    #[inline]
+    pub fn thaw(&self) -> Result<()> {
+        ioctl(&self.open()?, FITHAW, "FITHAW")
+    }
+}
+
+impl From<MountInfo> for MountedFilesystem {
+    #[inline]
+    fn from(info: MountInfo) -> Self {
+        Self::new(info)
+    }
+}
+
+fn ioctl(f: &File, op: u32, name: &'static str) -> Result<()> {
+    let mut arg: libc::c_int = 0;
+    ioctl::allowed(name)?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), op as _, &mut arg) };
+    if ret < 0 {
+        Err(Error::ioctl(name, io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct FreezeGuard {
+    f: Option<File>,
+}
+
+impl FreezeGuard {
+    pub fn thaw(mut self) -> Result<()> {
+        match self.f.take() {
+            Some(f) => ioctl(&f, FITHAW, "FITHAW"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for FreezeGuard {
+    fn drop(&mut self) {
+        if let Some(f) = self.f.take() {
+            let _ = ioctl(&f, FITHAW, "FITHAW");
+        }
+    }
+}
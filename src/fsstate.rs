@@ -0,0 +1,358 @@
+use std::{fmt, fs::File, io, os::unix::fs::FileExt};
+
+use crate::Result;
+
+const EXT_SB_OFFSET: u64 = 1024;
+const EXT_MAGIC: u16 = 0xef53;
+const EXT_VALID_FS: u16 = 0x0001;
+const EXT_ERROR_FS: u16 = 0x0002;
+const EXT_FEATURE_INCOMPAT_RECOVER: u32 = 0x0004;
+
+const XFS_MAGIC: &[u8] = b"XFSB";
+const XLOG_HEADER_MAGIC: u32 = 0xfeed_babe;
+const XLOG_UNMOUNT_TRANS: u8 = 0x04;
+const XLOG_VERSION_2: u32 = 0x2;
+const XLOG_HEADER_CYCLE_SIZE: u32 = 32 * 1024;
+const XLOG_MAX_RECORD_BLOCKS: u64 = 2 * 1024 * 1024 / 512;
+const BBSIZE: u64 = 512;
+
+const NTFS_OEM_ID: &[u8] = b"NTFS    ";
+const NTFS_VOLUME_MFT_RECORD: u64 = 3;
+const NTFS_AT_VOLUME_INFORMATION: u32 = 0x70;
+const NTFS_AT_END: u32 = 0xffff_ffff;
+const NTFS_VOLUME_IS_DIRTY: u16 = 0x0001;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FsState {
+    Clean,
+    Dirty,
+    NeedsRecovery,
+    Errors,
+}
+
+impl FsState {
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        matches!(self, Self::Clean)
+    }
+
+    #[inline]
+    pub fn needs_check(&self) -> bool {
+        !self.is_clean()
+    }
+}
+
+impl fmt::Display for FsState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Clean => "clean",
+            Self::Dirty => "dirty",
+            Self::NeedsRecovery => "needs recovery",
+            Self::Errors => "errors",
+        })
+    }
+}
+
+fn read(f: &File, offset: u64, len: usize) -> Result<Option<Vec<u8>>> {
+    let mut buf = vec![0u8; len];
+    match f.read_exact_at(&mut buf, offset) {
+        Ok(()) => Ok(Some(buf)),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[inline]
+fn le16(buf: &[u8], off: usize) -> u16 {
+    u16::from_le_bytes(buf[off..off + 2].try_into().unwrap())
+}
+
+#[inline]
+fn le32(buf: &[u8], off: usize) -> u32 {
+    u32::from_le_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+#[inline]
+fn be32(buf: &[u8], off: usize) -> u32 {
+    u32::from_be_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+#[inline]
+fn be64(buf: &[u8], off: usize) -> u64 {
+    u64::from_be_bytes(buf[off..off + 8].try_into().unwrap())
+}
+
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(level = "debug", skip_all, ret, err)
+)]
+pub(crate) fn probe(f: &File) -> Result<Option<FsState>> {
+    if let Some(state) = ext(f)? {
+        return Ok(Some(state));
+    }
+    if let Some(state) = xfs(f)? {
+        return Ok(Some(state));
+    }
+    ntfs(f)
+}
+
+fn ext(f: &File) -> Result<Option<FsState>> {
+    let sb = match read(f, EXT_SB_OFFSET, 1024)? {
+        Some(sb) if le16(&sb, 0x38) == EXT_MAGIC => sb,
+        _ => return Ok(None),
+    };
+
+    let state = le16(&sb, 0x3a);
+    let incompat = le32(&sb, 0x60);
+    let error_count = le32(&sb, 0x194);
+    Ok(Some(if state & EXT_ERROR_FS != 0 || error_count != 0 {
+        FsState::Errors
+    } else if incompat & EXT_FEATURE_INCOMPAT_RECOVER != 0 {
+        FsState::NeedsRecovery
+    } else if state & EXT_VALID_FS == 0 {
+        FsState::Dirty
+    } else {
+        FsState::Clean
+    }))
+}
+
+struct XfsLog<'a> {
+    f: &'a File,
+    start: u64,
+    blocks: u64,
+}
+
+impl XfsLog<'_> {
+    #[inline]
+    fn block(&self, n: u64) -> Result<Vec<u8>> {
+        let offset = self
+            .start
+            .checked_add(n)
+            .and_then(|block| block.checked_mul(BBSIZE));
+        match offset {
+            Some(offset) => read(self.f, offset, BBSIZE as usize)?,
+            None => None,
+        }
+        .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof).into())
+    }
+
+    fn cycle(&self, n: u64) -> Result<u32> {
+        let block = self.block(n)?;
+        // record headers keep their magic in place of the cycle stamp
+        if be32(&block, 0) == XLOG_HEADER_MAGIC {
+            Ok(be32(&block, 4))
+        } else {
+            Ok(be32(&block, 0))
+        }
+    }
+
+    // every block written in the same pass carries the same cycle number,
+    // the head is the first block still stamped with the previous one
+    fn head(&self) -> Result<u64> {
+        let first = self.cycle(0)?;
+        if self.cycle(self.blocks - 1)? == first {
+            return Ok(self.blocks);
+        }
+        let (mut lo, mut hi) = (0, self.blocks - 1);
+        while hi - lo > 1 {
+            let mid = lo + (hi - lo) / 2;
+            if self.cycle(mid)? == first {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        Ok(hi)
+    }
+
+    fn is_clean(&self) -> Result<bool> {
+        if self.cycle(0)? == 0 {
+            return Ok(true);
+        }
+
+        let head = self.head()?;
+        let limit = self.blocks.min(XLOG_MAX_RECORD_BLOCKS);
+        for i in 1..=limit {
+            let n = (head + self.blocks - i) % self.blocks;
+            let rec = self.block(n)?;
+            if be32(&rec, 0) != XLOG_HEADER_MAGIC {
+                continue;
+            }
+
+            let version = be32(&rec, 8);
+            let size = be32(&rec, 320);
+            let header_blocks = if version & XLOG_VERSION_2 != 0 && size > XLOG_HEADER_CYCLE_SIZE {
+                size.div_ceil(XLOG_HEADER_CYCLE_SIZE) as u64
+            } else {
+                1
+            };
+            // a clean unmount leaves a lone unmount transaction as last record
+            if be32(&rec, 40) != 1 {
+                return Ok(false);
+            }
+            let op = self.block((n + header_blocks) % self.blocks)?;
+            return Ok(op[9] & XLOG_UNMOUNT_TRANS != 0);
+        }
+        Ok(false)
+    }
+}
+
+fn xfs(f: &File) -> Result<Option<FsState>> {
+    let sb = match read(f, 0, 512)? {
+        Some(sb) if &sb[0..4] == XFS_MAGIC => sb,
+        _ => return Ok(None),
+    };
+
+    let block_size = be32(&sb, 4) as u64;
+    let log_start = be64(&sb, 0x30);
+    let ag_blocks = be32(&sb, 0x54) as u64;
+    let log_blocks = be32(&sb, 0x60) as u64;
+    let ag_blklog = sb[0x7c] as u32;
+    // external logs live on another device
+    if log_start == 0 || log_blocks == 0 || block_size < BBSIZE || ag_blklog >= 64 {
+        return Ok(None);
+    }
+
+    let bb_per_block = block_size / BBSIZE;
+    let ag = log_start >> ag_blklog;
+    let ag_block = log_start & ((1u64 << ag_blklog) - 1);
+    let start = ag
+        .checked_mul(ag_blocks)
+        .and_then(|block| block.checked_add(ag_block))
+        .and_then(|block| block.checked_mul(bb_per_block));
+    let (start, blocks) = match (start, log_blocks.checked_mul(bb_per_block)) {
+        (Some(start), Some(blocks)) => (start, blocks),
+        _ => return Ok(None),
+    };
+    let log = XfsLog { f, start, blocks };
+    match log.is_clean() {
+        Ok(true) => Ok(Some(FsState::Clean)),
+        Ok(false) => Ok(Some(FsState::NeedsRecovery)),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn ntfs(f: &File) -> Result<Option<FsState>> {
+    let boot = match read(f, 0, 512)? {
+        Some(boot) if &boot[3..11] == NTFS_OEM_ID => boot,
+        _ => return Ok(None),
+    };
+
+    let sector_size = le16(&boot, 0x0b) as u64;
+    let cluster_size = sector_size * boot[0x0d] as u64;
+    let mft_lcn = u64::from_le_bytes(boot[0x30..0x38].try_into().unwrap());
+    let record_size = match boot[0x40] as i8 {
+        n if n < 0 => 1u64.checked_shl(-(n as i32) as u32).unwrap_or(0),
+        n => n as u64 * cluster_size,
+    };
+    if sector_size < 256 || record_size < sector_size || record_size > 64 * 1024 {
+        return Ok(None);
+    }
+
+    let offset = match mft_lcn
+        .checked_mul(cluster_size)
+        .and_then(|mft| mft.checked_add(NTFS_VOLUME_MFT_RECORD * record_size))
+    {
+        Some(offset) => offset,
+        None => return Ok(None),
+    };
+    let mut rec = match read(f, offset, record_size as usize)? {
+        Some(rec) if &rec[0..4] == b"FILE" => rec,
+        _ => return Ok(None),
+    };
+
+    // undo the update sequence fixups stored at the end of every sector
+    let usa_offset = le16(&rec, 4) as usize;
+    let usa_count = le16(&rec, 6) as usize;
+    if usa_offset + usa_count * 2 > rec.len() {
+        return Ok(None);
+    }
+    for i in 1..usa_count {
+        let end = i * sector_size as usize;
+        if end > rec.len() {
+            break;
+        }
+        let fixup = usa_offset + i * 2;
+        let (a, b) = (rec[fixup], rec[fixup + 1]);
+        rec[end - 2] = a;
+        rec[end - 1] = b;
+    }
+
+    let mut off = le16(&rec, 0x14) as usize;
+    while off + 0x18 <= rec.len() {
+        let ty = le32(&rec, off);
+        let len = le32(&rec, off + 4) as usize;
+        if ty == NTFS_AT_END || len == 0 {
+            break;
+        }
+        if ty == NTFS_AT_VOLUME_INFORMATION && rec[off + 8] == 0 {
+            let value = off + le16(&rec, off + 0x14) as usize;
+            if value + 12 > rec.len() {
+                break;
+            }
+            return Ok(Some(
+                if le16(&rec, value + 10) & NTFS_VOLUME_IS_DIRTY != 0 {
+                    FsState::Dirty
+                } else {
+                    FsState::Clean
+                },
+            ));
+        }
+        off += len;
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parttable::test_image;
+
+    fn xfs_sb(log_start: u64, ag_blocks: u32, ag_blklog: u8) -> Vec<u8> {
+        let mut sb = vec![0u8; 4096];
+        sb[0..4].copy_from_slice(XFS_MAGIC);
+        sb[4..8].copy_from_slice(&4096u32.to_be_bytes());
+        sb[0x30..0x38].copy_from_slice(&log_start.to_be_bytes());
+        sb[0x54..0x58].copy_from_slice(&ag_blocks.to_be_bytes());
+        sb[0x60..0x64].copy_from_slice(&1024u32.to_be_bytes());
+        sb[0x7c] = ag_blklog;
+        sb
+    }
+
+    #[test]
+    fn ext_clean() {
+        let mut image = vec![0u8; 4096];
+        let sb = EXT_SB_OFFSET as usize;
+        image[sb + 0x38..sb + 0x3a].copy_from_slice(&EXT_MAGIC.to_le_bytes());
+        image[sb + 0x3a..sb + 0x3c].copy_from_slice(&EXT_VALID_FS.to_le_bytes());
+        let f = test_image("fsstate-ext", &image);
+        assert_eq!(probe(&f).unwrap(), Some(FsState::Clean));
+    }
+
+    #[test]
+    fn xfs_corrupt_superblocks() {
+        for (name, sb) in [
+            ("fsstate-xfs-shift", xfs_sb(1, 16, 200)),
+            ("fsstate-xfs-mul", xfs_sb(u64::MAX, u32::MAX, 0)),
+            ("fsstate-xfs-eof", xfs_sb(1 << 40, 1, 0)),
+        ] {
+            let f = test_image(name, &sb);
+            assert_eq!(probe(&f).unwrap(), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn ntfs_corrupt_mft_location() {
+        let mut boot = vec![0u8; 4096];
+        boot[3..11].copy_from_slice(NTFS_OEM_ID);
+        boot[0x0b..0x0d].copy_from_slice(&512u16.to_le_bytes());
+        boot[0x0d] = 8;
+        boot[0x30..0x38].copy_from_slice(&u64::MAX.to_le_bytes());
+        // 2^10 bytes per record
+        boot[0x40] = -10i8 as u8;
+        let f = test_image("fsstate-ntfs", &boot);
+        assert_eq!(probe(&f).unwrap(), None);
+    }
+}
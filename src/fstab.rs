@@ -0,0 +1,240 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Lines},
+    os::unix::prelude::OsStrExt,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    procfs::{unescape, unescape_path, Escaped, MountOptions},
+    Blocks, Device, Error, Result,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FstabSource {
+    Path(PathBuf),
+    Uuid(String),
+    Label(String),
+    PartUuid(String),
+    PartLabel(String),
+    Other(String),
+}
+
+impl FstabSource {
+    pub(crate) fn parse(s: &str) -> Self {
+        let tagged = [
+            ("UUID=", Self::Uuid as fn(String) -> Self),
+            ("LABEL=", Self::Label),
+            ("PARTUUID=", Self::PartUuid),
+            ("PARTLABEL=", Self::PartLabel),
+        ];
+        for (prefix, ctor) in tagged {
+            if let Some(value) = s.strip_prefix(prefix) {
+                let value =
+                    String::from_utf8_lossy(&unescape(value.trim_matches('"'))).into_owned();
+                return ctor(value);
+            }
+        }
+
+        if s.starts_with('/') {
+            Self::Path(unescape_path(s))
+        } else {
+            Self::Other(String::from_utf8_lossy(&unescape(s)).into_owned())
+        }
+    }
+
+    pub fn resolve(&self, blocks: &Blocks) -> Result<Option<Device>> {
+        let (key, link, value) = match self.udev_key() {
+            Some(x) => x,
+            None => {
+                return match self {
+                    Self::Path(p) if p.exists() => blocks.from_path(p).map(Some),
+                    _ => Ok(None),
+                }
+            }
+        };
+
+        if let Some(devfs) = blocks.devfs() {
+            let p = devfs.path().join("disk").join(link).join(value);
+            if devfs.lookup(&p).is_some() {
+                return blocks.from_path(p).map(Some);
+            }
+        }
+
+        for device in blocks.blocks()? {
+            let device = device?;
+            if let Some(record) = device.udev_properties()? {
+                if record
+                    .get(key)
+                    .is_some_and(|v| v.eq_ignore_ascii_case(value))
+                {
+                    return Ok(Some(device));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    fn udev_key(&self) -> Option<(&'static str, &'static str, &str)> {
+        match self {
+            Self::Uuid(uuid) => Some(("ID_FS_UUID", "by-uuid", uuid)),
+            Self::Label(label) => Some(("ID_FS_LABEL", "by-label", label)),
+            Self::PartUuid(uuid) => Some(("ID_PART_ENTRY_UUID", "by-partuuid", uuid)),
+            Self::PartLabel(label) => Some(("ID_PART_ENTRY_NAME", "by-partlabel", label)),
+            Self::Path(_) | Self::Other(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for FstabSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(p) => Escaped(p.as_os_str().as_bytes()).fmt(f),
+            Self::Uuid(uuid) => write!(f, "UUID={}", Escaped(uuid.as_bytes())),
+            Self::Label(label) => write!(f, "LABEL={}", Escaped(label.as_bytes())),
+            Self::PartUuid(uuid) => write!(f, "PARTUUID={}", Escaped(uuid.as_bytes())),
+            Self::PartLabel(label) => write!(f, "PARTLABEL={}", Escaped(label.as_bytes())),
+            Self::Other(s) => Escaped(s.as_bytes()).fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FstabEntry {
+    pub source: FstabSource,
+    pub target: PathBuf,
+    pub file_system: String,
+    pub options: MountOptions,
+    pub dump: u32,
+    pub pass: u32,
+}
+
+#[derive(Debug)]
+pub struct ParseFstabError;
+
+impl FromStr for FstabEntry {
+    type Err = ParseFstabError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.split_whitespace();
+
+        Ok(Self {
+            source: FstabSource::parse(it.next().ok_or(ParseFstabError)?),
+            target: unescape_path(it.next().ok_or(ParseFstabError)?),
+            file_system: it.next().ok_or(ParseFstabError)?.to_string(),
+            options: MountOptions::parse(it.next().unwrap_or("defaults")),
+            dump: match it.next() {
+                Some(dump) => dump.parse().map_err(|_| ParseFstabError)?,
+                None => 0,
+            },
+            pass: match it.next() {
+                Some(pass) => pass.parse().map_err(|_| ParseFstabError)?,
+                None => 0,
+            },
+        })
+    }
+}
+
+impl std::fmt::Display for FstabEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {} {}",
+            self.source,
+            Escaped(self.target.as_os_str().as_bytes()),
+            self.file_system,
+            self.options,
+            self.dump,
+            self.pass
+        )
+    }
+}
+
+impl FstabEntry {
+    #[inline]
+    pub fn resolve(&self, blocks: &Blocks) -> Result<Option<Device>> {
+        self.source.resolve(blocks)
+    }
+}
+
+pub struct FstabIterator {
+    path: PathBuf,
+    lines: Lines<BufReader<File>>,
+    line: usize,
+}
+
+impl FstabIterator {
+    pub fn new<P: AsRef<Path>>(file: P) -> Result<Self> {
+        let path = file.as_ref().to_path_buf();
+        let f = File::open(&path).map_err(|err| Error::path(&path, err))?;
+        Ok(Self {
+            path,
+            lines: BufReader::new(f).lines(),
+            line: 0,
+        })
+    }
+}
+
+impl Iterator for FstabIterator {
+    type Item = Result<FstabEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line += 1;
+            let line = match self.lines.next()? {
+                Ok(line) => line,
+                Err(err) => return Some(Err(Error::path(&self.path, err))),
+            };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            return Some(line.parse().map_err(|_| Error::ParseError {
+                file: self.path.clone(),
+                line: Some(self.line),
+            }));
+        }
+    }
+}
+
+pub struct Fstab {
+    path: PathBuf,
+}
+
+impl Fstab {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_path("/etc/fstab")
+    }
+
+    #[inline]
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Self {
+        Self {
+            path: p.as_ref().to_path_buf(),
+        }
+    }
+
+    #[inline]
+    pub fn iter(&self) -> Result<FstabIterator> {
+        FstabIterator::new(&self.path)
+    }
+
+    #[inline]
+    pub fn all(&self) -> Result<Vec<FstabEntry>> {
+        self.iter()?.collect()
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Default for Fstab {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    alloc::{self, Layout},
+    fmt,
+    fs::File,
+    io,
+    ops::{Deref, DerefMut},
+    os::unix::prelude::FileExt,
+    ptr::NonNull,
+};
+
+use crate::{parttable, Result};
+
+const BOUNCE_SIZE: usize = 1024 * 1024;
+
+pub struct AlignedBuf {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+unsafe impl Send for AlignedBuf {}
+unsafe impl Sync for AlignedBuf {}
+
+impl AlignedBuf {
+    pub fn new(len: usize, align: usize) -> Result<Self> {
+        let layout = Layout::from_size_align(len.max(1), align)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid buffer alignment"))?;
+        let ptr = NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+            .unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        Ok(Self { ptr, layout })
+    }
+
+    #[inline]
+    pub fn align(&self) -> usize {
+        self.layout.align()
+    }
+}
+
+impl Deref for AlignedBuf {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl DerefMut for AlignedBuf {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.layout.size()) }
+    }
+}
+
+impl Drop for AlignedBuf {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
+}
+
+impl fmt::Debug for AlignedBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AlignedBuf")
+            .field("len", &self.layout.size())
+            .field("align", &self.layout.align())
+            .finish()
+    }
+}
+
+#[derive(Debug)]
+pub struct DeviceHandle {
+    file: File,
+    block_size: u64,
+    size: u64,
+}
+
+impl DeviceHandle {
+    pub(crate) fn new(file: File, block_size: u64) -> Result<Self> {
+        let size = parttable::disk_sectors(&file, block_size)? * block_size;
+        Ok(Self {
+            file,
+            block_size,
+            size,
+        })
+    }
+
+    #[inline]
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    #[inline]
+    pub fn into_file(self) -> File {
+        self.file
+    }
+
+    #[inline]
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    #[inline]
+    pub fn alloc(&self, len: usize) -> Result<AlignedBuf> {
+        let bs = self.block_size as usize;
+        AlignedBuf::new(len.next_multiple_of(bs), bs)
+    }
+
+    fn check(&self, len: usize, offset: u64) -> Result<()> {
+        if !offset.is_multiple_of(self.block_size) || !(len as u64).is_multiple_of(self.block_size)
+        {
+            Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "offset and length must be multiples of the logical block size",
+            )
+            .into())
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline]
+    fn is_aligned(&self, ptr: *const u8) -> bool {
+        (ptr as usize).is_multiple_of(self.block_size as usize)
+    }
+
+    // keeps reading until the buffer is full or the device ends
+    fn read_full(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let mut done = 0;
+        while done < buf.len() {
+            match self.file.read_at(&mut buf[done..], offset + done as u64) {
+                Ok(0) => break,
+                Ok(n) => done += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(done)
+    }
+
+    fn write_full(&self, buf: &[u8], offset: u64) -> Result<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            match self.file.write_at(&buf[done..], offset + done as u64) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::WriteZero).into()),
+                Ok(n) => done += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.check(buf.len(), offset)?;
+        if self.is_aligned(buf.as_ptr()) {
+            return self.read_full(buf, offset);
+        }
+
+        let mut bounce = self.alloc(buf.len().min(BOUNCE_SIZE))?;
+        let mut done = 0;
+        for chunk in buf.chunks_mut(bounce.len()) {
+            let n = self.read_full(&mut bounce[..chunk.len()], offset + done as u64)?;
+            chunk[..n].copy_from_slice(&bounce[..n]);
+            done += n;
+            if n < chunk.len() {
+                break;
+            }
+        }
+        Ok(done)
+    }
+
+    pub fn write_at(&self, buf: &[u8], offset: u64) -> Result<()> {
+        self.check(buf.len(), offset)?;
+        if offset
+            .checked_add(buf.len() as u64)
+            .is_none_or(|end| end > self.size)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "write past the end of device",
+            )
+            .into());
+        }
+        if self.is_aligned(buf.as_ptr()) {
+            return self.write_full(buf, offset);
+        }
+
+        let mut bounce = self.alloc(buf.len().min(BOUNCE_SIZE))?;
+        let mut done = 0;
+        for chunk in buf.chunks(bounce.len()) {
+            bounce[..chunk.len()].copy_from_slice(chunk);
+            self.write_full(&bounce[..chunk.len()], offset + done as u64)?;
+            done += chunk.len();
+        }
+        Ok(())
+    }
+
+    #[inline]
+    pub fn sync(&self) -> Result<()> {
+        Ok(self.file.sync_data()?)
+    }
+}
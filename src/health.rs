@@ -0,0 +1,135 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd};
+
+use crate::{
+    ioctl::{self, HDIO_DRIVE_TASK},
+    power::{self, nvme_admin_cmd},
+    scsi::{self, SG_DXFER_FROM_DEV},
+    Error, Result,
+};
+
+const ATA_OP_SMART: u8 = 0xb0;
+const SMART_STATUS: u8 = 0xda;
+// lcyl/hcyl after SMART RETURN STATUS
+const SMART_CYL_OK: (u8, u8) = (0x4f, 0xc2);
+const SMART_CYL_FAILING: (u8, u8) = (0xf4, 0x2c);
+
+const NVME_ADMIN_GET_LOG_PAGE: u8 = 0x02;
+const NVME_LOG_SMART: u32 = 0x02;
+const NVME_NSID_ALL: u32 = 0xffff_ffff;
+const NVME_SMART_LOG_LEN: usize = 512;
+// spare, temperature and PMR warnings leave the data intact, these do not
+const NVME_CRIT_FAILING: u8 = 0x04 | 0x08 | 0x10;
+
+const LOG_SENSE: u8 = 0x4d;
+const LOG_PC_CUMULATIVE: u8 = 0x40;
+const IE_LOG_PAGE: u8 = 0x2f;
+const IE_ASC_FAILURE_PREDICTED: u8 = 0x5d;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum Health {
+    Good,
+    Warning,
+    Failing,
+}
+
+impl Health {
+    #[inline]
+    pub fn is_good(&self) -> bool {
+        matches!(self, Self::Good)
+    }
+}
+
+impl std::fmt::Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Good => "good",
+            Self::Warning => "warning",
+            Self::Failing => "failing",
+        })
+    }
+}
+
+pub(crate) fn ata_health(f: &File) -> Result<Health> {
+    // command, feature, nsector, sector, lcyl, hcyl, select
+    let mut args = [
+        ATA_OP_SMART,
+        SMART_STATUS,
+        0,
+        0,
+        SMART_CYL_OK.0,
+        SMART_CYL_OK.1,
+        0,
+    ];
+    ioctl::allowed("HDIO_DRIVE_TASK")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), HDIO_DRIVE_TASK as _, args.as_mut_ptr()) };
+    if ret < 0 {
+        return Err(Error::ioctl("HDIO_DRIVE_TASK", io::Error::last_os_error()));
+    }
+
+    match (args[4], args[5]) {
+        SMART_CYL_OK => Ok(Health::Good),
+        SMART_CYL_FAILING => Ok(Health::Failing),
+        (lcyl, hcyl) => Err(io::Error::other(format!(
+            "unexpected SMART status {:#04x}/{:#04x}",
+            lcyl, hcyl
+        ))
+        .into()),
+    }
+}
+
+pub(crate) fn nvme_health(f: &File) -> Result<Health> {
+    let mut log = [0u8; NVME_SMART_LOG_LEN];
+    let mut cmd = nvme_admin_cmd {
+        opcode: NVME_ADMIN_GET_LOG_PAGE,
+        nsid: NVME_NSID_ALL,
+        addr: log.as_mut_ptr() as u64,
+        data_len: log.len() as u32,
+        // number of dwords, 0-based
+        cdw10: NVME_LOG_SMART | ((log.len() as u32 / 4 - 1) << 16),
+        ..Default::default()
+    };
+    power::nvme_admin(f, &mut cmd)?;
+
+    let (critical_warning, percentage_used) = (log[0], log[5]);
+    Ok(if critical_warning & NVME_CRIT_FAILING != 0 {
+        Health::Failing
+    } else if critical_warning != 0 || percentage_used >= 100 {
+        Health::Warning
+    } else {
+        Health::Good
+    })
+}
+
+pub(crate) fn scsi_health(f: &File) -> Result<Health> {
+    let mut page = [0u8; 64];
+    let mut sense = [0; 32];
+    let cdb = [
+        LOG_SENSE,
+        0,
+        LOG_PC_CUMULATIVE | IE_LOG_PAGE,
+        0,
+        0,
+        0,
+        0,
+        0,
+        page.len() as u8,
+        0,
+    ];
+    scsi::sg_io(f, &cdb, SG_DXFER_FROM_DEV, &mut page, &mut sense)?;
+
+    // parameter 0000h holds the pending exception's ASC/ASCQ
+    if page[0] & 0x3f != IE_LOG_PAGE || page[4..6] != [0, 0] || page[7] < 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "malformed informational exceptions log page",
+        )
+        .into());
+    }
+    Ok(match page[8] {
+        0 => Health::Good,
+        IE_ASC_FAILURE_PREDICTED => Health::Failing,
+        _ => Health::Warning,
+    })
+}
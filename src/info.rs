@@ -0,0 +1,296 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    blocks::Blocks,
+    sysfs::{parse_attr, read_attr},
+    trace, Devno, Error, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DeviceType {
+    Disk,
+    Partition,
+    Rom,
+    Loop,
+    Md,
+    Dm,
+}
+
+impl DeviceType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Disk => "disk",
+            Self::Partition => "part",
+            Self::Rom => "rom",
+            Self::Loop => "loop",
+            Self::Md => "md",
+            Self::Dm => "dm",
+        }
+    }
+}
+
+impl std::fmt::Display for DeviceType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum DeviceKind {
+    Scsi,
+    Nvme,
+    Mmc,
+    Virtio,
+    Xen,
+    Optical,
+    Loop,
+    Dm,
+    Md,
+    Zram,
+    Nbd,
+    Other,
+}
+
+impl DeviceKind {
+    pub fn from_name(name: &str) -> Self {
+        let prefixes = [
+            ("nvme", Self::Nvme),
+            ("mmcblk", Self::Mmc),
+            ("xvd", Self::Xen),
+            ("vd", Self::Virtio),
+            ("sd", Self::Scsi),
+            ("sr", Self::Optical),
+            ("loop", Self::Loop),
+            ("dm-", Self::Dm),
+            ("md", Self::Md),
+            ("zram", Self::Zram),
+            ("nbd", Self::Nbd),
+        ];
+        prefixes
+            .iter()
+            .find(|(prefix, _)| name.starts_with(prefix))
+            .map(|(_, kind)| *kind)
+            .unwrap_or(Self::Other)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsInfo {
+    pub fs_type: Option<String>,
+    pub uuid: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DmInfo {
+    pub name: String,
+    pub uuid: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdInfo {
+    pub level: String,
+    pub raid_disks: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoopInfo {
+    pub backing_file: Option<PathBuf>,
+    pub offset: u64,
+    pub autoclear: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeviceInfo {
+    pub name: String,
+    pub devno: Devno,
+    pub path: Option<PathBuf>,
+    pub device_type: DeviceType,
+    pub size: u64,
+    pub read_only: bool,
+    pub removable: bool,
+    pub model: Option<String>,
+    pub partition_number: Option<usize>,
+    pub fs: Option<FsInfo>,
+    pub dm: Option<DmInfo>,
+    pub md: Option<MdInfo>,
+    pub loop_info: Option<LoopInfo>,
+    pub parent: Option<Devno>,
+    pub mountpoints: Vec<PathBuf>,
+    pub unavailable: Vec<Unavailable>,
+}
+
+// a field left at its default because reading it was not permitted
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Unavailable {
+    pub field: String,
+    pub reason: String,
+}
+
+fn flag<P: AsRef<Path>>(p: P) -> Result<bool> {
+    Ok(read_attr(p)?.as_deref() == Some("1"))
+}
+
+#[derive(Default)]
+struct Collector {
+    unavailable: Vec<Unavailable>,
+}
+
+impl Collector {
+    // permission errors degrade to the default, anything else is still fatal
+    fn get<T: Default>(&mut self, field: &str, res: Result<T>) -> Result<T> {
+        match res {
+            Ok(value) => Ok(value),
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                trace::debug!(field, error = %err, "device info field unavailable");
+                self.unavailable.push(Unavailable {
+                    field: field.to_string(),
+                    reason: err.to_string(),
+                });
+                Ok(T::default())
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+fn read_dm(dir: &Path) -> Result<Option<DmInfo>> {
+    Ok(match read_attr(dir.join("dm").join("name"))? {
+        Some(name) => Some(DmInfo {
+            name,
+            uuid: read_attr(dir.join("dm").join("uuid"))?,
+        }),
+        None => None,
+    })
+}
+
+fn read_md(dir: &Path) -> Result<Option<MdInfo>> {
+    Ok(match read_attr(dir.join("md").join("level"))? {
+        Some(level) => Some(MdInfo {
+            level,
+            raid_disks: parse_attr(dir.join("md").join("raid_disks"))?,
+        }),
+        None => None,
+    })
+}
+
+fn read_loop(dir: &Path) -> Result<Option<LoopInfo>> {
+    Ok(if dir.join("loop").is_dir() {
+        Some(LoopInfo {
+            backing_file: read_attr(dir.join("loop").join("backing_file"))?.map(PathBuf::from),
+            offset: parse_attr(dir.join("loop").join("offset"))?.unwrap_or(0),
+            autoclear: flag(dir.join("loop").join("autoclear"))?,
+        })
+    } else {
+        None
+    })
+}
+
+fn read_fs(blocks: &Blocks, devno: &Devno) -> Result<Option<FsInfo>> {
+    Ok(blocks.udev_properties(devno)?.and_then(|record| {
+        let fs = FsInfo {
+            fs_type: record.fs_type().map(str::to_string),
+            uuid: record.fs_uuid().map(str::to_string),
+            label: record.fs_label().map(str::to_string),
+        };
+        if fs == FsInfo::default() {
+            None
+        } else {
+            Some(fs)
+        }
+    }))
+}
+
+impl DeviceInfo {
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(blocks), fields(%devno), err))]
+    pub(crate) fn read(blocks: &Blocks, devno: &Devno) -> Result<Self> {
+        let dir = blocks.sysfs().resolve(devno)?;
+        let name = match dir.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(Error::NotFound { devno: *devno }),
+        };
+
+        let mut c = Collector::default();
+        let dm = c.get("dm", read_dm(&dir))?;
+        let md = c.get("md", read_md(&dir))?;
+        let loop_info = c.get("loop_info", read_loop(&dir))?;
+
+        let partition_number = match c.get("partition_number", parse_attr(dir.join("partition")))? {
+            Some(partno) => Some(partno),
+            None => dm
+                .as_ref()
+                .and_then(|dm| dm.uuid.as_deref()?.strip_prefix("part"))
+                .and_then(|rest| rest.split('-').next()?.parse().ok()),
+        };
+
+        let device_type = if partition_number.is_some() {
+            DeviceType::Partition
+        } else if dm.is_some() {
+            DeviceType::Dm
+        } else if md.is_some() {
+            DeviceType::Md
+        } else if loop_info.is_some() {
+            DeviceType::Loop
+        } else if name.starts_with("sr") {
+            DeviceType::Rom
+        } else {
+            DeviceType::Disk
+        };
+
+        // partitions inherit removable and model from their disk
+        let disk_dir = if partition_number.is_some() && dm.is_none() {
+            dir.parent().unwrap_or(&dir)
+        } else {
+            &dir
+        };
+
+        let size = c.get("size", parse_attr::<u64, _>(dir.join("size")))?;
+        Ok(Self {
+            path: blocks.resolve(devno).ok(),
+            device_type,
+            size: size.unwrap_or(0) * 512,
+            read_only: c.get("read_only", flag(dir.join("ro")))?,
+            removable: c.get("removable", flag(disk_dir.join("removable")))?,
+            model: c.get("model", read_attr(disk_dir.join("device").join("model")))?,
+            partition_number,
+            fs: c.get("fs", read_fs(blocks, devno))?,
+            dm,
+            md,
+            loop_info,
+            parent: c.get("parent", blocks.parent(devno))?,
+            mountpoints: c
+                .get("mountpoints", blocks.mounts(devno))?
+                .into_iter()
+                .map(|m| m.mount_point)
+                .collect(),
+            name,
+            devno: *devno,
+            unavailable: c.unavailable,
+        })
+    }
+
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.unavailable.is_empty()
+    }
+
+    pub fn unavailable(&self, field: &str) -> Option<&str> {
+        self.unavailable
+            .iter()
+            .find(|u| u.field == field)
+            .map(|u| u.reason.as_str())
+    }
+}
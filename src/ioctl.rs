@@ -0,0 +1,99 @@
+use std::mem::size_of;
+
+use crate::{Error, Result};
+
+pub(crate) const ENABLED: bool = cfg!(not(feature = "no-ioctl"));
+
+// with the no-ioctl feature every ioctl is refused up front, for seccomp
+// profiles that kill the process on the syscall instead of failing it
+#[inline]
+pub(crate) fn allowed(op: &'static str) -> Result<()> {
+    if ENABLED {
+        Ok(())
+    } else {
+        Err(Error::Unsupported { op })
+    }
+}
+
+#[cfg(any(
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "sparc",
+    target_arch = "sparc64"
+))]
+mod arch {
+    pub const NONE: u32 = 1;
+    pub const READ: u32 = 2;
+    pub const WRITE: u32 = 4;
+    pub const SIZEBITS: u32 = 13;
+}
+
+#[cfg(not(any(
+    target_arch = "powerpc",
+    target_arch = "powerpc64",
+    target_arch = "mips",
+    target_arch = "mips32r6",
+    target_arch = "mips64",
+    target_arch = "mips64r6",
+    target_arch = "sparc",
+    target_arch = "sparc64"
+)))]
+mod arch {
+    pub const NONE: u32 = 0;
+    pub const READ: u32 = 2;
+    pub const WRITE: u32 = 1;
+    pub const SIZEBITS: u32 = 14;
+}
+
+const NRSHIFT: u32 = 0;
+const TYPESHIFT: u32 = 8;
+const SIZESHIFT: u32 = 16;
+const DIRSHIFT: u32 = SIZESHIFT + arch::SIZEBITS;
+
+#[inline]
+pub(crate) const fn ioc(dir: u32, ty: u32, nr: u32, size: usize) -> u32 {
+    (dir << DIRSHIFT)
+        | (ty << TYPESHIFT)
+        | (nr << NRSHIFT)
+        | (((size as u32) & ((1 << arch::SIZEBITS) - 1)) << SIZESHIFT)
+}
+
+#[inline]
+pub(crate) const fn io(ty: u32, nr: u32) -> u32 {
+    ioc(arch::NONE, ty, nr, 0)
+}
+
+#[inline]
+pub(crate) const fn ior<T>(ty: u32, nr: u32) -> u32 {
+    ioc(arch::READ, ty, nr, size_of::<T>())
+}
+
+#[inline]
+#[allow(dead_code)]
+pub(crate) const fn iow<T>(ty: u32, nr: u32) -> u32 {
+    ioc(arch::WRITE, ty, nr, size_of::<T>())
+}
+
+#[inline]
+pub(crate) const fn iowr<T>(ty: u32, nr: u32) -> u32 {
+    ioc(arch::READ | arch::WRITE, ty, nr, size_of::<T>())
+}
+
+pub(crate) const BLKRRPART: u32 = io(0x12, 95);
+pub(crate) const BLKFLSBUF: u32 = io(0x12, 97);
+pub(crate) const BLKPG: u32 = io(0x12, 105);
+pub(crate) const BLKZEROOUT: u32 = io(0x12, 127);
+pub(crate) const BLKGETSIZE64: u32 = ior::<usize>(0x12, 114);
+pub(crate) const FIFREEZE: u32 = iowr::<libc::c_int>(b'X' as _, 119);
+pub(crate) const FITHAW: u32 = iowr::<libc::c_int>(b'X' as _, 120);
+
+// legacy numbers that predate the _IOC encoding
+pub(crate) const HDIO_DRIVE_TASK: u32 = 0x031e;
+pub(crate) const HDIO_DRIVE_CMD: u32 = 0x031f;
+pub(crate) const CDROM_EJECT: u32 = 0x5309;
+pub(crate) const CDROM_MEDIA_CHANGED: u32 = 0x5325;
+pub(crate) const SG_IO: u32 = 0x2285;
@@ -1,20 +1,20 @@
 mod partitions;
 mod slaves;
 
-use std::{borrow::Cow, io, rc::Rc};
+use std::{borrow::Cow, cmp::Ordering, sync::Arc};
 
 pub use partitions::PartitionsIterator;
 pub(crate) use slaves::RawSlavesIterator;
 pub use slaves::SlavesIterator;
 
-use crate::{blocks, Blocks, Device, Devno};
+use crate::{blocks, sysfs::iter::DisksIterator, Blocks, Device, Devno, Error, Result};
 
 pub struct DevnoMapper<'a, I> {
     blocks: Cow<'a, Blocks>,
     inner: I,
 }
 
-impl<'a, I: Iterator<Item = io::Result<Devno>>> DevnoMapper<'a, I> {
+impl<'a, E: Into<Error>, I: Iterator<Item = std::result::Result<Devno, E>>> DevnoMapper<'a, I> {
     #[inline]
     pub fn new(blocks: &'a Blocks, inner: I) -> Self {
         Self {
@@ -23,21 +23,111 @@ impl<'a, I: Iterator<Item = io::Result<Devno>>> DevnoMapper<'a, I> {
         }
     }
 
-    pub(crate) fn from_raw(blocks: &Rc<blocks::Blocks>, inner: I) -> Self {
+    pub(crate) fn from_raw(blocks: &Arc<blocks::Blocks>, inner: I) -> Self {
         Self {
-            blocks: Cow::Owned(Blocks::from_inner(Rc::clone(blocks))),
+            blocks: Cow::Owned(Blocks::from_inner(Arc::clone(blocks))),
             inner,
         }
     }
 }
 
-impl<'a, I: Iterator<Item = io::Result<Devno>>> Iterator for DevnoMapper<'a, I> {
-    type Item = io::Result<Device>;
+pub type SortedDevices = std::vec::IntoIter<Device>;
+
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let la = a.iter().take_while(|c| c.is_ascii_digit()).count();
+                let lb = b.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (na, nb) = (trim_zeros(&a[..la]), trim_zeros(&b[..lb]));
+                let ord = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                a = &a[la..];
+                b = &b[lb..];
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                a = &a[1..];
+                b = &b[1..];
+            }
+        }
+    }
+}
+
+#[inline]
+fn trim_zeros(s: &[u8]) -> &[u8] {
+    let zeros = s.iter().take_while(|c| **c == b'0').count();
+    &s[zeros..]
+}
+
+impl<'a, E: Into<Error>, I: Iterator<Item = std::result::Result<Devno, E>>> DevnoMapper<'a, I> {
+    pub fn sorted(self) -> Result<SortedDevices> {
+        let mut devices = self
+            .map(|device| {
+                let device = device?;
+                Ok((device.name()?, device))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        devices.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+        Ok(devices
+            .into_iter()
+            .map(|(_, device)| device)
+            .collect::<Vec<_>>()
+            .into_iter())
+    }
+}
+
+impl<'a> DevnoMapper<'a, DisksIterator> {
+    #[inline]
+    pub fn skip_hidden(self, skip: bool) -> Self {
+        Self {
+            blocks: self.blocks,
+            inner: self.inner.skip_hidden(skip),
+        }
+    }
+
+    #[inline]
+    pub fn skip_empty(self, skip: bool) -> Self {
+        Self {
+            blocks: self.blocks,
+            inner: self.inner.skip_empty(skip),
+        }
+    }
+
+    #[inline]
+    pub fn physical_only(self) -> Self {
+        Self {
+            blocks: self.blocks,
+            inner: self.inner.physical_only(),
+        }
+    }
+
+    #[inline]
+    pub fn virtual_only(self) -> Self {
+        Self {
+            blocks: self.blocks,
+            inner: self.inner.virtual_only(),
+        }
+    }
+}
+
+impl<'a, E: Into<Error>, I: Iterator<Item = std::result::Result<Devno, E>>> Iterator
+    for DevnoMapper<'a, I>
+{
+    type Item = Result<Device>;
 
     fn next(&mut self) -> Option<Self::Item> {
         match self.inner.next()? {
             Ok(devno) => Some(self.blocks.from_devno(devno)),
-            Err(err) => Some(Err(err)),
+            Err(err) => Some(Err(err.into())),
         }
     }
 }
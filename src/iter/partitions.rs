@@ -1,11 +1,10 @@
 use std::{
     fs::ReadDir,
-    io,
     iter::{empty, Empty},
     path::Path,
 };
 
-use crate::{blocks, sysfs::iter::BlocksIterator, Devno};
+use crate::{blocks::BlocksRef, sysfs::iter::BlocksIterator, Devno, Error, Result};
 
 struct RawPartitionsIterator {
     dir: ReadDir,
@@ -13,7 +12,7 @@ struct RawPartitionsIterator {
 
 impl RawPartitionsIterator {
     #[inline]
-    pub fn new<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
         Ok(Self {
             dir: std::fs::read_dir(p)?,
         })
@@ -21,13 +20,13 @@ impl RawPartitionsIterator {
 }
 
 impl Iterator for RawPartitionsIterator {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             let entry = match self.dir.next()? {
                 Ok(e) => e,
-                Err(err) => return Some(Err(err)),
+                Err(err) => return Some(Err(err.into())),
             }
             .path();
 
@@ -36,12 +35,13 @@ impl Iterator for RawPartitionsIterator {
             }
 
             if entry.join("partition").is_file() {
-                match std::fs::read_to_string(entry.join("dev")) {
+                let dev = entry.join("dev");
+                match std::fs::read_to_string(&dev) {
                     Ok(content) => match content.trim().parse::<Devno>() {
                         Ok(devno) => return Some(Ok(devno)),
-                        Err(_) => return Some(Err(io::ErrorKind::InvalidData.into())),
+                        Err(_) => return Some(Err(Error::parse(dev))),
                     },
-                    Err(err) => return Some(Err(err)),
+                    Err(err) => return Some(Err(Error::path(dev, err))),
                 }
             }
         }
@@ -49,13 +49,13 @@ impl Iterator for RawPartitionsIterator {
 }
 
 struct MastersIterator<'a> {
-    blocks: &'a blocks::Blocks,
+    blocks: BlocksRef<'a>,
     slave: Devno,
     inner: BlocksIterator,
 }
 
 impl<'a> MastersIterator<'a> {
-    pub(crate) fn new(blocks: &'a blocks::Blocks, slave: Devno) -> io::Result<Self> {
+    pub(crate) fn new(blocks: BlocksRef<'a>, slave: Devno) -> Result<Self> {
         Ok(Self {
             inner: blocks.blocks()?,
             blocks,
@@ -65,7 +65,7 @@ impl<'a> MastersIterator<'a> {
 }
 
 impl<'a> Iterator for MastersIterator<'a> {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
@@ -90,28 +90,28 @@ impl<'a> Iterator for MastersIterator<'a> {
 enum InternalPartitionsIterator<'a> {
     Masters(MastersIterator<'a>),
     Partitions(RawPartitionsIterator),
-    Empty(Empty<io::Result<Devno>>),
+    Empty(Empty<Result<Devno>>),
 }
 
 impl<'a> InternalPartitionsIterator<'a> {
     #[inline]
-    pub fn masters(blocks: &'a blocks::Blocks, devno: Devno) -> io::Result<Self> {
+    pub fn masters(blocks: BlocksRef<'a>, devno: Devno) -> Result<Self> {
         Ok(Self::Masters(MastersIterator::new(blocks, devno)?))
     }
 
     #[inline]
-    pub fn partitions<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub fn partitions<P: AsRef<Path>>(p: P) -> Result<Self> {
         Ok(Self::Partitions(RawPartitionsIterator::new(p)?))
     }
 
     #[inline]
-    pub fn empty() -> io::Result<Self> {
+    pub fn empty() -> Result<Self> {
         Ok(Self::Empty(empty()))
     }
 }
 
 impl<'a> Iterator for InternalPartitionsIterator<'a> {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
@@ -127,23 +127,23 @@ pub struct PartitionsIterator<'a>(InternalPartitionsIterator<'a>);
 
 impl<'a> PartitionsIterator<'a> {
     #[inline]
-    pub(crate) fn masters(blocks: &'a blocks::Blocks, devno: Devno) -> io::Result<Self> {
+    pub(crate) fn masters(blocks: BlocksRef<'a>, devno: Devno) -> Result<Self> {
         InternalPartitionsIterator::masters(blocks, devno).map(Self)
     }
 
     #[inline]
-    pub(crate) fn partitions<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub(crate) fn partitions<P: AsRef<Path>>(p: P) -> Result<Self> {
         InternalPartitionsIterator::partitions(p).map(Self)
     }
 
     #[inline]
-    pub(crate) fn empty() -> io::Result<Self> {
+    pub(crate) fn empty() -> Result<Self> {
         InternalPartitionsIterator::empty().map(Self)
     }
 }
 
 impl<'a> Iterator for PartitionsIterator<'a> {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     fn next(&mut self) -> Option<Self::Item> {
         self.0.next()
@@ -1,11 +1,10 @@
 use std::{
     fs::ReadDir,
-    io,
     iter::{empty, Empty},
     path::Path,
 };
 
-use crate::Devno;
+use crate::{Devno, Error, Result};
 
 pub struct RawSlavesIterator {
     dir: ReadDir,
@@ -13,51 +12,53 @@ pub struct RawSlavesIterator {
 
 impl RawSlavesIterator {
     #[inline]
-    pub fn new<P: AsRef<Path>>(slaves_dir: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(slaves_dir: P) -> Result<Self> {
         Ok(Self {
-            dir: std::fs::read_dir(slaves_dir)?,
+            dir: std::fs::read_dir(slaves_dir.as_ref())
+                .map_err(|err| Error::path(slaves_dir.as_ref(), err))?,
         })
     }
 }
 
 impl Iterator for RawSlavesIterator {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let devdir = match self.dir.next()? {
             Ok(devdir) => devdir,
-            Err(err) => return Some(Err(err)),
+            Err(err) => return Some(Err(err.into())),
         };
 
-        match std::fs::read_to_string(devdir.path().join("dev")) {
+        let dev = devdir.path().join("dev");
+        match std::fs::read_to_string(&dev) {
             Ok(content) => match content.trim().parse::<Devno>() {
                 Ok(devno) => Some(Ok(devno)),
-                Err(_) => Some(Err(io::ErrorKind::InvalidData.into())),
+                Err(_) => Some(Err(Error::parse(dev))),
             },
-            Err(err) => Some(Err(err)),
+            Err(err) => Some(Err(Error::path(dev, err))),
         }
     }
 }
 
 enum InnerSlavesIterator {
     Iter(RawSlavesIterator),
-    Empty(Empty<io::Result<Devno>>),
+    Empty(Empty<Result<Devno>>),
 }
 
 impl InnerSlavesIterator {
     #[inline]
-    pub fn new<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
         Ok(Self::Iter(RawSlavesIterator::new(p)?))
     }
 
     #[inline]
-    pub fn empty() -> io::Result<Self> {
+    pub fn empty() -> Result<Self> {
         Ok(Self::Empty(empty()))
     }
 }
 
 impl Iterator for InnerSlavesIterator {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
@@ -72,7 +73,7 @@ pub struct SlavesIterator(InnerSlavesIterator);
 
 impl SlavesIterator {
     #[inline]
-    pub(crate) fn new<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub(crate) fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
         let p = p.as_ref().join("slaves");
         if p.exists() {
             InnerSlavesIterator::new(p).map(Self)
@@ -82,13 +83,13 @@ impl SlavesIterator {
     }
 
     #[inline]
-    pub(crate) fn empty() -> io::Result<Self> {
+    pub(crate) fn empty() -> Result<Self> {
         InnerSlavesIterator::empty().map(Self)
     }
 }
 
 impl Iterator for SlavesIterator {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
@@ -1,30 +1,129 @@
+mod alignment;
+#[cfg(feature = "tokio")]
+pub mod asyncio;
+mod bench;
+mod boot;
+mod builder;
+mod capability;
+mod capture;
+pub mod cgroup;
+pub mod clone;
+pub mod crypttab;
 pub mod devfs;
 mod device;
+mod eject;
+mod error;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod fiemap;
+mod fingerprint;
+mod flags;
+mod freeze;
+mod fsstate;
+pub mod fstab;
+mod handle;
+mod health;
+mod info;
+mod ioctl;
 pub mod iter;
+mod lsblk;
+mod magic;
+mod media;
+mod mountpoint;
+pub mod naming;
+pub mod parse;
+pub mod parttable;
+mod power;
+mod privilege;
 pub mod procfs;
+pub mod query;
+mod rescan;
+mod resize;
+mod scsi;
+mod select;
+mod snapshot;
+mod swap;
 pub mod sysfs;
-use std::{borrow::Borrow, io, path::Path, rc::Rc};
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod topology;
+mod trace;
+pub mod udevdb;
+mod unmount;
+mod usage;
+mod wait;
+use std::{borrow::Borrow, path::Path, sync::Arc, time::Duration};
 
+pub use alignment::PartitionAlignment;
+pub use bench::{BenchOptions, BenchReport};
+pub use builder::BlocksBuilder;
+pub use capability::Capabilities;
+use cgroup::Cgroup;
 use devfs::DevFs;
 pub use device::*;
+pub use eject::EjectError;
+pub use error::{Error, Result};
+pub use fingerprint::{Fingerprint, FingerprintOptions, ParseFingerprintError};
+pub use freeze::{FreezeGuard, MountedFilesystem};
+pub use fsstate::FsState;
+pub use handle::{AlignedBuf, DeviceHandle};
+pub use health::Health;
+pub use info::{DeviceInfo, DeviceKind, DeviceType, DmInfo, FsInfo, LoopInfo, MdInfo, Unavailable};
 use iter::DevnoMapper;
+pub use media::{DiskEvent, ParseDiskEventError};
+pub use mountpoint::MountedDevice;
+pub use power::PowerState;
+pub use privilege::Privileges;
 use procfs::{MountInfo, ProcFs};
+pub use rescan::{RescanOptions, RescanOutcome};
+pub use resize::SizeChange;
+pub use select::DiskSelection;
+pub use snapshot::{Snapshot, SnapshotDiff, SnapshotEntry};
+pub use swap::SwapHeader;
 use sysfs::{
     iter::{BlocksIterator, DisksIterator},
     SysFs,
 };
+use udevdb::UdevDb;
+pub use unmount::UnmountOptions;
+pub use usage::FsUsage;
+pub use wait::WaitTarget;
 pub(crate) mod blocks;
 
-pub struct Blocks(Rc<blocks::Blocks>);
+pub struct Blocks(Arc<blocks::Blocks>);
 
 impl Blocks {
     #[inline]
-    pub fn new() -> io::Result<Self> {
-        Ok(Self(Rc::new(blocks::Blocks::new()?)))
+    pub fn new() -> Result<Self> {
+        Ok(Self(Arc::new(blocks::Blocks::new()?)))
     }
 
     #[inline]
-    pub(crate) fn from_inner(inner: Rc<blocks::Blocks>) -> Self {
+    pub fn builder() -> BlocksBuilder {
+        BlocksBuilder::new()
+    }
+
+    // answers read-only queries from a tree captured on another machine,
+    // anything that would open a device fails with ErrorKind::Unsupported
+    #[inline]
+    pub fn from_capture<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        Ok(Self(Arc::new(capture::load(dir.as_ref())?)))
+    }
+
+    // proc, sys, the udev database and a listing of /dev, as from_capture reads them
+    #[inline]
+    pub fn capture_to<P: AsRef<Path>>(&self, dir: P) -> Result<()> {
+        capture::save(&self.0, dir.as_ref())
+    }
+
+    #[inline]
+    pub fn is_captured(&self) -> bool {
+        self.0.is_captured()
+    }
+
+    #[inline]
+    pub(crate) fn from_inner(inner: Arc<blocks::Blocks>) -> Self {
         Self(inner)
     }
 
@@ -39,38 +138,170 @@ impl Blocks {
     }
 
     #[inline]
-    pub fn devfs(&self) -> &DevFs {
-        self.0.devfs()
+    pub fn devfs(&self) -> Option<&DevFs> {
+        self.0.devfs().ok()
+    }
+
+    #[inline]
+    pub fn has_devfs(&self) -> bool {
+        self.0.has_devfs()
+    }
+
+    #[inline]
+    pub fn udevdb(&self) -> &UdevDb {
+        self.0.udevdb()
     }
 
     #[inline]
-    pub fn from_path<P: AsRef<Path>>(&self, p: P) -> io::Result<Device> {
+    pub fn from_path<P: AsRef<Path>>(&self, p: P) -> Result<Device> {
         Ok(Device::new(self.0.clone(), self.0.from_path(p)?))
     }
 
     #[inline]
-    pub fn from_devno<D: Borrow<Devno>>(&self, d: D) -> io::Result<Device> {
+    pub fn from_devno<D: Borrow<Devno>>(&self, d: D) -> Result<Device> {
         Ok(Device::new(self.0.clone(), self.0.from_devno(d)?))
     }
 
     #[inline]
-    pub fn disks(&self) -> io::Result<DevnoMapper<DisksIterator>> {
-        Ok(DevnoMapper::new(&self, self.0.disks()?))
+    pub fn cgroup<S: AsRef<str>>(&self, name: S) -> Result<Cgroup> {
+        Ok(Cgroup::from_name_in(Cgroup::root(self.procfs())?, name))
+    }
+
+    #[inline]
+    pub fn current_cgroup(&self) -> Result<Cgroup> {
+        Cgroup::current_in(self.procfs())
+    }
+
+    // what the current process may do, so callers can tell up front
+    #[inline]
+    pub fn capabilities(&self) -> Result<Privileges> {
+        Privileges::current(self.procfs().path())
+    }
+
+    #[inline]
+    pub fn from_dm_name<S: AsRef<str>>(&self, name: S) -> Result<Device> {
+        Ok(Device::new(
+            self.0.clone(),
+            self.0.from_dm_name(name.as_ref())?,
+        ))
+    }
+
+    #[inline]
+    pub fn disks(&self) -> Result<DevnoMapper<'static, DisksIterator>> {
+        Ok(DevnoMapper::from_raw(&self.0, self.0.disks()?))
+    }
+
+    #[inline]
+    pub fn blocks(&self) -> Result<DevnoMapper<'static, BlocksIterator>> {
+        Ok(DevnoMapper::from_raw(&self.0, self.0.blocks()?))
+    }
+
+    #[inline]
+    pub fn set_attribute_cache(&self, enabled: bool) {
+        self.sysfs().set_caching(enabled)
+    }
+
+    #[inline]
+    pub fn invalidate<D: Borrow<Devno>>(&self, d: D) {
+        self.0.invalidate(d.borrow())
+    }
+
+    #[inline]
+    pub fn refresh(&self) -> Result<()> {
+        self.0.refresh()
     }
 
     #[inline]
-    pub fn blocks(&self) -> io::Result<DevnoMapper<BlocksIterator>> {
-        Ok(DevnoMapper::new(&self, self.0.blocks()?))
+    pub fn query(&self) -> query::Query<'_> {
+        query::Query::new(self)
     }
 
     #[inline]
-    pub fn mountinfo_from_path<P: AsRef<Path>>(&self, p: P) -> io::Result<MountInfo> {
+    pub fn watch(&self) -> Result<events::Watcher> {
+        events::Watcher::new(self)
+    }
+
+    #[inline]
+    pub fn tree(&self) -> Result<topology::DeviceTree> {
+        topology::DeviceTree::new(&self.0)
+    }
+
+    #[inline]
+    pub fn to_lsblk_json(&self) -> Result<String> {
+        Ok(lsblk::to_json(&self.tree()?))
+    }
+
+    #[inline]
+    pub fn wait_for<T: Into<WaitTarget>>(&self, target: T, timeout: Duration) -> Result<Device> {
+        wait::wait_for(self, target.into(), timeout)
+    }
+
+    #[inline]
+    pub fn from_mountpoint<P: AsRef<Path>>(&self, p: P) -> Result<MountedDevice> {
+        MountedDevice::resolve(self, p)
+    }
+
+    #[inline]
+    pub fn mounted_filesystem<P: AsRef<Path>>(&self, p: P) -> Result<MountedFilesystem> {
+        Ok(MountedFilesystem::new(self.mountinfo_from_path(p)?))
+    }
+
+    #[inline]
+    pub fn file_extents<P: AsRef<Path>>(&self, p: P) -> Result<Vec<fiemap::Extent>> {
+        fiemap::extents(self, p)
+    }
+
+    #[inline]
+    pub fn snapshot(&self) -> Result<Snapshot> {
+        Snapshot::take(&self.0)
+    }
+
+    #[inline]
+    pub fn unused_disks(&self, selection: DiskSelection) -> Result<Vec<Device>> {
+        select::unused_disks(self, selection)
+    }
+
+    #[inline]
+    pub fn largest_unused_disk(&self, min_size: u64) -> Result<Option<Device>> {
+        Ok(self
+            .unused_disks(DiskSelection::new().min_size(min_size))?
+            .into_iter()
+            .next())
+    }
+
+    #[inline]
+    pub fn smallest_unused_disk(&self, min_size: u64) -> Result<Option<Device>> {
+        Ok(self
+            .unused_disks(DiskSelection::new().min_size(min_size))?
+            .pop())
+    }
+
+    #[inline]
+    pub fn boot_disk(&self) -> Result<Vec<Device>> {
+        boot::boot_disks(self)
+    }
+
+    #[inline]
+    pub fn esp(&self) -> Result<Option<Device>> {
+        boot::esp(self)
+    }
+
+    #[inline]
+    pub fn mountinfo_from_path<P: AsRef<Path>>(&self, p: P) -> Result<MountInfo> {
         self.procfs().mountinfo_from_path(p)
     }
+
+    #[inline]
+    pub fn mountinfo_from_fd<F: std::os::unix::prelude::AsRawFd>(
+        &self,
+        fd: &F,
+    ) -> Result<MountInfo> {
+        self.procfs().mountinfo_from_fd(fd)
+    }
 }
 
 impl Clone for Blocks {
     fn clone(&self) -> Self {
-        Self(Rc::clone(&self.0))
+        Self(Arc::clone(&self.0))
     }
 }
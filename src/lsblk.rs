@@ -0,0 +1,101 @@
+use std::fmt::Write;
+
+use crate::{topology::DeviceTree, DeviceInfo, DeviceType};
+
+fn device_type(info: &DeviceInfo) -> &str {
+    match info.device_type {
+        DeviceType::Dm => match info.dm.as_ref().and_then(|dm| dm.uuid.as_deref()) {
+            Some(uuid) if uuid.starts_with("CRYPT-") => "crypt",
+            Some(uuid) if uuid.starts_with("LVM-") => "lvm",
+            Some(uuid) if uuid.starts_with("mpath-") => "mpath",
+            _ => "dm",
+        },
+        DeviceType::Md => match info.md.as_ref() {
+            Some(md) if md.level.starts_with("raid") => md.level.as_str(),
+            _ => "md",
+        },
+        ty => ty.as_str(),
+    }
+}
+
+fn write_str(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn write_opt_str(out: &mut String, s: Option<&str>) {
+    match s {
+        Some(s) => write_str(out, s),
+        None => out.push_str("null"),
+    }
+}
+
+fn write_device(out: &mut String, tree: &DeviceTree, info: &DeviceInfo) {
+    out.push_str("{\"name\":");
+    write_str(out, &info.name);
+    out.push_str(",\"maj:min\":");
+    write_str(out, &info.devno.to_string());
+    let _ = write!(
+        out,
+        ",\"rm\":{},\"size\":{},\"ro\":{},\"type\":",
+        info.removable, info.size, info.read_only
+    );
+    write_str(out, device_type(info));
+
+    out.push_str(",\"fstype\":");
+    write_opt_str(out, info.fs.as_ref().and_then(|fs| fs.fs_type.as_deref()));
+    out.push_str(",\"label\":");
+    write_opt_str(out, info.fs.as_ref().and_then(|fs| fs.label.as_deref()));
+    out.push_str(",\"uuid\":");
+    write_opt_str(out, info.fs.as_ref().and_then(|fs| fs.uuid.as_deref()));
+
+    out.push_str(",\"mountpoints\":[");
+    if info.mountpoints.is_empty() {
+        out.push_str("null");
+    }
+    for (i, mp) in info.mountpoints.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_str(out, &mp.to_string_lossy());
+    }
+    out.push(']');
+
+    let mut children = tree.children(&info.devno).peekable();
+    if children.peek().is_some() {
+        out.push_str(",\"children\":[");
+        for (i, child) in children.enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_device(out, tree, child);
+        }
+        out.push(']');
+    }
+    out.push('}');
+}
+
+pub(crate) fn to_json(tree: &DeviceTree) -> String {
+    let mut out = String::from("{\"blockdevices\":[");
+    for (i, info) in tree.roots().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_device(&mut out, tree, info);
+    }
+    out.push_str("]}");
+    out
+}
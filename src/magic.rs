@@ -0,0 +1,26 @@
+use std::{ffi::CString, io, os::unix::prelude::OsStrExt, path::Path};
+
+use crate::{Error, Result};
+
+pub(crate) const PROC_SUPER_MAGIC: u64 = 0x9fa0;
+pub(crate) const SYSFS_MAGIC: u64 = 0x62656572;
+pub(crate) const TMPFS_MAGIC: u64 = 0x01021994;
+pub(crate) const RAMFS_MAGIC: u64 = 0x858458f6;
+
+pub(crate) fn fs_magic<P: AsRef<Path>>(p: P) -> Result<u64> {
+    let p = p.as_ref();
+    let path = CString::new(p.as_os_str().as_bytes())
+        .map_err(|_| Error::from(io::ErrorKind::InvalidInput))?;
+    let mut buf = std::mem::MaybeUninit::<libc::statfs>::uninit();
+    let ret = unsafe { libc::statfs(path.as_ptr(), buf.as_mut_ptr()) };
+    if ret < 0 {
+        Err(Error::path(p, io::Error::last_os_error()))
+    } else {
+        Ok(unsafe { buf.assume_init() }.f_type as u64)
+    }
+}
+
+#[inline]
+pub(crate) fn is_fs<P: AsRef<Path>>(p: P, magics: &[u64]) -> bool {
+    fs_magic(p).is_ok_and(|magic| magics.contains(&magic))
+}
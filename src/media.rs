@@ -0,0 +1,58 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd, str::FromStr};
+
+use crate::{
+    ioctl::{self, CDROM_MEDIA_CHANGED},
+    Error, Result,
+};
+
+const CDSL_CURRENT: libc::c_int = libc::c_int::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DiskEvent {
+    MediaChange,
+    EjectRequest,
+}
+
+#[derive(Debug)]
+pub struct ParseDiskEventError;
+
+impl FromStr for DiskEvent {
+    type Err = ParseDiskEventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "media_change" => Ok(Self::MediaChange),
+            "eject_request" => Ok(Self::EjectRequest),
+            _ => Err(ParseDiskEventError),
+        }
+    }
+}
+
+impl std::fmt::Display for DiskEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MediaChange => write!(f, "media_change"),
+            Self::EjectRequest => write!(f, "eject_request"),
+        }
+    }
+}
+
+pub(crate) fn parse_events(s: &str) -> io::Result<Vec<DiskEvent>> {
+    s.split_whitespace()
+        .map(|x| x.parse().map_err(|_| io::ErrorKind::InvalidData.into()))
+        .collect()
+}
+
+pub(crate) fn cdrom_media_changed(f: &File) -> Result<bool> {
+    ioctl::allowed("CDROM_MEDIA_CHANGED")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), CDROM_MEDIA_CHANGED as _, CDSL_CURRENT) };
+    if ret < 0 {
+        Err(Error::ioctl(
+            "CDROM_MEDIA_CHANGED",
+            io::Error::last_os_error(),
+        ))
+    } else {
+        Ok(ret > 0)
+    }
+}
@@ -0,0 +1,55 @@
+use std::path::{Path, PathBuf};
+
+use crate::{procfs::MountInfo, Blocks, Device, Error, MountedFilesystem, Result};
+
+#[inline]
+pub(crate) fn mount_device(blocks: &Blocks, mount: &MountInfo) -> Result<Device> {
+    Ok(Device::new(blocks.0.clone(), blocks.0.mount_devno(mount)?))
+}
+
+#[derive(Debug, Clone)]
+pub struct MountedDevice {
+    pub device: Device,
+    pub mount: MountInfo,
+    pub root: PathBuf,
+    pub path: PathBuf,
+    pub subvol: Option<String>,
+    pub subvolid: Option<u64>,
+}
+
+impl MountedDevice {
+    pub(crate) fn resolve<P: AsRef<Path>>(blocks: &Blocks, p: P) -> Result<Self> {
+        let p = p.as_ref();
+        let mount = blocks.mountinfo_from_path(p)?;
+        let device = mount_device(blocks, &mount)?;
+
+        let relative = p
+            .canonicalize()
+            .map_err(|err| Error::path(p, err))?
+            .strip_prefix(&mount.mount_point)
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        let opts = mount.super_opts();
+
+        Ok(Self {
+            device,
+            root: mount.root.clone(),
+            path: mount.root.join(relative),
+            subvol: opts.subvol().map(str::to_string),
+            subvolid: opts.subvolid(),
+            mount,
+        })
+    }
+
+    #[inline]
+    pub fn filesystem(&self) -> MountedFilesystem {
+        MountedFilesystem::new(self.mount.clone())
+    }
+
+    #[inline]
+    pub fn is_same_subvolume(&self, other: &MountedDevice) -> bool {
+        self.device == other.device
+            && self.subvolid == other.subvolid
+            && self.subvol == other.subvol
+    }
+}
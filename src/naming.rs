@@ -0,0 +1,172 @@
+pub fn partition_name(disk: &str, number: u32) -> String {
+    // same rule as the kernel: a "p" separates the number when the disk ends with a digit
+    if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{}p{}", disk, number)
+    } else {
+        format!("{}{}", disk, number)
+    }
+}
+
+// whole disks whose names end with their own index, their partitions take a "p"
+const NUMBERED_DISKS: &[&str] = &["loop", "md", "nbd", "zram", "mmcblk", "dm-"];
+
+#[inline]
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit())
+}
+
+fn is_numbered_disk(name: &str) -> bool {
+    let name = name.rsplit('/').next().unwrap_or(name);
+    if let Some((ctrl, ns)) = name.strip_prefix("nvme").and_then(|n| n.split_once('n')) {
+        return is_number(ctrl) && is_number(ns);
+    }
+    NUMBERED_DISKS
+        .iter()
+        .any(|prefix| name.strip_prefix(prefix).is_some_and(is_number))
+}
+
+// a guess from the name alone: the families above are recognized as whole
+// disks, anything else ending with digits (sr1, a dm device named "data2")
+// still comes back split, use partition_number when the disk is known
+pub fn split_partition_name(name: &str) -> Option<(&str, u32)> {
+    if is_numbered_disk(name) {
+        return None;
+    }
+    let disk = name.trim_end_matches(|c: char| c.is_ascii_digit());
+    if disk.is_empty() || disk.len() == name.len() {
+        return None;
+    }
+    let number = name[disk.len()..].parse().ok()?;
+    if number == 0 {
+        return None;
+    }
+
+    match disk.strip_suffix('p') {
+        Some(base) if base.ends_with(|c: char| c.is_ascii_digit()) => Some((base, number)),
+        _ if disk.ends_with(|c: char| c.is_ascii_digit()) => None,
+        _ => Some((disk, number)),
+    }
+}
+
+// the inverse of partition_name for a known disk
+pub fn partition_number(disk: &str, name: &str) -> Option<u32> {
+    let rest = name.strip_prefix(disk)?;
+    let rest = if disk.ends_with(|c: char| c.is_ascii_digit()) {
+        rest.strip_prefix('p')?
+    } else {
+        rest
+    };
+    if !is_number(rest) {
+        return None;
+    }
+    rest.parse().ok().filter(|number| *number != 0)
+}
+
+// LVM doubles the dashes inside the volume group and logical volume names so
+// the single dash joining them stays unambiguous
+pub fn dm_name(vg: &str, lv: &str) -> String {
+    format!("{}-{}", vg.replace('-', "--"), lv.replace('-', "--"))
+}
+
+pub fn split_dm_name(name: &str) -> Option<(String, String)> {
+    let bytes = name.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'-' {
+            if bytes.get(i + 1) == Some(&b'-') {
+                i += 2;
+                continue;
+            }
+            let (vg, lv) = (&name[..i], &name[i + 1..]);
+            if vg.is_empty() || lv.is_empty() {
+                return None;
+            }
+            return Some((vg.replace("--", "-"), lv.replace("--", "-")));
+        }
+        i += 1;
+    }
+    None
+}
+
+// /dev/mapper entries have characters outside of [a-zA-Z0-9#+-.:=@_]
+// written as \xNN by libdevmapper
+pub fn unmangle_dm_name(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') {
+            let hex = name
+                .get(i + 2..i + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(c) = hex {
+                res.push(c);
+                i += 4;
+                continue;
+            }
+        }
+        res.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whole_disks_are_not_partitions() {
+        for name in [
+            "nvme0n1",
+            "mmcblk1",
+            "loop1",
+            "md127",
+            "nbd3",
+            "zram0",
+            "dm-1",
+            "/dev/loop7",
+        ] {
+            assert_eq!(split_partition_name(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn split_partitions() {
+        for (name, disk, number) in [
+            ("sda1", "sda", 1),
+            ("vdb12", "vdb", 12),
+            ("nvme0n1p1", "nvme0n1", 1),
+            ("mmcblk0p2", "mmcblk0", 2),
+            ("loop0p1", "loop0", 1),
+            ("md0p3", "md0", 3),
+            ("/dev/sdb2", "/dev/sdb", 2),
+        ] {
+            assert_eq!(split_partition_name(name), Some((disk, number)), "{}", name);
+            assert_eq!(partition_number(disk, name), Some(number), "{}", name);
+            assert_eq!(partition_name(disk, number), name);
+        }
+        assert_eq!(split_partition_name("sda"), None);
+        assert_eq!(split_partition_name("sda0"), None);
+    }
+
+    #[test]
+    fn partition_number_needs_the_disk() {
+        assert_eq!(partition_number("sda", "sdb1"), None);
+        assert_eq!(partition_number("nvme0n1", "nvme0n11"), None);
+        assert_eq!(partition_number("loop1", "loop1"), None);
+        assert_eq!(partition_number("sda", "sda+1"), None);
+        // a dm device named data2 is only split when asked about its disk
+        assert_eq!(partition_number("data", "data2"), Some(2));
+    }
+
+    #[test]
+    fn dm_names() {
+        assert_eq!(dm_name("vg-a", "lv"), "vg--a-lv");
+        assert_eq!(
+            split_dm_name("vg--a-lv--b"),
+            Some(("vg-a".to_string(), "lv-b".to_string()))
+        );
+        assert_eq!(unmangle_dm_name("a\\x20b"), "a b");
+    }
+}
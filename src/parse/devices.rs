@@ -0,0 +1,92 @@
+use std::io::{self, BufRead};
+
+use super::ParseLineError;
+use crate::procfs::DeviceClass;
+
+// the section a /proc/devices header line starts, if it is one
+pub fn section(line: &str) -> Option<DeviceClass> {
+    if line.starts_with("Character devices:") {
+        Some(DeviceClass::Char)
+    } else if line.starts_with("Block devices:") {
+        Some(DeviceClass::Block)
+    } else {
+        None
+    }
+}
+
+// "  8 sd", names are lowercased like the rest of the crate expects
+pub fn entry(line: &str) -> Option<(u32, String)> {
+    let mut it = line.split_whitespace();
+    let id = it.next()?.parse().ok()?;
+    let name = it.next()?;
+    if it.next().is_some() {
+        return None;
+    }
+    Some((id, name.to_lowercase()))
+}
+
+pub fn from_str(s: &str) -> Result<Vec<(DeviceClass, u32, String)>, ParseLineError> {
+    let mut class = None;
+    let mut res = Vec::new();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(c) = section(line) {
+            class = Some(c);
+            continue;
+        }
+        match (class, entry(line)) {
+            (Some(class), Some((id, name))) => res.push((class, id, name)),
+            _ => return Err(ParseLineError::new(i, line)),
+        }
+    }
+    Ok(res)
+}
+
+#[inline]
+pub fn from_reader<R: BufRead>(r: R) -> io::Result<Vec<(DeviceClass, u32, String)>> {
+    super::from_reader(r, from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "Character devices:
+  1 mem
+  4 /dev/vc/0
+ 10 misc
+
+Block devices:
+  8 sd
+259 blkext
+";
+
+    #[test]
+    fn sample() {
+        let entries = from_str(SAMPLE).unwrap();
+        assert_eq!(entries.len(), 5);
+        assert_eq!(entries[0], (DeviceClass::Char, 1, "mem".to_string()));
+        assert_eq!(entries[1], (DeviceClass::Char, 4, "/dev/vc/0".to_string()));
+        assert_eq!(entries[3], (DeviceClass::Block, 8, "sd".to_string()));
+        assert_eq!(entries[4], (DeviceClass::Block, 259, "blkext".to_string()));
+        assert_eq!(from_reader(SAMPLE.as_bytes()).unwrap(), entries);
+    }
+
+    #[test]
+    fn malformed() {
+        let err = from_str("Block devices:\n  8 sd\n  x sd\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.content, "  x sd");
+
+        // entries before any section header
+        assert_eq!(from_str("  8 sd\n").unwrap_err().line, 1);
+        assert_eq!(
+            from_str("Block devices:\n\n  8 sd extra\n")
+                .unwrap_err()
+                .line,
+            3
+        );
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    io::{self, BufRead},
+    path::{Component, Path, PathBuf},
+};
+
+use super::ParseLineError;
+use crate::{devfs::DevNode, procfs::unescape_path, Devno};
+
+// a /dev tree known only from a listing, either `maj:min path` lines as
+// written by Blocks::capture_to or the output of `ls -lR /dev`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DevListing {
+    pub nodes: Vec<DevNode>,
+    // symlinks whose target is one of the nodes, /dev/mapper and /dev/disk
+    pub links: Vec<DevNode>,
+}
+
+impl DevListing {
+    pub fn lookup(&self, p: &Path) -> Option<Devno> {
+        self.nodes
+            .iter()
+            .chain(self.links.iter())
+            .find(|node| node.path == p)
+            .map(|node| node.devno)
+    }
+}
+
+fn normalize(p: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for c in p.components() {
+        match c {
+            Component::ParentDir => {
+                res.pop();
+            }
+            Component::CurDir => (),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+enum Line {
+    Node(DevNode),
+    Alias(DevNode),
+    Link(PathBuf, PathBuf),
+    Dir(PathBuf),
+    Skip,
+}
+
+// 253:0 /dev/mapper/vg-lv -> ../dm-0
+fn parse_listed(line: &str) -> Option<Line> {
+    let (devno, rest) = line.split_once(char::is_whitespace)?;
+    let devno = devno.parse::<Devno>().ok()?;
+    Some(match rest.trim().split_once(" -> ") {
+        Some((path, _)) => Line::Alias(DevNode {
+            path: unescape_path(path),
+            devno,
+        }),
+        None => Line::Node(DevNode {
+            path: unescape_path(rest.trim()),
+            devno,
+        }),
+    })
+}
+
+// brw-rw---- 1 root disk 8, 0 Jan  1 10:00 sda
+fn parse_ls(line: &str, dir: &Path) -> Option<Line> {
+    let tokens = line.split_whitespace().collect::<Vec<_>>();
+    match line.as_bytes()[0] {
+        b'b' => {
+            let pos = tokens.iter().position(|t| t.contains(','))?;
+            let (major, minor) = tokens[pos].split_once(',')?;
+            let minor = if minor.is_empty() {
+                tokens.get(pos + 1)?
+            } else {
+                minor
+            };
+            Some(Line::Node(DevNode {
+                path: dir.join(tokens.last()?),
+                devno: Devno::new(major.parse().ok()?, minor.parse().ok()?),
+            }))
+        }
+        b'l' => {
+            let arrow = tokens.iter().position(|t| *t == "->")?;
+            let name = tokens.get(arrow.checked_sub(1)?)?;
+            let target = tokens.get(arrow + 1)?;
+            Some(Line::Link(dir.join(name), normalize(&dir.join(target))))
+        }
+        _ => Some(Line::Skip),
+    }
+}
+
+fn parse_line(line: &str, dir: &Path) -> Option<Line> {
+    if line.starts_with("total ") {
+        return Some(Line::Skip);
+    }
+    if let Some(dir) = line.strip_suffix(':') {
+        return Some(Line::Dir(PathBuf::from(dir)));
+    }
+    let is_ls = line.len() >= 10
+        && line.split_whitespace().next().is_some_and(|mode| {
+            mode.len() >= 10 && mode.starts_with(['b', 'c', 'd', 'l', 'p', 's', '-'])
+        });
+    if is_ls {
+        parse_ls(line, dir)
+    } else {
+        parse_listed(line)
+    }
+}
+
+pub fn from_str(s: &str) -> Result<DevListing, ParseLineError> {
+    let mut res = DevListing::default();
+    let mut links = Vec::new();
+    // plain `ls -l /dev` has no directory headers
+    let mut dir = PathBuf::from("/dev");
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match parse_line(line, &dir).ok_or_else(|| ParseLineError::new(i, line))? {
+            Line::Node(node) => res.nodes.push(node),
+            Line::Alias(node) => res.links.push(node),
+            Line::Link(path, target) => links.push((path, target)),
+            Line::Dir(p) => dir = p,
+            Line::Skip => (),
+        }
+    }
+
+    for (path, target) in links {
+        if let Some(devno) = res.lookup(&target) {
+            res.links.push(DevNode { path, devno });
+        }
+    }
+    Ok(res)
+}
+
+#[inline]
+pub fn from_reader<R: BufRead>(r: R) -> io::Result<DevListing> {
+    super::from_reader(r, from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn listed() {
+        let s = "8:0 /dev/sda
+8:1 /dev/sda1
+253:0 /dev/dm-0
+253:0 /dev/mapper/vg-root -> ../dm-0
+253:0 /dev/disk/by-label/my\\040root -> ../../dm-0
+";
+        let listing = from_str(s).unwrap();
+        assert_eq!(listing.nodes.len(), 3);
+        assert_eq!(listing.links.len(), 2);
+        assert_eq!(
+            listing.lookup(Path::new("/dev/sda1")),
+            Some(Devno::new(8, 1))
+        );
+        assert_eq!(
+            listing.lookup(Path::new("/dev/mapper/vg-root")),
+            Some(Devno::new(253, 0))
+        );
+        assert_eq!(
+            listing.lookup(Path::new("/dev/disk/by-label/my root")),
+            Some(Devno::new(253, 0))
+        );
+        assert_eq!(listing.lookup(Path::new("/dev/sdb")), None);
+        assert_eq!(from_reader(s.as_bytes()).unwrap(), listing);
+    }
+
+    #[test]
+    fn ls() {
+        let s = "/dev:
+total 0
+brw-rw----  1 root disk      8,   0 Jan  1 10:00 sda
+brw-rw----  1 root disk      8,   1 Jan  1 10:00 sda1
+brw-rw----  1 root disk    253,   0 Jan  1 10:00 dm-0
+crw-rw-rw-  1 root root      1,   3 Jan  1 10:00 null
+drwxr-xr-x  2 root root         60 Jan  1 10:00 mapper
+
+/dev/mapper:
+total 0
+lrwxrwxrwx  1 root root          7 Jan  1 10:00 vg-root -> ../dm-0
+lrwxrwxrwx  1 root root          7 Jan  1 10:00 dangling -> ../dm-9
+";
+        let listing = from_str(s).unwrap();
+        assert_eq!(listing.nodes.len(), 3);
+        assert_eq!(
+            listing.lookup(Path::new("/dev/sda")),
+            Some(Devno::new(8, 0))
+        );
+        assert_eq!(listing.lookup(Path::new("/dev/null")), None);
+        assert_eq!(
+            listing.links,
+            [DevNode {
+                path: PathBuf::from("/dev/mapper/vg-root"),
+                devno: Devno::new(253, 0),
+            }]
+        );
+    }
+
+    #[test]
+    fn malformed() {
+        let err = from_str("8:0 /dev/sda\n\nsda1\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.content, "sda1");
+
+        let err = from_str("/dev:\nbrw-rw---- 1 root disk 8 0 Jan  1 10:00 sda\n").unwrap_err();
+        assert_eq!(err.line, 2);
+    }
+}
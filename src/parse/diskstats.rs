@@ -0,0 +1,172 @@
+use std::io::{self, BufRead};
+
+use super::ParseLineError;
+use crate::Devno;
+
+// times are in milliseconds, sectors are 512 bytes regardless of the device;
+// discard fields appeared in 4.18 and flush fields in 5.5, older kernels
+// leave them at 0
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiskStat {
+    pub devno: Devno,
+    pub name: String,
+    pub reads: u64,
+    pub reads_merged: u64,
+    pub sectors_read: u64,
+    pub read_ms: u64,
+    pub writes: u64,
+    pub writes_merged: u64,
+    pub sectors_written: u64,
+    pub write_ms: u64,
+    pub in_flight: u64,
+    pub io_ms: u64,
+    pub weighted_io_ms: u64,
+    pub discards: u64,
+    pub discards_merged: u64,
+    pub sectors_discarded: u64,
+    pub discard_ms: u64,
+    pub flushes: u64,
+    pub flush_ms: u64,
+}
+
+impl DiskStat {
+    #[inline]
+    pub fn bytes_read(&self) -> u64 {
+        self.sectors_read.saturating_mul(512)
+    }
+
+    #[inline]
+    pub fn bytes_written(&self) -> u64 {
+        self.sectors_written.saturating_mul(512)
+    }
+}
+
+fn parse_line(line: &str) -> Option<DiskStat> {
+    let mut it = line.split_whitespace();
+    let major = it.next()?.parse().ok()?;
+    let minor = it.next()?.parse().ok()?;
+    let name = it.next()?.to_string();
+    let fields = it.map(|v| v.parse().ok()).collect::<Option<Vec<u64>>>()?;
+    if fields.len() < 11 {
+        return None;
+    }
+    // sector counts have to convert to bytes, no device gets near that
+    if [2, 6, 13]
+        .iter()
+        .any(|i| fields.get(*i).is_some_and(|s| s.checked_mul(512).is_none()))
+    {
+        return None;
+    }
+    let field = |i: usize| fields.get(i).copied().unwrap_or(0);
+    Some(DiskStat {
+        devno: Devno::new(major, minor),
+        name,
+        reads: field(0),
+        reads_merged: field(1),
+        sectors_read: field(2),
+        read_ms: field(3),
+        writes: field(4),
+        writes_merged: field(5),
+        sectors_written: field(6),
+        write_ms: field(7),
+        in_flight: field(8),
+        io_ms: field(9),
+        weighted_io_ms: field(10),
+        discards: field(11),
+        discards_merged: field(12),
+        sectors_discarded: field(13),
+        discard_ms: field(14),
+        flushes: field(15),
+        flush_ms: field(16),
+    })
+}
+
+pub fn from_str(s: &str) -> Result<Vec<DiskStat>, ParseLineError> {
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_line(line).ok_or_else(|| ParseLineError::new(i, line)))
+        .collect()
+}
+
+#[inline]
+pub fn from_reader<R: BufRead>(r: R) -> io::Result<Vec<DiskStat>> {
+    super::from_reader(r, from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn field_counts() {
+        // 3.x kernels stop at 11 fields, 4.18 adds discards, 5.5 flushes
+        let s = "   8       0 sda 100 2 3000 40 50 6 700 80 0 90 120
+   8       1 sda1 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15
+ 259       0 nvme0n1 1 2 3 4 5 6 7 8 9 10 11 12 13 14 15 16 17
+";
+        let stats = from_str(s).unwrap();
+        assert_eq!(stats.len(), 3);
+
+        assert_eq!(stats[0].devno, Devno::new(8, 0));
+        assert_eq!(stats[0].name, "sda");
+        assert_eq!(stats[0].reads, 100);
+        assert_eq!(stats[0].bytes_read(), 3000 * 512);
+        assert_eq!(stats[0].bytes_written(), 700 * 512);
+        assert_eq!(stats[0].weighted_io_ms, 120);
+        assert_eq!(stats[0].discards, 0);
+
+        assert_eq!(stats[1].discard_ms, 15);
+        assert_eq!(stats[1].flushes, 0);
+
+        assert_eq!(stats[2].devno, Devno::new(259, 0));
+        assert_eq!(stats[2].flushes, 16);
+        assert_eq!(stats[2].flush_ms, 17);
+
+        assert_eq!(from_reader(s.as_bytes()).unwrap(), stats);
+    }
+
+    #[test]
+    fn malformed() {
+        let s = "   8       0 sda 1 2 3 4 5 6 7 8 9 10 11\n\n   8       1 sda1 1 2 3\n";
+        let err = from_str(s).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.content, "   8       1 sda1 1 2 3");
+
+        assert_eq!(
+            from_str("   8   x sda 1 2 3 4 5 6 7 8 9 10 11\n")
+                .unwrap_err()
+                .line,
+            1
+        );
+        assert_eq!(
+            from_str("   8   0 sda 1 2 3 4 5 -6 7 8 9 10 11\n")
+                .unwrap_err()
+                .line,
+            1
+        );
+    }
+
+    #[test]
+    fn sector_overflow() {
+        let big = u64::MAX / 512 + 1;
+        for line in [
+            format!("8 0 sda 1 2 {} 4 5 6 7 8 9 10 11", big),
+            format!("8 0 sda 1 2 3 4 5 6 {} 8 9 10 11", big),
+            format!("8 0 sda 1 2 3 4 5 6 7 8 9 10 11 12 13 {} 15", big),
+        ] {
+            let err =
+                from_str(&format!("8 1 sda1 1 2 3 4 5 6 7 8 9 10 11\n{}\n", line)).unwrap_err();
+            assert_eq!(err.line, 2);
+        }
+
+        let stat = &from_str(&format!("8 0 sda 1 2 {} 4 5 6 7 8 9 10 11\n", big - 1)).unwrap()[0];
+        assert_eq!(stat.bytes_read(), (big - 1) * 512);
+        let stat = DiskStat {
+            sectors_written: u64::MAX,
+            ..stat.clone()
+        };
+        assert_eq!(stat.bytes_written(), u64::MAX);
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::{self, BufRead};
+
+use super::ParseLineError;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdMember {
+    pub name: String,
+    pub role: u32,
+    pub faulty: bool,
+    pub spare: bool,
+    pub write_mostly: bool,
+    pub replacement: bool,
+    pub journal: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdArray {
+    pub name: String,
+    pub active: bool,
+    pub read_only: bool,
+    pub level: Option<String>,
+    pub members: Vec<MdMember>,
+    // in KiB
+    pub blocks: Option<u64>,
+    // expected and working members, "[2/1]"
+    pub disks: Option<(u32, u32)>,
+    // resync, recovery or reshape progress line, as found
+    pub progress: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MdStat {
+    pub personalities: Vec<String>,
+    pub arrays: Vec<MdArray>,
+}
+
+// "sda1[0](F)"
+fn parse_member(s: &str) -> Option<MdMember> {
+    let (name, rest) = s.split_once('[')?;
+    let (role, flags) = rest.split_once(']')?;
+    let mut member = MdMember {
+        name: name.to_string(),
+        role: role.parse().ok()?,
+        ..Default::default()
+    };
+    for flag in flags.split(['(', ')']).filter(|f| !f.is_empty()) {
+        match flag {
+            "F" => member.faulty = true,
+            "S" => member.spare = true,
+            "W" => member.write_mostly = true,
+            "R" => member.replacement = true,
+            "J" => member.journal = true,
+            _ => (),
+        }
+    }
+    Some(member)
+}
+
+fn parse_array(name: &str, rest: &str) -> Option<MdArray> {
+    let mut array = MdArray {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    let mut it = rest.split_whitespace().peekable();
+    array.active = match it.next()? {
+        "active" => true,
+        "inactive" => false,
+        _ => return None,
+    };
+    while let Some(word) = it.peek() {
+        match *word {
+            "(read-only)" | "(auto-read-only)" => array.read_only = true,
+            // inactive arrays list their members straight away
+            word if word.contains('[') => break,
+            word if array.level.is_none() && array.active => array.level = Some(word.to_string()),
+            _ => (),
+        }
+        it.next();
+    }
+    array.members = it.map(parse_member).collect::<Option<_>>()?;
+    Some(array)
+}
+
+fn parse_details(array: &mut MdArray, line: &str) {
+    let line = line.trim();
+    if line.contains('%') || line.starts_with('[') {
+        array.progress = Some(line.to_string());
+        return;
+    }
+
+    let mut it = line.split_whitespace();
+    if let (Some(blocks), Some("blocks")) = (it.next(), it.next()) {
+        array.blocks = blocks.parse().ok();
+    }
+    for word in line.split_whitespace() {
+        let disks = word
+            .strip_prefix('[')
+            .and_then(|w| w.strip_suffix(']'))
+            .and_then(|w| w.split_once('/'));
+        if let Some((total, working)) = disks {
+            if let (Ok(total), Ok(working)) = (total.parse(), working.parse()) {
+                array.disks = Some((total, working));
+            }
+        }
+    }
+}
+
+pub fn from_str(s: &str) -> Result<MdStat, ParseLineError> {
+    let mut res = MdStat::default();
+    for (i, line) in s.lines().enumerate() {
+        if line.trim().is_empty() || line.starts_with("unused devices:") {
+            continue;
+        }
+        if let Some(rest) = line.strip_prefix("Personalities :") {
+            res.personalities = rest
+                .split_whitespace()
+                .map(|p| p.trim_matches(['[', ']']).to_string())
+                .collect();
+        } else if line.starts_with(char::is_whitespace) {
+            match res.arrays.last_mut() {
+                Some(array) => parse_details(array, line),
+                None => return Err(ParseLineError::new(i, line)),
+            }
+        } else {
+            let array = line
+                .split_once(" : ")
+                .and_then(|(name, rest)| parse_array(name.trim(), rest))
+                .ok_or_else(|| ParseLineError::new(i, line))?;
+            res.arrays.push(array);
+        }
+    }
+    Ok(res)
+}
+
+#[inline]
+pub fn from_reader<R: BufRead>(r: R) -> io::Result<MdStat> {
+    super::from_reader(r, from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "Personalities : [raid1] [raid6] [raid5] [raid4]
+md1 : active raid5 sdd1[3] sdc1[1] sdb1[0](F)
+      2093056 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/2] [_UU]
+      [==>..................]  recovery = 12.6% (132096/1046528) finish=0.4min speed=33024K/sec
+
+md0 : active (auto-read-only) raid1 sda2[1](W) sde2[2](S) sdf2[0]
+      1046528 blocks super 1.2 [2/2] [UU]
+
+md127 : inactive sdg[0](S) sdh[1](S)
+      2093056 blocks super 1.2
+
+unused devices: <none>
+";
+
+    #[test]
+    fn sample() {
+        let stat = from_str(SAMPLE).unwrap();
+        assert_eq!(stat.personalities, ["raid1", "raid6", "raid5", "raid4"]);
+        assert_eq!(stat.arrays.len(), 3);
+
+        let md1 = &stat.arrays[0];
+        assert_eq!(md1.name, "md1");
+        assert!(md1.active && !md1.read_only);
+        assert_eq!(md1.level.as_deref(), Some("raid5"));
+        assert_eq!(md1.blocks, Some(2093056));
+        assert_eq!(md1.disks, Some((3, 2)));
+        assert!(md1
+            .progress
+            .as_deref()
+            .unwrap()
+            .contains("recovery = 12.6%"));
+        assert_eq!(md1.members.len(), 3);
+        assert_eq!(md1.members[2].name, "sdb1");
+        assert!(md1.members[2].faulty);
+
+        let md0 = &stat.arrays[1];
+        assert!(md0.read_only);
+        assert_eq!(md0.level.as_deref(), Some("raid1"));
+        assert!(md0.members[0].write_mostly);
+        assert_eq!(md0.members[1].role, 2);
+        assert!(md0.members[1].spare);
+        assert_eq!(md0.progress, None);
+
+        let md127 = &stat.arrays[2];
+        assert!(!md127.active);
+        assert_eq!(md127.level, None);
+        assert_eq!(md127.members.len(), 2);
+        assert!(md127.members.iter().all(|m| m.spare));
+        assert_eq!(md127.disks, None);
+
+        assert_eq!(from_reader(SAMPLE.as_bytes()).unwrap(), stat);
+    }
+
+    #[test]
+    fn malformed() {
+        // detail lines need an array to belong to
+        let err = from_str("Personalities : [raid1]\n      100 blocks\n").unwrap_err();
+        assert_eq!(err.line, 2);
+
+        let err =
+            from_str("Personalities : [raid1]\n\nmd0 : rebuilding raid1 sda[0]\n").unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.content, "md0 : rebuilding raid1 sda[0]");
+
+        assert_eq!(from_str("md0 : active raid1 sda[x]\n").unwrap_err().line, 1);
+        assert_eq!(from_str("md0 active raid1 sda[0]\n").unwrap_err().line, 1);
+    }
+}
@@ -0,0 +1,42 @@
+pub mod devices;
+pub mod devlist;
+pub mod diskstats;
+pub mod mdstat;
+pub mod mountinfo;
+pub mod swaps;
+
+use std::io::{self, BufRead};
+
+// line is 1-based, content the offending line as found
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid line {line}: {content:?}")]
+pub struct ParseLineError {
+    pub line: usize,
+    pub content: String,
+}
+
+impl ParseLineError {
+    #[inline]
+    fn new(line: usize, content: &str) -> Self {
+        Self {
+            line: line + 1,
+            content: content.to_string(),
+        }
+    }
+}
+
+impl From<ParseLineError> for io::Error {
+    #[inline]
+    fn from(err: ParseLineError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+fn from_reader<R: BufRead, T>(
+    mut r: R,
+    parse: fn(&str) -> Result<T, ParseLineError>,
+) -> io::Result<T> {
+    let mut content = String::new();
+    r.read_to_string(&mut content)?;
+    Ok(parse(&content)?)
+}
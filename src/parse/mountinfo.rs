@@ -0,0 +1,66 @@
+use std::io::{self, BufRead};
+
+use super::ParseLineError;
+use crate::procfs::MountInfo;
+
+pub fn from_str(s: &str) -> Result<Vec<MountInfo>, ParseLineError> {
+    s.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| line.parse().map_err(|_| ParseLineError::new(i, line)))
+        .collect()
+}
+
+#[inline]
+pub fn from_reader<R: BufRead>(r: R) -> io::Result<Vec<MountInfo>> {
+    super::from_reader(r, from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+    use crate::Devno;
+
+    const SAMPLE: &str = "22 1 253:0 / / rw,relatime shared:1 - ext4 /dev/mapper/vg-root rw
+23 22 8:1 / /boot rw,relatime shared:2 - vfat /dev/sda1 rw,fmask=0022
+
+24 22 0:21 /home /mnt/my\\040home rw master:3 - btrfs /dev/sdb1 rw,subvol=/home
+";
+
+    #[test]
+    fn sample() {
+        let mounts = from_str(SAMPLE).unwrap();
+        assert_eq!(mounts.len(), 3);
+
+        assert_eq!(mounts[0].dev, Devno::new(253, 0));
+        assert_eq!(mounts[0].mount_point, Path::new("/"));
+        assert_eq!(mounts[0].file_system, "ext4");
+        assert_eq!(mounts[0].source.as_deref(), Some("/dev/mapper/vg-root"));
+
+        assert_eq!(mounts[1].parent_id, 22);
+        assert_eq!(mounts[1].super_options, "rw,fmask=0022");
+
+        assert_eq!(mounts[2].root, Path::new("/home"));
+        assert_eq!(mounts[2].mount_point, Path::new("/mnt/my home"));
+        assert!(mounts[2].propagation.is_slave());
+
+        assert_eq!(from_reader(SAMPLE.as_bytes()).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn malformed() {
+        let s = "22 1 253:0 / / rw shared:1 - ext4 /dev/dm-0 rw\n\n23 22 8:1 / /boot rw\n";
+        let err = from_str(s).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.content, "23 22 8:1 / /boot rw");
+
+        assert_eq!(
+            from_str("x 1 8:1 / / rw - ext4 /dev/sda1 rw\n")
+                .unwrap_err()
+                .line,
+            1
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    io::{self, BufRead},
+    path::PathBuf,
+};
+
+use super::ParseLineError;
+use crate::procfs::unescape_path;
+
+// sizes are converted from the KiB the kernel reports
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwapEntry {
+    pub path: PathBuf,
+    pub kind: String,
+    pub size: u64,
+    pub used: u64,
+    pub priority: i32,
+}
+
+fn parse_line(line: &str) -> Option<SwapEntry> {
+    let mut it = line.split_whitespace();
+    let entry = SwapEntry {
+        path: unescape_path(it.next()?),
+        kind: it.next()?.to_string(),
+        size: it.next()?.parse::<u64>().ok()?.checked_mul(1024)?,
+        used: it.next()?.parse::<u64>().ok()?.checked_mul(1024)?,
+        priority: it.next()?.parse().ok()?,
+    };
+    Some(entry)
+}
+
+pub fn from_str(s: &str) -> Result<Vec<SwapEntry>, ParseLineError> {
+    s.lines()
+        .enumerate()
+        // the first line is the column header
+        .skip(1)
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| parse_line(line).ok_or_else(|| ParseLineError::new(i, line)))
+        .collect()
+}
+
+#[inline]
+pub fn from_reader<R: BufRead>(r: R) -> io::Result<Vec<SwapEntry>> {
+    super::from_reader(r, from_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    const SAMPLE: &str = "Filename\t\t\t\tType\t\tSize\t\tUsed\t\tPriority
+/dev/dm-1                               partition\t8388604\t\t1024\t\t-2
+/swap\\040file                           file\t\t1048572\t\t0\t\t10
+";
+
+    #[test]
+    fn sample() {
+        let entries = from_str(SAMPLE).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, Path::new("/dev/dm-1"));
+        assert_eq!(entries[0].kind, "partition");
+        assert_eq!(entries[0].size, 8388604 * 1024);
+        assert_eq!(entries[0].used, 1024 * 1024);
+        assert_eq!(entries[0].priority, -2);
+        assert_eq!(entries[1].path, Path::new("/swap file"));
+        assert_eq!(entries[1].priority, 10);
+        assert_eq!(from_reader(SAMPLE.as_bytes()).unwrap(), entries);
+
+        // a header alone is an empty table
+        assert!(from_str("Filename Type Size Used Priority\n")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn malformed() {
+        let s = "Filename Type Size Used Priority\n/dev/sda2 partition 100 0 -2\n/dev/sda3 partition 100\n";
+        let err = from_str(s).unwrap_err();
+        assert_eq!(err.line, 3);
+        assert_eq!(err.content, "/dev/sda3 partition 100");
+
+        assert_eq!(
+            from_str("Filename Type Size Used Priority\n/dev/sda2 partition 1k 0 -2\n")
+                .unwrap_err()
+                .line,
+            2
+        );
+        // KiB counts that do not fit in bytes
+        let err = from_str(&format!(
+            "Filename Type Size Used Priority\n/dev/sda2 partition {} 0 -2\n",
+            u64::MAX / 1024 + 1
+        ))
+        .unwrap_err();
+        assert_eq!(err.line, 2);
+        assert!(from_str(&format!(
+            "Filename Type Size Used Priority\n/dev/sda2 partition {} 0 -2\n",
+            u64::MAX / 1024
+        ))
+        .is_ok());
+    }
+}
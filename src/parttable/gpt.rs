@@ -0,0 +1,267 @@
+use std::{io, os::unix::fs::FileExt};
+
+use super::{crc32::crc32, read_at, read_sector, Guid, PartitionEntry, PartitionType};
+use crate::Result;
+
+pub(crate) const SIGNATURE: &[u8; 8] = b"EFI PART";
+pub(crate) const LEGACY_BOOTABLE: u64 = 1 << 2;
+
+pub(crate) const HEADER_SIZE: usize = 92;
+pub(crate) const ENTRY_SIZE: usize = 128;
+pub(crate) const ENTRIES_COUNT: usize = 128;
+pub(crate) const ENTRIES_BYTES: usize = ENTRY_SIZE * ENTRIES_COUNT;
+
+// bounds on what a header may claim before anything gets allocated
+const MAX_ENTRY_SIZE: usize = 4096;
+const MAX_ENTRIES_COUNT: u32 = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Header {
+    pub crc_valid: bool,
+    pub current_lba: u64,
+    pub backup_lba: u64,
+    pub first_usable: u64,
+    pub last_usable: u64,
+    pub disk_guid: Guid,
+    pub entries_lba: u64,
+    pub entries_count: u32,
+    pub entry_size: u32,
+    pub entries_crc: u32,
+}
+
+impl Header {
+    pub fn parse(sector: &[u8]) -> Option<Self> {
+        if &sector[0..8] != SIGNATURE {
+            return None;
+        }
+        let u32_at = |off: usize| u32::from_le_bytes(sector[off..off + 4].try_into().unwrap());
+        let u64_at = |off: usize| u64::from_le_bytes(sector[off..off + 8].try_into().unwrap());
+
+        let size = u32_at(12) as usize;
+        let crc_valid = (HEADER_SIZE..=sector.len()).contains(&size) && {
+            let mut raw = sector[..size].to_vec();
+            raw[16..20].fill(0);
+            crc32(&raw) == u32_at(16)
+        };
+
+        Some(Self {
+            crc_valid,
+            current_lba: u64_at(24),
+            backup_lba: u64_at(32),
+            first_usable: u64_at(40),
+            last_usable: u64_at(48),
+            disk_guid: Guid::from_bytes(sector[56..72].try_into().unwrap()),
+            entries_lba: u64_at(72),
+            entries_count: u32_at(80),
+            entry_size: u32_at(84),
+            entries_crc: u32_at(88),
+        })
+    }
+}
+
+pub(crate) fn header<F: FileExt>(f: &F, lba: u64, sector_size: u64) -> Result<Option<Header>> {
+    Ok(Header::parse(&read_sector(f, lba, sector_size)?))
+}
+
+pub(crate) fn raw_entries<F: FileExt>(f: &F, header: &Header, sector_size: u64) -> Result<Vec<u8>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid GPT entry array");
+    let entry_size = header.entry_size as usize;
+    if !(ENTRY_SIZE..=MAX_ENTRY_SIZE).contains(&entry_size)
+        || !entry_size.is_multiple_of(8)
+        || header.entries_count > MAX_ENTRIES_COUNT
+    {
+        return Err(invalid().into());
+    }
+
+    let offset = header
+        .entries_lba
+        .checked_mul(sector_size)
+        .ok_or_else(invalid)?;
+    let len = entry_size
+        .checked_mul(header.entries_count as usize)
+        .ok_or_else(invalid)?;
+    read_at(f, offset, len)
+}
+
+#[inline]
+pub(crate) fn entries<F: FileExt>(
+    f: &F,
+    header: &Header,
+    sector_size: u64,
+) -> Result<Vec<PartitionEntry>> {
+    Ok(parse_entries(
+        &raw_entries(f, header, sector_size)?,
+        header.entry_size as usize,
+    ))
+}
+
+pub(crate) fn parse_entries(raw: &[u8], entry_size: usize) -> Vec<PartitionEntry> {
+    let mut res = Vec::new();
+    for (i, e) in raw.chunks_exact(entry_size).enumerate() {
+        let part_type = Guid::from_bytes(e[0..16].try_into().unwrap());
+        if part_type.is_zero() {
+            continue;
+        }
+
+        let first = u64::from_le_bytes(e[32..40].try_into().unwrap());
+        let last = u64::from_le_bytes(e[40..48].try_into().unwrap());
+        let attrs = u64::from_le_bytes(e[48..56].try_into().unwrap());
+        let name = e[56..128]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0)
+            .collect::<Vec<_>>();
+
+        res.push(PartitionEntry {
+            number: i as u32 + 1,
+            start: first,
+            sectors: last.saturating_sub(first).saturating_add(1),
+            part_type: PartitionType::Gpt(part_type),
+            uuid: Some(Guid::from_bytes(e[16..32].try_into().unwrap())),
+            name: Some(String::from_utf16_lossy(&name)),
+            // legacy BIOS bootable attribute
+            bootable: attrs & LEGACY_BOOTABLE != 0,
+            attributes: attrs & !LEGACY_BOOTABLE,
+        });
+    }
+    res
+}
+
+pub(crate) fn encode_entries(entries: &[PartitionEntry]) -> Result<Vec<u8>> {
+    let mut raw = vec![0u8; ENTRIES_BYTES];
+    let mut seen = [false; ENTRIES_COUNT];
+    for e in entries {
+        let part_type = match e.part_type {
+            PartitionType::Gpt(guid) => guid,
+            PartitionType::Mbr(_) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "MBR partition type in a GPT table",
+                )
+                .into())
+            }
+        };
+        let idx = (e.number as usize)
+            .checked_sub(1)
+            .filter(|idx| *idx < ENTRIES_COUNT)
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "invalid partition number")
+            })?;
+        if std::mem::replace(&mut seen[idx], true) {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "duplicate partition number").into(),
+            );
+        }
+        if e.sectors == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "empty partition").into());
+        }
+
+        let mut attrs = e.attributes & !LEGACY_BOOTABLE;
+        if e.bootable {
+            attrs |= LEGACY_BOOTABLE;
+        }
+
+        let buf = &mut raw[idx * ENTRY_SIZE..(idx + 1) * ENTRY_SIZE];
+        buf[0..16].copy_from_slice(part_type.as_bytes());
+        buf[16..32].copy_from_slice(e.uuid.unwrap_or_default().as_bytes());
+        buf[32..40].copy_from_slice(&e.start.to_le_bytes());
+        buf[40..48].copy_from_slice(&(e.end() - 1).to_le_bytes());
+        buf[48..56].copy_from_slice(&attrs.to_le_bytes());
+        for (i, c) in e
+            .name
+            .as_deref()
+            .unwrap_or("")
+            .encode_utf16()
+            .take(36)
+            .enumerate()
+        {
+            buf[56 + i * 2..58 + i * 2].copy_from_slice(&c.to_le_bytes());
+        }
+    }
+    Ok(raw)
+}
+
+pub(crate) struct HeaderLayout {
+    pub current: u64,
+    pub backup: u64,
+    pub first_usable: u64,
+    pub last_usable: u64,
+    pub entries_lba: u64,
+    pub entries_count: u32,
+    pub entry_size: u32,
+}
+
+pub(crate) fn encode_header(
+    layout: &HeaderLayout,
+    disk_guid: &Guid,
+    entries_crc: u32,
+    sector_size: u64,
+) -> Vec<u8> {
+    let mut buf = vec![0u8; sector_size as usize];
+    buf[0..8].copy_from_slice(SIGNATURE);
+    buf[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+    buf[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+    buf[24..32].copy_from_slice(&layout.current.to_le_bytes());
+    buf[32..40].copy_from_slice(&layout.backup.to_le_bytes());
+    buf[40..48].copy_from_slice(&layout.first_usable.to_le_bytes());
+    buf[48..56].copy_from_slice(&layout.last_usable.to_le_bytes());
+    buf[56..72].copy_from_slice(disk_guid.as_bytes());
+    buf[72..80].copy_from_slice(&layout.entries_lba.to_le_bytes());
+    buf[80..84].copy_from_slice(&layout.entries_count.to_le_bytes());
+    buf[84..88].copy_from_slice(&layout.entry_size.to_le_bytes());
+    buf[88..92].copy_from_slice(&entries_crc.to_le_bytes());
+    let crc = crc32(&buf[..HEADER_SIZE]);
+    buf[16..20].copy_from_slice(&crc.to_le_bytes());
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parttable::test_image;
+
+    fn header(entries_lba: u64, entries_count: u32, entry_size: u32) -> Header {
+        Header {
+            crc_valid: true,
+            current_lba: 1,
+            backup_lba: 0,
+            first_usable: 34,
+            last_usable: 0,
+            disk_guid: Guid::ZERO,
+            entries_lba,
+            entries_count,
+            entry_size,
+            entries_crc: 0,
+        }
+    }
+
+    #[test]
+    fn rejects_oversized_entry_arrays() {
+        let f = test_image("gpt-bounds", &[0u8; 4 * 512]);
+        for h in [
+            header(2, 4096, u32::MAX),
+            header(2, 128, 8192),
+            header(2, 128, 130),
+            header(2, 128, 64),
+            header(2, u32::MAX, 128),
+            header(u64::MAX, 128, 128),
+        ] {
+            let err = raw_entries(&f, &h, 512).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", h);
+        }
+    }
+
+    #[test]
+    fn reads_entry_array() {
+        let mut image = vec![0u8; 4 * 512];
+        image[2 * 512..2 * 512 + 16].copy_from_slice(Guid::ESP.as_bytes());
+        image[2 * 512 + 32..2 * 512 + 40].copy_from_slice(&2048u64.to_le_bytes());
+        image[2 * 512 + 40..2 * 512 + 48].copy_from_slice(&u64::MAX.to_le_bytes());
+        let f = test_image("gpt-entries", &image);
+
+        let entries = entries(&f, &header(2, 4, 256), 512).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].start, 2048);
+        assert_eq!(entries[0].end(), u64::MAX);
+    }
+}
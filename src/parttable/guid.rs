@@ -0,0 +1,125 @@
+use std::{io, str::FromStr};
+
+use crate::Result;
+
+pub(crate) fn fill_random(buf: &mut [u8]) -> Result<()> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        let ret = unsafe {
+            libc::getrandom(
+                buf[filled..].as_mut_ptr() as *mut libc::c_void,
+                buf.len() - filled,
+                0,
+            )
+        };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
+        }
+        filled += ret as usize;
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Guid([u8; 16]);
+
+#[derive(Debug)]
+pub struct ParseGuidError;
+
+impl Guid {
+    pub const ZERO: Self = Self([0; 16]);
+    // C12A7328-F81F-11D2-BA4B-00A0C93EC93B in on-disk byte order
+    pub const ESP: Self = Self([
+        0x28, 0x73, 0x2a, 0xc1, 0x1f, 0xf8, 0xd2, 0x11, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9,
+        0x3b,
+    ]);
+
+    #[inline]
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self(bytes)
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        *self == Self::ZERO
+    }
+
+    pub fn random() -> Result<Self> {
+        let mut raw = [0u8; 16];
+        fill_random(&mut raw)?;
+        // version 4, RFC 4122 variant (the third group is stored little endian)
+        raw[7] = (raw[7] & 0x0f) | 0x40;
+        raw[8] = (raw[8] & 0x3f) | 0x80;
+        Ok(Self(raw))
+    }
+}
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s.replace('-', "");
+        if hex.len() != 32 || s.len() != 36 {
+            return Err(ParseGuidError);
+        }
+
+        let mut raw = [0u8; 16];
+        for (i, b) in raw.iter_mut().enumerate() {
+            *b = u8::from_str_radix(hex.get(i * 2..i * 2 + 2).ok_or(ParseGuidError)?, 16)
+                .map_err(|_| ParseGuidError)?;
+        }
+
+        // the first three groups are stored little endian on disk
+        raw[0..4].reverse();
+        raw[4..6].reverse();
+        raw[6..8].reverse();
+        Ok(Self(raw))
+    }
+}
+
+impl std::fmt::Display for Guid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let b = &self.0;
+        write!(
+            f,
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-",
+            b[3], b[2], b[1], b[0], b[5], b[4], b[7], b[6], b[8], b[9]
+        )?;
+        for x in &b[10..] {
+            write!(f, "{:02X}", x)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Guid {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Guid({})", self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Guid {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Guid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| serde::de::Error::custom(format_args!("invalid GUID {:?}", s)))
+    }
+}
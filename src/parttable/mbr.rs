@@ -0,0 +1,226 @@
+use std::{collections::BTreeSet, io, os::unix::fs::FileExt};
+
+use super::{read_sector, PartitionEntry, PartitionType};
+use crate::Result;
+
+pub(crate) const SIGNATURE_OFFSET: usize = 510;
+pub(crate) const ENTRIES_OFFSET: usize = 446;
+pub(crate) const ENTRY_SIZE: usize = 16;
+pub(crate) const GPT_PROTECTIVE: u8 = 0xee;
+
+// logical partitions chains are capped like the kernel does
+const MAX_LOGICAL: u32 = 256;
+
+pub(crate) struct RawEntry {
+    pub bootable: bool,
+    pub part_type: u8,
+    pub start: u64,
+    pub sectors: u64,
+}
+
+#[inline]
+pub(crate) fn has_signature(sector: &[u8]) -> bool {
+    sector[SIGNATURE_OFFSET] == 0x55 && sector[SIGNATURE_OFFSET + 1] == 0xaa
+}
+
+#[inline]
+pub(crate) fn disk_id(sector: &[u8]) -> u32 {
+    u32::from_le_bytes(sector[440..444].try_into().unwrap())
+}
+
+pub(crate) fn raw_entries(sector: &[u8]) -> [RawEntry; 4] {
+    std::array::from_fn(|i| {
+        let e = &sector[ENTRIES_OFFSET + i * ENTRY_SIZE..ENTRIES_OFFSET + (i + 1) * ENTRY_SIZE];
+        RawEntry {
+            bootable: e[0] == 0x80,
+            part_type: e[4],
+            start: u32::from_le_bytes(e[8..12].try_into().unwrap()) as u64,
+            sectors: u32::from_le_bytes(e[12..16].try_into().unwrap()) as u64,
+        }
+    })
+}
+
+pub(crate) const ESP_TYPE: u8 = 0xef;
+
+#[inline]
+pub(crate) fn is_extended(part_type: u8) -> bool {
+    matches!(part_type, 0x05 | 0x0f | 0x85)
+}
+
+pub(crate) fn entries<F: FileExt>(
+    f: &F,
+    sector: &[u8],
+    sector_size: u64,
+) -> Result<Vec<PartitionEntry>> {
+    let mut res = Vec::new();
+    let mut extended = None;
+
+    for (i, raw) in raw_entries(sector).into_iter().enumerate() {
+        if raw.part_type == 0 || raw.sectors == 0 {
+            continue;
+        }
+        if is_extended(raw.part_type) && extended.is_none() {
+            extended = Some(raw.start);
+        }
+        res.push(PartitionEntry {
+            number: i as u32 + 1,
+            start: raw.start,
+            sectors: raw.sectors,
+            part_type: PartitionType::Mbr(raw.part_type),
+            uuid: None,
+            name: None,
+            bootable: raw.bootable,
+            attributes: 0,
+        });
+    }
+
+    if let Some(base) = extended {
+        let mut ebr = base;
+        let mut number = 5;
+        // crafted chains may loop back or hold nothing but empty logicals
+        let mut visited = BTreeSet::new();
+        while number < 5 + MAX_LOGICAL && visited.len() < MAX_LOGICAL as usize {
+            if !visited.insert(ebr) {
+                break;
+            }
+            let sector = read_sector(f, ebr, sector_size)?;
+            if !has_signature(&sector) {
+                break;
+            }
+            let [logical, next, ..] = raw_entries(&sector);
+            if logical.part_type != 0 && logical.sectors != 0 {
+                let start = match ebr.checked_add(logical.start) {
+                    Some(start) => start,
+                    None => break,
+                };
+                res.push(PartitionEntry {
+                    number,
+                    start,
+                    sectors: logical.sectors,
+                    part_type: PartitionType::Mbr(logical.part_type),
+                    uuid: None,
+                    name: None,
+                    bootable: logical.bootable,
+                    attributes: 0,
+                });
+                number += 1;
+            }
+            if next.part_type == 0 || next.start == 0 {
+                break;
+            }
+            ebr = match base.checked_add(next.start) {
+                Some(ebr) => ebr,
+                None => break,
+            };
+        }
+    }
+
+    Ok(res)
+}
+
+pub(crate) fn encode_entry(
+    buf: &mut [u8],
+    bootable: bool,
+    part_type: u8,
+    start: u64,
+    sectors: u64,
+) {
+    buf[0] = if bootable { 0x80 } else { 0 };
+    // CHS addressing is long dead, use the LBA-only marker
+    buf[1..4].copy_from_slice(&[0xfe, 0xff, 0xff]);
+    buf[4] = part_type;
+    buf[5..8].copy_from_slice(&[0xfe, 0xff, 0xff]);
+    buf[8..12].copy_from_slice(&(start.min(u32::MAX as u64) as u32).to_le_bytes());
+    buf[12..16].copy_from_slice(&(sectors.min(u32::MAX as u64) as u32).to_le_bytes());
+}
+
+pub(crate) fn encode(
+    boot_sector: &mut [u8],
+    disk_id: u32,
+    entries: &[PartitionEntry],
+) -> Result<()> {
+    boot_sector[440..444].copy_from_slice(&disk_id.to_le_bytes());
+    boot_sector[444..446].fill(0);
+    boot_sector[ENTRIES_OFFSET..SIGNATURE_OFFSET].fill(0);
+    for e in entries {
+        let part_type = match e.part_type {
+            PartitionType::Mbr(ty) if !is_extended(ty) && (1..=4).contains(&e.number) => ty,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    "only primary MBR partitions can be written",
+                )
+                .into())
+            }
+        };
+        if e.start > u32::MAX as u64 || e.sectors > u32::MAX as u64 {
+            return Err(
+                io::Error::new(io::ErrorKind::InvalidInput, "partition beyond 2TiB").into(),
+            );
+        }
+        let off = ENTRIES_OFFSET + (e.number as usize - 1) * ENTRY_SIZE;
+        encode_entry(
+            &mut boot_sector[off..off + ENTRY_SIZE],
+            e.bootable,
+            part_type,
+            e.start,
+            e.sectors,
+        );
+    }
+    boot_sector[SIGNATURE_OFFSET] = 0x55;
+    boot_sector[SIGNATURE_OFFSET + 1] = 0xaa;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parttable::test_image;
+
+    fn slot(image: &mut [u8], lba: usize, i: usize) -> &mut [u8] {
+        let off = lba * 512 + ENTRIES_OFFSET + i * ENTRY_SIZE;
+        &mut image[off..off + ENTRY_SIZE]
+    }
+
+    fn sign(image: &mut [u8], lba: usize) {
+        image[lba * 512 + SIGNATURE_OFFSET..lba * 512 + SIGNATURE_OFFSET + 2]
+            .copy_from_slice(&[0x55, 0xaa]);
+    }
+
+    #[test]
+    fn ebr_loop_terminates() {
+        let mut image = vec![0u8; 4 * 512];
+        encode_entry(slot(&mut image, 0, 0), false, 0x05, 1, 3);
+        sign(&mut image, 0);
+        // lba 1 links to lba 2, which links to itself, all logicals empty
+        encode_entry(slot(&mut image, 1, 1), false, 0x05, 1, 1);
+        sign(&mut image, 1);
+        encode_entry(slot(&mut image, 2, 1), false, 0x05, 1, 1);
+        sign(&mut image, 2);
+
+        let f = test_image("ebr-loop", &image);
+        let entries = entries(&f, &image[..512], 512).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert!(entries[0].is_extended());
+    }
+
+    #[test]
+    fn ebr_chain() {
+        let mut image = vec![0u8; 8 * 512];
+        encode_entry(slot(&mut image, 0, 0), false, 0x05, 1, 7);
+        sign(&mut image, 0);
+        encode_entry(slot(&mut image, 1, 0), false, 0x83, 1, 2);
+        encode_entry(slot(&mut image, 1, 1), false, 0x05, 3, 4);
+        sign(&mut image, 1);
+        encode_entry(slot(&mut image, 4, 0), false, 0x82, 1, 3);
+        sign(&mut image, 4);
+
+        let f = test_image("ebr-chain", &image);
+        let entries = entries(&f, &image[..512], 512).unwrap();
+        let logical = entries
+            .iter()
+            .map(|e| (e.number, e.start, e.sectors))
+            .collect::<Vec<_>>();
+        assert_eq!(logical[1..], [(5, 2, 2), (6, 5, 3)]);
+    }
+}
@@ -0,0 +1,288 @@
+mod crc32;
+mod gpt;
+mod guid;
+mod mbr;
+mod script;
+mod validate;
+mod writer;
+
+use std::{
+    fs::File,
+    io,
+    os::unix::{fs::FileExt, prelude::FileTypeExt},
+};
+
+pub(crate) use guid::fill_random;
+pub use guid::{Guid, ParseGuidError};
+pub use script::ParseDumpError;
+pub use validate::{GptFinding, GptHeaderKind, GptTable};
+pub use writer::NewPartition;
+
+use crate::{resize, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionType {
+    Mbr(u8),
+    Gpt(Guid),
+}
+
+impl PartitionType {
+    #[inline]
+    pub fn is_esp(&self) -> bool {
+        match self {
+            Self::Mbr(ty) => *ty == mbr::ESP_TYPE,
+            Self::Gpt(guid) => *guid == Guid::ESP,
+        }
+    }
+}
+
+impl std::fmt::Display for PartitionType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mbr(ty) => write!(f, "{:x}", ty),
+            Self::Gpt(guid) => guid.fmt(f),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionEntry {
+    pub number: u32,
+    pub start: u64,
+    pub sectors: u64,
+    pub part_type: PartitionType,
+    pub uuid: Option<Guid>,
+    pub name: Option<String>,
+    pub bootable: bool,
+    pub attributes: u64,
+}
+
+impl PartitionEntry {
+    #[inline]
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.sectors)
+    }
+
+    #[inline]
+    pub fn is_extended(&self) -> bool {
+        matches!(self.part_type, PartitionType::Mbr(ty) if mbr::is_extended(ty))
+    }
+}
+
+const ISO9660_MAGIC_OFFSET: u64 = 16 * 2048 + 1;
+const ISO9660_MAGIC: &[u8] = b"CD001";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TableKind {
+    Mbr { disk_id: u32 },
+    Gpt { disk_guid: Guid },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TableLayout {
+    Dos,
+    ProtectiveGpt,
+    HybridGpt,
+    IsoHybrid,
+}
+
+impl TableLayout {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(f), err)
+    )]
+    pub fn probe(f: &File, sector_size: u64) -> Result<Option<Self>> {
+        check_sector_size(sector_size)?;
+        let first = match read_sector(f, 0, sector_size) {
+            Ok(sector) => sector,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if !mbr::has_signature(&first) {
+            return Ok(None);
+        }
+
+        // iso9660 primary volume descriptor lives in the 17th 2048 bytes block
+        match read_at(f, ISO9660_MAGIC_OFFSET, ISO9660_MAGIC.len()) {
+            Ok(magic) if magic == ISO9660_MAGIC => return Ok(Some(Self::IsoHybrid)),
+            Ok(_) => (),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => (),
+            Err(err) => return Err(err),
+        }
+
+        let entries = mbr::raw_entries(&first);
+        let used = entries.iter().filter(|e| e.part_type != 0);
+        let protective = entries.iter().any(|e| e.part_type == mbr::GPT_PROTECTIVE);
+        if !protective || gpt::header(f, 1, sector_size)?.is_none() {
+            return Ok(Some(Self::Dos));
+        }
+
+        if used.count() > 1 {
+            Ok(Some(Self::HybridGpt))
+        } else {
+            Ok(Some(Self::ProtectiveGpt))
+        }
+    }
+
+    #[inline]
+    pub fn is_gpt(&self) -> bool {
+        matches!(self, Self::ProtectiveGpt | Self::HybridGpt)
+    }
+}
+
+// the naming libblkid and udev use in ID_PART_TABLE_TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PartitionTableType {
+    Gpt,
+    Dos,
+}
+
+impl PartitionTableType {
+    pub(crate) fn from_udev(s: &str) -> Option<Self> {
+        match s {
+            "gpt" => Some(Self::Gpt),
+            "dos" => Some(Self::Dos),
+            _ => None,
+        }
+    }
+}
+
+impl From<TableLayout> for PartitionTableType {
+    #[inline]
+    fn from(layout: TableLayout) -> Self {
+        if layout.is_gpt() {
+            Self::Gpt
+        } else {
+            // isohybrid images carry a plain MBR
+            Self::Dos
+        }
+    }
+}
+
+impl std::fmt::Display for PartitionTableType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Gpt => "gpt",
+            Self::Dos => "dos",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PartitionTable {
+    pub kind: TableKind,
+    pub sector_size: u64,
+    pub first_usable: u64,
+    pub last_usable: u64,
+    pub entries: Vec<PartitionEntry>,
+}
+
+pub(crate) fn read_at<F: FileExt>(f: &F, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let mut buf = vec![0u8; len];
+    f.read_exact_at(&mut buf, offset)?;
+    Ok(buf)
+}
+
+#[inline]
+pub(crate) fn read_sector<F: FileExt>(f: &F, lba: u64, sector_size: u64) -> Result<Vec<u8>> {
+    let offset = lba
+        .checked_mul(sector_size)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "sector beyond any disk size"))?;
+    read_at(f, offset, sector_size as usize)
+}
+
+pub(crate) fn disk_sectors(f: &File, sector_size: u64) -> Result<u64> {
+    let md = f.metadata()?;
+    let size = if md.file_type().is_block_device() {
+        resize::blk_getsize64(f)?
+    } else {
+        md.len()
+    };
+    Ok(size / sector_size)
+}
+
+#[inline]
+pub(crate) fn check_sector_size(sector_size: u64) -> Result<()> {
+    if sector_size < 512 || !sector_size.is_power_of_two() {
+        Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid sector size").into())
+    } else {
+        Ok(())
+    }
+}
+
+impl PartitionTable {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip(f), err)
+    )]
+    pub fn read(f: &File, sector_size: u64) -> Result<Option<Self>> {
+        check_sector_size(sector_size)?;
+
+        let first = match read_sector(f, 0, sector_size) {
+            Ok(sector) => sector,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        };
+        if !mbr::has_signature(&first) {
+            return Ok(None);
+        }
+
+        let protective = mbr::raw_entries(&first)
+            .iter()
+            .any(|e| e.part_type == mbr::GPT_PROTECTIVE);
+        if protective {
+            if let Some(header) = gpt::header(f, 1, sector_size)? {
+                return Ok(Some(Self {
+                    kind: TableKind::Gpt {
+                        disk_guid: header.disk_guid,
+                    },
+                    entries: gpt::entries(f, &header, sector_size)?,
+                    first_usable: header.first_usable,
+                    last_usable: header.last_usable,
+                    sector_size,
+                }));
+            }
+        }
+
+        Ok(Some(Self {
+            kind: TableKind::Mbr {
+                disk_id: mbr::disk_id(&first),
+            },
+            entries: mbr::entries(f, &first, sector_size)?,
+            first_usable: 1,
+            last_usable: disk_sectors(f, sector_size)?
+                .min(u32::MAX as u64)
+                .saturating_sub(1),
+            sector_size,
+        }))
+    }
+
+    #[inline]
+    pub fn is_gpt(&self) -> bool {
+        matches!(self.kind, TableKind::Gpt { .. })
+    }
+
+    #[inline]
+    pub fn get(&self, number: u32) -> Option<&PartitionEntry> {
+        self.entries.iter().find(|e| e.number == number)
+    }
+}
+
+#[cfg(test)]
+pub(crate) fn test_image(name: &str, content: &[u8]) -> File {
+    let p = std::env::temp_dir().join(format!("linux-blocks-{}-{}.img", name, std::process::id()));
+    std::fs::write(&p, content).unwrap();
+    let f = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&p)
+        .unwrap();
+    std::fs::remove_file(&p).unwrap();
+    f
+}
@@ -0,0 +1,306 @@
+use std::fmt::Write;
+
+use super::{Guid, PartitionEntry, PartitionTable, PartitionType, TableKind};
+use crate::naming;
+
+const GPT_ATTRS: [(u32, &str); 3] = [
+    (0, "RequiredPartition"),
+    (1, "NoBlockIOProtocol"),
+    (2, "LegacyBIOSBootable"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDumpError {
+    pub line: usize,
+}
+
+impl std::fmt::Display for ParseDumpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid partition table dump at line {}", self.line)
+    }
+}
+
+impl std::error::Error for ParseDumpError {}
+
+fn format_attrs(attrs: u64) -> String {
+    let mut res = Vec::new();
+    for bit in 0..64 {
+        if attrs & (1 << bit) == 0 {
+            continue;
+        }
+        match GPT_ATTRS.iter().find(|(b, _)| *b == bit) {
+            Some((_, name)) => res.push(name.to_string()),
+            None => res.push(format!("GUID:{}", bit)),
+        }
+    }
+    res.join(" ")
+}
+
+fn parse_attrs(s: &str) -> Option<u64> {
+    let mut attrs = 0u64;
+    for attr in s.split([' ', ',']).filter(|a| !a.is_empty()) {
+        if let Some(bits) = attr.strip_prefix("GUID:") {
+            for bit in bits.split(',') {
+                let bit: u32 = bit.parse().ok()?;
+                attrs |= 1u64.checked_shl(bit)?;
+            }
+        } else {
+            let (bit, _) = GPT_ATTRS.iter().find(|(_, name)| *name == attr)?;
+            attrs |= 1 << bit;
+        }
+    }
+    Some(attrs)
+}
+
+fn parse_type(s: &str, gpt: bool) -> Option<PartitionType> {
+    let alias = match (s, gpt) {
+        ("L", true) => Some("0FC63DAF-8483-4772-8E79-3D47DE475F37"),
+        ("S", true) => Some("0657FD6D-A4AB-436F-8768-9B8E5BED7F4C"),
+        ("U", true) => Some("C12A7328-F81F-11D2-BA4B-00A0C93EC93B"),
+        ("L", false) => Some("83"),
+        ("S", false) => Some("82"),
+        ("U", false) => Some("ef"),
+        ("E", false) => Some("5"),
+        _ => None,
+    };
+    let s = alias.unwrap_or(s);
+    if gpt {
+        s.parse().ok().map(PartitionType::Gpt)
+    } else {
+        u8::from_str_radix(s.trim_start_matches("0x"), 16)
+            .ok()
+            .map(PartitionType::Mbr)
+    }
+}
+
+// split a `key=value, key="quoted, value"` list
+fn split_fields(s: &str) -> Vec<(&str, &str)> {
+    let mut res = Vec::new();
+    let mut rest = s.trim();
+    while !rest.is_empty() {
+        let (key, value, next) = match rest.find([',', '=']) {
+            Some(idx) if rest.as_bytes()[idx] == b'=' => {
+                let key = rest[..idx].trim();
+                let after = rest[idx + 1..].trim_start();
+                if let Some(quoted) = after.strip_prefix('"') {
+                    let end = quoted.find('"').unwrap_or(quoted.len());
+                    let next = quoted[end..].trim_start_matches('"');
+                    (key, &quoted[..end], next)
+                } else {
+                    let end = after.find(',').unwrap_or(after.len());
+                    (key, after[..end].trim(), &after[end..])
+                }
+            }
+            Some(idx) => (rest[..idx].trim(), "", &rest[idx..]),
+            None => (rest.trim(), "", ""),
+        };
+        if !key.is_empty() {
+            res.push((key, value));
+        }
+        rest = next.trim_start().trim_start_matches(',').trim_start();
+    }
+    res
+}
+
+impl PartitionTable {
+    #[inline]
+    pub fn dump(&self) -> String {
+        self.dump_device(None)
+    }
+
+    pub fn dump_device(&self, device: Option<&str>) -> String {
+        let mut res = String::new();
+        match self.kind {
+            TableKind::Gpt { disk_guid } => {
+                let _ = writeln!(res, "label: gpt");
+                let _ = writeln!(res, "label-id: {}", disk_guid);
+            }
+            TableKind::Mbr { disk_id } => {
+                let _ = writeln!(res, "label: dos");
+                let _ = writeln!(res, "label-id: {:#010x}", disk_id);
+            }
+        }
+        if let Some(device) = device {
+            let _ = writeln!(res, "device: {}", device);
+        }
+        let _ = writeln!(res, "unit: sectors");
+        let _ = writeln!(res, "first-lba: {}", self.first_usable);
+        let _ = writeln!(res, "last-lba: {}", self.last_usable);
+        let _ = writeln!(res, "sector-size: {}", self.sector_size);
+        res.push('\n');
+
+        let disk = device.unwrap_or("disk");
+        for e in self.entries.iter() {
+            let _ = write!(
+                res,
+                "{} : start={:>12}, size={:>12}, type={}",
+                naming::partition_name(disk, e.number),
+                e.start,
+                e.sectors,
+                e.part_type
+            );
+            if let Some(uuid) = e.uuid {
+                let _ = write!(res, ", uuid={}", uuid);
+            }
+            if let Some(name) = e.name.as_deref().filter(|n| !n.is_empty()) {
+                let _ = write!(res, ", name=\"{}\"", name.replace('"', "'"));
+            }
+            if e.bootable && !self.is_gpt() {
+                res.push_str(", bootable");
+            }
+            if self.is_gpt() {
+                let attrs = format_attrs(e.attributes | if e.bootable { 1 << 2 } else { 0 });
+                if !attrs.is_empty() {
+                    let _ = write!(res, ", attrs=\"{}\"", attrs);
+                }
+            }
+            res.push('\n');
+        }
+        res
+    }
+
+    pub fn restore(dump: &str) -> Result<Self, ParseDumpError> {
+        let mut kind = None;
+        let mut sector_size = 512;
+        let mut first_usable = None;
+        let mut last_usable = None;
+        let mut device = None;
+        let mut entries = Vec::new();
+
+        for (idx, line) in dump.lines().enumerate() {
+            let err = || ParseDumpError { line: idx + 1 };
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // header lines are `key: value`, partition lines `name : fields` or bare fields
+            if !line.contains('=') {
+                let (key, value) = line.split_once(':').ok_or_else(err)?;
+                let value = value.trim();
+                match key.trim() {
+                    "label" => {
+                        kind = Some(match value {
+                            "gpt" => TableKind::Gpt {
+                                disk_guid: Guid::ZERO,
+                            },
+                            "dos" => TableKind::Mbr { disk_id: 0 },
+                            _ => return Err(err()),
+                        })
+                    }
+                    "label-id" => {
+                        kind = Some(match kind.ok_or_else(err)? {
+                            TableKind::Gpt { .. } => TableKind::Gpt {
+                                disk_guid: value.parse().map_err(|_| err())?,
+                            },
+                            TableKind::Mbr { .. } => TableKind::Mbr {
+                                disk_id: u32::from_str_radix(value.trim_start_matches("0x"), 16)
+                                    .map_err(|_| err())?,
+                            },
+                        })
+                    }
+                    "unit" if value != "sectors" => return Err(err()),
+                    "first-lba" => first_usable = Some(value.parse().map_err(|_| err())?),
+                    "last-lba" => last_usable = Some(value.parse().map_err(|_| err())?),
+                    "sector-size" => sector_size = value.parse().map_err(|_| err())?,
+                    "device" => device = Some(value),
+                    _ => (),
+                }
+                continue;
+            }
+
+            let gpt = matches!(kind.ok_or_else(err)?, TableKind::Gpt { .. });
+            let (name, fields) = match line.split_once(" : ") {
+                Some((name, fields)) => (Some(name.trim()), fields),
+                None => (None, line),
+            };
+            let number = match name {
+                Some(name) => match device {
+                    Some(device) => naming::partition_number(device, name),
+                    None => naming::split_partition_name(name).map(|(_, number)| number),
+                }
+                .ok_or_else(err)?,
+                None => {
+                    entries
+                        .last()
+                        .map(|e: &PartitionEntry| e.number)
+                        .unwrap_or(0)
+                        + 1
+                }
+            };
+
+            let mut entry = PartitionEntry {
+                number,
+                start: 0,
+                sectors: 0,
+                part_type: PartitionType::Mbr(0),
+                uuid: None,
+                name: None,
+                bootable: false,
+                attributes: 0,
+            };
+            let mut part_type = None;
+            for (key, value) in split_fields(fields) {
+                match key {
+                    "start" => entry.start = value.parse().map_err(|_| err())?,
+                    "size" => entry.sectors = value.parse().map_err(|_| err())?,
+                    "type" | "Id" => part_type = Some(parse_type(value, gpt).ok_or_else(err)?),
+                    "uuid" => entry.uuid = Some(value.parse().map_err(|_| err())?),
+                    "name" => entry.name = Some(value.to_string()),
+                    "attrs" => entry.attributes = parse_attrs(value).ok_or_else(err)?,
+                    "bootable" => entry.bootable = true,
+                    _ => (),
+                }
+            }
+            // sfdisk can place partitions on its own, we need explicit geometry
+            if entry.sectors == 0 || entry.start == 0 {
+                return Err(err());
+            }
+            entry.part_type = part_type.ok_or_else(err)?;
+            if gpt {
+                entry.bootable = entry.attributes & (1 << 2) != 0;
+                entry.attributes &= !(1 << 2);
+            }
+            entries.push(entry);
+        }
+
+        let kind = kind.ok_or(ParseDumpError { line: 0 })?;
+        Ok(Self {
+            kind,
+            sector_size,
+            first_usable: first_usable.unwrap_or(1),
+            last_usable: last_usable.unwrap_or(u64::MAX - 1),
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parttable::NewPartition;
+
+    #[test]
+    fn restore_numbered_disk() {
+        let mut table = PartitionTable::new_mbr(40 * 2048, 512).unwrap();
+        for _ in 0..2 {
+            table
+                .add(NewPartition::new(PartitionType::Mbr(0x83)).sectors(2048))
+                .unwrap();
+        }
+        for device in ["/dev/loop1", "/dev/nvme0n1", "/dev/sda"] {
+            let restored = PartitionTable::restore(&table.dump_device(Some(device))).unwrap();
+            assert_eq!(restored.entries, table.entries, "{}", device);
+        }
+    }
+
+    #[test]
+    fn restore_rejects_foreign_names() {
+        let dump =
+            "label: dos\ndevice: /dev/loop1\n\n/dev/loop2p1 : start=2048, size=2048, type=83\n";
+        assert_eq!(
+            PartitionTable::restore(dump).unwrap_err(),
+            ParseDumpError { line: 4 }
+        );
+    }
+}
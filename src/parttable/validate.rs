@@ -0,0 +1,444 @@
+use std::{fmt, fs::File, io, os::unix::fs::FileExt};
+
+use super::{check_sector_size, crc32::crc32, disk_sectors, gpt, read_sector};
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GptHeaderKind {
+    Primary,
+    Backup,
+}
+
+impl fmt::Display for GptHeaderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::Primary => "primary",
+            Self::Backup => "backup",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GptFinding {
+    MissingHeader(GptHeaderKind),
+    HeaderCrc(GptHeaderKind),
+    EntriesCrc(GptHeaderKind),
+    HeadersMismatch,
+    EntriesMismatch,
+    BackupNotAtEnd { expected: u64, found: u64 },
+}
+
+impl fmt::Display for GptFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingHeader(kind) => write!(f, "{} GPT header not found", kind),
+            Self::HeaderCrc(kind) => write!(f, "{} GPT header checksum mismatch", kind),
+            Self::EntriesCrc(kind) => write!(f, "{} GPT entry array checksum mismatch", kind),
+            Self::HeadersMismatch => f.write_str("primary and backup GPT headers differ"),
+            Self::EntriesMismatch => f.write_str("primary and backup GPT entry arrays differ"),
+            Self::BackupNotAtEnd { expected, found } => write!(
+                f,
+                "backup GPT header at LBA {} instead of LBA {}",
+                found, expected
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct GptCopy {
+    lba: u64,
+    header: gpt::Header,
+    entries: Option<Vec<u8>>,
+}
+
+impl GptCopy {
+    fn read(f: &File, lba: u64, sector_size: u64) -> Result<Option<Self>> {
+        let header = match read_sector(f, lba, sector_size) {
+            Ok(sector) => gpt::Header::parse(&sector),
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => None,
+            Err(err) => return Err(err),
+        };
+        let header = match header {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        let entries = match gpt::raw_entries(f, &header, sector_size) {
+            Ok(entries) => Some(entries),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+                ) =>
+            {
+                None
+            }
+            Err(err) => return Err(err),
+        };
+        Ok(Some(Self {
+            lba,
+            header,
+            entries,
+        }))
+    }
+
+    #[inline]
+    fn entries_valid(&self) -> bool {
+        self.entries
+            .as_deref()
+            .is_some_and(|e| crc32(e) == self.header.entries_crc)
+    }
+
+    #[inline]
+    fn is_valid(&self) -> bool {
+        self.header.crc_valid && self.entries_valid()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GptTable {
+    sector_size: u64,
+    disk_sectors: u64,
+    primary: Option<GptCopy>,
+    backup: Option<GptCopy>,
+}
+
+impl GptTable {
+    pub fn read(f: &File, sector_size: u64) -> Result<Option<Self>> {
+        check_sector_size(sector_size)?;
+        let disk = disk_sectors(f, sector_size)?;
+        if disk < 3 {
+            return Ok(None);
+        }
+
+        let primary = GptCopy::read(f, 1, sector_size)?;
+        // a disk that grew leaves the backup where the old end used to be
+        let mut candidates = primary
+            .as_ref()
+            .map(|p| p.header.backup_lba)
+            .filter(|lba| *lba > 1 && *lba < disk)
+            .into_iter()
+            .chain(std::iter::once(disk - 1));
+        let backup = loop {
+            match candidates.next() {
+                Some(lba) => {
+                    if let Some(backup) = GptCopy::read(f, lba, sector_size)? {
+                        break Some(backup);
+                    }
+                }
+                None => break None,
+            }
+        };
+
+        if primary.is_none() && backup.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Self {
+            sector_size,
+            disk_sectors: disk,
+            primary,
+            backup,
+        }))
+    }
+
+    #[inline]
+    pub fn sector_size(&self) -> u64 {
+        self.sector_size
+    }
+
+    #[inline]
+    pub fn disk_sectors(&self) -> u64 {
+        self.disk_sectors
+    }
+
+    #[inline]
+    pub fn primary_lba(&self) -> Option<u64> {
+        self.primary.as_ref().map(|p| p.lba)
+    }
+
+    #[inline]
+    pub fn backup_lba(&self) -> Option<u64> {
+        self.backup.as_ref().map(|b| b.lba)
+    }
+
+    pub fn validate(&self) -> Vec<GptFinding> {
+        let mut res = Vec::new();
+        let copies = [
+            (GptHeaderKind::Primary, self.primary.as_ref()),
+            (GptHeaderKind::Backup, self.backup.as_ref()),
+        ];
+        for (kind, copy) in copies {
+            match copy {
+                None => res.push(GptFinding::MissingHeader(kind)),
+                Some(copy) => {
+                    if !copy.header.crc_valid {
+                        res.push(GptFinding::HeaderCrc(kind));
+                    }
+                    if !copy.entries_valid() {
+                        res.push(GptFinding::EntriesCrc(kind));
+                    }
+                }
+            }
+        }
+
+        if let (Some(primary), Some(backup)) = (&self.primary, &self.backup) {
+            let (p, b) = (&primary.header, &backup.header);
+            if p.first_usable != b.first_usable
+                || p.last_usable != b.last_usable
+                || p.disk_guid != b.disk_guid
+                || p.entries_count != b.entries_count
+                || p.entry_size != b.entry_size
+                || p.current_lba != primary.lba
+                || p.backup_lba != backup.lba
+                || b.current_lba != backup.lba
+                || b.backup_lba != primary.lba
+            {
+                res.push(GptFinding::HeadersMismatch);
+            }
+            if primary.entries != backup.entries {
+                res.push(GptFinding::EntriesMismatch);
+            }
+        }
+
+        let expected = self.disk_sectors - 1;
+        let found = match (&self.backup, &self.primary) {
+            (Some(backup), _) => Some(backup.lba),
+            (None, Some(primary)) => Some(primary.header.backup_lba),
+            (None, None) => None,
+        };
+        if let Some(found) = found.filter(|lba| *lba != expected) {
+            res.push(GptFinding::BackupNotAtEnd { expected, found });
+        }
+        res
+    }
+
+    #[inline]
+    pub fn is_valid(&self) -> bool {
+        self.validate().is_empty()
+    }
+
+    pub fn repair_backup(&self, f: &File) -> Result<()> {
+        let primary = match &self.primary {
+            Some(primary) if primary.is_valid() => primary,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "primary GPT is damaged, cannot rebuild the backup from it",
+                )
+                .into())
+            }
+        };
+        let entries = primary.entries.as_deref().unwrap_or_default();
+        let header = &primary.header;
+        let ss = self.sector_size;
+
+        let disk = disk_sectors(f, ss)?;
+        let entries_sectors = (entries.len() as u64).div_ceil(ss);
+        let backup_entries = disk
+            .checked_sub(1 + entries_sectors)
+            .filter(|lba| {
+                header
+                    .entries_lba
+                    .checked_add(entries_sectors)
+                    .is_some_and(|end| *lba > end)
+            })
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "disk too small for GPT"))?;
+
+        // only grow or shrink the usable area when the backup has to move
+        let last_usable = if header.backup_lba == disk - 1 {
+            header.last_usable
+        } else {
+            backup_entries - 1
+        };
+        let used_end = gpt::parse_entries(entries, header.entry_size as usize)
+            .iter()
+            .map(|e| e.end())
+            .max()
+            .unwrap_or(0);
+        let fits = last_usable
+            .checked_add(1)
+            .is_some_and(|end| used_end <= end);
+        if !fits || last_usable < header.first_usable || last_usable >= backup_entries {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "partition table does not fit the disk",
+            )
+            .into());
+        }
+
+        let mut layout = gpt::HeaderLayout {
+            current: disk - 1,
+            backup: 1,
+            first_usable: header.first_usable,
+            last_usable,
+            entries_lba: backup_entries,
+            entries_count: header.entries_count,
+            entry_size: header.entry_size,
+        };
+        let backup_header = gpt::encode_header(&layout, &header.disk_guid, header.entries_crc, ss);
+        layout.current = 1;
+        layout.backup = disk - 1;
+        layout.entries_lba = header.entries_lba;
+        let primary_header = gpt::encode_header(&layout, &header.disk_guid, header.entries_crc, ss);
+
+        f.write_all_at(entries, backup_entries * ss)?;
+        f.write_all_at(&backup_header, (disk - 1) * ss)?;
+        f.sync_data()?;
+        // keep the primary pointing at the relocated backup
+        if layout.last_usable != header.last_usable || header.backup_lba != disk - 1 {
+            f.write_all_at(&primary_header, ss)?;
+        }
+        f.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parttable::{test_image, Guid, NewPartition, PartitionTable, PartitionType};
+
+    const SS: u64 = 512;
+    // 8MiB
+    const DISK: u64 = 16384;
+
+    fn image(name: &str) -> File {
+        let f = test_image(name, &vec![0u8; (DISK * SS) as usize]);
+        let mut table = PartitionTable::new_gpt(DISK, SS).unwrap();
+        table
+            .add(NewPartition::new(PartitionType::Gpt(Guid::ESP)).sectors(4096))
+            .unwrap();
+        table
+            .add(NewPartition::new(PartitionType::Gpt(Guid::from_bytes(
+                [0x11; 16],
+            ))))
+            .unwrap();
+        table.write(&f).unwrap();
+        f
+    }
+
+    fn read(f: &File) -> GptTable {
+        GptTable::read(f, SS).unwrap().unwrap()
+    }
+
+    fn primary_header(f: &File) -> gpt::Header {
+        gpt::Header::parse(&read_sector(f, 1, SS).unwrap()).unwrap()
+    }
+
+    fn layout(h: &gpt::Header) -> gpt::HeaderLayout {
+        gpt::HeaderLayout {
+            current: h.current_lba,
+            backup: h.backup_lba,
+            first_usable: h.first_usable,
+            last_usable: h.last_usable,
+            entries_lba: h.entries_lba,
+            entries_count: h.entries_count,
+            entry_size: h.entry_size,
+        }
+    }
+
+    #[test]
+    fn fresh_table_is_valid() {
+        let f = image("validate-fresh");
+        let table = read(&f);
+        assert_eq!(table.validate(), []);
+        assert_eq!(table.primary_lba(), Some(1));
+        assert_eq!(table.backup_lba(), Some(DISK - 1));
+    }
+
+    #[test]
+    fn missing_backup() {
+        let f = image("validate-missing");
+        f.write_all_at(&[0u8; SS as usize], (DISK - 1) * SS)
+            .unwrap();
+        let table = read(&f);
+        assert_eq!(
+            table.validate(),
+            [GptFinding::MissingHeader(GptHeaderKind::Backup)]
+        );
+
+        table.repair_backup(&f).unwrap();
+        assert!(read(&f).is_valid());
+    }
+
+    #[test]
+    fn backup_entries_differ() {
+        let f = image("validate-entries");
+        let backup_entries = DISK - 1 - (gpt::ENTRIES_BYTES as u64).div_ceil(SS);
+        f.write_all_at(&[0x5a], backup_entries * SS + 32).unwrap();
+        let findings = read(&f).validate();
+        assert!(findings.contains(&GptFinding::EntriesCrc(GptHeaderKind::Backup)));
+        assert!(findings.contains(&GptFinding::EntriesMismatch));
+
+        read(&f).repair_backup(&f).unwrap();
+        assert_eq!(read(&f).validate(), []);
+    }
+
+    #[test]
+    fn backup_header_differs() {
+        let f = image("validate-header");
+        let primary = primary_header(&f);
+        let mut backup = layout(&primary);
+        backup.current = DISK - 1;
+        backup.backup = 1;
+        backup.entries_lba = DISK - 1 - (gpt::ENTRIES_BYTES as u64).div_ceil(SS);
+        backup.last_usable -= 8;
+        let sector = gpt::encode_header(&backup, &primary.disk_guid, primary.entries_crc, SS);
+        f.write_all_at(&sector, (DISK - 1) * SS).unwrap();
+        assert_eq!(read(&f).validate(), [GptFinding::HeadersMismatch]);
+
+        read(&f).repair_backup(&f).unwrap();
+        assert_eq!(read(&f).validate(), []);
+    }
+
+    #[test]
+    fn grown_disk() {
+        let f = image("validate-grown");
+        f.set_len((DISK + 2048) * SS).unwrap();
+        let table = read(&f);
+        assert_eq!(table.backup_lba(), Some(DISK - 1));
+        assert_eq!(
+            table.validate(),
+            [GptFinding::BackupNotAtEnd {
+                expected: DISK + 2047,
+                found: DISK - 1,
+            }]
+        );
+
+        table.repair_backup(&f).unwrap();
+        let table = read(&f);
+        assert_eq!(table.validate(), []);
+        assert_eq!(table.backup_lba(), Some(DISK + 2047));
+        assert_eq!(primary_header(&f).last_usable, DISK + 2048 - 34);
+    }
+
+    #[test]
+    fn damaged_primary_is_not_copied() {
+        let f = image("validate-primary");
+        f.write_all_at(&[0xff], SS + 60).unwrap();
+        let table = read(&f);
+        assert!(table
+            .validate()
+            .contains(&GptFinding::HeaderCrc(GptHeaderKind::Primary)));
+        let err = table.repair_backup(&f).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn crafted_last_usable() {
+        let f = image("validate-crafted");
+        let primary = primary_header(&f);
+        for last_usable in [u64::MAX, DISK - 1] {
+            let mut crafted = layout(&primary);
+            crafted.last_usable = last_usable;
+            let sector = gpt::encode_header(&crafted, &primary.disk_guid, primary.entries_crc, SS);
+            f.write_all_at(&sector, SS).unwrap();
+
+            let err = read(&f).repair_backup(&f).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+}
@@ -0,0 +1,478 @@
+use std::{fs::File, io, os::unix::fs::FileExt};
+
+use super::{
+    check_sector_size, disk_sectors, gpt, guid::fill_random, mbr, read_sector, Guid,
+    PartitionEntry, PartitionTable, PartitionType, TableKind,
+};
+use crate::{parttable::crc32::crc32, Result};
+
+const DEFAULT_ALIGNMENT: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewPartition {
+    pub start: Option<u64>,
+    pub sectors: Option<u64>,
+    pub part_type: PartitionType,
+    pub name: Option<String>,
+    pub bootable: bool,
+}
+
+impl NewPartition {
+    #[inline]
+    pub fn new(part_type: PartitionType) -> Self {
+        Self {
+            start: None,
+            sectors: None,
+            part_type,
+            name: None,
+            bootable: false,
+        }
+    }
+
+    #[inline]
+    pub fn start(mut self, start: u64) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    #[inline]
+    pub fn sectors(mut self, sectors: u64) -> Self {
+        self.sectors = Some(sectors);
+        self
+    }
+
+    #[inline]
+    pub fn name<S: Into<String>>(mut self, name: S) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    #[inline]
+    pub fn bootable(mut self, bootable: bool) -> Self {
+        self.bootable = bootable;
+        self
+    }
+}
+
+#[inline]
+fn invalid(msg: &'static str) -> crate::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg).into()
+}
+
+#[inline]
+fn entries_sectors(sector_size: u64) -> u64 {
+    (gpt::ENTRIES_BYTES as u64).div_ceil(sector_size)
+}
+
+impl PartitionTable {
+    pub fn new_gpt(disk_sectors: u64, sector_size: u64) -> Result<Self> {
+        check_sector_size(sector_size)?;
+        let reserved = 2 + entries_sectors(sector_size);
+        if disk_sectors <= reserved * 2 {
+            return Err(invalid("disk too small for GPT"));
+        }
+        Ok(Self {
+            kind: TableKind::Gpt {
+                disk_guid: Guid::random()?,
+            },
+            sector_size,
+            first_usable: reserved,
+            last_usable: disk_sectors - reserved,
+            entries: Vec::new(),
+        })
+    }
+
+    pub fn new_mbr(disk_sectors: u64, sector_size: u64) -> Result<Self> {
+        check_sector_size(sector_size)?;
+        let mut disk_id = [0u8; 4];
+        fill_random(&mut disk_id)?;
+        Ok(Self {
+            kind: TableKind::Mbr {
+                disk_id: u32::from_le_bytes(disk_id),
+            },
+            sector_size,
+            first_usable: 1,
+            last_usable: disk_sectors.min(u32::MAX as u64).saturating_sub(1),
+            entries: Vec::new(),
+        })
+    }
+
+    #[inline]
+    pub fn alignment(&self) -> u64 {
+        (DEFAULT_ALIGNMENT / self.sector_size).max(1)
+    }
+
+    #[inline]
+    fn max_entries(&self) -> u32 {
+        match self.kind {
+            TableKind::Gpt { .. } => gpt::ENTRIES_COUNT as u32,
+            TableKind::Mbr { .. } => 4,
+        }
+    }
+
+    fn overlaps(&self, start: u64, end: u64, skip: Option<u32>) -> bool {
+        self.entries
+            .iter()
+            .filter(|e| Some(e.number) != skip && !e.is_extended())
+            .any(|e| start < e.end() && e.start < end)
+    }
+
+    fn free_start(&self, sectors: Option<u64>) -> Option<u64> {
+        let align = self.alignment();
+        let mut start = self.first_usable.next_multiple_of(align);
+        let mut used = self
+            .entries
+            .iter()
+            .filter(|e| !e.is_extended())
+            .map(|e| (e.start, e.end()))
+            .collect::<Vec<_>>();
+        used.sort_unstable();
+
+        let sectors = sectors.unwrap_or(1);
+        for (s, e) in used {
+            if start.checked_add(sectors)? <= s {
+                return Some(start);
+            }
+            start = start.max(e.checked_next_multiple_of(align)?);
+        }
+        // end - 1 so a last_usable of u64::MAX can not overflow
+        if start.checked_add(sectors)? - 1 <= self.last_usable {
+            Some(start)
+        } else {
+            None
+        }
+    }
+
+    pub fn add(&mut self, new: NewPartition) -> Result<u32> {
+        let number = (1..=self.max_entries())
+            .find(|n| self.get(*n).is_none())
+            .ok_or_else(|| invalid("partition table is full"))?;
+
+        let start = match new.start {
+            Some(start) => start,
+            None => self
+                .free_start(new.sectors)
+                .ok_or_else(|| invalid("no free space left"))?,
+        };
+        let sectors = match new.sectors {
+            Some(sectors) => sectors,
+            None => {
+                // let the partition run up to the next one or the end of the disk
+                let limit = self
+                    .entries
+                    .iter()
+                    .filter(|e| !e.is_extended() && e.start >= start)
+                    .map(|e| e.start)
+                    .min()
+                    .unwrap_or(self.last_usable.saturating_add(1));
+                limit.saturating_sub(start)
+            }
+        };
+        self.check_range(start, sectors, None)?;
+
+        let uuid = match self.kind {
+            TableKind::Gpt { .. } => Some(Guid::random()?),
+            TableKind::Mbr { .. } => None,
+        };
+        self.entries.push(PartitionEntry {
+            number,
+            start,
+            sectors,
+            part_type: new.part_type,
+            uuid,
+            name: new.name,
+            bootable: new.bootable,
+            attributes: 0,
+        });
+        self.entries.sort_by_key(|e| e.number);
+        Ok(number)
+    }
+
+    pub fn delete(&mut self, number: u32) -> Result<PartitionEntry> {
+        match self.entries.iter().position(|e| e.number == number) {
+            Some(idx) => Ok(self.entries.remove(idx)),
+            None => Err(invalid("no such partition")),
+        }
+    }
+
+    pub fn resize(&mut self, number: u32, sectors: u64) -> Result<()> {
+        let start = self
+            .get(number)
+            .ok_or_else(|| invalid("no such partition"))?
+            .start;
+        self.check_range(start, sectors, Some(number))?;
+        if let Some(e) = self.entries.iter_mut().find(|e| e.number == number) {
+            e.sectors = sectors;
+        }
+        Ok(())
+    }
+
+    fn check_range(&self, start: u64, sectors: u64, skip: Option<u32>) -> Result<()> {
+        if sectors == 0 {
+            return Err(invalid("empty partition"));
+        }
+        let end = start
+            .checked_add(sectors)
+            .ok_or_else(|| invalid("partition end overflows"))?;
+        if start < self.first_usable || end - 1 > self.last_usable {
+            return Err(invalid("partition outside of the usable area"));
+        }
+        if self.overlaps(start, end, skip) {
+            return Err(invalid("partition overlaps another one"));
+        }
+        Ok(())
+    }
+
+    // entries may have been edited by hand or restored from a dump, so they
+    // get the same checks add and resize apply before anything is written
+    fn check_entries(&self, first: u64, last: u64) -> Result<()> {
+        let mut ranges = Vec::with_capacity(self.entries.len());
+        for (i, e) in self.entries.iter().enumerate() {
+            if !(1..=self.max_entries()).contains(&e.number) {
+                return Err(invalid("invalid partition number"));
+            }
+            if self.entries[..i].iter().any(|o| o.number == e.number) {
+                return Err(invalid("duplicate partition number"));
+            }
+            if e.sectors == 0 {
+                return Err(invalid("empty partition"));
+            }
+            let end = e
+                .start
+                .checked_add(e.sectors)
+                .ok_or_else(|| invalid("partition end overflows"))?;
+            if e.start < first || end - 1 > last {
+                return Err(invalid("partition table does not fit the disk"));
+            }
+            if !e.is_extended() {
+                ranges.push((e.start, end));
+            }
+        }
+        ranges.sort_unstable();
+        if ranges.windows(2).any(|w| w[1].0 < w[0].1) {
+            return Err(invalid("partition overlaps another one"));
+        }
+        Ok(())
+    }
+
+    pub fn write(&self, f: &File) -> Result<()> {
+        let ss = self.sector_size;
+        let disk = disk_sectors(f, ss)?;
+        let reserved = 2 + entries_sectors(ss);
+        // tables restored from a dump may not know the exact disk geometry
+        let (first, last) = match self.kind {
+            TableKind::Gpt { .. } if disk > reserved * 2 => (
+                self.first_usable.max(reserved),
+                self.last_usable.min(disk - reserved),
+            ),
+            TableKind::Gpt { .. } => return Err(invalid("disk too small for GPT")),
+            TableKind::Mbr { .. } => (1, disk.min(u32::MAX as u64).saturating_sub(1)),
+        };
+        self.check_entries(first, last)?;
+
+        let mut boot = read_sector(f, 0, ss)?;
+        match self.kind {
+            TableKind::Gpt { disk_guid } => {
+                let entries = gpt::encode_entries(&self.entries)?;
+                let entries_crc = crc32(&entries);
+                let backup_entries = disk - 1 - entries_sectors(ss);
+
+                let mut primary = gpt::HeaderLayout {
+                    current: 1,
+                    backup: disk - 1,
+                    first_usable: first,
+                    last_usable: last,
+                    entries_lba: 2,
+                    entries_count: gpt::ENTRIES_COUNT as u32,
+                    entry_size: gpt::ENTRY_SIZE as u32,
+                };
+                let primary_header = gpt::encode_header(&primary, &disk_guid, entries_crc, ss);
+                std::mem::swap(&mut primary.current, &mut primary.backup);
+                primary.entries_lba = backup_entries;
+                let backup_header = gpt::encode_header(&primary, &disk_guid, entries_crc, ss);
+
+                boot[mbr::ENTRIES_OFFSET..].fill(0);
+                mbr::encode_entry(
+                    &mut boot[mbr::ENTRIES_OFFSET..mbr::ENTRIES_OFFSET + mbr::ENTRY_SIZE],
+                    false,
+                    mbr::GPT_PROTECTIVE,
+                    1,
+                    (disk - 1).min(u32::MAX as u64),
+                );
+                boot[mbr::SIGNATURE_OFFSET] = 0x55;
+                boot[mbr::SIGNATURE_OFFSET + 1] = 0xaa;
+
+                // backup first so a crash in between leaves one consistent copy
+                f.write_all_at(&entries, backup_entries * ss)?;
+                f.write_all_at(&backup_header, (disk - 1) * ss)?;
+                f.sync_data()?;
+                f.write_all_at(&entries, 2 * ss)?;
+                f.write_all_at(&primary_header, ss)?;
+                f.write_all_at(&boot, 0)?;
+            }
+            TableKind::Mbr { disk_id } => {
+                mbr::encode(&mut boot, disk_id, &self.entries)?;
+                // wipe a stale GPT header so it can not shadow the new table
+                if gpt::Header::parse(&read_sector(f, 1, ss)?).is_some() {
+                    f.write_all_at(&vec![0u8; ss as usize], ss)?;
+                    f.write_all_at(&vec![0u8; ss as usize], (disk - 1) * ss)?;
+                }
+                f.write_all_at(&boot, 0)?;
+            }
+        }
+        f.sync_all()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parttable::test_image;
+
+    const SS: u64 = 512;
+    // 16MiB
+    const DISK: u64 = 32768;
+
+    fn linux() -> PartitionType {
+        PartitionType::Gpt(Guid::from_bytes([0x11; 16]))
+    }
+
+    #[test]
+    fn gpt_round_trip() {
+        let mut table = PartitionTable::new_gpt(DISK, SS).unwrap();
+        assert_eq!(table.first_usable, 34);
+        assert_eq!(table.last_usable, DISK - 34);
+
+        let esp = table
+            .add(
+                NewPartition::new(PartitionType::Gpt(Guid::ESP))
+                    .sectors(2048)
+                    .name("EFI"),
+            )
+            .unwrap();
+        let root = table.add(NewPartition::new(linux()).name("root")).unwrap();
+        assert_eq!((esp, root), (1, 2));
+        // aligned to 1MiB, the second runs up to the end of the usable area
+        assert_eq!(table.get(1).unwrap().start, 2048);
+        assert_eq!(table.get(2).unwrap().start, 4096);
+        assert_eq!(table.get(2).unwrap().end(), DISK - 33);
+
+        let f = test_image("writer-gpt", &vec![0u8; (DISK * SS) as usize]);
+        table.write(&f).unwrap();
+        let read = PartitionTable::read(&f, SS).unwrap().unwrap();
+        assert!(read.is_gpt());
+        assert_eq!(read.kind, table.kind);
+        assert_eq!(read.entries, table.entries);
+        assert_eq!(read.get(1).unwrap().name.as_deref(), Some("EFI"));
+    }
+
+    #[test]
+    fn mbr_round_trip() {
+        let mut table = PartitionTable::new_mbr(DISK, SS).unwrap();
+        table
+            .add(
+                NewPartition::new(PartitionType::Mbr(0x83))
+                    .sectors(4096)
+                    .bootable(true),
+            )
+            .unwrap();
+
+        let f = test_image("writer-mbr", &vec![0u8; (DISK * SS) as usize]);
+        table.write(&f).unwrap();
+        let read = PartitionTable::read(&f, SS).unwrap().unwrap();
+        assert!(!read.is_gpt());
+        assert_eq!(read.entries.len(), 1);
+        let e = read.get(1).unwrap();
+        assert_eq!((e.start, e.sectors, e.bootable), (2048, 4096, true));
+    }
+
+    #[test]
+    fn rejects_overflowing_ranges() {
+        let mut table = PartitionTable::new_gpt(DISK, SS).unwrap();
+        let err = table
+            .add(NewPartition::new(linux()).start(u64::MAX - 10).sectors(100))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = table
+            .add(NewPartition::new(linux()).start(2048).sectors(u64::MAX))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        table.add(NewPartition::new(linux()).sectors(2048)).unwrap();
+        assert_eq!(
+            table.resize(1, u64::MAX).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        assert_eq!(table.get(1).unwrap().sectors, 2048);
+
+        // a hand made table covering the whole u64 range
+        table.last_usable = u64::MAX;
+        assert!(table
+            .add(NewPartition::new(linux()).start(u64::MAX - 2).sectors(2))
+            .is_ok());
+        assert!(table.free_start(Some(u64::MAX)).is_none());
+    }
+
+    #[test]
+    fn rejects_overlaps() {
+        let mut table = PartitionTable::new_gpt(DISK, SS).unwrap();
+        table
+            .add(NewPartition::new(linux()).start(2048).sectors(2048))
+            .unwrap();
+        let err = table
+            .add(NewPartition::new(linux()).start(4095).sectors(10))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        table
+            .add(NewPartition::new(linux()).start(4096).sectors(10))
+            .unwrap();
+        assert!(table.resize(1, 2049).is_err());
+        assert!(table.resize(1, 2048).is_ok());
+    }
+
+    #[test]
+    fn write_validates_entries() {
+        let f = test_image("writer-invalid", &vec![0u8; (DISK * SS) as usize]);
+        let mut table = PartitionTable::new_gpt(DISK, SS).unwrap();
+        table
+            .add(NewPartition::new(linux()).start(2048).sectors(2048))
+            .unwrap();
+        table
+            .add(NewPartition::new(linux()).start(4096).sectors(2048))
+            .unwrap();
+
+        let mut overlapping = table.clone();
+        overlapping.entries[1].start = 3000;
+        let mut duplicate = table.clone();
+        duplicate.entries[1].number = 1;
+        let mut empty = table.clone();
+        empty.entries[1].sectors = 0;
+        let mut overflowing = table.clone();
+        overflowing.entries[1].sectors = u64::MAX;
+        let mut numbered = table.clone();
+        numbered.entries[1].number = 0;
+
+        for bad in [overlapping, duplicate, empty, overflowing, numbered] {
+            let err = bad.write(&f).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        // nothing reached the disk
+        assert!(PartitionTable::read(&f, SS).unwrap().is_none());
+
+        table.write(&f).unwrap();
+        assert_eq!(
+            PartitionTable::read(&f, SS).unwrap().unwrap().entries.len(),
+            2
+        );
+    }
+
+    #[test]
+    fn encode_entries_rejects_duplicates() {
+        let mut table = PartitionTable::new_gpt(DISK, SS).unwrap();
+        table.add(NewPartition::new(linux()).sectors(2048)).unwrap();
+        let mut entries = table.entries.clone();
+        entries.push(entries[0].clone());
+        assert!(gpt::encode_entries(&entries).is_err());
+    }
+}
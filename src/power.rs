@@ -0,0 +1,139 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd};
+
+use crate::{
+    ioctl::{self, HDIO_DRIVE_CMD},
+    Error, Result,
+};
+
+const NVME_IOCTL_ADMIN_CMD: u32 = ioctl::iowr::<nvme_admin_cmd>(b'N' as _, 0x41);
+
+const ATA_OP_CHECKPOWERMODE1: u8 = 0xe5;
+const ATA_OP_CHECKPOWERMODE2: u8 = 0x98;
+const ATA_OP_STANDBYNOW1: u8 = 0xe0;
+const ATA_OP_STANDBYNOW2: u8 = 0x94;
+
+const NVME_ADMIN_GET_FEATURES: u8 = 0x0a;
+const NVME_FEAT_POWER_MGMT: u32 = 0x02;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PowerState {
+    Active,
+    Idle,
+    Standby,
+    Sleep,
+    Nvme(u8),
+    Unknown(u8),
+}
+
+impl PowerState {
+    #[inline]
+    pub fn is_spinning(&self) -> bool {
+        !matches!(self, Self::Standby | Self::Sleep)
+    }
+}
+
+impl std::fmt::Display for PowerState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Active => write!(f, "active/idle"),
+            Self::Idle => write!(f, "idle"),
+            Self::Standby => write!(f, "standby"),
+            Self::Sleep => write!(f, "sleeping"),
+            Self::Nvme(ps) => write!(f, "ps{}", ps),
+            Self::Unknown(x) => write!(f, "unknown ({:#04x})", x),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub(crate) struct nvme_admin_cmd {
+    pub opcode: u8,
+    pub flags: u8,
+    pub rsvd1: u16,
+    pub nsid: u32,
+    pub cdw2: u32,
+    pub cdw3: u32,
+    pub metadata: u64,
+    pub addr: u64,
+    pub metadata_len: u32,
+    pub data_len: u32,
+    pub cdw10: u32,
+    pub cdw11: u32,
+    pub cdw12: u32,
+    pub cdw13: u32,
+    pub cdw14: u32,
+    pub cdw15: u32,
+    pub timeout_ms: u32,
+    pub result: u32,
+}
+
+fn drive_cmd(f: &File, args: &mut [u8; 4]) -> Result<()> {
+    ioctl::allowed("HDIO_DRIVE_CMD")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), HDIO_DRIVE_CMD as _, args.as_mut_ptr()) };
+    if ret < 0 {
+        Err(Error::ioctl("HDIO_DRIVE_CMD", io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn ata_power_state(f: &File) -> Result<PowerState> {
+    let mut args = [ATA_OP_CHECKPOWERMODE1, 0, 0, 0];
+    if let Err(err) = drive_cmd(f, &mut args) {
+        if err.raw_os_error() != Some(libc::EIO) {
+            return Err(err);
+        }
+        args = [ATA_OP_CHECKPOWERMODE2, 0, 0, 0];
+        if let Err(err) = drive_cmd(f, &mut args) {
+            if err.raw_os_error() == Some(libc::EIO) && args[0] == 0 && args[1] == 0 {
+                return Ok(PowerState::Sleep);
+            }
+            return Err(err);
+        }
+    }
+
+    Ok(match args[2] {
+        0x00 => PowerState::Standby,
+        0x40 | 0x41 | 0x80 => PowerState::Idle,
+        0xff => PowerState::Active,
+        x => PowerState::Unknown(x),
+    })
+}
+
+pub(crate) fn ata_standby_now(f: &File) -> Result<()> {
+    let mut args = [ATA_OP_STANDBYNOW1, 0, 0, 0];
+    match drive_cmd(f, &mut args) {
+        Err(err) if err.raw_os_error() == Some(libc::EIO) => {
+            args = [ATA_OP_STANDBYNOW2, 0, 0, 0];
+            drive_cmd(f, &mut args)
+        }
+        res => res,
+    }
+}
+
+pub(crate) fn nvme_admin(f: &File, cmd: &mut nvme_admin_cmd) -> Result<()> {
+    ioctl::allowed("NVME_IOCTL_ADMIN_CMD")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), NVME_IOCTL_ADMIN_CMD as _, cmd) };
+    if ret < 0 {
+        Err(Error::ioctl(
+            "NVME_IOCTL_ADMIN_CMD",
+            io::Error::last_os_error(),
+        ))
+    } else if ret > 0 {
+        Err(io::Error::other(format!("nvme status {:#x}", ret)).into())
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn nvme_power_state(f: &File) -> Result<PowerState> {
+    let mut cmd = nvme_admin_cmd {
+        opcode: NVME_ADMIN_GET_FEATURES,
+        cdw10: NVME_FEAT_POWER_MGMT,
+        ..Default::default()
+    };
+    nvme_admin(f, &mut cmd)?;
+    Ok(PowerState::Nvme((cmd.result & 0x1f) as u8))
+}
@@ -0,0 +1,142 @@
+use std::{io, path::Path};
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Privileges {
+    pub euid: u32,
+    pub effective: u64,
+    pub disk_group: bool,
+}
+
+fn effective_caps(procfs: &Path) -> Result<u64> {
+    let p = procfs.join("self").join("status");
+    let status = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|caps| u64::from_str_radix(caps.trim(), 16).ok())
+        .ok_or_else(|| Error::parse(p))
+}
+
+// through NSS, so groups that only live in sssd or systemd-userdb count too
+fn disk_gid() -> io::Result<Option<u32>> {
+    let mut buf = vec![0 as libc::c_char; 1024];
+    loop {
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut res = std::ptr::null_mut();
+        let ret = unsafe {
+            libc::getgrnam_r(
+                c"disk".as_ptr(),
+                &mut group,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut res,
+            )
+        };
+        match ret {
+            0 if res.is_null() => return Ok(None),
+            0 => return Ok(Some(group.gr_gid)),
+            // some NSS modules report a missing group as an error
+            libc::ENOENT | libc::ESRCH => return Ok(None),
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            err => return Err(io::Error::from_raw_os_error(err)),
+        }
+    }
+}
+
+fn in_group(gid: u32) -> io::Result<bool> {
+    if unsafe { libc::getegid() } == gid {
+        return Ok(true);
+    }
+    let len = unsafe { libc::getgroups(0, std::ptr::null_mut()) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut groups = vec![0 as libc::gid_t; len as usize];
+    let len = unsafe { libc::getgroups(len, groups.as_mut_ptr()) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(groups[..len as usize].contains(&gid))
+}
+
+impl Privileges {
+    // only the capabilities the crate's operations depend on
+    pub const CAP_DAC_OVERRIDE: u32 = 1;
+    pub const CAP_DAC_READ_SEARCH: u32 = 2;
+    pub const CAP_SYS_RAWIO: u32 = 17;
+    pub const CAP_SYS_ADMIN: u32 = 21;
+
+    pub(crate) fn current(procfs: &Path) -> Result<Self> {
+        let disk_group = match disk_gid()? {
+            Some(gid) => in_group(gid)?,
+            None => false,
+        };
+        Ok(Self {
+            euid: unsafe { libc::geteuid() },
+            effective: effective_caps(procfs)?,
+            disk_group,
+        })
+    }
+
+    #[inline]
+    pub fn is_root(&self) -> bool {
+        self.euid == 0
+    }
+
+    #[inline]
+    pub fn has_capability(&self, cap: u32) -> bool {
+        cap < 64 && self.effective & (1 << cap) != 0
+    }
+
+    // device nodes are root:disk 0660, so this is plain file access
+    pub fn can_read_devices(&self) -> bool {
+        self.disk_group
+            || self.has_capability(Self::CAP_DAC_OVERRIDE)
+            || self.has_capability(Self::CAP_DAC_READ_SEARCH)
+    }
+
+    #[inline]
+    pub fn can_write_devices(&self) -> bool {
+        self.disk_group || self.has_capability(Self::CAP_DAC_OVERRIDE)
+    }
+
+    // block ioctls act on a descriptor opened for writing
+    #[inline]
+    pub fn can_ioctl(&self) -> bool {
+        self.can_write_devices()
+    }
+
+    // BLKRRPART, BLKPG and friends also check CAP_SYS_ADMIN
+    #[inline]
+    pub fn can_reread_partitions(&self) -> bool {
+        self.can_ioctl() && self.has_capability(Self::CAP_SYS_ADMIN)
+    }
+
+    // SMART status is read with HDIO_DRIVE_TASK, which libata refuses
+    // without both CAP_SYS_ADMIN and CAP_SYS_RAWIO
+    #[inline]
+    pub fn can_query_smart(&self) -> bool {
+        self.can_read_devices()
+            && self.has_capability(Self::CAP_SYS_ADMIN)
+            && self.has_capability(Self::CAP_SYS_RAWIO)
+    }
+
+    #[inline]
+    pub fn can_send_raw_commands(&self) -> bool {
+        self.can_read_devices() && self.has_capability(Self::CAP_SYS_RAWIO)
+    }
+
+    #[inline]
+    pub fn can_mount(&self) -> bool {
+        self.has_capability(Self::CAP_SYS_ADMIN)
+    }
+
+    // sysfs attributes are root owned, nothing but DAC protects them
+    #[inline]
+    pub fn can_write_sysfs(&self) -> bool {
+        self.is_root() || self.has_capability(Self::CAP_DAC_OVERRIDE)
+    }
+}
@@ -1,16 +1,21 @@
 use std::{
-    cell::{Ref, RefCell},
     collections::{BTreeMap, BTreeSet},
     fs::File,
     io::{self, BufRead, BufReader},
-    ops::Deref,
     path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
 };
 
 use indexmap::IndexSet;
 
+use crate::{parse, Error, Result};
+
+// names are shared with callers, a refresh swaps in a new table instead of
+// touching the one they hold on to
+#[derive(Default)]
 struct DevicesCache {
-    names: IndexSet<String>,
+    names: IndexSet<Arc<str>>,
     by_id: BTreeMap<u32, usize>,
     by_name: BTreeMap<usize, BTreeSet<u32>>,
 }
@@ -26,14 +31,19 @@ impl DevicesCache {
     }
 
     pub fn insert(&mut self, id: u32, name: String) {
-        let idx = self.names.insert_full(name).0;
-        self.by_id.insert(id, idx);
+        let idx = self.names.insert_full(name.into()).0;
+        if let Some(old) = self.by_id.insert(id, idx) {
+            if let Some(ids) = self.by_name.get_mut(&old) {
+                ids.remove(&id);
+            }
+        }
+        self.by_name.entry(idx).or_default().insert(id);
     }
 
-    pub fn get_by_id(&self, id: u32) -> Option<&str> {
+    pub fn get_by_id(&self, id: u32) -> Option<Arc<str>> {
         self.by_id
             .get(&id)
-            .and_then(|idx| self.names.get_index(*idx).map(|x| x.as_str()))
+            .and_then(|idx| self.names.get_index(*idx).cloned())
     }
 
     pub fn get_by_name<S: AsRef<str>>(&self, name: S) -> Option<&BTreeSet<u32>> {
@@ -41,11 +51,6 @@ impl DevicesCache {
             .get_index_of(name.as_ref())
             .and_then(|idx| self.by_name.get(&idx))
     }
-
-    #[inline]
-    pub fn iter<'a>(&'a self) -> DevicesCacheIter<'a> {
-        DevicesCacheIter::new(&self)
-    }
 }
 
 impl FromIterator<(u32, String)> for DevicesCache {
@@ -78,96 +83,141 @@ impl<'a> FromIterator<(u32, &'a str)> for DevicesCache {
     }
 }
 
-struct DevicesCacheIter<'a> {
-    names: &'a IndexSet<String>,
-    inner: std::collections::btree_map::Iter<'a, u32, usize>,
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeviceClass {
+    Char,
+    Block,
 }
 
-impl<'a> DevicesCacheIter<'a> {
-    #[inline]
-    pub fn new(cache: &'a DevicesCache) -> Self {
-        Self {
-            names: &cache.names,
-            inner: cache.by_id.iter(),
-        }
-    }
+#[derive(Default)]
+struct DevicesTable {
+    block: DevicesCache,
+    char: DevicesCache,
+    loaded_at: Option<Instant>,
 }
 
-impl<'a> Iterator for DevicesCacheIter<'a> {
-    type Item = (u32, &'a str);
+impl DevicesTable {
+    #[inline]
+    fn age(&self) -> Option<Duration> {
+        self.loaded_at.map(|t| t.elapsed())
+    }
 
     #[inline]
-    fn next(&mut self) -> Option<Self::Item> {
-        let (&id, &idx) = self.inner.next()?;
-        self.names.get_index(idx).map(|n| (id, n.as_str()))
+    fn section(&self, class: DeviceClass) -> &DevicesCache {
+        match class {
+            DeviceClass::Block => &self.block,
+            DeviceClass::Char => &self.char,
+        }
     }
 }
 
-fn skip_proc_devices_header(x: &io::Result<String>) -> bool {
-    match x {
-        Ok(s) => !s.starts_with("Block devices:"),
-        Err(_) => false,
+impl FromIterator<(DeviceClass, u32, String)> for DevicesTable {
+    fn from_iter<T: IntoIterator<Item = (DeviceClass, u32, String)>>(iter: T) -> Self {
+        let mut acc = DevicesTable::default();
+        for (class, id, name) in iter {
+            match class {
+                DeviceClass::Block => acc.block.insert(id, name),
+                DeviceClass::Char => acc.char.insert(id, name),
+            }
+        }
+        acc
     }
 }
 
 pub struct DeviceIterator {
-    inner: Box<dyn Iterator<Item = io::Result<String>>>,
+    path: PathBuf,
+    inner: Box<dyn Iterator<Item = (usize, io::Result<String>)>>,
+    class: Option<DeviceClass>,
 }
 
 impl DeviceIterator {
     #[inline]
-    pub fn new<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
+        let path = p.as_ref().join("devices");
+        let f = File::open(&path).map_err(|err| Error::path(&path, err))?;
         Ok(Self {
-            inner: Box::new(
-                BufReader::new(File::open(p.as_ref().join("devices"))?)
-                    .lines()
-                    .skip_while(skip_proc_devices_header)
-                    .skip(1),
-            ),
+            path,
+            inner: Box::new(BufReader::new(f).lines().enumerate()),
+            class: None,
         })
     }
 
-    fn remap(x: io::Result<String>) -> io::Result<(u32, String)> {
+    fn remap(
+        &self,
+        class: DeviceClass,
+        (lineno, x): (usize, io::Result<String>),
+    ) -> Result<(DeviceClass, u32, String)> {
+        let err = || Error::ParseError {
+            file: self.path.clone(),
+            line: Some(lineno + 1),
+        };
         match x {
             Ok(line) => {
-                let mut it = line.trim().split_whitespace();
-                let id: u32 = it
-                    .next()
-                    .map_or_else(
-                        || Err(Into::<io::Error>::into(io::ErrorKind::InvalidInput)),
-                        |x| Ok(x),
-                    )
-                    .and_then(|x| {
-                        x.parse::<u32>()
-                            .map_err(|_| Into::<io::Error>::into(io::ErrorKind::InvalidInput))
-                    })?;
-                let name = it.next().map_or_else(
-                    || Err(Into::<io::Error>::into(io::ErrorKind::InvalidInput)),
-                    |x| Ok(x.trim()),
-                )?;
-                if !matches!(it.next(), None) {
-                    Err(io::ErrorKind::InvalidInput.into())
-                } else {
-                    Ok((id, name.to_lowercase()))
-                }
+                let (id, name) = parse::devices::entry(&line).ok_or_else(err)?;
+                Ok((class, id, name))
             }
-            Err(err) => Err(err),
+            Err(e) => Err(Error::path(&self.path, e)),
         }
     }
 }
 
 impl Iterator for DeviceIterator {
-    type Item = io::Result<(u32, String)>;
+    type Item = Result<(DeviceClass, u32, String)>;
 
-    #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        Some(Self::remap(self.inner.next()?))
+        loop {
+            let (lineno, line) = self.inner.next()?;
+            match line {
+                Ok(ref s) if s.trim().is_empty() => continue,
+                Ok(ref s) => {
+                    if let Some(class) = parse::devices::section(s) {
+                        self.class = Some(class);
+                        continue;
+                    }
+                }
+                _ => (),
+            }
+
+            return match self.class {
+                Some(class) => Some(self.remap(class, (lineno, line))),
+                None => Some(Err(Error::ParseError {
+                    file: self.path.clone(),
+                    line: Some(lineno + 1),
+                })),
+            };
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RefreshPolicy {
+    #[default]
+    OnMiss,
+    Ttl(Duration),
+    Manual,
+}
+
+impl RefreshPolicy {
+    fn is_stale(&self, age: Option<Duration>) -> bool {
+        match (self, age) {
+            (_, None) => true,
+            (Self::Ttl(ttl), Some(age)) => age >= *ttl,
+            _ => false,
+        }
+    }
+
+    fn refresh_on_miss(&self, age: Option<Duration>) -> bool {
+        match self {
+            Self::OnMiss => true,
+            Self::Ttl(_) | Self::Manual => self.is_stale(age),
+        }
     }
 }
 
 pub struct Devices {
     proc_path: PathBuf,
-    cache: RefCell<DevicesCache>,
+    cache: RwLock<Arc<DevicesTable>>,
+    policy: RwLock<RefreshPolicy>,
 }
 
 impl Devices {
@@ -175,87 +225,281 @@ impl Devices {
     pub fn new<P: AsRef<Path>>(p: P) -> Self {
         Self {
             proc_path: p.as_ref().to_path_buf(),
-            cache: RefCell::new(DevicesCache::new()),
+            cache: RwLock::new(Arc::new(DevicesTable::default())),
+            policy: RwLock::new(RefreshPolicy::default()),
         }
     }
 
-    fn _get_by_id<'a>(&'a self, id: u32) -> Option<Ref<'a, str>> {
-        let cache = self.cache.borrow();
-        cache
-            .get_by_id(id)
-            .map(|s| s as *const _)
-            .map(|ptr| Ref::map(cache, |_| unsafe { &*ptr }))
+    #[inline]
+    pub fn refresh_policy(&self) -> RefreshPolicy {
+        *self.policy.read().unwrap()
     }
 
-    fn _get_by_name<'a, S: AsRef<str>>(&'a self, name: S) -> Option<Ref<'a, BTreeSet<u32>>> {
-        let cache = self.cache.borrow();
-        cache
-            .get_by_name(name)
-            .map(|s| s as *const _)
-            .map(|p| Ref::map(cache, |_| unsafe { &*p }))
+    #[inline]
+    pub fn set_refresh_policy(&self, policy: RefreshPolicy) {
+        *self.policy.write().unwrap() = policy;
     }
 
-    pub fn get_by_id<'a>(&'a self, id: u32) -> io::Result<Option<Ref<'a, str>>> {
+    fn refresh_if_stale(&self) -> Result<()> {
+        if self.refresh_policy().is_stale(self.cache().age()) {
+            self.refresh()?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn refresh_on_miss(&self) -> Result<bool> {
+        if self.refresh_policy().refresh_on_miss(self.cache().age()) {
+            self.refresh()?;
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    #[inline]
+    fn cache(&self) -> Arc<DevicesTable> {
+        Arc::clone(&self.cache.read().unwrap())
+    }
+
+    #[inline]
+    fn _get_by_id(&self, class: DeviceClass, id: u32) -> Option<Arc<str>> {
+        self.cache().section(class).get_by_id(id)
+    }
+
+    #[inline]
+    fn _get_by_name<S: AsRef<str>>(&self, class: DeviceClass, name: S) -> Option<BTreeSet<u32>> {
+        self.cache().section(class).get_by_name(name).cloned()
+    }
+
+    pub fn get_class_by_id(&self, class: DeviceClass, id: u32) -> Result<Option<Arc<str>>> {
+        self.refresh_if_stale()?;
         {
-            if let Some(v) = self._get_by_id(id) {
+            if let Some(v) = self._get_by_id(class, id) {
                 return Ok(Some(v));
             }
         }
 
-        self.refresh()?;
+        if !self.refresh_on_miss()? {
+            return Ok(None);
+        }
 
-        Ok(self._get_by_id(id))
+        Ok(self._get_by_id(class, id))
     }
 
-    pub fn get_by_name<'a, S: AsRef<str>>(
-        &'a self,
+    pub fn get_class_by_name<S: AsRef<str>>(
+        &self,
+        class: DeviceClass,
         name: S,
-    ) -> io::Result<Option<Ref<'a, BTreeSet<u32>>>> {
+    ) -> Result<Option<BTreeSet<u32>>> {
+        self.refresh_if_stale()?;
         {
-            if let Some(v) = self._get_by_name(name.as_ref()) {
+            if let Some(v) = self._get_by_name(class, name.as_ref()) {
                 return Ok(Some(v));
             }
         }
 
-        self.refresh()?;
+        if !self.refresh_on_miss()? {
+            return Ok(None);
+        }
+
+        Ok(self._get_by_name(class, name))
+    }
+
+    #[inline]
+    pub fn get_by_id(&self, id: u32) -> Result<Option<Arc<str>>> {
+        self.get_block_by_id(id)
+    }
 
-        Ok(self._get_by_name(name))
+    #[inline]
+    pub fn get_by_name<S: AsRef<str>>(&self, name: S) -> Result<Option<BTreeSet<u32>>> {
+        self.get_class_by_name(DeviceClass::Block, name)
+    }
+
+    pub fn majors_for<S: AsRef<str>>(&self, name: S) -> Result<Vec<u32>> {
+        Ok(self
+            .get_by_name(name.as_ref().to_lowercase())?
+            .map(|ids| ids.into_iter().collect())
+            .unwrap_or_default())
+    }
+
+    #[inline]
+    pub fn is_major<S: AsRef<str>>(&self, major: u32, name: S) -> Result<bool> {
+        Ok(self
+            .get_by_id(major)?
+            .is_some_and(|n| n.eq_ignore_ascii_case(name.as_ref())))
+    }
+
+    #[inline]
+    pub fn get_block_by_id(&self, id: u32) -> Result<Option<Arc<str>>> {
+        self.get_class_by_id(DeviceClass::Block, id)
+    }
+
+    #[inline]
+    pub fn get_char_by_id(&self, id: u32) -> Result<Option<Arc<str>>> {
+        self.get_class_by_id(DeviceClass::Char, id)
     }
 
     #[inline]
     pub fn iter(&self) -> DevicesIter {
-        DevicesIter::new(self.cache.borrow())
+        DevicesIter::new(self.cache(), DeviceClass::Block)
     }
 
-    pub fn refresh(&self) -> io::Result<()> {
-        self.cache
-            .replace(DeviceIterator::new(&self.proc_path)?.collect::<io::Result<DevicesCache>>()?);
+    #[inline]
+    pub fn iter_char(&self) -> DevicesIter {
+        DevicesIter::new(self.cache(), DeviceClass::Char)
+    }
+
+    pub fn snapshot_class(&self, class: DeviceClass) -> Result<BTreeMap<u32, String>> {
+        self.refresh_if_stale()?;
+        Ok(DevicesIter::new(self.cache(), class).owned().collect())
+    }
+
+    #[inline]
+    pub fn snapshot(&self) -> Result<BTreeMap<u32, String>> {
+        self.snapshot_class(DeviceClass::Block)
+    }
+
+    pub fn refresh(&self) -> Result<()> {
+        let mut cache = DeviceIterator::new(&self.proc_path)?.collect::<Result<DevicesTable>>()?;
+        cache.loaded_at = Some(Instant::now());
+        *self.cache.write().unwrap() = Arc::new(cache);
         Ok(())
     }
 }
 
-pub struct DevicesIter<'a> {
-    holder: Ref<'a, DevicesCache>,
-    inner: DevicesCacheIter<'a>,
+pub struct DevicesIter {
+    holder: Arc<DevicesTable>,
+    class: DeviceClass,
+    ids: std::vec::IntoIter<u32>,
 }
 
-impl<'a> DevicesIter<'a> {
+impl DevicesIter {
+    #[inline]
+    fn new(holder: Arc<DevicesTable>, class: DeviceClass) -> Self {
+        let ids = holder
+            .section(class)
+            .by_id
+            .keys()
+            .copied()
+            .collect::<Vec<_>>()
+            .into_iter();
+        Self { holder, class, ids }
+    }
+
     #[inline]
-    fn new(holder: Ref<'a, DevicesCache>) -> Self {
-        let inner = unsafe { &*(Ref::deref(&holder) as *const DevicesCache) }.iter();
-        Self { holder, inner }
+    pub fn owned(self) -> OwnedDevicesIter {
+        self.map(|(id, name)| (id, name.to_string()))
     }
 }
 
-impl<'a> Iterator for DevicesIter<'a> {
-    type Item = (u32, Ref<'a, str>);
+pub type OwnedDevicesIter = std::iter::Map<DevicesIter, fn((u32, Arc<str>)) -> (u32, String)>;
+
+impl Iterator for DevicesIter {
+    type Item = (u32, Arc<str>);
 
     #[inline]
     fn next(&mut self) -> Option<Self::Item> {
-        let (id, name) = self.inner.next()?;
-        let name = Ref::map(Ref::clone(&self.holder), |_| unsafe {
-            &*(name as *const _)
-        });
+        let id = self.ids.next()?;
+        let name = self.holder.section(self.class).get_by_id(id)?;
         Some((id, name))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "Character devices:
+  1 mem
+  4 tty
+ 10 misc
+253 tpm
+
+Block devices:
+  7 loop
+  8 sd
+ 65 sd
+253 device-mapper
+259 blkext
+";
+
+    fn devices(name: &str, content: &str) -> Devices {
+        let dir = std::env::temp_dir().join(format!(
+            "linux-blocks-devices-{}-{}",
+            name,
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("devices"), content).unwrap();
+        let devices = Devices::new(&dir);
+        // the file is gone after this, misses must not reload
+        devices.set_refresh_policy(RefreshPolicy::Manual);
+        devices.refresh().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        devices
+    }
+
+    #[test]
+    fn lookups() {
+        let devices = devices("lookups", SAMPLE);
+        assert_eq!(
+            devices.get_by_name("device-mapper").unwrap(),
+            Some(BTreeSet::from([253]))
+        );
+        assert_eq!(devices.majors_for("SD").unwrap(), vec![8, 65]);
+        assert_eq!(devices.majors_for("nbd").unwrap(), Vec::<u32>::new());
+        assert!(devices.is_major(7, "loop").unwrap());
+        assert!(devices.is_major(259, "BLKEXT").unwrap());
+        assert!(!devices.is_major(8, "loop").unwrap());
+    }
+
+    #[test]
+    fn classes_do_not_clash() {
+        let devices = devices("classes", SAMPLE);
+        assert_eq!(
+            devices.get_block_by_id(253).unwrap().as_deref(),
+            Some("device-mapper")
+        );
+        assert_eq!(devices.get_char_by_id(253).unwrap().as_deref(), Some("tpm"));
+        assert!(!devices.is_major(253, "tpm").unwrap());
+        assert_eq!(devices.get_by_name("tpm").unwrap(), None);
+        assert_eq!(
+            devices.get_class_by_name(DeviceClass::Char, "tpm").unwrap(),
+            Some(BTreeSet::from([253]))
+        );
+        assert_eq!(
+            devices
+                .snapshot_class(DeviceClass::Char)
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            [&1, &4, &10, &253]
+        );
+    }
+
+    #[test]
+    fn reinsert_moves_major() {
+        let mut cache = DevicesCache::new();
+        cache.insert(8, "sd".to_string());
+        cache.insert(65, "sd".to_string());
+        cache.insert(8, "other".to_string());
+        assert_eq!(cache.get_by_name("sd"), Some(&BTreeSet::from([65])));
+        assert_eq!(cache.get_by_id(8).as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn malformed_line() {
+        let dir =
+            std::env::temp_dir().join(format!("linux-blocks-devices-bad-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("devices"), "Block devices:\n  7 loop\nbogus\n").unwrap();
+        let err = Devices::new(&dir).refresh().unwrap_err();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(
+            matches!(err, Error::ParseError { line: Some(3), .. }),
+            "{:?}",
+            err
+        );
+    }
+}
@@ -1,17 +1,25 @@
 mod devices;
 mod mountinfo;
+mod openers;
+mod options;
 
 use std::{
-    ffi::CString,
+    ffi::{CStr, CString},
     io,
-    os::unix::prelude::{MetadataExt, OsStrExt},
+    os::unix::prelude::{AsRawFd, MetadataExt, OsStrExt, RawFd},
     path::{Path, PathBuf},
 };
 
 pub use devices::*;
 pub use mountinfo::*;
+pub use openers::{OpenKind, Opener};
+pub use options::*;
 
-use crate::Devno;
+use crate::{
+    magic,
+    parse::{self, diskstats::DiskStat, mdstat::MdStat, swaps::SwapEntry, ParseLineError},
+    Devno, Error, Result,
+};
 
 pub struct ProcFs {
     path: PathBuf,
@@ -21,17 +29,28 @@ pub struct ProcFs {
 
 impl ProcFs {
     #[inline]
-    pub fn new() -> io::Result<Self> {
+    pub fn new() -> Result<Self> {
         Self::remap(Self::probe()?)
     }
 
     #[inline]
-    pub fn with_path<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Result<Self> {
         Self::remap(Self::probe_by_path(p)?)
     }
 
     #[inline]
-    pub fn with_paths<P: AsRef<Path>, I: IntoIterator<Item = P>>(it: I) -> io::Result<Self> {
+    pub fn with_root<P: AsRef<Path>>(p: P) -> Result<Self> {
+        Self::remap(Some(p.as_ref().to_path_buf()))
+    }
+
+    pub fn for_pid(pid: u32) -> Result<Self> {
+        let mut procfs = Self::new()?;
+        procfs.mounts = MountInfos::from_procfs_pid(&procfs.path, pid)?;
+        Ok(procfs)
+    }
+
+    #[inline]
+    pub fn with_paths<P: AsRef<Path>, I: IntoIterator<Item = P>>(it: I) -> Result<Self> {
         Self::remap(Self::probe_by_paths(it)?)
     }
 
@@ -45,65 +64,107 @@ impl ProcFs {
         &self.mounts
     }
 
-    pub fn mountinfo_from_path<P: AsRef<Path>>(&self, p: P) -> io::Result<MountInfo> {
+    pub fn mountinfo_from_path<P: AsRef<Path>>(&self, p: P) -> Result<MountInfo> {
         let dev: Devno = {
-            let md = p.as_ref().metadata()?;
+            let md = p
+                .as_ref()
+                .metadata()
+                .map_err(|err| Error::path(p.as_ref(), err))?;
             md.dev().into()
         };
 
-        let mount_id = {
-            let path =
-                unsafe { CString::from_vec_unchecked(p.as_ref().as_os_str().as_bytes().to_vec()) };
-            let mut handle = file_handle {
-                handle_bytes: 0,
-                handle_type: 0,
-                f_handle: [],
-            };
-            let mut mount_id = 0;
-            let ret = unsafe {
-                name_to_handle_at(
-                    libc::AT_FDCWD,
-                    path.as_ptr(),
-                    &mut handle,
-                    &mut mount_id,
-                    libc::AT_EMPTY_PATH,
-                )
-            };
-
-            if ret < 0 && io::Error::last_os_error().raw_os_error().unwrap_or(0) != libc::EOVERFLOW
-            {
-                None
-            } else {
-                Some(mount_id as u32)
+        let path =
+            unsafe { CString::from_vec_unchecked(p.as_ref().as_os_str().as_bytes().to_vec()) };
+        self.find_mountinfo(dev, mount_id(libc::AT_FDCWD, &path))
+    }
+
+    pub fn mountinfo_from_fd<F: AsRawFd>(&self, fd: &F) -> Result<MountInfo> {
+        let fd = fd.as_raw_fd();
+        let dev: Devno = {
+            let mut st = unsafe { std::mem::zeroed::<libc::stat>() };
+            if unsafe { libc::fstat(fd, &mut st) } < 0 {
+                return Err(io::Error::last_os_error().into());
             }
+            st.st_dev.into()
         };
 
+        self.find_mountinfo(dev, mount_id(fd, c""))
+    }
+
+    fn find_mountinfo(&self, dev: Devno, mount_id: Option<u32>) -> Result<MountInfo> {
         self.mounts()
             .find(|info| mount_id.map(|id| info.id == id).unwrap_or(false) || info.dev == dev)?
             .ok_or(io::ErrorKind::NotFound.into())
     }
 
+    fn read_parsed<T>(
+        &self,
+        name: &str,
+        parse: fn(&str) -> std::result::Result<T, ParseLineError>,
+    ) -> Result<T> {
+        let p = self.path.join(name);
+        let content = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+        parse(&content).map_err(|err| Error::ParseError {
+            file: p,
+            line: Some(err.line),
+        })
+    }
+
+    #[inline]
+    pub fn diskstats(&self) -> Result<Vec<DiskStat>> {
+        self.read_parsed("diskstats", parse::diskstats::from_str)
+    }
+
+    #[inline]
+    pub fn mdstat(&self) -> Result<MdStat> {
+        self.read_parsed("mdstat", parse::mdstat::from_str)
+    }
+
+    #[inline]
+    pub fn swaps(&self) -> Result<Vec<SwapEntry>> {
+        self.read_parsed("swaps", parse::swaps::from_str)
+    }
+
+    #[inline]
+    pub fn openers(&self, devno: &Devno) -> Result<Vec<Opener>> {
+        openers::openers(&self.path, devno)
+    }
+
+    pub fn kernel_version(&self) -> Result<Option<(u32, u32)>> {
+        let p = self.path.join("sys").join("kernel").join("osrelease");
+        let release = match std::fs::read_to_string(&p) {
+            Ok(release) => release,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::path(p, err)),
+        };
+        let mut it = release
+            .trim()
+            .split(|c: char| !c.is_ascii_digit())
+            .map(|n| n.parse::<u32>());
+        match (it.next(), it.next()) {
+            (Some(Ok(major)), Some(Ok(minor))) => Ok(Some((major, minor))),
+            _ => Err(Error::parse(p)),
+        }
+    }
+
     #[inline]
-    pub fn is_type(&self, devno: &Devno, ty: impl AsRef<str>) -> io::Result<bool> {
-        Ok(&*match self.devices().get_by_id(devno.major())? {
-            Some(x) => x,
-            None => return Ok(false),
-        } == ty.as_ref())
+    pub fn is_type(&self, devno: &Devno, ty: impl AsRef<str>) -> Result<bool> {
+        self.devices().is_major(devno.major(), ty)
     }
 
     #[inline]
-    fn remap(p: Option<PathBuf>) -> io::Result<Self> {
+    fn remap(p: Option<PathBuf>) -> Result<Self> {
         match p {
             Some(path) => Ok(Self {
                 devices: Devices::new(&path),
                 mounts: MountInfos::from_procfs(&path),
                 path,
             }),
-            None => Err(io::ErrorKind::NotFound.into()),
+            None => Err(Error::ProcfsNotFound),
         }
     }
 
-    fn probe_by_mtab() -> io::Result<Option<PathBuf>> {
+    fn probe_by_mtab() -> Result<Option<PathBuf>> {
         let p = Path::new("/etc/mtab");
         if !p.is_symlink() {
             return Ok(None);
@@ -124,14 +185,17 @@ impl ProcFs {
             Some(procfs) => procfs,
             None => return Ok(None),
         };
-        if matches!(procfs.file_name(), Some(_)) {
+        if procfs.file_name().is_some() {
+            if let Some(p) = Self::probe_by_magic(procfs) {
+                return Ok(Some(p));
+            }
             Self::probe_by_path(procfs)
         } else {
             Ok(None)
         }
     }
 
-    fn probe_by_path<P: AsRef<Path>>(p: P) -> io::Result<Option<PathBuf>> {
+    fn probe_by_path<P: AsRef<Path>>(p: P) -> Result<Option<PathBuf>> {
         let p = p.as_ref().canonicalize()?;
 
         if p.join("self").canonicalize()? == p.join(std::process::id().to_string()) {
@@ -141,19 +205,27 @@ impl ProcFs {
         }
     }
 
-    fn probe_by_paths<P: AsRef<Path>, I: IntoIterator<Item = P>>(
-        it: I,
-    ) -> io::Result<Option<PathBuf>> {
+    fn probe_by_paths<P: AsRef<Path>, I: IntoIterator<Item = P>>(it: I) -> Result<Option<PathBuf>> {
         for p in it {
-            match Self::probe_by_path(p)? {
-                Some(path) => return Ok(Some(path)),
-                _ => (),
+            if let Some(path) = Self::probe_by_path(p)? {
+                return Ok(Some(path));
             }
         }
         Ok(None)
     }
 
-    fn probe() -> io::Result<Option<PathBuf>> {
+    fn probe_by_magic<P: AsRef<Path>>(p: P) -> Option<PathBuf> {
+        if magic::is_fs(p.as_ref(), &[magic::PROC_SUPER_MAGIC]) {
+            Some(p.as_ref().to_path_buf())
+        } else {
+            None
+        }
+    }
+
+    fn probe() -> Result<Option<PathBuf>> {
+        if let Some(p) = Self::probe_by_magic("/proc") {
+            return Ok(Some(p));
+        }
         if let Some(p) = Self::probe_by_mtab()? {
             return Ok(Some(p));
         }
@@ -166,6 +238,30 @@ impl ProcFs {
     }
 }
 
+fn mount_id(dirfd: RawFd, path: &CStr) -> Option<u32> {
+    let mut handle = file_handle {
+        handle_bytes: 0,
+        handle_type: 0,
+        f_handle: [],
+    };
+    let mut mount_id = 0;
+    let ret = unsafe {
+        name_to_handle_at(
+            dirfd,
+            path.as_ptr(),
+            &mut handle,
+            &mut mount_id,
+            libc::AT_EMPTY_PATH,
+        )
+    };
+
+    if ret < 0 && io::Error::last_os_error().raw_os_error().unwrap_or(0) != libc::EOVERFLOW {
+        None
+    } else {
+        Some(mount_id as u32)
+    }
+}
+
 #[repr(C)]
 pub struct file_handle {
     pub handle_bytes: libc::c_uint,
@@ -1,13 +1,20 @@
 use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsString,
+    fmt::Write,
     fs::File,
     io::{self, BufRead, BufReader, Lines},
+    os::unix::prelude::{AsRawFd, OsStrExt, OsStringExt, RawFd},
     path::{Path, PathBuf},
     str::FromStr,
+    time::Duration,
 };
 
-use crate::Devno;
+use super::MountOptions;
+use crate::{Blocks, Device, Devno, Error, Result};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct MountInfo {
     pub id: u32,
     pub parent_id: u32,
@@ -15,67 +22,289 @@ pub struct MountInfo {
     pub root: PathBuf,
     pub mount_point: PathBuf,
     pub mount_options: String,
+    #[deprecated(note = "use `propagation`")]
+    #[cfg_attr(feature = "serde", serde(default))]
     pub fields: Vec<String>,
+    pub propagation: Propagation,
     pub file_system: String,
     pub source: Option<String>,
     pub super_options: String,
 }
 
-#[derive(Debug)]
-pub struct ParseMountInfoError;
+// field is the whitespace separated column the line broke at
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("invalid mountinfo line {line:?} at field {field}")]
+pub struct ParseMountInfoError {
+    pub line: String,
+    pub field: usize,
+}
+
+struct Fields<'a> {
+    line: &'a str,
+    it: std::str::SplitWhitespace<'a>,
+    field: usize,
+}
+
+impl<'a> Fields<'a> {
+    #[inline]
+    fn new(line: &'a str) -> Self {
+        Self {
+            line,
+            it: line.split_whitespace(),
+            field: 0,
+        }
+    }
+
+    #[inline]
+    fn error(&self) -> ParseMountInfoError {
+        ParseMountInfoError {
+            line: self.line.to_string(),
+            field: self.field.saturating_sub(1),
+        }
+    }
+
+    fn next(&mut self) -> Result<&'a str, ParseMountInfoError> {
+        self.field += 1;
+        self.it.next().ok_or_else(|| self.error())
+    }
+
+    fn parse<T: FromStr>(&mut self) -> Result<T, ParseMountInfoError> {
+        self.next()?.parse().map_err(|_| self.error())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Propagation {
+    pub shared: Option<u32>,
+    pub master: Option<u32>,
+    pub propagate_from: Option<u32>,
+    pub unbindable: bool,
+}
+
+impl Propagation {
+    fn parse_field(&mut self, field: &str) -> Option<()> {
+        match field.split_once(':') {
+            Some(("shared", id)) => self.shared = Some(id.parse().ok()?),
+            Some(("master", id)) => self.master = Some(id.parse().ok()?),
+            Some(("propagate_from", id)) => self.propagate_from = Some(id.parse().ok()?),
+            None if field == "unbindable" => self.unbindable = true,
+            // newer kernels may add tags, ignore them whatever their value
+            _ => (),
+        }
+        Some(())
+    }
+
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        self.shared.is_some()
+    }
+
+    #[inline]
+    pub fn is_slave(&self) -> bool {
+        self.master.is_some()
+    }
+
+    #[inline]
+    pub fn is_private(&self) -> bool {
+        self.shared.is_none() && self.master.is_none()
+    }
+
+    pub fn receives_from(&self, other: &Propagation) -> bool {
+        match other.shared {
+            Some(group) => self.shared == Some(group) || self.master == Some(group),
+            None => false,
+        }
+    }
+}
+
+impl std::fmt::Display for Propagation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(id) = self.shared {
+            write!(f, "shared:{} ", id)?;
+        }
+        if let Some(id) = self.master {
+            write!(f, "master:{} ", id)?;
+        }
+        if let Some(id) = self.propagate_from {
+            write!(f, "propagate_from:{} ", id)?;
+        }
+        if self.unbindable {
+            f.write_str("unbindable ")?;
+        }
+        Ok(())
+    }
+}
+
+// the kernel encodes space, tab, newline and backslash as \ooo
+pub(crate) fn unescape(s: &str) -> Vec<u8> {
+    let s = s.as_bytes();
+    let mut res = Vec::with_capacity(s.len());
+    let mut i = 0;
+    while i < s.len() {
+        if s[i] == b'\\'
+            && i + 3 < s.len()
+            && s[i + 1..i + 4].iter().all(|c| (b'0'..=b'7').contains(c))
+        {
+            res.push(
+                s[i + 1..i + 4]
+                    .iter()
+                    .fold(0u8, |acc, c| acc.wrapping_mul(8) + (c - b'0')),
+            );
+            i += 4;
+        } else {
+            res.push(s[i]);
+            i += 1;
+        }
+    }
+    res
+}
+
+#[inline]
+pub(crate) fn unescape_path(s: &str) -> PathBuf {
+    PathBuf::from(OsString::from_vec(unescape(s)))
+}
+
+// octal escapes first, then the backslash escapes added by overlayfs
+fn unescape_layer(s: &str) -> PathBuf {
+    let bytes = unescape(s);
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut bytes = bytes.into_iter();
+    while let Some(b) = bytes.next() {
+        match b {
+            b'\\' => res.extend(bytes.next()),
+            b => res.push(b),
+        }
+    }
+    PathBuf::from(OsString::from_vec(res))
+}
+
+pub(crate) struct Escaped<'a>(pub &'a [u8]);
+
+impl std::fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.0.utf8_chunks() {
+            for c in chunk.valid().chars() {
+                match c {
+                    ' ' | '\t' | '\n' | '\\' => write!(f, "\\{:03o}", c as u32)?,
+                    c => f.write_char(c)?,
+                }
+            }
+            for b in chunk.invalid() {
+                write!(f, "\\{:03o}", b)?;
+            }
+        }
+        Ok(())
+    }
+}
 
 impl FromStr for MountInfo {
     type Err = ParseMountInfoError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let s = s.trim();
-        let mut it = s.split_whitespace();
+        let mut it = Fields::new(s);
+        let mut fields = Vec::new();
 
+        #[allow(deprecated)]
         Ok(Self {
-            id: it
-                .next()
-                .unwrap()
-                .parse()
-                .map_err(|_| ParseMountInfoError)?,
-            parent_id: it
-                .next()
-                .ok_or(ParseMountInfoError)?
-                .parse()
-                .map_err(|_| ParseMountInfoError)?,
-            dev: it
-                .next()
-                .ok_or(ParseMountInfoError)?
-                .parse()
-                .map_err(|_| ParseMountInfoError)?,
-            root: it.next().ok_or(ParseMountInfoError)?.into(),
-            mount_point: it.next().ok_or(ParseMountInfoError)?.into(),
-            mount_options: it.next().ok_or(ParseMountInfoError)?.into(),
-            fields: {
-                let mut fields = Vec::new();
+            id: it.parse()?,
+            parent_id: it.parse()?,
+            dev: it.parse()?,
+            root: unescape_path(it.next()?),
+            mount_point: unescape_path(it.next()?),
+            mount_options: it.next()?.into(),
+            propagation: {
+                let mut propagation = Propagation::default();
                 loop {
-                    let v = it.next().ok_or(ParseMountInfoError)?;
+                    let v = it.next()?;
                     if v == "-" {
                         break;
                     } else {
+                        propagation.parse_field(v).ok_or_else(|| it.error())?;
                         fields.push(v.to_string());
                     }
                 }
-                fields
+                propagation
             },
-            file_system: it.next().ok_or(ParseMountInfoError)?.to_string(),
+            fields,
+            file_system: it.next()?.to_string(),
             source: {
-                let source = it.next().ok_or(ParseMountInfoError)?;
+                let source = it.next()?;
                 if source == "none" {
                     None
                 } else {
-                    Some(source.to_string())
+                    Some(String::from_utf8_lossy(&unescape(source)).into_owned())
                 }
             },
-            super_options: it.next().ok_or(ParseMountInfoError)?.to_string(),
+            super_options: it.next()?.to_string(),
         })
     }
 }
 
+impl MountInfo {
+    #[inline]
+    pub fn options(&self) -> MountOptions {
+        MountOptions::parse(&self.mount_options)
+    }
+
+    #[inline]
+    pub fn super_opts(&self) -> MountOptions {
+        MountOptions::parse(&self.super_options)
+    }
+
+    #[inline]
+    pub fn is_overlay(&self) -> bool {
+        self.file_system == "overlay"
+    }
+
+    pub fn backing_devices(&self, blocks: &Blocks) -> Result<Vec<Device>> {
+        let mut res = Vec::new();
+        let mut seen = BTreeSet::new();
+        self.collect_backing_devices(blocks, &mut seen, &mut res)?;
+        Ok(res)
+    }
+
+    fn collect_backing_devices(
+        &self,
+        blocks: &Blocks,
+        seen: &mut BTreeSet<u32>,
+        res: &mut Vec<Device>,
+    ) -> Result<()> {
+        if !seen.insert(self.id) {
+            return Ok(());
+        }
+
+        if !self.is_overlay() {
+            match crate::mountpoint::mount_device(blocks, self) {
+                Ok(device) => {
+                    if !res.contains(&device) {
+                        res.push(device);
+                    }
+                }
+                // anonymous devices (tmpfs, nested overlays...) have no block device
+                Err(Error::NotFound { .. } | Error::NotBlockDevice { .. }) => (),
+                Err(err) if self.dev.major() == 0 && err.kind() == io::ErrorKind::NotFound => (),
+                Err(err) => return Err(err),
+            }
+            return Ok(());
+        }
+
+        let opts = self.super_opts();
+        let layers = opts
+            .lowerdir()
+            .into_iter()
+            .chain(opts.upperdir())
+            .map(unescape_layer);
+        for layer in layers {
+            if let Some(mount) = blocks.procfs().mounts().find_target(layer)? {
+                mount.collect_backing_devices(blocks, seen, res)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 impl std::fmt::Display for MountInfo {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -84,52 +313,59 @@ impl std::fmt::Display for MountInfo {
             self.id,
             self.parent_id,
             self.dev,
-            self.root.display(),
-            self.mount_point.display(),
+            Escaped(self.root.as_os_str().as_bytes()),
+            Escaped(self.mount_point.as_os_str().as_bytes()),
             self.mount_options
         )?;
 
-        for field in self.fields.iter() {
-            write!(f, "{} ", field)?;
-        }
-
         write!(
             f,
-            "- {} {} {}",
+            "{}- {} {} {}",
+            self.propagation,
             self.file_system,
-            self.source.as_ref().map(|x| x.as_str()).unwrap_or("none"),
+            Escaped(self.source.as_deref().unwrap_or("none").as_bytes()),
             self.super_options
         )
     }
 }
 
 pub struct MountInfoIterator {
+    path: PathBuf,
     lines: Lines<BufReader<File>>,
+    line: usize,
 }
 
 impl MountInfoIterator {
-    pub fn new<P: AsRef<Path>>(file: P) -> io::Result<Self> {
+    pub fn new<P: AsRef<Path>>(file: P) -> Result<Self> {
+        let path = file.as_ref().to_path_buf();
+        let f = File::open(&path).map_err(|err| Error::path(&path, err))?;
         Ok(Self {
-            lines: BufReader::new(File::open(file)?).lines(),
+            path,
+            lines: BufReader::new(f).lines(),
+            line: 0,
         })
     }
 
     #[inline]
-    pub fn from_procfs<P: AsRef<Path>>(procfs: P) -> io::Result<Self> {
+    pub fn from_procfs<P: AsRef<Path>>(procfs: P) -> Result<Self> {
         Self::new(procfs.as_ref().join("self").join("mountinfo"))
     }
 }
 
 impl Iterator for MountInfoIterator {
-    type Item = io::Result<MountInfo>;
+    type Item = Result<MountInfo>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.line += 1;
         match self.lines.next()? {
             Ok(line) => match line.parse() {
                 Ok(info) => Some(Ok(info)),
-                Err(_) => Some(Err(io::ErrorKind::InvalidData.into())),
+                Err(_) => Some(Err(Error::ParseError {
+                    file: self.path.clone(),
+                    line: Some(self.line),
+                })),
             },
-            Err(err) => Some(Err(err)),
+            Err(err) => Some(Err(Error::path(&self.path, err))),
         }
     }
 }
@@ -151,7 +387,27 @@ impl MountInfos {
         Self::new(procfs.as_ref().join("self").join("mountinfo"))
     }
 
-    pub fn find<F: Fn(&MountInfo) -> bool>(&self, f: F) -> io::Result<Option<MountInfo>> {
+    pub fn from_procfs_pid<P: AsRef<Path>>(procfs: P, pid: u32) -> Result<Self> {
+        let path = procfs.as_ref().join(pid.to_string()).join("mountinfo");
+        if !path.exists() {
+            return Err(Error::path(path, io::ErrorKind::NotFound.into()));
+        }
+        Ok(Self::new(path))
+    }
+
+    pub fn for_pid(pid: u32) -> Result<Self> {
+        match super::ProcFs::probe()? {
+            Some(procfs) => Self::from_procfs_pid(procfs, pid),
+            None => Err(Error::ProcfsNotFound),
+        }
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn find<F: Fn(&MountInfo) -> bool>(&self, f: F) -> Result<Option<MountInfo>> {
         for mount in self.iter()? {
             let mount = mount?;
             if f(&mount) {
@@ -162,12 +418,261 @@ impl MountInfos {
     }
 
     #[inline]
-    pub fn iter(&self) -> io::Result<MountInfoIterator> {
+    pub fn iter(&self) -> Result<MountInfoIterator> {
         MountInfoIterator::new(&self.path)
     }
 
     #[inline]
-    pub fn all(&self) -> io::Result<Vec<MountInfo>> {
+    pub fn watch(&self) -> Result<MountWatcher> {
+        MountWatcher::new(&self.path)
+    }
+
+    pub fn find_mount_point<F: Fn(&MountInfo) -> bool, P: AsRef<Path>>(
+        &self,
+        f: F,
+        preferred: &[P],
+    ) -> Result<Option<PathBuf>> {
+        let mut candidates = Vec::new();
+        for mount in self.iter()? {
+            let mount = mount?;
+            if f(&mount) {
+                candidates.push(mount.mount_point);
+            }
+        }
+
+        for p in preferred {
+            if let Some(found) = candidates.iter().find(|c| *c == p.as_ref()) {
+                return Ok(Some(found.clone()));
+            }
+        }
+        Ok(candidates.into_iter().next())
+    }
+
+    #[inline]
+    pub fn all(&self) -> Result<Vec<MountInfo>> {
         self.iter()?.collect()
     }
+
+    pub fn find_target<P: AsRef<Path>>(&self, p: P) -> Result<Option<MountInfo>> {
+        let p = p
+            .as_ref()
+            .canonicalize()
+            .map_err(|err| Error::path(p.as_ref(), err))?;
+
+        let mut best: Option<MountInfo> = None;
+        for mount in self.iter()? {
+            let mount = mount?;
+            if !p.starts_with(&mount.mount_point) {
+                continue;
+            }
+            // later entries stack on top of earlier ones with the same mount point
+            let depth = mount.mount_point.components().count();
+            if best
+                .as_ref()
+                .is_none_or(|b| depth >= b.mount_point.components().count())
+            {
+                best = Some(mount);
+            }
+        }
+        Ok(best)
+    }
+
+    pub fn propagation_peers(&self, info: &MountInfo) -> Result<Vec<MountInfo>> {
+        let mut res = Vec::new();
+        for mount in self.iter()? {
+            let mount = mount?;
+            if mount.id != info.id && mount.propagation.receives_from(&info.propagation) {
+                res.push(mount);
+            }
+        }
+        Ok(res)
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MountChange {
+    Mounted(MountInfo),
+    Unmounted(MountInfo),
+}
+
+pub struct MountWatcher {
+    file: File,
+    path: PathBuf,
+    last: BTreeMap<u32, MountInfo>,
+}
+
+impl MountWatcher {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = File::open(&path).map_err(|err| Error::path(&path, err))?;
+        let last = Self::snapshot(&path)?;
+        Ok(Self { file, path, last })
+    }
+
+    fn snapshot(path: &Path) -> Result<BTreeMap<u32, MountInfo>> {
+        MountInfoIterator::new(path)?
+            .map(|m| m.map(|m| (m.id, m)))
+            .collect()
+    }
+
+    pub fn changes(&mut self) -> Result<Vec<MountChange>> {
+        let current = Self::snapshot(&self.path)?;
+        let mut res = Vec::new();
+
+        for (id, info) in self.last.iter() {
+            if !current.contains_key(id) {
+                res.push(MountChange::Unmounted(info.clone()));
+            }
+        }
+        for (id, info) in current.iter() {
+            if !self.last.contains_key(id) {
+                res.push(MountChange::Mounted(info.clone()));
+            }
+        }
+
+        self.last = current;
+        Ok(res)
+    }
+
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<bool> {
+        let mut fd = libc::pollfd {
+            fd: self.file.as_raw_fd(),
+            events: libc::POLLPRI,
+            revents: 0,
+        };
+        let timeout = timeout
+            .map(|t| t.as_millis().min(libc::c_int::MAX as u128) as libc::c_int)
+            .unwrap_or(-1);
+        loop {
+            let ret = unsafe { libc::poll(&mut fd, 1, timeout) };
+            if ret < 0 {
+                let err = io::Error::last_os_error();
+                if err.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(err.into());
+            }
+            return Ok(ret > 0);
+        }
+    }
+
+    #[inline]
+    pub fn mounts(&self) -> impl Iterator<Item = &MountInfo> {
+        self.last.values()
+    }
+}
+
+impl AsRawFd for MountWatcher {
+    #[inline]
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl Iterator for MountWatcher {
+    type Item = Result<Vec<MountChange>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Err(err) = self.wait(None) {
+                return Some(Err(err));
+            }
+            match self.changes() {
+                Ok(changes) if changes.is_empty() => (),
+                res => return Some(res),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescape_octal() {
+        assert_eq!(unescape(r"a\040b"), b"a b");
+        assert_eq!(unescape(r"a\011b\012c"), b"a\tb\nc");
+        assert_eq!(unescape(r"back\134slash"), b"back\\slash");
+        assert_eq!(unescape(r"\040"), b" ");
+    }
+
+    #[test]
+    fn overlay_layers() {
+        let opts = MountOptions::parse(r"lowerdir=/a\:b:/c\134:d:/e\072f,upperdir=/u");
+        let layers = opts.lowerdir();
+        assert_eq!(layers, [r"/a\:b", r"/c\134:d", r"/e\072f"]);
+        let layers: Vec<_> = layers.into_iter().map(unescape_layer).collect();
+        assert_eq!(
+            layers,
+            [Path::new("/a:b"), Path::new("/c:d"), Path::new("/e:f")]
+        );
+        assert_eq!(unescape_layer(r"/x\134\134y"), Path::new(r"/x\y"));
+    }
+
+    #[test]
+    fn unescape_malformed() {
+        // too short or not octal, kept as written
+        assert_eq!(unescape(r"a\0"), br"a\0");
+        assert_eq!(unescape(r"\999"), br"\999");
+        assert_eq!(unescape(r"\04"), br"\04");
+        assert_eq!(unescape("trailing\\"), b"trailing\\");
+    }
+
+    #[test]
+    fn escaped_round_trip() {
+        for raw in [
+            &b"with space"[..],
+            b"tab\there",
+            b"new\nline",
+            b"back\\slash",
+            b"\\999",
+            b"invalid \xff utf8",
+        ] {
+            let escaped = Escaped(raw).to_string();
+            assert!(!escaped.contains([' ', '\t', '\n']), "{:?}", escaped);
+            assert_eq!(unescape(&escaped), raw, "{:?}", escaped);
+        }
+        assert_eq!(
+            Escaped(b"a b\tc\nd\\e").to_string(),
+            r"a\040b\011c\012d\134e"
+        );
+    }
+
+    #[test]
+    fn mountinfo_round_trip() {
+        let line = r"36 35 98:0 /mnt\040a /mnt/with\011tab\012nl rw,noatime master:1 - ext3 /dev/my\134disk rw,errors=continue";
+        let mount = line.parse::<MountInfo>().unwrap();
+        assert_eq!(mount.root, Path::new("/mnt a"));
+        assert_eq!(mount.mount_point, Path::new("/mnt/with\ttab\nnl"));
+        assert_eq!(mount.source.as_deref(), Some(r"/dev/my\disk"));
+        assert_eq!(mount.to_string(), line);
+    }
+
+    #[test]
+    fn mountinfo_errors() {
+        let err = "36 35 98:0 /".parse::<MountInfo>().unwrap_err();
+        assert_eq!(err.field, 4);
+        let err = "36 x 98:0 / / rw - ext3 /dev/root rw"
+            .parse::<MountInfo>()
+            .unwrap_err();
+        assert_eq!(err.field, 1);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn propagation_fields() {
+        let line = "36 35 98:0 / / rw shared:2 future:x master:1 flag - ext3 /dev/root rw";
+        let mount = line.parse::<MountInfo>().unwrap();
+        assert_eq!(mount.propagation.shared, Some(2));
+        assert_eq!(mount.propagation.master, Some(1));
+        assert!(!mount.propagation.unbindable);
+        assert_eq!(mount.fields, ["shared:2", "future:x", "master:1", "flag"]);
+
+        let err = "36 35 98:0 / / rw shared:x - ext3 /dev/root rw"
+            .parse::<MountInfo>()
+            .unwrap_err();
+        assert_eq!(err.field, 6);
+    }
 }
@@ -0,0 +1,137 @@
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    os::unix::prelude::{FileTypeExt, MetadataExt},
+    path::{Path, PathBuf},
+};
+
+use crate::{Devno, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum OpenKind {
+    Fd(u32),
+    Mmap,
+    Cwd,
+    Root,
+    Exe,
+}
+
+impl fmt::Display for OpenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fd(fd) => write!(f, "fd {}", fd),
+            Self::Mmap => f.pad("mmap"),
+            Self::Cwd => f.pad("cwd"),
+            Self::Root => f.pad("root"),
+            Self::Exe => f.pad("exe"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Opener {
+    pub pid: u32,
+    pub command: Option<String>,
+    pub kind: OpenKind,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Opener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}) {}: {}",
+            self.pid,
+            self.command.as_deref().unwrap_or("?"),
+            self.kind,
+            self.path.display()
+        )
+    }
+}
+
+// either the device node itself or a file living on the device
+fn matches(md: &fs::Metadata, devno: &Devno) -> bool {
+    (md.file_type().is_block_device() && Devno::from(md.rdev()) == *devno)
+        || Devno::from(md.dev()) == *devno
+}
+
+fn parse_maps_dev(dev: &str) -> Option<Devno> {
+    let (major, minor) = dev.split_once(':')?;
+    Some(Devno::from((
+        u32::from_str_radix(major, 16).ok()?,
+        u32::from_str_radix(minor, 16).ok()?,
+    )))
+}
+
+fn scan_pid(dir: &Path, pid: u32, devno: &Devno, res: &mut Vec<Opener>) {
+    let command = fs::read_to_string(dir.join("comm"))
+        .ok()
+        .map(|c| c.trim_end_matches('\n').to_string());
+    let mut push = |kind, path| {
+        res.push(Opener {
+            pid,
+            command: command.clone(),
+            kind,
+            path,
+        })
+    };
+
+    for (name, kind) in [
+        ("cwd", OpenKind::Cwd),
+        ("root", OpenKind::Root),
+        ("exe", OpenKind::Exe),
+    ] {
+        let link = dir.join(name);
+        if fs::metadata(&link).is_ok_and(|md| matches(&md, devno)) {
+            if let Ok(path) = fs::read_link(&link) {
+                push(kind, path);
+            }
+        }
+    }
+
+    // processes and descriptors come and go while scanning, skip what vanished
+    if let Ok(fds) = fs::read_dir(dir.join("fd")) {
+        for entry in fds.flatten() {
+            let fd = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+                Some(fd) => fd,
+                None => continue,
+            };
+            let link = entry.path();
+            if fs::metadata(&link).is_ok_and(|md| matches(&md, devno)) {
+                if let Ok(path) = fs::read_link(&link) {
+                    push(OpenKind::Fd(fd), path);
+                }
+            }
+        }
+    }
+
+    if let Ok(maps) = fs::read_to_string(dir.join("maps")) {
+        let mut seen = HashSet::new();
+        for line in maps.lines() {
+            let mut fields = line.splitn(6, ' ');
+            let dev = match fields.nth(3).and_then(parse_maps_dev) {
+                Some(dev) => dev,
+                None => continue,
+            };
+            let path = fields.nth(1).map(str::trim_start).unwrap_or("");
+            if dev == *devno && path.starts_with('/') && seen.insert(path) {
+                push(OpenKind::Mmap, PathBuf::from(path));
+            }
+        }
+    }
+}
+
+pub(crate) fn openers(procfs: &Path, devno: &Devno) -> Result<Vec<Opener>> {
+    let mut res = Vec::new();
+    for entry in fs::read_dir(procfs)? {
+        let entry = entry?;
+        let pid = match entry.file_name().to_str().and_then(|s| s.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        scan_pid(&entry.path(), pid, devno, &mut res);
+    }
+    Ok(res)
+}
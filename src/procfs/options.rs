@@ -0,0 +1,149 @@
+use std::str::FromStr;
+
+use indexmap::IndexMap;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MountOptions {
+    options: IndexMap<String, Option<String>>,
+}
+
+impl MountOptions {
+    pub fn parse(s: &str) -> Self {
+        let options = s
+            .split(',')
+            .filter(|opt| !opt.is_empty())
+            .map(|opt| match opt.split_once('=') {
+                Some((key, value)) => (key.to_string(), Some(value.to_string())),
+                None => (opt.to_string(), None),
+            })
+            .collect();
+        Self { options }
+    }
+
+    #[inline]
+    pub fn contains(&self, key: &str) -> bool {
+        self.options.contains_key(key)
+    }
+
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.options.get(key)?.as_deref()
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.options.iter().map(|(k, v)| (k.as_str(), v.as_deref()))
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.options.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.options.is_empty()
+    }
+
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.contains("ro")
+    }
+
+    #[inline]
+    pub fn is_noexec(&self) -> bool {
+        self.contains("noexec")
+    }
+
+    #[inline]
+    pub fn is_nosuid(&self) -> bool {
+        self.contains("nosuid")
+    }
+
+    #[inline]
+    pub fn is_nodev(&self) -> bool {
+        self.contains("nodev")
+    }
+
+    #[inline]
+    pub fn is_noatime(&self) -> bool {
+        self.contains("noatime")
+    }
+
+    #[inline]
+    pub fn is_relatime(&self) -> bool {
+        self.contains("relatime")
+    }
+
+    #[inline]
+    pub fn subvol(&self) -> Option<&str> {
+        self.get("subvol")
+    }
+
+    #[inline]
+    pub fn subvolid(&self) -> Option<u64> {
+        self.get("subvolid")?.parse().ok()
+    }
+
+    #[inline]
+    pub fn lowerdir(&self) -> Vec<&str> {
+        self.get("lowerdir").map(split_layers).unwrap_or_default()
+    }
+
+    #[inline]
+    pub fn upperdir(&self) -> Option<&str> {
+        self.get("upperdir")
+    }
+
+    #[inline]
+    pub fn workdir(&self) -> Option<&str> {
+        self.get("workdir")
+    }
+}
+
+// overlayfs escapes ':' inside a layer as "\:", and mountinfo shows that
+// backslash as \134; the layers are returned still escaped
+fn split_layers(dirs: &str) -> Vec<&str> {
+    let bytes = dirs.as_bytes();
+    let mut res = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if bytes[i + 1..].starts_with(b"134") => i += 5,
+            b'\\' => i += 2,
+            b':' => {
+                res.push(&dirs[start..i]);
+                start = i + 1;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    res.push(&dirs[start..]);
+    res
+}
+
+impl FromStr for MountOptions {
+    type Err = std::convert::Infallible;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self::parse(s))
+    }
+}
+
+impl std::fmt::Display for MountOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, (key, value)) in self.options.iter().enumerate() {
+            if i != 0 {
+                f.write_str(",")?;
+            }
+            f.write_str(key)?;
+            if let Some(value) = value {
+                write!(f, "={}", value)?;
+            }
+        }
+        Ok(())
+    }
+}
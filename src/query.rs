@@ -0,0 +1,131 @@
+use crate::{iter::DevnoMapper, sysfs::iter::BlocksIterator, Blocks, Device, DeviceKind, Result};
+
+#[derive(Clone)]
+pub struct Query<'a> {
+    blocks: &'a Blocks,
+    kinds: Vec<DeviceKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    rotational: Option<bool>,
+    unused: bool,
+    exclude_removable: bool,
+    partitions: bool,
+}
+
+impl<'a> Query<'a> {
+    #[inline]
+    pub(crate) fn new(blocks: &'a Blocks) -> Self {
+        Self {
+            blocks,
+            kinds: Vec::new(),
+            min_size: None,
+            max_size: None,
+            rotational: None,
+            unused: false,
+            exclude_removable: false,
+            partitions: false,
+        }
+    }
+
+    #[inline]
+    pub fn kind(mut self, kind: DeviceKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    #[inline]
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    #[inline]
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    #[inline]
+    pub fn rotational(mut self, rotational: bool) -> Self {
+        self.rotational = Some(rotational);
+        self
+    }
+
+    #[inline]
+    pub fn unused(mut self) -> Self {
+        self.unused = true;
+        self
+    }
+
+    #[inline]
+    pub fn exclude_removable(mut self) -> Self {
+        self.exclude_removable = true;
+        self
+    }
+
+    #[inline]
+    pub fn partitions(mut self, include: bool) -> Self {
+        self.partitions = include;
+        self
+    }
+
+    fn accept(&self, device: &Device) -> Result<bool> {
+        if !self.partitions && device.is_partition()? {
+            return Ok(false);
+        }
+        if !self.kinds.is_empty() && !self.kinds.contains(&device.kind()?) {
+            return Ok(false);
+        }
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let size = device.size()?;
+            if self.min_size.is_some_and(|min| size < min)
+                || self.max_size.is_some_and(|max| size > max)
+            {
+                return Ok(false);
+            }
+        }
+        if let Some(rotational) = self.rotational {
+            if device.is_rotational()? != rotational {
+                return Ok(false);
+            }
+        }
+        if self.exclude_removable && device.is_removable()? {
+            return Ok(false);
+        }
+        if self.unused && device.in_use()? {
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    #[inline]
+    pub fn iter(self) -> Result<QueryIterator<'a>> {
+        Ok(QueryIterator {
+            inner: self.blocks.blocks()?,
+            query: self,
+        })
+    }
+}
+
+pub struct QueryIterator<'a> {
+    query: Query<'a>,
+    inner: DevnoMapper<'a, BlocksIterator>,
+}
+
+impl<'a> Iterator for QueryIterator<'a> {
+    type Item = Result<Device>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let device = match self.inner.next()? {
+                Ok(device) => device,
+                Err(err) => return Some(Err(err)),
+            };
+            match self.query.accept(&device) {
+                Ok(true) => return Some(Ok(device)),
+                Ok(false) => (),
+                Err(err) => return Some(Err(err)),
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd, time::Duration};
+
+use crate::{
+    blocks::Blocks,
+    ioctl::{self, BLKPG},
+    parttable::{PartitionEntry, PartitionTable},
+    Devno, Error, Result,
+};
+
+const BLKPG_ADD_PARTITION: libc::c_int = 1;
+const BLKPG_DEL_PARTITION: libc::c_int = 2;
+const BLKPG_RESIZE_PARTITION: libc::c_int = 3;
+
+#[repr(C)]
+struct blkpg_partition {
+    start: libc::c_longlong,
+    length: libc::c_longlong,
+    pno: libc::c_int,
+    devname: [libc::c_char; 64],
+    volname: [libc::c_char; 64],
+}
+
+#[repr(C)]
+struct blkpg_ioctl_arg {
+    op: libc::c_int,
+    flags: libc::c_int,
+    datalen: libc::c_int,
+    data: *mut libc::c_void,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RescanOptions {
+    retries: u32,
+    backoff: Duration,
+    blkpg_fallback: bool,
+}
+
+impl Default for RescanOptions {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            retries: 5,
+            backoff: Duration::from_millis(100),
+            blkpg_fallback: true,
+        }
+    }
+}
+
+impl RescanOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    #[inline]
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    #[inline]
+    pub fn blkpg_fallback(mut self, enabled: bool) -> Self {
+        self.blkpg_fallback = enabled;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RescanOutcome {
+    Reread,
+    Blkpg {
+        added: Vec<u32>,
+        removed: Vec<u32>,
+        resized: Vec<u32>,
+    },
+}
+
+struct KernelPartition {
+    number: u32,
+    start: u64,
+    length: u64,
+    busy: bool,
+}
+
+fn blkpg(f: &File, op: libc::c_int, number: u32, start: u64, length: u64) -> Result<()> {
+    let mut part = blkpg_partition {
+        start: start as _,
+        length: length as _,
+        pno: number as _,
+        devname: [0; 64],
+        volname: [0; 64],
+    };
+    let mut arg = blkpg_ioctl_arg {
+        op,
+        flags: 0,
+        datalen: std::mem::size_of::<blkpg_partition>() as _,
+        data: &mut part as *mut blkpg_partition as *mut libc::c_void,
+    };
+
+    ioctl::allowed("BLKPG")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), BLKPG as _, &mut arg) };
+    if ret < 0 {
+        Err(Error::ioctl("BLKPG", io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+fn kernel_partitions(blocks: &Blocks, disk: &Devno) -> Result<Vec<KernelPartition>> {
+    let mut res = Vec::new();
+    for part in blocks.partitions(disk)? {
+        let part = part?;
+        let number = match blocks.partition_number(&part)? {
+            Some(number) => number as u32,
+            None => continue,
+        };
+        let start = match blocks.sysfs().partition_start(&part)? {
+            Some(start) => start,
+            None => continue,
+        };
+        res.push(KernelPartition {
+            number,
+            start,
+            length: blocks.size(&part)?,
+            busy: !blocks.mounts(&part)?.is_empty() || blocks.sysfs().has_holders(&part)?,
+        });
+    }
+    Ok(res)
+}
+
+pub(crate) fn busy_error(blocks: &Blocks, disk: &Devno) -> Result<Error> {
+    let partitions = kernel_partitions(blocks, disk)?
+        .into_iter()
+        .filter(|p| p.busy)
+        .map(|p| p.number)
+        .collect();
+    Ok(Error::PartitionsBusy {
+        disk: *disk,
+        partitions,
+    })
+}
+
+pub(crate) fn rescan(blocks: &Blocks, disk: &Devno, opts: RescanOptions) -> Result<RescanOutcome> {
+    let mut delay = opts.backoff;
+    for attempt in 0..=opts.retries {
+        match blocks.reread_partition_table(disk) {
+            Ok(()) => return Ok(RescanOutcome::Reread),
+            Err(err) if err.raw_os_error() == Some(libc::EBUSY) => {
+                if attempt < opts.retries {
+                    std::thread::sleep(delay);
+                    delay = delay.saturating_mul(2);
+                }
+            }
+            Err(err) => return Err(err),
+        }
+    }
+
+    if opts.blkpg_fallback {
+        update(blocks, disk)
+    } else {
+        Err(busy_error(blocks, disk)?)
+    }
+}
+
+fn update(blocks: &Blocks, disk: &Devno) -> Result<RescanOutcome> {
+    let f = blocks.open(disk, false)?;
+    let sector_size = blocks.sysfs().logical_block_size(disk)?;
+    let entries = PartitionTable::read(&f, sector_size)?
+        .map(|t| t.entries)
+        .unwrap_or_default();
+    let kernel = kernel_partitions(blocks, disk)?;
+
+    // the kernel only exposes the first sectors of an extended partition
+    let length = |e: &PartitionEntry| {
+        if e.is_extended() {
+            1024
+        } else {
+            e.sectors * sector_size
+        }
+    };
+
+    let mut removed = Vec::new();
+    let mut resized = Vec::new();
+    let mut added = Vec::new();
+    let mut busy = Vec::new();
+
+    for part in kernel.iter() {
+        match entries.iter().find(|e| e.number == part.number) {
+            Some(e) if e.start * sector_size == part.start => {
+                if length(e) != part.length {
+                    resized.push(part.number);
+                }
+            }
+            _ if part.busy => busy.push(part.number),
+            _ => removed.push(part.number),
+        }
+    }
+    if !busy.is_empty() {
+        return Err(Error::PartitionsBusy {
+            disk: *disk,
+            partitions: busy,
+        });
+    }
+
+    for e in entries.iter() {
+        if removed.contains(&e.number) || !kernel.iter().any(|p| p.number == e.number) {
+            added.push(e.number);
+        }
+    }
+
+    for number in removed.iter() {
+        blkpg(&f, BLKPG_DEL_PARTITION, *number, 0, 0)?;
+    }
+    for e in entries.iter() {
+        let op = if resized.contains(&e.number) {
+            BLKPG_RESIZE_PARTITION
+        } else if added.contains(&e.number) {
+            BLKPG_ADD_PARTITION
+        } else {
+            continue;
+        };
+        blkpg(&f, op, e.number, e.start * sector_size, length(e))?;
+    }
+
+    // partitions that were only moved show up as removed and added again
+    removed.retain(|n| !added.contains(n));
+    Ok(RescanOutcome::Blkpg {
+        added,
+        removed,
+        resized,
+    })
+}
@@ -0,0 +1,48 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd};
+
+use crate::{
+    ioctl::{self, BLKGETSIZE64},
+    Error, Result,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SizeChange {
+    Unchanged(u64),
+    Grew { old: u64, new: u64 },
+    Shrank { old: u64, new: u64 },
+}
+
+impl SizeChange {
+    pub(crate) fn new(old: u64, new: u64) -> Self {
+        match new.cmp(&old) {
+            std::cmp::Ordering::Equal => Self::Unchanged(new),
+            std::cmp::Ordering::Greater => Self::Grew { old, new },
+            std::cmp::Ordering::Less => Self::Shrank { old, new },
+        }
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Unchanged(size) => *size,
+            Self::Grew { new, .. } | Self::Shrank { new, .. } => *new,
+        }
+    }
+
+    #[inline]
+    pub fn is_changed(&self) -> bool {
+        !matches!(self, Self::Unchanged(_))
+    }
+}
+
+pub(crate) fn blk_getsize64(f: &File) -> Result<u64> {
+    let mut size: u64 = 0;
+    ioctl::allowed("BLKGETSIZE64")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), BLKGETSIZE64 as _, &mut size) };
+    if ret < 0 {
+        Err(Error::ioctl("BLKGETSIZE64", io::Error::last_os_error()))
+    } else {
+        Ok(size)
+    }
+}
@@ -0,0 +1,99 @@
+use std::{fs::File, io, os::unix::prelude::AsRawFd};
+
+use crate::{
+    ioctl::{self, SG_IO},
+    Error, Result,
+};
+
+const SG_INTERFACE_ID: libc::c_int = b'S' as _;
+
+pub(crate) const SG_DXFER_NONE: libc::c_int = -1;
+pub(crate) const SG_DXFER_FROM_DEV: libc::c_int = -3;
+
+const ALLOW_MEDIUM_REMOVAL: u8 = 0x1e;
+const START_STOP_UNIT: u8 = 0x1b;
+
+#[repr(C)]
+struct sg_io_hdr {
+    interface_id: libc::c_int,
+    dxfer_direction: libc::c_int,
+    cmd_len: libc::c_uchar,
+    mx_sb_len: libc::c_uchar,
+    iovec_count: libc::c_ushort,
+    dxfer_len: libc::c_uint,
+    dxferp: *mut libc::c_void,
+    cmdp: *const libc::c_uchar,
+    sbp: *mut libc::c_uchar,
+    timeout: libc::c_uint,
+    flags: libc::c_uint,
+    pack_id: libc::c_int,
+    usr_ptr: *mut libc::c_void,
+    status: libc::c_uchar,
+    masked_status: libc::c_uchar,
+    msg_status: libc::c_uchar,
+    sb_len_wr: libc::c_uchar,
+    host_status: libc::c_ushort,
+    driver_status: libc::c_ushort,
+    resid: libc::c_int,
+    duration: libc::c_uint,
+    info: libc::c_uint,
+}
+
+pub(crate) fn sg_io(
+    f: &File,
+    cdb: &[u8],
+    direction: libc::c_int,
+    data: &mut [u8],
+    sense: &mut [u8; 32],
+) -> Result<()> {
+    let mut hdr = sg_io_hdr {
+        interface_id: SG_INTERFACE_ID,
+        dxfer_direction: direction,
+        cmd_len: cdb.len() as _,
+        mx_sb_len: sense.len() as _,
+        iovec_count: 0,
+        dxfer_len: data.len() as _,
+        dxferp: data.as_mut_ptr() as *mut _,
+        cmdp: cdb.as_ptr(),
+        sbp: sense.as_mut_ptr(),
+        timeout: 30_000,
+        flags: 0,
+        pack_id: 0,
+        usr_ptr: std::ptr::null_mut(),
+        status: 0,
+        masked_status: 0,
+        msg_status: 0,
+        sb_len_wr: 0,
+        host_status: 0,
+        driver_status: 0,
+        resid: 0,
+        duration: 0,
+        info: 0,
+    };
+
+    ioctl::allowed("SG_IO")?;
+    let ret = unsafe { libc::ioctl(f.as_raw_fd(), SG_IO as _, &mut hdr) };
+    if ret < 0 {
+        Err(Error::ioctl("SG_IO", io::Error::last_os_error()))
+    } else if hdr.info & 1 != 0 {
+        Err(io::Error::other(format!(
+            "SCSI command {:#04x} failed (status {:#x}, host {:#x}, driver {:#x})",
+            cdb[0], hdr.status, hdr.host_status, hdr.driver_status
+        ))
+        .into())
+    } else {
+        Ok(())
+    }
+}
+
+#[inline]
+fn command(f: &File, cdb: &[u8]) -> Result<()> {
+    let mut sense = [0; 32];
+    sg_io(f, cdb, SG_DXFER_NONE, &mut [], &mut sense)
+}
+
+pub(crate) fn eject(f: &File) -> Result<()> {
+    command(f, &[ALLOW_MEDIUM_REMOVAL, 0, 0, 0, 0, 0])?;
+    command(f, &[START_STOP_UNIT, 0, 0, 0, 0x01, 0])?;
+    command(f, &[START_STOP_UNIT, 0, 0, 0, 0x02, 0])
+}
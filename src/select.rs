@@ -0,0 +1,94 @@
+use crate::{Blocks, Device, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskSelection {
+    min_size: u64,
+    max_size: Option<u64>,
+    removable: bool,
+    virtual_disks: bool,
+    read_only: bool,
+}
+
+impl Default for DiskSelection {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            min_size: 0,
+            max_size: None,
+            removable: false,
+            virtual_disks: false,
+            read_only: false,
+        }
+    }
+}
+
+impl DiskSelection {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn min_size(mut self, size: u64) -> Self {
+        self.min_size = size;
+        self
+    }
+
+    #[inline]
+    pub fn max_size(mut self, size: Option<u64>) -> Self {
+        self.max_size = size;
+        self
+    }
+
+    #[inline]
+    pub fn removable(mut self, allow: bool) -> Self {
+        self.removable = allow;
+        self
+    }
+
+    #[inline]
+    pub fn virtual_disks(mut self, allow: bool) -> Self {
+        self.virtual_disks = allow;
+        self
+    }
+
+    #[inline]
+    pub fn read_only(mut self, allow: bool) -> Self {
+        self.read_only = allow;
+        self
+    }
+
+    fn accept(&self, disk: &Device, size: u64) -> Result<bool> {
+        if size == 0 || size < self.min_size || self.max_size.is_some_and(|max| size > max) {
+            return Ok(false);
+        }
+        if !self.removable && disk.is_removable()? {
+            return Ok(false);
+        }
+        if !self.read_only && disk.is_read_only()? {
+            return Ok(false);
+        }
+        Ok(!disk.in_use()?)
+    }
+}
+
+// unused disks matching `sel`, largest first
+pub(crate) fn unused_disks(blocks: &Blocks, sel: DiskSelection) -> Result<Vec<Device>> {
+    let disks = blocks.disks()?.skip_hidden(true).skip_empty(true);
+    let disks = if sel.virtual_disks {
+        disks
+    } else {
+        disks.physical_only()
+    };
+
+    let mut res = Vec::new();
+    for disk in disks {
+        let disk = disk?;
+        let size = disk.size()?;
+        if sel.accept(&disk, size)? {
+            res.push((size, disk));
+        }
+    }
+    res.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(res.into_iter().map(|(_, disk)| disk).collect())
+}
@@ -0,0 +1,113 @@
+use std::collections::BTreeMap;
+
+use crate::{blocks::Blocks, Devno, Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotEntry {
+    pub devno: Devno,
+    pub name: String,
+    pub size: u64,
+    pub diskseq: Option<u64>,
+}
+
+impl SnapshotEntry {
+    // a reused devno with another name or disk sequence is a different device
+    #[inline]
+    fn is_same_device(&self, other: &Self) -> bool {
+        self.name == other.name && self.diskseq == other.diskseq
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotDiff {
+    pub added: Vec<SnapshotEntry>,
+    pub removed: Vec<SnapshotEntry>,
+    pub resized: Vec<(SnapshotEntry, SnapshotEntry)>,
+}
+
+impl SnapshotDiff {
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.resized.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    entries: BTreeMap<Devno, SnapshotEntry>,
+}
+
+impl Snapshot {
+    pub(crate) fn take(blocks: &Blocks) -> Result<Self> {
+        let mut entries = BTreeMap::new();
+        for devno in blocks.blocks()? {
+            let devno = devno?;
+            let entry = match Self::entry(blocks, &devno) {
+                Ok(entry) => entry,
+                // gone while enumerating
+                Err(Error::NotFound { .. }) => continue,
+                Err(err) => return Err(err),
+            };
+            entries.insert(devno, entry);
+        }
+        Ok(Self { entries })
+    }
+
+    fn entry(blocks: &Blocks, devno: &Devno) -> Result<SnapshotEntry> {
+        Ok(SnapshotEntry {
+            devno: *devno,
+            name: blocks.sysfs().name(devno)?,
+            size: blocks.sysfs().size(devno)?,
+            diskseq: blocks.diskseq(devno)?,
+        })
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    pub fn get(&self, devno: &Devno) -> Option<&SnapshotEntry> {
+        self.entries.get(devno)
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &SnapshotEntry> {
+        self.entries.values()
+    }
+
+    // changes needed to go from `self` to `newer`
+    pub fn diff(&self, newer: &Self) -> SnapshotDiff {
+        let mut res = SnapshotDiff::default();
+        for (devno, old) in &self.entries {
+            match newer.entries.get(devno) {
+                Some(new) if old.is_same_device(new) => {
+                    if old.size != new.size {
+                        res.resized.push((old.clone(), new.clone()));
+                    }
+                }
+                Some(new) => {
+                    res.removed.push(old.clone());
+                    res.added.push(new.clone());
+                }
+                None => res.removed.push(old.clone()),
+            }
+        }
+        for (devno, new) in &newer.entries {
+            if !self.entries.contains_key(devno) {
+                res.added.push(new.clone());
+            }
+        }
+        res.added.sort_by_key(|entry| entry.devno);
+        res
+    }
+}
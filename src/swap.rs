@@ -0,0 +1,135 @@
+use std::{
+    ffi::CString,
+    fs::{self, File},
+    io,
+    os::unix::prelude::{FileExt, FileTypeExt, MetadataExt, OsStrExt},
+    path::Path,
+};
+
+use crate::{parse, parttable::Guid, Devno, Error, Result};
+
+const SWAP_MAGIC: &[u8] = b"SWAPSPACE2";
+const SWAP_HEADER_OFFSET: u64 = 1024;
+const SWAP_FLAG_PREFER: libc::c_int = 0x8000;
+const SWAP_FLAG_PRIO_MASK: libc::c_int = 0x7fff;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SwapHeader {
+    pub page_size: u64,
+    pub version: u32,
+    pub last_page: u32,
+    pub bad_pages: u32,
+    pub uuid: Option<Guid>,
+    pub label: Option<String>,
+}
+
+impl SwapHeader {
+    pub fn read(f: &File) -> Result<Option<Self>> {
+        // the signature sits at the end of the first page, whatever its size was
+        for shift in 12..=16 {
+            let page_size = 1u64 << shift;
+            let mut magic = [0u8; SWAP_MAGIC.len()];
+            match f.read_exact_at(&mut magic, page_size - SWAP_MAGIC.len() as u64) {
+                Ok(()) => (),
+                Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err.into()),
+            }
+            if magic != SWAP_MAGIC {
+                continue;
+            }
+
+            let mut raw = [0u8; 44];
+            f.read_exact_at(&mut raw, SWAP_HEADER_OFFSET)?;
+            // mkswap writes the header in the byte order of the machine it ran on
+            let le = u32::from_le_bytes(raw[0..4].try_into().unwrap()) == 1;
+            let u32_at = |off: usize| {
+                let bytes = raw[off..off + 4].try_into().unwrap();
+                if le {
+                    u32::from_le_bytes(bytes)
+                } else {
+                    u32::from_be_bytes(bytes)
+                }
+            };
+
+            // the uuid is stored as plain big-endian bytes, unlike GPT guids
+            let mut uuid: [u8; 16] = raw[12..28].try_into().unwrap();
+            uuid[0..4].reverse();
+            uuid[4..6].reverse();
+            uuid[6..8].reverse();
+            let uuid = Guid::from_bytes(uuid);
+            let label = raw[28..44]
+                .split(|c| *c == 0)
+                .next()
+                .filter(|l| !l.is_empty())
+                .map(|l| String::from_utf8_lossy(l).into_owned());
+
+            return Ok(Some(Self {
+                page_size,
+                version: u32_at(0),
+                last_page: u32_at(4),
+                bad_pages: u32_at(8),
+                uuid: (!uuid.is_zero()).then_some(uuid),
+                label,
+            }));
+        }
+        Ok(None)
+    }
+
+    #[inline]
+    pub fn size(&self) -> u64 {
+        (self.last_page as u64 + 1) * self.page_size
+    }
+}
+
+fn cpath(p: &Path) -> Result<CString> {
+    CString::new(p.as_os_str().as_bytes()).map_err(|_| Error::from(io::ErrorKind::InvalidInput))
+}
+
+pub(crate) fn swapon(p: &Path, priority: Option<i32>) -> Result<()> {
+    let f = File::open(p).map_err(|err| Error::path(p, err))?;
+    if SwapHeader::read(&f)?.is_none() {
+        return Err(Error::path(
+            p,
+            io::Error::new(io::ErrorKind::InvalidData, "no swap signature found"),
+        ));
+    }
+    drop(f);
+
+    let flags = match priority {
+        Some(prio) => SWAP_FLAG_PREFER | (prio.max(0) & SWAP_FLAG_PRIO_MASK),
+        None => 0,
+    };
+    let path = cpath(p)?;
+    if unsafe { libc::swapon(path.as_ptr(), flags) } < 0 {
+        Err(Error::path(p, io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn swapoff(p: &Path) -> Result<()> {
+    let path = cpath(p)?;
+    if unsafe { libc::swapoff(path.as_ptr()) } < 0 {
+        Err(Error::path(p, io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn is_active(procfs: &Path, devno: &Devno) -> Result<bool> {
+    let p = procfs.join("swaps");
+    let swaps = fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+    let swaps = parse::swaps::from_str(&swaps).map_err(|err| Error::ParseError {
+        file: p,
+        line: Some(err.line),
+    })?;
+    for swap in swaps {
+        if fs::metadata(&swap.path)
+            .is_ok_and(|md| md.file_type().is_block_device() && Devno::from(md.rdev()) == *devno)
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
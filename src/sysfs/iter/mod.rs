@@ -1,67 +1,126 @@
-use std::{fs::ReadDir, io, path::Path};
+use std::{
+    fs::ReadDir,
+    os::fd::{AsFd, OwnedFd},
+    path::{Path, PathBuf},
+};
 
-use crate::Devno;
+use crate::{Devno, Error, Result};
 
-use super::SysFs;
+use super::{open_dir, read_attr_at, SysFs};
 
+// entries are read relative to `fd` instead of walking their full path
 struct DirIterator {
+    path: PathBuf,
+    fd: OwnedFd,
     dir: ReadDir,
 }
 
 impl DirIterator {
     #[inline]
-    pub(crate) fn new<P: AsRef<Path>>(p: P) -> io::Result<Self> {
+    pub(crate) fn new<P: AsRef<Path>>(p: P) -> Result<Self> {
+        let path = p.as_ref().to_path_buf();
         Ok(Self {
-            dir: std::fs::read_dir(p)?,
+            fd: open_dir(&path).map_err(|err| Error::path(&path, err))?,
+            dir: std::fs::read_dir(&path).map_err(|err| Error::path(&path, err))?,
+            path,
         })
     }
 }
 
 impl Iterator for DirIterator {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let dev = match self.dir.next()? {
-            Ok(dev) => dev,
-            Err(err) => return Some(Err(err)),
-        }
-        .path();
+        let name = match self.dir.next()? {
+            Ok(entry) => entry.file_name(),
+            Err(err) => return Some(Err(Error::path(&self.path, err))),
+        };
+        let name = format!("{}/dev", name.to_string_lossy());
 
-        match match std::fs::read_to_string(dev.join("dev")) {
-            Ok(d) => d,
-            Err(err) => return Some(Err(err)),
-        }
-        .trim()
-        .parse::<Devno>()
-        {
-            Ok(devno) => Some(Ok(devno)),
-            Err(_) => Some(Err(io::ErrorKind::InvalidData.into())),
+        match read_attr_at(self.fd.as_fd(), &self.path, &name) {
+            Ok(Some(dev)) => match dev.parse::<Devno>() {
+                Ok(devno) => Some(Ok(devno)),
+                Err(_) => Some(Err(Error::parse(self.path.join(name)))),
+            },
+            Ok(None) => Some(Err(Error::path(
+                self.path.join(name),
+                std::io::ErrorKind::NotFound.into(),
+            ))),
+            Err(err) => Some(Err(err)),
         }
     }
 }
 
-pub struct DisksIterator<'a> {
-    sysfs: &'a SysFs,
+pub struct DisksIterator {
+    sysfs: SysFs,
     inner: DirIterator,
+    skip_hidden: bool,
+    skip_empty: bool,
+    only_virtual: Option<bool>,
 }
 
-impl<'a> DisksIterator<'a> {
+impl DisksIterator {
     #[inline]
-    pub(crate) fn new(sysfs: &'a SysFs) -> io::Result<Self> {
+    pub(crate) fn new(sysfs: &SysFs) -> Result<Self> {
         Ok(Self {
             inner: DirIterator::new(sysfs.path().join("block"))?,
-            sysfs,
+            sysfs: sysfs.clone(),
+            skip_hidden: false,
+            skip_empty: false,
+            only_virtual: None,
         })
     }
+
+    #[inline]
+    pub fn skip_hidden(mut self, skip: bool) -> Self {
+        self.skip_hidden = skip;
+        self
+    }
+
+    #[inline]
+    pub fn skip_empty(mut self, skip: bool) -> Self {
+        self.skip_empty = skip;
+        self
+    }
+
+    #[inline]
+    pub fn physical_only(mut self) -> Self {
+        self.only_virtual = Some(false);
+        self
+    }
+
+    #[inline]
+    pub fn virtual_only(mut self) -> Self {
+        self.only_virtual = Some(true);
+        self
+    }
+
+    fn accept(&self, devno: &Devno) -> Result<bool> {
+        if !self.sysfs.is_wholedisk(devno)? {
+            return Ok(false);
+        }
+        if self.skip_hidden && self.sysfs.is_hidden(devno)? {
+            return Ok(false);
+        }
+        if self.skip_empty && self.sysfs.size(devno)? == 0 {
+            return Ok(false);
+        }
+        if let Some(only_virtual) = self.only_virtual {
+            if self.sysfs.is_virtual(devno)? != only_virtual {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
 }
 
-impl<'a> Iterator for DisksIterator<'a> {
-    type Item = io::Result<Devno>;
+impl Iterator for DisksIterator {
+    type Item = Result<Devno>;
 
     fn next(&mut self) -> Option<Self::Item> {
         loop {
             match self.inner.next()? {
-                Ok(devno) => match self.sysfs.is_wholedisk(&devno) {
+                Ok(devno) => match self.accept(&devno) {
                     Ok(true) => return Some(Ok(devno)),
                     Ok(false) => (),
                     Err(err) => return Some(Err(err)),
@@ -78,7 +137,7 @@ pub struct BlocksIterator {
 
 impl BlocksIterator {
     #[inline]
-    pub(crate) fn new(sysfs: &SysFs) -> io::Result<Self> {
+    pub(crate) fn new(sysfs: &SysFs) -> Result<Self> {
         Ok(Self {
             inner: DirIterator::new(sysfs.path().join("dev").join("block"))?,
         })
@@ -86,7 +145,7 @@ impl BlocksIterator {
 }
 
 impl Iterator for BlocksIterator {
-    type Item = io::Result<Devno>;
+    type Item = Result<Devno>;
 
     #[inline(always)]
     fn next(&mut self) -> Option<Self::Item> {
@@ -0,0 +1,125 @@
+use std::{io, path::Path};
+
+use super::{parse_hex, read_attr};
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MmcCardType {
+    Sd,
+    Mmc,
+    Sdio,
+    SdCombo,
+    Other(String),
+}
+
+impl From<String> for MmcCardType {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "SD" => Self::Sd,
+            "MMC" => Self::Mmc,
+            "SDIO" => Self::Sdio,
+            "SDcombo" => Self::SdCombo,
+            _ => Self::Other(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MmcArea {
+    User,
+    Boot(u8),
+    GeneralPurpose(u8),
+    Rpmb,
+}
+
+impl MmcArea {
+    fn from_name(name: &str) -> Self {
+        let number = |s: &str| s.parse().unwrap_or(0);
+        if let Some((_, n)) = name.split_once("boot") {
+            Self::Boot(number(n))
+        } else if name.ends_with("rpmb") {
+            Self::Rpmb
+        } else if let Some((_, n)) = name.split_once("gp") {
+            Self::GeneralPurpose(number(n))
+        } else {
+            Self::User
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MmcInfo {
+    pub card_type: MmcCardType,
+    pub area: MmcArea,
+    pub cid: String,
+    pub csd: Option<String>,
+    pub manufacturer_id: u32,
+    pub oem_id: u32,
+    pub name: String,
+    pub serial: u32,
+    pub date: Option<String>,
+    pub hw_revision: Option<String>,
+    pub fw_revision: Option<String>,
+    pub boot_partitions: Vec<String>,
+    pub rpmb: Option<String>,
+}
+
+impl MmcInfo {
+    pub(crate) fn find<P: AsRef<Path>>(device: P) -> Result<Option<Self>> {
+        let device = device.as_ref();
+        let name = match device.file_name().and_then(|n| n.to_str()) {
+            Some(name) if name.starts_with("mmcblk") => name,
+            _ => return Ok(None),
+        };
+
+        for dir in device.ancestors() {
+            if dir.join("cid").is_file() && dir.join("type").is_file() {
+                return Self::read(dir, name).map(Some);
+            }
+        }
+        Ok(None)
+    }
+
+    fn read(card: &Path, name: &str) -> Result<Self> {
+        let mut boot_partitions = Vec::new();
+        let mut rpmb = None;
+        for dir in [card.to_path_buf(), card.join("block")] {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in std::fs::read_dir(dir)? {
+                let entry = entry?.file_name().to_string_lossy().into_owned();
+                if !entry.starts_with("mmcblk") {
+                    continue;
+                }
+                match MmcArea::from_name(&entry) {
+                    MmcArea::Boot(_) => boot_partitions.push(entry),
+                    MmcArea::Rpmb => rpmb = Some(entry),
+                    _ => (),
+                }
+            }
+        }
+        boot_partitions.sort();
+
+        Ok(Self {
+            card_type: read_attr(card.join("type"))?
+                .ok_or(io::ErrorKind::InvalidData)?
+                .into(),
+            area: MmcArea::from_name(name),
+            cid: read_attr(card.join("cid"))?.ok_or(io::ErrorKind::InvalidData)?,
+            csd: read_attr(card.join("csd"))?,
+            manufacturer_id: parse_hex(read_attr(card.join("manfid"))?)?,
+            oem_id: parse_hex(read_attr(card.join("oemid"))?)?,
+            name: read_attr(card.join("name"))?.unwrap_or_default(),
+            serial: parse_hex(read_attr(card.join("serial"))?)?,
+            date: read_attr(card.join("date"))?,
+            hw_revision: read_attr(card.join("hwrev"))?,
+            fw_revision: read_attr(card.join("fwrev"))?,
+            boot_partitions,
+            rpmb,
+        })
+    }
+}
@@ -1,63 +1,382 @@
 pub mod iter;
+mod mmc;
+mod nd;
+mod pci;
+pub mod queue;
+mod uevent;
+mod usb;
+mod virtio;
 
 use std::{
-    io,
+    collections::BTreeMap,
+    ffi::CString,
+    fs::{File, OpenOptions},
+    io::{self, Read},
+    os::unix::prelude::*,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
 };
 
-use crate::{procfs::ProcFs, Devno};
+use crate::{magic, media, procfs::ProcFs, trace, Devno, DiskEvent, Error, Result};
 
-use self::iter::{BlocksIterator, DisksIterator};
+use self::{
+    iter::{BlocksIterator, DisksIterator},
+    queue::{QueueTuning, WriteCache},
+};
+pub use self::{
+    mmc::{MmcArea, MmcCardType, MmcInfo},
+    nd::{PmemInfo, PmemMode},
+    pci::{ParsePciAddressError, PciAddress},
+    uevent::{ParseUeventActionError, ParseUeventError, Uevent, UeventAction},
+    usb::UsbInfo,
+    virtio::{VirtioFeatures, VirtioInfo},
+};
+
+pub(crate) fn read_attr<P: AsRef<Path>>(p: P) -> Result<Option<String>> {
+    trace::trace!(path = %p.as_ref().display(), "sysfs read");
+    match std::fs::read_to_string(p.as_ref()) {
+        Ok(content) => Ok(Some(content.trim().to_string())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => {
+            trace::debug!(path = %p.as_ref().display(), error = %err, "sysfs read failed");
+            Err(Error::path(p.as_ref(), err))
+        }
+    }
+}
+
+fn openat(dir: BorrowedFd<'_>, name: &str, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let name = CString::new(name).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    let fd = unsafe { libc::openat(dir.as_raw_fd(), name.as_ptr(), flags | libc::O_CLOEXEC) };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+// `base` is where `dir` points to, only used to report errors
+pub(crate) fn read_attr_at(dir: BorrowedFd<'_>, base: &Path, name: &str) -> Result<Option<String>> {
+    trace::trace!(path = %base.join(name).display(), "sysfs read");
+    let res = openat(dir, name, libc::O_RDONLY).and_then(|fd| {
+        let mut content = String::new();
+        File::from(fd).read_to_string(&mut content)?;
+        Ok(content)
+    });
+    match res {
+        Ok(content) => Ok(Some(content.trim().to_string())),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => {
+            trace::debug!(path = %base.join(name).display(), error = %err, "sysfs read failed");
+            Err(Error::path(base.join(name), err))
+        }
+    }
+}
+
+pub(crate) fn open_dir<P: AsRef<Path>>(p: P) -> io::Result<OwnedFd> {
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_PATH | libc::O_DIRECTORY)
+        .open(p)
+        .map(OwnedFd::from)
+}
+
+pub(crate) fn parse_attr<T: std::str::FromStr, P: AsRef<Path>>(p: P) -> Result<Option<T>> {
+    match read_attr(p.as_ref())? {
+        Some(value) => match value.parse() {
+            Ok(value) => Ok(Some(value)),
+            Err(_) => Err(Error::parse(p.as_ref())),
+        },
+        None => Ok(None),
+    }
+}
+
+// ids like "0x1af4" or "046d", a missing attribute is as bad as a garbled one
+pub(crate) fn parse_hex<T: TryFrom<u64>>(s: Option<String>) -> Result<T> {
+    s.and_then(|s| u64::from_str_radix(s.trim_start_matches("0x"), 16).ok())
+        .and_then(|v| T::try_from(v).ok())
+        .ok_or_else(|| io::ErrorKind::InvalidData.into())
+}
+
+pub(crate) fn write_attr<P: AsRef<Path>, S: AsRef<[u8]>>(p: P, value: S) -> Result<()> {
+    std::fs::write(p.as_ref(), value).map_err(|err| Error::path(p.as_ref(), err))
+}
+
+// every cached directory holds an fd open, hosts with thousands of LUNs
+// would run out of them
+const MAX_CACHED_DIRS: usize = 256;
+
+#[derive(Default, Clone)]
+struct CachedAttrs {
+    path: Option<PathBuf>,
+    dir: Option<Arc<OwnedFd>>,
+    dm_uuid: Option<Option<String>>,
+    partition_number: Option<Option<usize>>,
+}
 
+#[derive(Default)]
+struct AttrCache {
+    enabled: AtomicBool,
+    entries: RwLock<BTreeMap<Devno, CachedAttrs>>,
+    // filled by the /sys/block scan, kept even with caching off since every
+    // lookup would rescan otherwise
+    scanned: RwLock<BTreeMap<Devno, PathBuf>>,
+}
+
+impl AttrCache {
+    fn get<T, F: FnOnce(&CachedAttrs) -> Option<T>>(&self, devno: &Devno, f: F) -> Option<T> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        self.entries.read().unwrap().get(devno).and_then(f)
+    }
+
+    fn update<F: FnOnce(&mut CachedAttrs)>(&self, devno: &Devno, f: F) {
+        if self.enabled.load(Ordering::Relaxed) {
+            f(self.entries.write().unwrap().entry(*devno).or_default())
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct SysFs {
     path: PathBuf,
+    cache: Arc<AttrCache>,
 }
 
 impl SysFs {
-    pub fn new(procfs: &ProcFs) -> io::Result<Self> {
-        let path = match procfs.mounts().find(|m| {
-            m.file_system == "sysfs" && matches!(m.source.as_ref().map(|x| x.as_str()), Some("sys"))
-        })? {
-            Some(pp) => pp.mount_point,
-            None => return Err(io::Error::new(io::ErrorKind::NotFound, "sysfs not found")),
+    pub fn new(procfs: &ProcFs) -> Result<Self> {
+        let path = match procfs
+            .mounts()
+            .find_mount_point(|m| m.file_system == "sysfs", &["/sys"])?
+        {
+            Some(p) => p,
+            None if magic::is_fs("/sys", &[magic::SYSFS_MAGIC]) => PathBuf::from("/sys"),
+            None => return Err(Error::SysfsNotFound),
         };
-        Ok(Self { path })
+        Ok(Self {
+            path,
+            cache: Arc::default(),
+        })
     }
 
-    pub fn resolve(&self, devno: &Devno) -> io::Result<PathBuf> {
-        let path = self.path.join("dev").join("block").join(devno.to_string());
-        if path.exists() {
-            path.canonicalize()
-        } else {
-            Err(io::ErrorKind::NotFound.into())
+    #[inline]
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Self {
+        Self {
+            path: p.as_ref().to_path_buf(),
+            cache: Arc::default(),
         }
     }
 
-    pub fn dm_name(&self, devno: &Devno) -> io::Result<Option<String>> {
-        let p = self.resolve(devno)?;
-        let dmname_path = p.join("dm").join("name");
-        if dmname_path.exists() {
-            Ok(Some(
-                std::fs::read_to_string(dmname_path)?.trim().to_string(),
-            ))
-        } else {
-            Ok(None)
+    #[inline]
+    pub fn set_caching(&self, enabled: bool) {
+        self.cache.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.clear_cache();
         }
     }
 
-    pub fn dm_uuid(&self, devno: &Devno) -> io::Result<Option<String>> {
-        let p = self.resolve(devno)?;
-        let dmuuid_path = p.join("dm").join("uuid");
-        if dmuuid_path.exists() {
-            Ok(Some(
-                std::fs::read_to_string(dmuuid_path)?.trim().to_string(),
-            ))
+    #[inline]
+    pub fn is_caching(&self) -> bool {
+        self.cache.enabled.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn invalidate(&self, devno: &Devno) {
+        self.cache.entries.write().unwrap().remove(devno);
+        self.cache.scanned.write().unwrap().remove(devno);
+    }
+
+    #[inline]
+    pub fn clear_cache(&self) {
+        self.cache.entries.write().unwrap().clear();
+        self.cache.scanned.write().unwrap().clear();
+    }
+
+    #[inline]
+    fn node(&self, devno: &Devno) -> PathBuf {
+        self.path.join("dev").join("block").join(devno.to_string())
+    }
+
+    pub fn resolve(&self, devno: &Devno) -> Result<PathBuf> {
+        if let Some(path) = self.cache.get(devno, |c| c.path.clone()) {
+            return Ok(path);
+        }
+
+        let path = self.node(devno);
+        let path = if path.exists() {
+            path.canonicalize().map_err(|err| Error::path(&path, err))?
+        } else if let Some(path) = self.scan(devno)? {
+            path
         } else {
-            Ok(None)
+            trace::debug!(%devno, path = %path.display(), "sysfs node not found");
+            return Err(Error::NotFound { devno: *devno });
+        };
+        self.cache.update(devno, |c| c.path = Some(path.clone()));
+        Ok(path)
+    }
+
+    fn read_dev(dir: &Path) -> Option<Devno> {
+        std::fs::read_to_string(dir.join("dev"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    // for kernels or containers without /sys/dev/block, look through the
+    // disks in /sys/block and their partitions
+    fn scan(&self, devno: &Devno) -> Result<Option<PathBuf>> {
+        if let Some(path) = self.cache.scanned.read().unwrap().get(devno) {
+            if Self::read_dev(path).as_ref() == Some(devno) {
+                return Ok(Some(path.clone()));
+            }
+        }
+
+        let block = self.path.join("block");
+        let disks = match std::fs::read_dir(&block) {
+            Ok(disks) => disks,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::path(block, err)),
+        };
+
+        let mut found = BTreeMap::new();
+        for disk in disks {
+            let disk = disk.map_err(|err| Error::path(&block, err))?.path();
+            let disk = match disk.canonicalize() {
+                Ok(disk) => disk,
+                Err(_) => continue,
+            };
+            if let Some(d) = Self::read_dev(&disk) {
+                found.insert(d, disk.clone());
+            }
+            for part in std::fs::read_dir(&disk).into_iter().flatten().flatten() {
+                let part = part.path();
+                if part.join("partition").exists() {
+                    if let Some(d) = Self::read_dev(&part) {
+                        found.insert(d, part);
+                    }
+                }
+            }
+        }
+
+        trace::debug!(devices = found.len(), "scanned /sys/block");
+        let res = found.get(devno).cloned();
+        *self.cache.scanned.write().unwrap() = found;
+        Ok(res)
+    }
+
+    // with caching on attributes are read relative to this, sparing the path
+    // walk on every read and pinning the node while it is held
+    pub fn dirfd(&self, devno: &Devno) -> Result<Arc<OwnedFd>> {
+        if let Some(dir) = self.cache.get(devno, |c| c.dir.clone()) {
+            return Ok(dir);
+        }
+
+        let mut path = self.node(devno);
+        if !path.exists() {
+            path = self.resolve(devno)?;
+        }
+        let dir = match open_dir(&path) {
+            Ok(dir) => Arc::new(dir),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                trace::debug!(%devno, path = %path.display(), "sysfs node not found");
+                return Err(Error::NotFound { devno: *devno });
+            }
+            Err(err) => return Err(Error::path(path, err)),
+        };
+        if self.is_caching() {
+            let mut entries = self.cache.entries.write().unwrap();
+            if entries.values().filter(|c| c.dir.is_some()).count() < MAX_CACHED_DIRS {
+                entries.entry(*devno).or_default().dir = Some(Arc::clone(&dir));
+            }
         }
+        Ok(dir)
     }
 
-    pub fn dm_type(&self, devno: &Devno) -> io::Result<Option<String>> {
+    // a removed device leaves the held directory empty and a new one reusing
+    // the devno gets a directory of its own
+    fn is_stale(&self, devno: &Devno, dir: &OwnedFd) -> bool {
+        let mut held = std::mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(dir.as_raw_fd(), held.as_mut_ptr()) } != 0 {
+            return true;
+        }
+        let held = unsafe { held.assume_init() };
+        match std::fs::metadata(self.node(devno)) {
+            Ok(md) => md.ino() != held.st_ino || md.dev() != held.st_dev,
+            Err(_) => true,
+        }
+    }
+
+    fn attr(&self, devno: &Devno, name: &str) -> Result<Option<String>> {
+        if !self.is_caching() {
+            let node = self.node(devno);
+            return match read_attr(node.join(name))? {
+                Some(value) => Ok(Some(value)),
+                // tell a missing attribute from a missing device
+                None if node.exists() => Ok(None),
+                None => read_attr(self.resolve(devno)?.join(name)),
+            };
+        }
+
+        let dir = self.dirfd(devno)?;
+        match read_attr_at(dir.as_fd(), &self.node(devno), name)? {
+            Some(value) => Ok(Some(value)),
+            None if self.is_stale(devno, &dir) => {
+                self.invalidate(devno);
+                read_attr_at(self.dirfd(devno)?.as_fd(), &self.node(devno), name)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn parse<T: std::str::FromStr>(&self, devno: &Devno, name: &str) -> Result<Option<T>> {
+        match self.attr(devno, name)? {
+            Some(value) => match value.parse() {
+                Ok(value) => Ok(Some(value)),
+                Err(_) => Err(Error::parse(self.node(devno).join(name))),
+            },
+            None => Ok(None),
+        }
+    }
+
+    #[inline]
+    fn flag(&self, devno: &Devno, name: &str) -> Result<bool> {
+        Ok(self.attr(devno, name)?.as_deref() == Some("1"))
+    }
+
+    #[inline]
+    pub(crate) fn cached_name(&self, devno: &Devno) -> Option<String> {
+        self.cache
+            .get(devno, |c| c.path.clone())?
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+    }
+
+    pub fn name(&self, devno: &Devno) -> Result<String> {
+        match self.resolve(devno)?.file_name() {
+            Some(name) => Ok(name.to_string_lossy().into_owned()),
+            None => Err(io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    pub fn dm_name(&self, devno: &Devno) -> Result<Option<String>> {
+        self.attr(devno, "dm/name")
+    }
+
+    pub fn dm_uuid(&self, devno: &Devno) -> Result<Option<String>> {
+        if let Some(uuid) = self.cache.get(devno, |c| c.dm_uuid.clone()) {
+            return Ok(uuid);
+        }
+
+        let uuid = self.attr(devno, "dm/uuid")?;
+        self.cache.update(devno, |c| c.dm_uuid = Some(uuid.clone()));
+        Ok(uuid)
+    }
+
+    pub fn dm_type(&self, devno: &Devno) -> Result<Option<String>> {
         let t = match self.dm_uuid(devno)? {
             Some(t) => t,
             None => return Ok(None),
@@ -84,25 +403,30 @@ impl SysFs {
         Ok(Some(res))
     }
 
-    pub fn partition_number(&self, devno: &Devno) -> io::Result<Option<usize>> {
-        let p = self.resolve(devno)?;
+    pub fn partition_number(&self, devno: &Devno) -> Result<Option<usize>> {
+        if let Some(partno) = self.cache.get(devno, |c| c.partition_number) {
+            return Ok(partno);
+        }
+
+        let partno = self.read_partition_number(devno)?;
+        self.cache
+            .update(devno, |c| c.partition_number = Some(partno));
+        Ok(partno)
+    }
 
-        let ppath = p.join("partition");
-        if ppath.exists() {
-            match std::fs::read_to_string(ppath)?.trim().parse::<usize>() {
-                Ok(partno) => Ok(Some(partno)),
-                Err(_) => Err(io::ErrorKind::InvalidData.into()),
-            }
+    fn read_partition_number(&self, devno: &Devno) -> Result<Option<usize>> {
+        if let Some(partno) = self.parse(devno, "partition")? {
+            Ok(Some(partno))
         } else {
             let dm_uuid = match self.dm_uuid(devno)? {
                 Some(u) => u,
                 None => return Ok(None),
             };
 
-            if dm_uuid.starts_with("part") {
-                match dm_uuid[4..].splitn(2, '-').next().unwrap().parse::<usize>() {
+            if let Some(rest) = dm_uuid.strip_prefix("part") {
+                match rest.split('-').next().unwrap().parse::<usize>() {
                     Ok(partno) => Ok(Some(partno)),
-                    Err(_) => Err(io::ErrorKind::InvalidData.into()),
+                    Err(_) => Err(Error::parse(self.node(devno).join("dm").join("uuid"))),
                 }
             } else {
                 Ok(None)
@@ -110,13 +434,224 @@ impl SysFs {
         }
     }
 
+    pub fn usb_device_path(&self, devno: &Devno) -> Result<Option<PathBuf>> {
+        Ok(usb::find_usb_device(self.resolve(devno)?))
+    }
+
+    pub fn usb_info(&self, devno: &Devno) -> Result<Option<UsbInfo>> {
+        UsbInfo::find(self.resolve(devno)?)
+    }
+
+    pub fn pci_address(&self, devno: &Devno) -> Result<Option<PciAddress>> {
+        Ok(PciAddress::find(self.resolve(devno)?))
+    }
+
+    pub fn numa_node(&self, devno: &Devno) -> Result<Option<u32>> {
+        pci::numa_node(self.resolve(devno)?)
+    }
+
+    pub fn mmc_info(&self, devno: &Devno) -> Result<Option<MmcInfo>> {
+        MmcInfo::find(self.resolve(devno)?)
+    }
+
+    pub fn virtio_info(&self, devno: &Devno) -> Result<Option<VirtioInfo>> {
+        VirtioInfo::find(self.resolve(devno)?)
+    }
+
+    pub fn pmem_info(&self, devno: &Devno) -> Result<Option<PmemInfo>> {
+        PmemInfo::find(self.resolve(devno)?)
+    }
+
+    pub fn is_pmem(&self, devno: &Devno) -> Result<bool> {
+        Ok(nd::find_region(self.resolve(devno)?).is_some())
+    }
+
+    pub fn size(&self, devno: &Devno) -> Result<u64> {
+        match self.parse::<u64>(devno, "size")? {
+            Some(sectors) => Ok(sectors * 512),
+            None => Err(Error::parse(self.node(devno).join("size"))),
+        }
+    }
+
+    pub fn logical_block_size(&self, devno: &Devno) -> Result<u64> {
+        Ok(self
+            .parse(devno, "queue/logical_block_size")?
+            .unwrap_or(512))
+    }
+
+    pub fn physical_block_size(&self, devno: &Devno) -> Result<u64> {
+        Ok(self
+            .parse(devno, "queue/physical_block_size")?
+            .unwrap_or(512))
+    }
+
+    pub fn optimal_io_size(&self, devno: &Devno) -> Result<Option<u64>> {
+        Ok(self
+            .parse(devno, "queue/optimal_io_size")?
+            .filter(|size| *size != 0))
+    }
+
+    pub fn alignment_offset(&self, devno: &Devno) -> Result<u64> {
+        Ok(self.parse(devno, "alignment_offset")?.unwrap_or(0))
+    }
+
+    pub fn write_cache(&self, devno: &Devno) -> Result<Option<WriteCache>> {
+        self.parse(devno, "queue/write_cache")
+    }
+
+    pub fn set_write_cache(&self, devno: &Devno, mode: WriteCache) -> Result<()> {
+        write_attr(
+            self.resolve(devno)?.join("queue").join("write_cache"),
+            mode.to_string(),
+        )
+    }
+
+    pub fn fua(&self, devno: &Devno) -> Result<bool> {
+        self.flag(devno, "queue/fua")
+    }
+
+    pub fn queue_tuning(&self, devno: &Devno) -> Result<QueueTuning> {
+        QueueTuning::read(&self.resolve(devno)?.join("queue"))
+    }
+
+    pub fn set_queue_tuning(&self, devno: &Devno, tuning: &QueueTuning) -> Result<()> {
+        tuning.apply(&self.resolve(devno)?.join("queue"))
+    }
+
+    pub fn partition_start(&self, devno: &Devno) -> Result<Option<u64>> {
+        Ok(self.parse::<u64>(devno, "start")?.map(|start| start * 512))
+    }
+
+    pub fn is_hidden(&self, devno: &Devno) -> Result<bool> {
+        self.flag(devno, "hidden")
+    }
+
+    pub fn capability(&self, devno: &Devno) -> Result<Option<u32>> {
+        match self.attr(devno, "capability")? {
+            Some(raw) => u32::from_str_radix(&raw, 16)
+                .map(Some)
+                .map_err(|_| Error::parse(self.node(devno).join("capability"))),
+            None => Ok(None),
+        }
+    }
+
+    pub fn is_read_only(&self, devno: &Devno) -> Result<bool> {
+        self.flag(devno, "ro")
+    }
+
+    pub fn is_removable(&self, devno: &Devno) -> Result<bool> {
+        self.flag(devno, "removable")
+    }
+
+    pub fn supports_dax(&self, devno: &Devno) -> Result<bool> {
+        self.flag(devno, "queue/dax")
+    }
+
+    pub fn is_rotational(&self, devno: &Devno) -> Result<bool> {
+        self.flag(devno, "queue/rotational")
+    }
+
+    pub fn has_holders(&self, devno: &Devno) -> Result<bool> {
+        let p = self.resolve(devno)?.join("holders");
+        match std::fs::read_dir(&p) {
+            Ok(mut dir) => Ok(dir.next().is_some()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(Error::path(p, err)),
+        }
+    }
+
+    pub fn events(&self, devno: &Devno) -> Result<Vec<DiskEvent>> {
+        match self.attr(devno, "events")? {
+            Some(events) => media::parse_events(&events)
+                .map_err(|_| Error::parse(self.node(devno).join("events"))),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    pub fn events_poll_msecs(&self, devno: &Devno) -> Result<Option<u64>> {
+        match self.parse::<i64>(devno, "events_poll_msecs")? {
+            Some(ms) if ms >= 0 => Ok(Some(ms as u64)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn set_events_poll_msecs(&self, devno: &Devno, ms: Option<u64>) -> Result<()> {
+        let value = match ms {
+            Some(ms) => ms.to_string(),
+            None => "-1".to_string(),
+        };
+        write_attr(self.resolve(devno)?.join("events_poll_msecs"), value)
+    }
+
+    pub fn diskseq(&self, devno: &Devno) -> Result<Option<u64>> {
+        self.parse(devno, "diskseq")
+    }
+
+    pub fn uevent(&self, devno: &Devno) -> Result<Uevent> {
+        let p = self.node(devno).join("uevent");
+        match self.attr(devno, "uevent")? {
+            Some(content) => content.parse().map_err(|_| Error::parse(p)),
+            None => Err(Error::path(p, io::ErrorKind::NotFound.into())),
+        }
+    }
+
+    pub fn trigger_uevent(&self, devno: &Devno, action: UeventAction) -> Result<()> {
+        write_attr(self.resolve(devno)?.join("uevent"), action.to_string())
+    }
+
+    pub fn delete(&self, devno: &Devno) -> Result<()> {
+        let p = self.resolve(devno)?.join("device").join("delete");
+        if p.exists() {
+            write_attr(p, "1")
+        } else {
+            Err(io::Error::new(io::ErrorKind::Unsupported, "device is not a SCSI device").into())
+        }
+    }
+
+    pub fn rescan(&self, devno: &Devno) -> Result<()> {
+        let p = self.resolve(devno)?.join("device");
+        if p.join("rescan_controller").exists() {
+            write_attr(p.join("rescan_controller"), "1")
+        } else if p.join("rescan").exists() {
+            write_attr(p.join("rescan"), "1")
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "device does not support rescanning",
+            )
+            .into())
+        }
+    }
+
+    pub fn rescan_scsi_host(&self, host: u32) -> Result<()> {
+        let p = self
+            .path
+            .join("class")
+            .join("scsi_host")
+            .join(format!("host{}", host))
+            .join("scan");
+        if p.exists() {
+            write_attr(p, "- - -")
+        } else {
+            Err(Error::path(p, io::ErrorKind::NotFound.into()))
+        }
+    }
+
     #[inline]
-    pub fn is_partition(&self, devno: &Devno) -> io::Result<bool> {
+    pub fn is_partition(&self, devno: &Devno) -> Result<bool> {
         self.partition_number(devno).map(|x| x.is_some())
     }
 
     #[inline]
-    pub fn is_wholedisk(&self, devno: &Devno) -> io::Result<bool> {
+    // loop, ram, zram, dm, md and nbd devices have no parent device and are
+    // registered under /sys/devices/virtual
+    pub fn is_virtual(&self, devno: &Devno) -> Result<bool> {
+        let path = self.resolve(devno)?;
+        let mut it = path.iter().skip_while(|c| *c != "devices").skip(1);
+        Ok(it.next().is_some_and(|c| c == "virtual"))
+    }
+
+    pub fn is_wholedisk(&self, devno: &Devno) -> Result<bool> {
         self.is_partition(devno).map(|x| !x)
     }
 
@@ -126,12 +661,110 @@ impl SysFs {
     }
 
     #[inline]
-    pub fn disks(&self) -> io::Result<DisksIterator> {
-        DisksIterator::new(&self)
+    pub fn disks(&self) -> Result<DisksIterator> {
+        DisksIterator::new(self)
     }
 
     #[inline]
-    pub fn blocks(&self) -> io::Result<BlocksIterator> {
-        BlocksIterator::new(&self)
+    pub fn blocks(&self) -> Result<BlocksIterator> {
+        BlocksIterator::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_ids() {
+        assert_eq!(parse_hex::<u16>(Some("0x1af4".into())).unwrap(), 0x1af4);
+        assert_eq!(parse_hex::<u16>(Some("046d".into())).unwrap(), 0x046d);
+        assert_eq!(
+            parse_hex::<u32>(Some("0x12345678".into())).unwrap(),
+            0x1234_5678
+        );
+        assert!(parse_hex::<u16>(Some("0x10000".into())).is_err());
+        assert!(parse_hex::<u16>(Some("zz".into())).is_err());
+        assert!(parse_hex::<u16>(None).is_err());
+    }
+
+    struct Tree(PathBuf);
+
+    impl Tree {
+        fn new(name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "linux-blocks-sysfs-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(root.join("dev/block")).unwrap();
+            Self(root)
+        }
+
+        // a fresh directory for the device, a new inode every time
+        fn device(&self, dir: &str, devno: &str, size: &str) {
+            let path = self.0.join("devices/virtual/block").join(dir);
+            std::fs::create_dir_all(&path).unwrap();
+            std::fs::write(path.join("dev"), format!("{}\n", devno)).unwrap();
+            std::fs::write(path.join("size"), format!("{}\n", size)).unwrap();
+            let link = self.0.join("dev/block").join(devno);
+            let _ = std::fs::remove_file(&link);
+            std::os::unix::fs::symlink(&path, link).unwrap();
+        }
+
+        fn remove(&self, dir: &str) {
+            std::fs::remove_dir_all(self.0.join("devices/virtual/block").join(dir)).unwrap();
+        }
+    }
+
+    impl Drop for Tree {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn attrs_uncached() {
+        let tree = Tree::new("uncached");
+        tree.device("loop0", "7:0", "8");
+        let sysfs = SysFs::with_path(&tree.0);
+        let devno = Devno::new(7, 0);
+
+        assert_eq!(sysfs.size(&devno).unwrap(), 4096);
+        assert_eq!(sysfs.attr(&devno, "missing").unwrap(), None);
+        assert!(sysfs.cache.entries.read().unwrap().is_empty());
+
+        tree.remove("loop0");
+        assert!(matches!(
+            sysfs.attr(&devno, "size"),
+            Err(Error::NotFound { .. })
+        ));
+    }
+
+    #[test]
+    fn attrs_cached_reused_devno() {
+        let tree = Tree::new("cached");
+        tree.device("loop0", "7:0", "8");
+        let sysfs = SysFs::with_path(&tree.0);
+        sysfs.set_caching(true);
+        let devno = Devno::new(7, 0);
+
+        assert_eq!(sysfs.size(&devno).unwrap(), 4096);
+        let held = sysfs.dirfd(&devno).unwrap();
+        assert_eq!(sysfs.attr(&devno, "missing").unwrap(), None);
+        assert!(Arc::ptr_eq(&held, &sysfs.dirfd(&devno).unwrap()));
+
+        // the old directory is gone and another device took the devno
+        tree.remove("loop0");
+        tree.device("loop0", "7:0", "16");
+        assert_eq!(sysfs.size(&devno).unwrap(), 8192);
+        assert!(!Arc::ptr_eq(&held, &sysfs.dirfd(&devno).unwrap()));
+
+        tree.remove("loop0");
+        assert!(matches!(
+            sysfs.attr(&devno, "size"),
+            Err(Error::NotFound { .. })
+        ));
     }
 }
@@ -0,0 +1,112 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{parse_attr, read_attr};
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PmemMode {
+    Fsdax,
+    Sector,
+    Devdax,
+    Raw,
+    Other(String),
+}
+
+impl From<String> for PmemMode {
+    fn from(s: String) -> Self {
+        match s.as_str() {
+            "fsdax" | "memory" => Self::Fsdax,
+            "sector" | "safe" => Self::Sector,
+            "devdax" | "dax" => Self::Devdax,
+            "raw" => Self::Raw,
+            _ => Self::Other(s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PmemInfo {
+    pub bus: String,
+    pub region: String,
+    pub namespace: String,
+    pub mode: PmemMode,
+    pub region_size: Option<u64>,
+    pub namespace_size: Option<u64>,
+    pub uuid: Option<String>,
+    pub align: Option<u64>,
+    pub numa_node: Option<u32>,
+}
+
+#[inline]
+fn dir_name(dir: &Path) -> Option<&str> {
+    dir.file_name().and_then(|n| n.to_str())
+}
+
+// the region is the directory right below the ndbus in the device path
+pub(crate) fn find_region<P: AsRef<Path>>(device: P) -> Option<PathBuf> {
+    device
+        .as_ref()
+        .ancestors()
+        .find(|dir| {
+            dir_name(dir).is_some_and(|n| n.starts_with("region"))
+                && dir
+                    .parent()
+                    .and_then(dir_name)
+                    .is_some_and(|n| n.starts_with("ndbus"))
+        })
+        .map(Path::to_path_buf)
+}
+
+impl PmemInfo {
+    pub(crate) fn find<P: AsRef<Path>>(device: P) -> Result<Option<Self>> {
+        let device = device.as_ref();
+        let region = match find_region(device) {
+            Some(region) => region,
+            None => return Ok(None),
+        };
+        // either the namespace itself or a btt/pfn/dax personality claiming one
+        let child = match device
+            .strip_prefix(&region)
+            .ok()
+            .and_then(|p| p.iter().next())
+        {
+            Some(child) => region.join(child),
+            None => return Ok(None),
+        };
+        let namespace = match read_attr(child.join("namespace"))? {
+            Some(ns) if !ns.is_empty() => region.join(ns),
+            _ => child,
+        };
+        Self::read(&region, &namespace).map(Some)
+    }
+
+    fn read(region: &Path, namespace: &Path) -> Result<Self> {
+        let name = |dir: &Path| {
+            dir_name(dir)
+                .map(str::to_string)
+                .ok_or(io::ErrorKind::InvalidData)
+        };
+
+        Ok(Self {
+            bus: region
+                .parent()
+                .ok_or(io::ErrorKind::InvalidData)
+                .and_then(name)?,
+            region: name(region)?,
+            namespace: name(namespace)?,
+            mode: read_attr(namespace.join("mode"))?
+                .unwrap_or_else(|| "raw".to_string())
+                .into(),
+            region_size: parse_attr(region.join("size"))?,
+            namespace_size: parse_attr(namespace.join("size"))?,
+            uuid: read_attr(namespace.join("uuid"))?.filter(|u| !u.is_empty()),
+            align: parse_attr(region.join("align"))?,
+            numa_node: read_attr(region.join("numa_node"))?.and_then(|n| n.parse().ok()),
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use std::{io, path::Path, str::FromStr};
+
+use super::read_attr;
+use crate::Result;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PciAddress {
+    pub domain: u32,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+#[derive(Debug)]
+pub struct ParsePciAddressError;
+
+impl FromStr for PciAddress {
+    type Err = ParsePciAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.split(':');
+        let domain = it.next().ok_or(ParsePciAddressError)?;
+        let bus = it.next().ok_or(ParsePciAddressError)?;
+        let (device, function) = it
+            .next()
+            .and_then(|x| x.split_once('.'))
+            .ok_or(ParsePciAddressError)?;
+        if it.next().is_some() || domain.len() != 4 || bus.len() != 2 || device.len() != 2 {
+            return Err(ParsePciAddressError);
+        }
+
+        Ok(Self {
+            domain: u32::from_str_radix(domain, 16).map_err(|_| ParsePciAddressError)?,
+            bus: u8::from_str_radix(bus, 16).map_err(|_| ParsePciAddressError)?,
+            device: u8::from_str_radix(device, 16).map_err(|_| ParsePciAddressError)?,
+            function: u8::from_str_radix(function, 16).map_err(|_| ParsePciAddressError)?,
+        })
+    }
+}
+
+impl std::fmt::Display for PciAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:04x}:{:02x}:{:02x}.{:x}",
+            self.domain, self.bus, self.device, self.function
+        )
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for PciAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PciAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| serde::de::Error::custom(format_args!("invalid PCI address {:?}", s)))
+    }
+}
+
+impl PciAddress {
+    pub(crate) fn find<P: AsRef<Path>>(device: P) -> Option<Self> {
+        device
+            .as_ref()
+            .ancestors()
+            .filter_map(|dir| dir.file_name()?.to_str()?.parse().ok())
+            .next()
+    }
+}
+
+pub(crate) fn numa_node<P: AsRef<Path>>(device: P) -> Result<Option<u32>> {
+    for dir in device.as_ref().ancestors() {
+        if let Some(node) = read_attr(dir.join("numa_node"))? {
+            return match node.parse::<i32>() {
+                Ok(n) if n < 0 => Ok(None),
+                Ok(n) => Ok(Some(n as u32)),
+                Err(_) => Err(io::ErrorKind::InvalidData.into()),
+            };
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,331 @@
+use std::{io, path::Path, str::FromStr};
+
+use super::{parse_attr, write_attr};
+use crate::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WriteCache {
+    WriteBack,
+    WriteThrough,
+}
+
+impl WriteCache {
+    #[inline]
+    pub fn is_volatile(&self) -> bool {
+        matches!(self, Self::WriteBack)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("invalid write cache mode, expected \"write back\" or \"write through\"")]
+pub struct ParseWriteCacheError;
+
+impl FromStr for WriteCache {
+    type Err = ParseWriteCacheError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "write back" => Ok(Self::WriteBack),
+            "write through" => Ok(Self::WriteThrough),
+            _ => Err(ParseWriteCacheError),
+        }
+    }
+}
+
+impl std::fmt::Display for WriteCache {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            Self::WriteBack => "write back",
+            Self::WriteThrough => "write through",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NoMerges {
+    Enabled,
+    SimpleOnly,
+    Disabled,
+}
+
+impl NoMerges {
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Enabled),
+            1 => Some(Self::SimpleOnly),
+            2 => Some(Self::Disabled),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn raw(&self) -> u8 {
+        match self {
+            Self::Enabled => 0,
+            Self::SimpleOnly => 1,
+            Self::Disabled => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RqAffinity {
+    Off,
+    Group,
+    Cpu,
+}
+
+impl RqAffinity {
+    fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Off),
+            1 => Some(Self::Group),
+            2 => Some(Self::Cpu),
+            _ => None,
+        }
+    }
+
+    #[inline]
+    fn raw(&self) -> u8 {
+        match self {
+            Self::Off => 0,
+            Self::Group => 1,
+            Self::Cpu => 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WbtLatency {
+    Disabled,
+    Usec(u64),
+    Default,
+}
+
+const MIN_NR_REQUESTS: u32 = 4;
+
+fn parse_raw<T>(queue: &Path, name: &str, f: fn(u8) -> Option<T>) -> crate::Result<Option<T>> {
+    let p = queue.join(name);
+    match parse_attr::<u8, _>(&p)? {
+        Some(raw) => f(raw).map(Some).ok_or_else(|| Error::parse(&p)),
+        None => Ok(None),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueueTuning {
+    pub nr_requests: Option<u32>,
+    pub nomerges: Option<NoMerges>,
+    pub rq_affinity: Option<RqAffinity>,
+    pub wbt_lat_usec: Option<WbtLatency>,
+    pub add_random: Option<bool>,
+}
+
+impl QueueTuning {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn nr_requests(mut self, n: u32) -> Self {
+        self.nr_requests = Some(n);
+        self
+    }
+
+    #[inline]
+    pub fn nomerges(mut self, mode: NoMerges) -> Self {
+        self.nomerges = Some(mode);
+        self
+    }
+
+    #[inline]
+    pub fn rq_affinity(mut self, mode: RqAffinity) -> Self {
+        self.rq_affinity = Some(mode);
+        self
+    }
+
+    #[inline]
+    pub fn wbt_lat_usec(mut self, latency: WbtLatency) -> Self {
+        self.wbt_lat_usec = Some(latency);
+        self
+    }
+
+    #[inline]
+    pub fn add_random(mut self, enabled: bool) -> Self {
+        self.add_random = Some(enabled);
+        self
+    }
+
+    pub fn validate(&self) -> crate::Result<()> {
+        match self.nr_requests {
+            Some(n) if n < MIN_NR_REQUESTS => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "nr_requests must be at least 4",
+            )
+            .into()),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn read(queue: &Path) -> crate::Result<Self> {
+        Ok(Self {
+            nr_requests: parse_attr(queue.join("nr_requests"))?,
+            nomerges: parse_raw(queue, "nomerges", NoMerges::from_raw)?,
+            rq_affinity: parse_raw(queue, "rq_affinity", RqAffinity::from_raw)?,
+            wbt_lat_usec: parse_attr::<u64, _>(queue.join("wbt_lat_usec"))?.map(|usec| {
+                if usec == 0 {
+                    WbtLatency::Disabled
+                } else {
+                    WbtLatency::Usec(usec)
+                }
+            }),
+            add_random: parse_attr::<u8, _>(queue.join("add_random"))?.map(|raw| raw != 0),
+        })
+    }
+
+    // the set knobs as attribute name and the value written to it
+    fn values(&self) -> Vec<(&'static str, String)> {
+        let mut res = Vec::new();
+        if let Some(n) = self.nr_requests {
+            res.push(("nr_requests", n.to_string()));
+        }
+        if let Some(mode) = self.nomerges {
+            res.push(("nomerges", mode.raw().to_string()));
+        }
+        if let Some(mode) = self.rq_affinity {
+            res.push(("rq_affinity", mode.raw().to_string()));
+        }
+        if let Some(latency) = self.wbt_lat_usec {
+            let value = match latency {
+                WbtLatency::Disabled => "0".to_string(),
+                WbtLatency::Usec(usec) => usec.to_string(),
+                // the kernel restores its own default on a negative value
+                WbtLatency::Default => "-1".to_string(),
+            };
+            res.push(("wbt_lat_usec", value));
+        }
+        if let Some(enabled) = self.add_random {
+            res.push(("add_random", if enabled { "1" } else { "0" }.to_string()));
+        }
+        res
+    }
+
+    // all or nothing: a knob the queue lacks fails before anything is written,
+    // and a failed write puts back the knobs already changed
+    pub(crate) fn apply(&self, queue: &Path) -> crate::Result<()> {
+        self.validate()?;
+        let previous = Self::read(queue)?.values();
+        let mut writes = Vec::new();
+        for (name, value) in self.values() {
+            match previous.iter().find(|(prev, _)| *prev == name) {
+                Some((_, old)) => writes.push((name, value, old)),
+                None => {
+                    return Err(Error::path(
+                        queue.join(name),
+                        io::ErrorKind::NotFound.into(),
+                    ))
+                }
+            }
+        }
+
+        for (i, (name, value, _)) in writes.iter().enumerate() {
+            if let Err(err) = write_attr(queue.join(name), value) {
+                // best effort, the first error is the one worth reporting
+                for (name, _, old) in writes[..i].iter().rev() {
+                    let _ = write_attr(queue.join(name), old);
+                }
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "linux-blocks-queue-{}-{}",
+            std::process::id(),
+            name
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (attr, value) in [
+            ("nr_requests", "64"),
+            ("nomerges", "0"),
+            ("rq_affinity", "1"),
+            ("wbt_lat_usec", "2000"),
+        ] {
+            std::fs::write(dir.join(attr), value).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn apply() {
+        let dir = queue("apply");
+        QueueTuning::new()
+            .nr_requests(128)
+            .wbt_lat_usec(WbtLatency::Disabled)
+            .apply(&dir)
+            .unwrap();
+        let tuning = QueueTuning::read(&dir).unwrap();
+        assert_eq!(tuning.nr_requests, Some(128));
+        assert_eq!(tuning.wbt_lat_usec, Some(WbtLatency::Disabled));
+        assert_eq!(tuning.rq_affinity, Some(RqAffinity::Group));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_knob_writes_nothing() {
+        let dir = queue("missing");
+        let err = QueueTuning::new()
+            .nr_requests(128)
+            .add_random(true)
+            .apply(&dir)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert_eq!(QueueTuning::read(&dir).unwrap().nr_requests, Some(64));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_write_rolls_back() {
+        // read-only even for root, and parses as a u8
+        let read_only = Path::new("/proc/sys/kernel/cap_last_cap");
+        if !read_only.exists() {
+            return;
+        }
+        let dir = queue("rollback");
+        std::os::unix::fs::symlink(read_only, dir.join("add_random")).unwrap();
+        assert!(QueueTuning::new()
+            .nr_requests(128)
+            .nomerges(NoMerges::Disabled)
+            .add_random(false)
+            .apply(&dir)
+            .is_err());
+        let tuning = QueueTuning::read(&dir).unwrap();
+        assert_eq!(tuning.nr_requests, Some(64));
+        assert_eq!(tuning.nomerges, Some(NoMerges::Enabled));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_cache() {
+        assert_eq!("write back".parse(), Ok(WriteCache::WriteBack));
+        assert_eq!(
+            "write-through".parse::<WriteCache>(),
+            Err(ParseWriteCacheError)
+        );
+        assert!(ParseWriteCacheError.to_string().contains("write through"));
+    }
+}
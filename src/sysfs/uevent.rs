@@ -0,0 +1,126 @@
+use std::{collections::BTreeMap, str::FromStr};
+
+use crate::Devno;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Uevent {
+    pub properties: BTreeMap<String, String>,
+}
+
+#[derive(Debug)]
+pub struct ParseUeventError;
+
+impl FromStr for Uevent {
+    type Err = ParseUeventError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut properties = BTreeMap::new();
+        for line in s.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            let (key, value) = line.split_once('=').ok_or(ParseUeventError)?;
+            properties.insert(key.to_string(), value.to_string());
+        }
+        Ok(Self { properties })
+    }
+}
+
+impl Uevent {
+    #[inline]
+    pub fn get<S: AsRef<str>>(&self, key: S) -> Option<&str> {
+        self.properties.get(key.as_ref()).map(|x| x.as_str())
+    }
+
+    #[inline]
+    pub fn major(&self) -> Option<u32> {
+        self.get("MAJOR")?.parse().ok()
+    }
+
+    #[inline]
+    pub fn minor(&self) -> Option<u32> {
+        self.get("MINOR")?.parse().ok()
+    }
+
+    #[inline]
+    pub fn devno(&self) -> Option<Devno> {
+        Some((self.major()?, self.minor()?).into())
+    }
+
+    #[inline]
+    pub fn devname(&self) -> Option<&str> {
+        self.get("DEVNAME")
+    }
+
+    #[inline]
+    pub fn devtype(&self) -> Option<&str> {
+        self.get("DEVTYPE")
+    }
+
+    #[inline]
+    pub fn partn(&self) -> Option<usize> {
+        self.get("PARTN")?.parse().ok()
+    }
+
+    #[inline]
+    pub fn partname(&self) -> Option<&str> {
+        self.get("PARTNAME")
+    }
+
+    #[inline]
+    pub fn dm_name(&self) -> Option<&str> {
+        self.get("DM_NAME")
+    }
+
+    #[inline]
+    pub fn dm_uuid(&self) -> Option<&str> {
+        self.get("DM_UUID")
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UeventAction {
+    Add,
+    Remove,
+    Change,
+    Move,
+    Online,
+    Offline,
+    Bind,
+    Unbind,
+}
+
+#[derive(Debug)]
+pub struct ParseUeventActionError;
+
+impl FromStr for UeventAction {
+    type Err = ParseUeventActionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "add" => Ok(Self::Add),
+            "remove" => Ok(Self::Remove),
+            "change" => Ok(Self::Change),
+            "move" => Ok(Self::Move),
+            "online" => Ok(Self::Online),
+            "offline" => Ok(Self::Offline),
+            "bind" => Ok(Self::Bind),
+            "unbind" => Ok(Self::Unbind),
+            _ => Err(ParseUeventActionError),
+        }
+    }
+}
+
+impl std::fmt::Display for UeventAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Add => "add",
+            Self::Remove => "remove",
+            Self::Change => "change",
+            Self::Move => "move",
+            Self::Online => "online",
+            Self::Offline => "offline",
+            Self::Bind => "bind",
+            Self::Unbind => "unbind",
+        })
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{parse_hex, read_attr};
+use crate::Result;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UsbInfo {
+    pub vendor_id: u16,
+    pub product_id: u16,
+    pub manufacturer: Option<String>,
+    pub product: Option<String>,
+    pub serial: Option<String>,
+    pub bus: u32,
+    pub port_path: String,
+    pub speed: Option<u32>,
+}
+
+pub(crate) fn find_usb_device<P: AsRef<Path>>(device: P) -> Option<PathBuf> {
+    device
+        .as_ref()
+        .ancestors()
+        .find(|dir| dir.join("idVendor").is_file())
+        .map(Path::to_path_buf)
+}
+
+impl UsbInfo {
+    pub(crate) fn find<P: AsRef<Path>>(device: P) -> Result<Option<Self>> {
+        match find_usb_device(device) {
+            Some(dir) => Self::read(&dir).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read(dir: &Path) -> Result<Self> {
+        let bus = read_attr(dir.join("busnum"))?
+            .and_then(|x| x.parse().ok())
+            .ok_or(io::ErrorKind::InvalidData)?;
+        let port_path = match read_attr(dir.join("devpath"))? {
+            Some(p) => p,
+            None => dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.split_once('-'))
+                .map(|(_, p)| p.to_string())
+                .ok_or(io::ErrorKind::InvalidData)?,
+        };
+
+        Ok(Self {
+            vendor_id: parse_hex(read_attr(dir.join("idVendor"))?)?,
+            product_id: parse_hex(read_attr(dir.join("idProduct"))?)?,
+            manufacturer: read_attr(dir.join("manufacturer"))?,
+            product: read_attr(dir.join("product"))?,
+            serial: read_attr(dir.join("serial"))?,
+            bus,
+            port_path,
+            speed: read_attr(dir.join("speed"))?.and_then(|x| x.parse().ok()),
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use super::{parse_hex, read_attr};
+use crate::Result;
+
+const VIRTIO_ID_BLOCK: u16 = 2;
+
+crate::flags::flags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+    #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+    pub struct VirtioFeatures(u64) {
+        const SIZE_MAX = 1 << 1 => "size_max";
+        const SEG_MAX = 1 << 2 => "seg_max";
+        const GEOMETRY = 1 << 4 => "geometry";
+        const RO = 1 << 5 => "ro";
+        const BLK_SIZE = 1 << 6 => "blk_size";
+        const FLUSH = 1 << 9 => "flush";
+        const TOPOLOGY = 1 << 10 => "topology";
+        const CONFIG_WCE = 1 << 11 => "config_wce";
+        const MQ = 1 << 12 => "mq";
+        const DISCARD = 1 << 13 => "discard";
+        const WRITE_ZEROES = 1 << 14 => "write_zeroes";
+        const LIFETIME = 1 << 15 => "lifetime";
+        const SECURE_ERASE = 1 << 16 => "secure_erase";
+        const ZONED = 1 << 17 => "zoned";
+        const VERSION_1 = 1 << 32 => "version_1";
+    }
+}
+
+impl VirtioFeatures {
+    // sysfs prints one '0'/'1' character per feature bit, lowest bit first
+    fn parse(s: &str) -> Option<Self> {
+        let mut bits = 0u64;
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                '1' if i < 64 => bits |= 1 << i,
+                '0' | '1' => (),
+                _ => return None,
+            }
+        }
+        Some(Self(bits))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VirtioInfo {
+    pub name: String,
+    pub device_id: u16,
+    pub vendor_id: u16,
+    pub features: VirtioFeatures,
+}
+
+pub(crate) fn find_virtio_device<P: AsRef<Path>>(device: P) -> Option<PathBuf> {
+    device
+        .as_ref()
+        .ancestors()
+        .find(|dir| {
+            dir.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with("virtio"))
+                && dir.join("features").is_file()
+        })
+        .map(Path::to_path_buf)
+}
+
+impl VirtioInfo {
+    pub(crate) fn find<P: AsRef<Path>>(device: P) -> Result<Option<Self>> {
+        match find_virtio_device(device) {
+            Some(dir) => Self::read(&dir).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn read(dir: &Path) -> Result<Self> {
+        Ok(Self {
+            name: dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(str::to_string)
+                .ok_or(io::ErrorKind::InvalidData)?,
+            device_id: parse_hex(read_attr(dir.join("device"))?)?,
+            vendor_id: parse_hex(read_attr(dir.join("vendor"))?)?,
+            features: read_attr(dir.join("features"))?
+                .and_then(|f| VirtioFeatures::parse(&f))
+                .ok_or(io::ErrorKind::InvalidData)?,
+        })
+    }
+
+    // virtio-scsi disks also sit below a virtio device, with other feature bits
+    #[inline]
+    pub fn is_blk(&self) -> bool {
+        self.device_id == VIRTIO_ID_BLOCK
+    }
+
+    #[inline]
+    pub fn supports_discard(&self) -> bool {
+        self.is_blk() && self.features.contains(VirtioFeatures::DISCARD)
+    }
+
+    #[inline]
+    pub fn supports_write_zeroes(&self) -> bool {
+        self.is_blk() && self.features.contains(VirtioFeatures::WRITE_ZEROES)
+    }
+
+    #[inline]
+    pub fn is_read_only(&self) -> bool {
+        self.is_blk() && self.features.contains(VirtioFeatures::RO)
+    }
+}
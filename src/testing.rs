@@ -0,0 +1,415 @@
+use std::{
+    collections::BTreeMap,
+    ffi::CString,
+    io,
+    os::unix::prelude::OsStrExt,
+    path::{Component, Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{Blocks, Devno, Error, Result};
+
+static FIXTURE_ID: AtomicUsize = AtomicUsize::new(0);
+
+fn write<P: AsRef<Path>, S: AsRef<[u8]>>(p: P, content: S) -> Result<()> {
+    let p = p.as_ref();
+    if let Some(parent) = p.parent() {
+        std::fs::create_dir_all(parent).map_err(|err| Error::path(parent, err))?;
+    }
+    std::fs::write(p, content).map_err(|err| Error::path(p, err))
+}
+
+fn mkdir<P: AsRef<Path>>(p: P) -> Result<()> {
+    std::fs::create_dir_all(p.as_ref()).map_err(|err| Error::path(p.as_ref(), err))
+}
+
+// relative like the kernel's, so the tree can be captured and moved around
+fn relative(target: &Path, base: &Path) -> PathBuf {
+    let common = target
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    base.components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(target.components().skip(common))
+        .collect()
+}
+
+fn symlink<P: AsRef<Path>, Q: AsRef<Path>>(target: P, link: Q) -> Result<()> {
+    let link = link.as_ref();
+    let mut target = target.as_ref().to_path_buf();
+    if let Some(parent) = link.parent() {
+        mkdir(parent)?;
+        if target.is_absolute() {
+            target = relative(&target, parent);
+        }
+    }
+    std::os::unix::fs::symlink(target, link).map_err(|err| Error::path(link, err))
+}
+
+// symlinks are copied as links, captures only hold relative ones
+fn copy_tree(src: &Path, dst: &Path) -> Result<()> {
+    mkdir(dst)?;
+    for entry in std::fs::read_dir(src).map_err(|err| Error::path(src, err))? {
+        let entry = entry.map_err(|err| Error::path(src, err))?;
+        let (from, to) = (entry.path(), dst.join(entry.file_name()));
+        let file_type = entry.file_type().map_err(|err| Error::path(&from, err))?;
+        if file_type.is_symlink() {
+            let target = std::fs::read_link(&from).map_err(|err| Error::path(&from, err))?;
+            symlink(target, to)?;
+        } else if file_type.is_dir() {
+            copy_tree(&from, &to)?;
+        } else {
+            std::fs::copy(&from, &to).map_err(|err| Error::path(&from, err))?;
+        }
+    }
+    Ok(())
+}
+
+pub struct Fixture {
+    root: PathBuf,
+    dirs: BTreeMap<String, PathBuf>,
+    majors: BTreeMap<u32, String>,
+    mounts: Vec<String>,
+    device_nodes: bool,
+    captured: bool,
+}
+
+impl Fixture {
+    pub fn new() -> Result<Self> {
+        let root = std::env::temp_dir().join(format!(
+            "linux-blocks-fixture-{}-{}",
+            std::process::id(),
+            FIXTURE_ID.fetch_add(1, Ordering::Relaxed)
+        ));
+        Self::with_root(root)
+    }
+
+    pub fn with_root<P: AsRef<Path>>(root: P) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        for dir in [
+            "proc/self",
+            "sys/block",
+            "sys/class/block",
+            "sys/dev/block",
+            "sys/devices/virtual/block",
+            "dev",
+            "run/udev/data",
+        ] {
+            mkdir(root.join(dir))?;
+        }
+
+        let fixture = Self {
+            root,
+            dirs: BTreeMap::new(),
+            majors: BTreeMap::new(),
+            mounts: Vec::new(),
+            device_nodes: true,
+            captured: false,
+        };
+        fixture.write_proc()?;
+        Ok(fixture)
+    }
+
+    // starts from a tree written by Blocks::capture_to, which blocks() then
+    // loads like Blocks::from_capture; devices can be added on top of it
+    pub fn from_capture<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        let mut fixture = Self::new()?;
+        copy_tree(dir.as_ref(), &fixture.root)?;
+        fixture.captured = true;
+        fixture.device_nodes = !fixture.dev_list().is_file();
+
+        let links = fixture.sysfs().join("dev").join("block");
+        for entry in std::fs::read_dir(&links).map_err(|err| Error::path(&links, err))? {
+            let p = entry.map_err(|err| Error::path(&links, err))?.path();
+            let dir = p.canonicalize().map_err(|err| Error::path(&p, err))?;
+            if let Some(name) = dir.file_name().and_then(|name| name.to_str()) {
+                fixture.dirs.insert(name.to_string(), dir.clone());
+            }
+        }
+
+        let p = fixture.procfs().join("devices");
+        let devices = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+        let block = devices
+            .lines()
+            .skip_while(|line| *line != "Block devices:")
+            .skip(1);
+        for line in block {
+            if let Some((major, name)) = line.trim().split_once(' ') {
+                let major = major.parse().map_err(|_| Error::parse(&p))?;
+                fixture.majors.insert(major, name.to_string());
+            }
+        }
+
+        let p = fixture.procfs().join("self").join("mountinfo");
+        let mountinfo = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+        fixture.mounts = mountinfo.lines().map(String::from).collect();
+        Ok(fixture)
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    #[inline]
+    pub fn sysfs(&self) -> PathBuf {
+        self.root.join("sys")
+    }
+
+    #[inline]
+    pub fn procfs(&self) -> PathBuf {
+        self.root.join("proc")
+    }
+
+    #[inline]
+    pub fn devfs(&self) -> PathBuf {
+        self.root.join("dev")
+    }
+
+    #[inline]
+    pub fn udevdb(&self) -> PathBuf {
+        self.root.join("run").join("udev").join("data")
+    }
+
+    #[inline]
+    pub fn has_device_nodes(&self) -> bool {
+        self.device_nodes
+    }
+
+    pub fn blocks(&self) -> Result<Blocks> {
+        if self.captured {
+            return Blocks::from_capture(&self.root);
+        }
+        Blocks::builder()
+            .procfs(self.procfs())
+            .sysfs(self.sysfs())
+            .devfs(self.devfs())
+            .udevdb(self.udevdb())
+            .build()
+    }
+
+    pub fn disk<D: Into<Devno>>(&mut self, name: &str, devno: D, size: u64) -> Result<&mut Self> {
+        let dir = self.sysfs().join("devices/virtual/block").join(name);
+        self.add_device(name, devno.into(), &dir, size, "disk")?;
+        write(dir.join("removable"), "0\n")?;
+        write(dir.join("queue").join("rotational"), "0\n")?;
+        symlink(&dir, self.sysfs().join("block").join(name))?;
+        Ok(self)
+    }
+
+    pub fn partition<D: Into<Devno>>(
+        &mut self,
+        disk: &str,
+        name: &str,
+        devno: D,
+        partno: usize,
+        size: u64,
+    ) -> Result<&mut Self> {
+        let dir = self.dir(disk)?.join(name);
+        self.add_device(name, devno.into(), &dir, size, "partition")?;
+        write(dir.join("partition"), format!("{}\n", partno))?;
+        Ok(self)
+    }
+
+    pub fn dm<D: Into<Devno>>(
+        &mut self,
+        name: &str,
+        devno: D,
+        dm_name: &str,
+        uuid: &str,
+        slaves: &[&str],
+        size: u64,
+    ) -> Result<&mut Self> {
+        let devno = devno.into();
+        self.majors
+            .insert(devno.major(), "device-mapper".to_string());
+        let dir = self.sysfs().join("devices/virtual/block").join(name);
+        self.add_device(name, devno, &dir, size, "disk")?;
+        write(dir.join("dm").join("name"), format!("{}\n", dm_name))?;
+        write(dir.join("dm").join("uuid"), format!("{}\n", uuid))?;
+        symlink(&dir, self.sysfs().join("block").join(name))?;
+        self.link_slaves(name, slaves)?;
+        self.write_proc()?;
+        Ok(self)
+    }
+
+    pub fn md<D: Into<Devno>>(
+        &mut self,
+        name: &str,
+        devno: D,
+        level: &str,
+        slaves: &[&str],
+        size: u64,
+    ) -> Result<&mut Self> {
+        let devno = devno.into();
+        self.majors.insert(devno.major(), "md".to_string());
+        let dir = self.sysfs().join("devices/virtual/block").join(name);
+        self.add_device(name, devno, &dir, size, "disk")?;
+        write(dir.join("md").join("level"), format!("{}\n", level))?;
+        write(
+            dir.join("md").join("raid_disks"),
+            format!("{}\n", slaves.len()),
+        )?;
+        symlink(&dir, self.sysfs().join("block").join(name))?;
+        self.link_slaves(name, slaves)?;
+        self.write_proc()?;
+        Ok(self)
+    }
+
+    pub fn attr(&mut self, name: &str, attr: &str, value: &str) -> Result<&mut Self> {
+        write(self.dir(name)?.join(attr), format!("{}\n", value))?;
+        Ok(self)
+    }
+
+    pub fn udev<I: IntoIterator<Item = (K, V)>, K: AsRef<str>, V: AsRef<str>, D: Into<Devno>>(
+        &mut self,
+        devno: D,
+        properties: I,
+    ) -> Result<&mut Self> {
+        let content = properties
+            .into_iter()
+            .map(|(k, v)| format!("E:{}={}\n", k.as_ref(), v.as_ref()))
+            .collect::<String>();
+        write(self.udevdb().join(format!("b{}", devno.into())), content)?;
+        Ok(self)
+    }
+
+    pub fn mount<D: Into<Devno>, P: AsRef<Path>>(
+        &mut self,
+        devno: D,
+        mount_point: P,
+        file_system: &str,
+        source: &str,
+    ) -> Result<&mut Self> {
+        let id = self
+            .mounts
+            .iter()
+            .filter_map(|line| line.split(' ').next()?.parse::<u32>().ok())
+            .max()
+            .map_or(20, |id| id + 1);
+        self.mounts.push(format!(
+            "{} 1 {} / {} rw,relatime shared:1 - {} {} rw",
+            id,
+            devno.into(),
+            mount_point.as_ref().display(),
+            file_system,
+            source
+        ));
+        self.write_proc()?;
+        Ok(self)
+    }
+
+    fn dir(&self, name: &str) -> Result<&PathBuf> {
+        self.dirs
+            .get(name)
+            .ok_or_else(|| Error::path(name, io::ErrorKind::NotFound.into()))
+    }
+
+    fn add_device(
+        &mut self,
+        name: &str,
+        devno: Devno,
+        dir: &Path,
+        size: u64,
+        devtype: &str,
+    ) -> Result<()> {
+        mkdir(dir.join("holders"))?;
+        mkdir(dir.join("slaves"))?;
+        write(dir.join("dev"), format!("{}\n", devno))?;
+        write(dir.join("size"), format!("{}\n", size / 512))?;
+        write(dir.join("ro"), "0\n")?;
+        write(
+            dir.join("uevent"),
+            format!(
+                "MAJOR={}\nMINOR={}\nDEVNAME={}\nDEVTYPE={}\n",
+                devno.major(),
+                devno.minor(),
+                name,
+                devtype
+            ),
+        )?;
+        symlink(dir, self.sysfs().join("class").join("block").join(name))?;
+        symlink(
+            dir,
+            self.sysfs()
+                .join("dev")
+                .join("block")
+                .join(devno.to_string()),
+        )?;
+        self.dirs.insert(name.to_string(), dir.to_path_buf());
+
+        let driver = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        self.majors
+            .entry(devno.major())
+            .or_insert_with(|| driver.to_string());
+        self.write_proc()?;
+
+        if self.captured && self.dev_list().is_file() {
+            self.list_node(name, devno)?;
+        } else if self.device_nodes {
+            self.device_nodes = self.mknod(name, devno)?;
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn dev_list(&self) -> PathBuf {
+        self.root.join("dev.list")
+    }
+
+    fn list_node(&self, name: &str, devno: Devno) -> Result<()> {
+        let p = self.dev_list();
+        let mut list = std::fs::read_to_string(&p).map_err(|err| Error::path(&p, err))?;
+        list.push_str(&format!("{} /dev/{}\n", devno, name));
+        write(p, list)
+    }
+
+    fn link_slaves(&mut self, name: &str, slaves: &[&str]) -> Result<()> {
+        let dir = self.dir(name)?.clone();
+        for slave in slaves {
+            let slave_dir = self.dir(slave)?.clone();
+            symlink(&slave_dir, dir.join("slaves").join(slave))?;
+            symlink(&dir, slave_dir.join("holders").join(name))?;
+        }
+        Ok(())
+    }
+
+    fn mknod(&self, name: &str, devno: Devno) -> Result<bool> {
+        let p = self.devfs().join(name);
+        let path = CString::new(p.as_os_str().as_bytes())
+            .map_err(|_| Error::from(io::ErrorKind::InvalidInput))?;
+        let ret = unsafe { libc::mknod(path.as_ptr(), libc::S_IFBLK | 0o600, *devno) };
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::PermissionDenied {
+                return Ok(false);
+            }
+            return Err(Error::path(p, err));
+        }
+        Ok(true)
+    }
+
+    fn write_proc(&self) -> Result<()> {
+        let mut devices = String::from("Character devices:\n  1 mem\n\nBlock devices:\n");
+        for (major, name) in self.majors.iter() {
+            devices.push_str(&format!("{:3} {}\n", major, name));
+        }
+        write(self.procfs().join("devices"), devices)?;
+
+        let mut mountinfo = String::new();
+        for line in self.mounts.iter() {
+            mountinfo.push_str(line);
+            mountinfo.push('\n');
+        }
+        write(self.procfs().join("self").join("mountinfo"), mountinfo)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
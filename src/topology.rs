@@ -0,0 +1,125 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+use crate::{blocks::Blocks, iter::RawSlavesIterator, DeviceInfo, DeviceType, Devno, Result};
+
+#[derive(Debug, Clone)]
+pub struct DeviceTree {
+    infos: BTreeMap<Devno, DeviceInfo>,
+    children: BTreeMap<Devno, BTreeSet<Devno>>,
+    roots: BTreeSet<Devno>,
+}
+
+impl DeviceTree {
+    pub(crate) fn new(blocks: &Blocks) -> Result<Self> {
+        let mut infos = BTreeMap::new();
+        let mut children = BTreeMap::<Devno, BTreeSet<Devno>>::new();
+
+        for devno in blocks.blocks()? {
+            let devno = devno?;
+            let info = blocks.info(&devno)?;
+
+            let mut parents = Vec::new();
+            if info.device_type == DeviceType::Partition {
+                parents.extend(info.parent);
+            } else {
+                let slaves = blocks.sysfs().resolve(&devno)?.join("slaves");
+                if slaves.is_dir() {
+                    for slave in RawSlavesIterator::new(slaves)? {
+                        parents.push(slave?);
+                    }
+                }
+            }
+
+            for parent in parents {
+                children.entry(parent).or_default().insert(devno);
+            }
+            infos.insert(devno, info);
+        }
+
+        // holders of devices we could not see are shown at the top level
+        children.retain(|parent, _| infos.contains_key(parent));
+        let nested = children
+            .values()
+            .flatten()
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let roots = infos
+            .keys()
+            .filter(|devno| !nested.contains(*devno))
+            .copied()
+            .collect();
+
+        Ok(Self {
+            infos,
+            children,
+            roots,
+        })
+    }
+
+    #[inline]
+    pub fn get(&self, devno: &Devno) -> Option<&DeviceInfo> {
+        self.infos.get(devno)
+    }
+
+    #[inline]
+    pub fn roots(&self) -> impl Iterator<Item = &DeviceInfo> {
+        self.roots.iter().map(|devno| &self.infos[devno])
+    }
+
+    #[inline]
+    pub fn children(&self, devno: &Devno) -> impl Iterator<Item = &DeviceInfo> {
+        self.children
+            .get(devno)
+            .into_iter()
+            .flatten()
+            .map(|devno| &self.infos[devno])
+    }
+
+    #[inline]
+    pub fn iter(&self) -> impl Iterator<Item = &DeviceInfo> {
+        self.infos.values()
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph blocks {\n    rankdir=LR;\n");
+
+        for info in self.infos.values() {
+            let shape = match info.device_type {
+                DeviceType::Partition => "box",
+                DeviceType::Dm | DeviceType::Md => "hexagon",
+                _ => "box3d",
+            };
+            let _ = writeln!(
+                out,
+                "    \"{}\" [label=\"{}\\n{} {}\" shape={}];",
+                info.devno, info.name, info.device_type, info.devno, shape
+            );
+        }
+
+        for (parent, children) in self.children.iter() {
+            for child in children {
+                let _ = writeln!(out, "    \"{}\" -> \"{}\";", parent, child);
+            }
+        }
+
+        for info in self.infos.values() {
+            for mp in info.mountpoints.iter() {
+                let mp = mp
+                    .to_string_lossy()
+                    .replace('\\', "\\\\")
+                    .replace('"', "\\\"");
+                let _ = writeln!(
+                    out,
+                    "    \"mnt:{mp}\" [label=\"{mp}\" shape=folder];\n    \"{}\" -> \"mnt:{mp}\" [style=dashed];",
+                    info.devno
+                );
+            }
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
@@ -0,0 +1,21 @@
+// forwards to `tracing` when the feature is enabled, expands to nothing otherwise
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    };
+}
+
+macro_rules! trace {
+    ($($arg:tt)+) => {
+        $crate::trace::event!(trace, $($arg)+)
+    };
+}
+
+macro_rules! debug {
+    ($($arg:tt)+) => {
+        $crate::trace::event!(debug, $($arg)+)
+    };
+}
+
+pub(crate) use {debug, event, trace};
@@ -0,0 +1,124 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+use crate::{Devno, Error, Result};
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UdevRecord {
+    pub properties: BTreeMap<String, String>,
+    pub symlinks: Vec<String>,
+    pub tags: Vec<String>,
+    pub link_priority: Option<i32>,
+    pub initialized_usec: Option<u64>,
+}
+
+impl UdevRecord {
+    pub fn from_reader<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut res = Self::default();
+
+        for line in reader.lines() {
+            let line = line?;
+            let (key, value) = match line.split_once(':') {
+                Some(x) => x,
+                None => continue,
+            };
+
+            match key {
+                "E" => {
+                    if let Some((k, v)) = value.split_once('=') {
+                        res.properties.insert(k.to_string(), v.to_string());
+                    }
+                }
+                "S" => res.symlinks.push(value.to_string()),
+                "G" => res.tags.push(value.to_string()),
+                "L" => res.link_priority = value.parse().ok(),
+                "I" => res.initialized_usec = value.parse().ok(),
+                _ => (),
+            }
+        }
+
+        Ok(res)
+    }
+
+    #[inline]
+    pub fn get<S: AsRef<str>>(&self, key: S) -> Option<&str> {
+        self.properties.get(key.as_ref()).map(|x| x.as_str())
+    }
+
+    #[inline]
+    pub fn fs_uuid(&self) -> Option<&str> {
+        self.get("ID_FS_UUID")
+    }
+
+    #[inline]
+    pub fn fs_type(&self) -> Option<&str> {
+        self.get("ID_FS_TYPE")
+    }
+
+    #[inline]
+    pub fn fs_label(&self) -> Option<&str> {
+        self.get("ID_FS_LABEL")
+    }
+
+    #[inline]
+    pub fn serial(&self) -> Option<&str> {
+        self.get("ID_SERIAL")
+    }
+
+    #[inline]
+    pub fn path_id(&self) -> Option<&str> {
+        self.get("ID_PATH")
+    }
+
+    #[inline]
+    pub fn wwn(&self) -> Option<&str> {
+        self.get("ID_WWN")
+    }
+}
+
+pub struct UdevDb {
+    path: PathBuf,
+}
+
+impl UdevDb {
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_path("/run/udev/data")
+    }
+
+    #[inline]
+    pub fn with_path<P: AsRef<Path>>(p: P) -> Self {
+        Self {
+            path: p.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn get(&self, devno: &Devno) -> Result<Option<UdevRecord>> {
+        let path = self.path.join(format!("b{}", devno));
+        let file = match File::open(&path) {
+            Ok(f) => f,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::path(path, err)),
+        };
+        UdevRecord::from_reader(BufReader::new(file))
+            .map(Some)
+            .map_err(|err| Error::path(path, err))
+    }
+
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Default for UdevDb {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
+}
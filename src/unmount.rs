@@ -0,0 +1,130 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    io,
+};
+
+use crate::{blocks::Blocks, eject, iter::RawSlavesIterator, procfs::MountInfo, Devno, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct UnmountOptions {
+    lazy: bool,
+    force: bool,
+}
+
+impl UnmountOptions {
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn lazy(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    #[inline]
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    fn flags(&self) -> libc::c_int {
+        let mut flags = 0;
+        if self.lazy {
+            flags |= libc::MNT_DETACH;
+        }
+        if self.force {
+            flags |= libc::MNT_FORCE;
+        }
+        flags
+    }
+}
+
+// the device itself, its partitions and everything stacked on top of them
+fn descendants(blocks: &Blocks, devno: &Devno) -> Result<BTreeSet<Devno>> {
+    let mut res = BTreeSet::new();
+    let mut queue = VecDeque::from([*devno]);
+    while let Some(devno) = queue.pop_front() {
+        if !res.insert(devno) {
+            continue;
+        }
+        for part in blocks.partitions(&devno)? {
+            queue.push_back(part?);
+        }
+        let holders = blocks.sysfs().resolve(&devno)?.join("holders");
+        if holders.is_dir() {
+            for holder in RawSlavesIterator::new(holders)? {
+                queue.push_back(holder?);
+            }
+        }
+    }
+    Ok(res)
+}
+
+pub(crate) fn unmount_all(
+    blocks: &Blocks,
+    devno: &Devno,
+    opts: UnmountOptions,
+) -> Result<Vec<MountInfo>> {
+    let devices = descendants(blocks, devno)?;
+    let all = blocks.procfs().mounts().all()?;
+    let targets = all
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| {
+            // anonymous devs (btrfs, overlay) only match through their source
+            devices.contains(&m.dev)
+                || (m.dev.major() == 0
+                    && blocks
+                        .mount_devno(m)
+                        .is_ok_and(|devno| devices.contains(&devno)))
+        })
+        .map(|(i, m)| (i, m.mount_point.clone()))
+        .collect::<Vec<_>>();
+    if targets.iter().any(|(_, p)| p.parent().is_none()) {
+        return Err(io::Error::new(
+            io::ErrorKind::ResourceBusy,
+            "device holds the root filesystem",
+        )
+        .into());
+    }
+    // mounts of other devices below or on top of ours keep them busy
+    let mut mounts = all
+        .into_iter()
+        .enumerate()
+        .filter(|(j, m)| {
+            targets
+                .iter()
+                .any(|(i, p)| i == j || (j > i && m.mount_point.starts_with(p)))
+        })
+        .collect::<Vec<_>>();
+    // deepest first, later mounts stacked on the same point before earlier ones
+    mounts.sort_by_key(|(i, m)| {
+        (
+            std::cmp::Reverse(m.mount_point.components().count()),
+            std::cmp::Reverse(*i),
+        )
+    });
+
+    let mut res = Vec::new();
+    for (_, mount) in mounts {
+        match eject::umount(&mount.mount_point, opts.flags()) {
+            Ok(()) => res.push(mount),
+            // unmounting a shared mount can take its peers away with it
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => {
+                let gone = blocks
+                    .procfs()
+                    .mounts()
+                    .find(|m| m.id == mount.id)?
+                    .is_none();
+                if !gone {
+                    return Err(err);
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(res)
+}
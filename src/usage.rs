@@ -0,0 +1,92 @@
+use std::{
+    ffi::CString,
+    io,
+    os::unix::prelude::OsStrExt,
+    path::{Path, PathBuf},
+};
+
+use crate::{procfs::MountInfo, Error, Result};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FsUsage {
+    pub mount_point: PathBuf,
+    pub block_size: u64,
+    pub total: u64,
+    pub free: u64,
+    pub available: u64,
+    pub inodes: u64,
+    pub inodes_free: u64,
+    pub inodes_available: u64,
+    pub read_only: bool,
+}
+
+impl FsUsage {
+    #[inline]
+    pub fn used(&self) -> u64 {
+        self.total.saturating_sub(self.free)
+    }
+
+    #[inline]
+    pub fn inodes_used(&self) -> u64 {
+        self.inodes.saturating_sub(self.inodes_free)
+    }
+
+    // same formula as df: used / (used + available)
+    pub fn used_percent(&self) -> Option<f64> {
+        let used = self.used();
+        let base = used + self.available;
+        if base == 0 {
+            None
+        } else {
+            Some(used as f64 * 100.0 / base as f64)
+        }
+    }
+
+    // statvfs field widths differ between 32 and 64 bit targets
+    #[allow(clippy::unnecessary_cast)]
+    pub fn statvfs<P: AsRef<Path>>(p: P) -> Result<Self> {
+        let p = p.as_ref();
+        let path = CString::new(p.as_os_str().as_bytes())
+            .map_err(|_| Error::from(io::ErrorKind::InvalidInput))?;
+        let mut buf = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+        let ret = unsafe { libc::statvfs(path.as_ptr(), buf.as_mut_ptr()) };
+        if ret < 0 {
+            return Err(Error::path(p, io::Error::last_os_error()));
+        }
+        let st = unsafe { buf.assume_init() };
+
+        let frsize = if st.f_frsize != 0 {
+            st.f_frsize
+        } else {
+            st.f_bsize
+        } as u64;
+        Ok(Self {
+            mount_point: p.to_path_buf(),
+            block_size: frsize,
+            total: st.f_blocks as u64 * frsize,
+            free: st.f_bfree as u64 * frsize,
+            available: st.f_bavail as u64 * frsize,
+            inodes: st.f_files as u64,
+            inodes_free: st.f_ffree as u64,
+            inodes_available: st.f_favail as u64,
+            read_only: st.f_flag & libc::ST_RDONLY != 0,
+        })
+    }
+}
+
+// every mount of the same superblock reports the same numbers, so the first
+// reachable mountpoint is enough
+pub(crate) fn fs_usage(mounts: &[MountInfo]) -> Result<Option<FsUsage>> {
+    let mut last = None;
+    for info in mounts {
+        match FsUsage::statvfs(&info.mount_point) {
+            Ok(usage) => return Ok(Some(usage)),
+            Err(err) => last = Some(err),
+        }
+    }
+    match last {
+        Some(err) => Err(err),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    fs::OpenOptions,
+    io,
+    os::unix::prelude::{AsRawFd, FileTypeExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::{events::UeventMonitor, Blocks, Device, Devno, Result};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WaitTarget {
+    Devno(Devno),
+    Path(PathBuf),
+}
+
+impl From<Devno> for WaitTarget {
+    #[inline]
+    fn from(devno: Devno) -> Self {
+        Self::Devno(devno)
+    }
+}
+
+impl From<PathBuf> for WaitTarget {
+    #[inline]
+    fn from(p: PathBuf) -> Self {
+        Self::Path(p)
+    }
+}
+
+impl From<&Path> for WaitTarget {
+    #[inline]
+    fn from(p: &Path) -> Self {
+        Self::Path(p.to_path_buf())
+    }
+}
+
+impl From<&str> for WaitTarget {
+    #[inline]
+    fn from(p: &str) -> Self {
+        Self::Path(PathBuf::from(p))
+    }
+}
+
+fn openable(p: &Path) -> bool {
+    match p.metadata() {
+        Ok(md) if md.file_type().is_block_device() => {
+            match OpenOptions::new()
+                .read(true)
+                .custom_flags(libc::O_NONBLOCK)
+                .open(p)
+            {
+                Ok(_) => true,
+                // the node is there and backed by a driver, permissions are the caller's business
+                Err(err) => !matches!(
+                    err.raw_os_error(),
+                    Some(libc::ENOENT | libc::ENXIO | libc::ENODEV)
+                ),
+            }
+        }
+        _ => false,
+    }
+}
+
+fn ready(blocks: &Blocks, target: &WaitTarget) -> Option<Device> {
+    match target {
+        WaitTarget::Devno(devno) => {
+            blocks.0.invalidate(devno);
+            blocks.sysfs().resolve(devno).ok()?;
+            if blocks.has_devfs() && !openable(&blocks.0.resolve(devno).ok()?) {
+                return None;
+            }
+            blocks.from_devno(devno).ok()
+        }
+        WaitTarget::Path(p) => {
+            if !openable(p) {
+                return None;
+            }
+            blocks.from_path(p).ok()
+        }
+    }
+}
+
+fn drain(blocks: &Blocks, monitor: &mut UeventMonitor) -> Result<()> {
+    loop {
+        match monitor.recv() {
+            Ok(Some(event)) => blocks.0.invalidate(&event.devno),
+            Ok(None) => (),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => (),
+            Err(err) => return Err(err.into()),
+        }
+    }
+}
+
+pub(crate) fn wait_for(blocks: &Blocks, target: WaitTarget, timeout: Duration) -> Result<Device> {
+    // a timeout too large to represent never expires
+    let deadline = Instant::now().checked_add(timeout);
+    // listening for uevents needs no privileges on most systems, but it is optional
+    let mut monitor = UeventMonitor::new()
+        .and_then(|m| m.set_nonblocking(true).map(|_| m))
+        .ok();
+
+    loop {
+        if let Some(device) = ready(blocks, &target) {
+            return Ok(device);
+        }
+
+        let step = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(io::ErrorKind::TimedOut.into());
+                }
+                (deadline - now).min(POLL_INTERVAL)
+            }
+            None => POLL_INTERVAL,
+        };
+
+        match monitor.as_mut() {
+            Some(monitor) => {
+                let mut pfd = libc::pollfd {
+                    fd: monitor.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let ret = unsafe { libc::poll(&mut pfd, 1, step.as_millis() as _) };
+                if ret < 0 {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err.into());
+                    }
+                } else if ret > 0 {
+                    drain(blocks, monitor)?;
+                }
+            }
+            None => std::thread::sleep(step),
+        }
+    }
+}
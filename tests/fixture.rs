@@ -0,0 +1,179 @@
+use std::{collections::BTreeSet, path::Path};
+
+use linux_blocks::{testing::Fixture, Device, Devno, Result};
+
+const GIB: u64 = 1 << 30;
+
+// sda1, sda2 + sdb1 -> md0 (raid1) -> dm-0 (vg-root) mounted on /
+fn stack() -> Result<Fixture> {
+    let mut fixture = Fixture::new()?;
+    fixture
+        .disk("sda", Devno::new(8, 0), 4 * GIB)?
+        .partition("sda", "sda1", Devno::new(8, 1), 1, GIB)?
+        .partition("sda", "sda2", Devno::new(8, 2), 2, 3 * GIB)?
+        .disk("sdb", Devno::new(8, 16), 4 * GIB)?
+        .partition("sdb", "sdb1", Devno::new(8, 17), 1, 3 * GIB)?
+        .md("md0", Devno::new(9, 0), "raid1", &["sda2", "sdb1"], 3 * GIB)?
+        .dm(
+            "dm-0",
+            Devno::new(253, 0),
+            "vg-root",
+            "LVM-0123456789abcdef",
+            &["md0"],
+            3 * GIB,
+        )?
+        .mount(Devno::new(253, 0), "/", "ext4", "/dev/mapper/vg-root")?
+        .mount(Devno::new(8, 1), "/boot", "vfat", "/dev/sda1")?;
+    Ok(fixture)
+}
+
+fn names<I: IntoIterator<Item = Result<Device>>>(it: I) -> Result<BTreeSet<String>> {
+    it.into_iter().map(|d| d?.name()).collect()
+}
+
+fn holders(device: &Device) -> Result<BTreeSet<String>> {
+    let mut names = BTreeSet::new();
+    for entry in std::fs::read_dir(device.sysfs_path()?.join("holders"))? {
+        names.insert(entry?.file_name().to_string_lossy().into_owned());
+    }
+    Ok(names)
+}
+
+#[test]
+fn enumeration() -> Result<()> {
+    let fixture = stack()?;
+    let blocks = fixture.blocks()?;
+
+    assert_eq!(
+        names(blocks.blocks()?)?,
+        ["dm-0", "md0", "sda", "sda1", "sda2", "sdb", "sdb1"]
+            .map(String::from)
+            .into()
+    );
+    assert_eq!(
+        names(blocks.disks()?)?,
+        ["dm-0", "md0", "sda", "sdb"].map(String::from).into()
+    );
+
+    let sda = blocks.from_devno(Devno::new(8, 0))?;
+    assert!(sda.is_disk()?);
+    assert_eq!(
+        names(sda.partitions()?)?,
+        ["sda1", "sda2"].map(String::from).into()
+    );
+
+    let sda2 = blocks.from_devno(Devno::new(8, 2))?;
+    assert!(sda2.is_partition()?);
+    assert_eq!(sda2.partition_number()?, Some(2));
+    assert_eq!(sda2.parent()?.map(|p| p.to_devno()), Some(sda.to_devno()));
+    assert_eq!(sda2.size()?, 3 * GIB);
+    Ok(())
+}
+
+#[test]
+fn holders_and_slaves() -> Result<()> {
+    let fixture = stack()?;
+    let blocks = fixture.blocks()?;
+
+    let md0 = blocks.from_devno(Devno::new(9, 0))?;
+    assert_eq!(
+        names(md0.slaves()?)?,
+        ["sda2", "sdb1"].map(String::from).into()
+    );
+    assert_eq!(holders(&md0)?, ["dm-0"].map(String::from).into());
+
+    let sdb1 = blocks.from_devno(Devno::new(8, 17))?;
+    assert_eq!(holders(&sdb1)?, ["md0"].map(String::from).into());
+    assert!(sdb1.in_use()?);
+
+    let dm = blocks.from_dm_name("vg/root")?;
+    assert_eq!(dm.to_devno(), Devno::new(253, 0));
+    assert!(dm.is_device_mapper()?);
+    assert_eq!(names(dm.slaves()?)?, ["md0"].map(String::from).into());
+    assert_eq!(
+        dm.physical_parents()?
+            .into_iter()
+            .map(|d| d.name())
+            .collect::<Result<BTreeSet<_>>>()?,
+        ["sda", "sdb"].map(String::from).into()
+    );
+    Ok(())
+}
+
+#[test]
+fn mountpoints() -> Result<()> {
+    let fixture = stack()?;
+    let blocks = fixture.blocks()?;
+
+    // from_mountpoint stats the real path, so resolve against the fixture's mountinfo
+    let root = blocks.procfs().mounts().find_target("/")?.unwrap();
+    assert_eq!(root.dev, Devno::new(253, 0));
+    assert_eq!(blocks.from_devno(root.dev)?.name()?, "dm-0");
+
+    let dm = blocks.from_devno(Devno::new(253, 0))?;
+    let mounts = dm.mounts()?;
+    assert_eq!(mounts.len(), 1);
+    assert_eq!(mounts[0].mount_point, Path::new("/"));
+
+    let sda1 = blocks.from_devno(Devno::new(8, 1))?;
+    assert_eq!(sda1.mounts()?[0].file_system, "vfat");
+    assert!(blocks.from_devno(Devno::new(8, 16))?.mounts()?.is_empty());
+    Ok(())
+}
+
+#[test]
+fn media_change() -> Result<()> {
+    let mut fixture = stack()?;
+    fixture
+        .disk("sdc", Devno::new(8, 32), 32 * GIB)?
+        .attr("sdc", "removable", "1")?
+        .attr("sdc", "events", "media_change eject_request")?
+        .attr("sdc", "diskseq", "7")?;
+    let blocks = fixture.blocks()?;
+
+    let sdc = blocks.from_devno(Devno::new(8, 32))?;
+    assert!(!sdc.check_media_changed()?);
+    assert!(!sdc.check_media_changed()?);
+
+    fixture.attr("sdc", "diskseq", "8")?;
+    assert!(sdc.check_media_changed()?);
+    assert!(!sdc.check_media_changed()?);
+
+    let err = blocks
+        .from_devno(Devno::new(8, 0))?
+        .check_media_changed()
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::Unsupported);
+    Ok(())
+}
+
+#[test]
+fn from_capture() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!(
+        "linux-blocks-fixture-capture-{}",
+        std::process::id()
+    ));
+    stack()?.blocks()?.capture_to(&dir)?;
+    let fixture = Fixture::from_capture(&dir);
+    std::fs::remove_dir_all(&dir)?;
+
+    let mut fixture = fixture?;
+    fixture
+        .partition("sdb", "sdb2", Devno::new(8, 18), 2, GIB)?
+        .mount(Devno::new(8, 18), "/srv", "xfs", "/dev/sdb2")?;
+    let blocks = fixture.blocks()?;
+    assert!(blocks.is_captured());
+
+    let sdb = blocks.from_devno(Devno::new(8, 16))?;
+    assert_eq!(
+        names(sdb.partitions()?)?,
+        ["sdb1", "sdb2"].map(String::from).into()
+    );
+    let dm = blocks.from_dm_name("vg-root")?;
+    assert_eq!(names(dm.slaves()?)?, ["md0"].map(String::from).into());
+    assert_eq!(
+        blocks.from_devno(Devno::new(8, 18))?.mounts()?[0].mount_point,
+        Path::new("/srv")
+    );
+    Ok(())
+}